@@ -0,0 +1,7 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // the gRPC service of `cco serve`, needs `protoc`
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/cco/v1/config.proto")?;
+
+    Ok(())
+}
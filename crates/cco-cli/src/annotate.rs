@@ -0,0 +1,148 @@
+//! Source comments for `cco eval --annotate-sources`
+use cco::cco_document::CcoDocument;
+use cco::hcl_documents::HclDocuments;
+use cco::value::Value;
+use std::collections::HashMap;
+
+/// Path of an expression that only names an element (`service.api`), `None` for anything else
+pub fn expression_path(expression: &hcl::Expression) -> Option<Vec<hcl::Identifier>> {
+    match expression {
+        hcl::Expression::Variable(variable) => Some(vec![variable.clone().into_inner()]),
+        hcl::Expression::Traversal(traversal) => {
+            let mut path = expression_path(&traversal.expr)?;
+            for operator in &traversal.operators {
+                let hcl::expr::TraversalOperator::GetAttr(name) = operator else {
+                    return None;
+                };
+                path.push(name.clone());
+            }
+            Some(path)
+        }
+        _ => None,
+    }
+}
+
+/// Comments of the top-level keys of a result and of the keys of each of its values, the
+/// documents of `--each` and files of `--output-dir`
+#[derive(Debug, Default)]
+pub struct Annotations {
+    pub keys: HashMap<String, String>,
+    pub nested: HashMap<String, HashMap<String, String>>,
+}
+
+impl Annotations {
+    /// Comments of the output of the value at `key`
+    pub fn of(&self, key: &str) -> Annotations {
+        Annotations {
+            keys: self.nested.get(key).cloned().unwrap_or_default(),
+            nested: HashMap::new(),
+        }
+    }
+}
+
+/// [sources] of `value` and of each of its values
+pub fn annotations(
+    hcl_documents: &HclDocuments,
+    document: &CcoDocument,
+    path: &[hcl::Identifier],
+    value: &Value,
+) -> Annotations {
+    let nested = value
+        .entries()
+        .map(|(key, value)| {
+            let mut child = path.to_vec();
+            child.push(hcl::Identifier::sanitized(key));
+            let comments = sources(hcl_documents, document, &child, value);
+            (key.to_string(), comments)
+        })
+        .collect();
+    Annotations {
+        keys: sources(hcl_documents, document, path, value),
+        nested,
+    }
+}
+
+/// `from <file>:<line>` for each top-level key of `value`, the value of the element at `path`
+///
+/// Keys that are not defined in a source document (virtual elements, keys of computed objects)
+/// get no comment. Files below the working directory are shown relative to it.
+pub fn sources(
+    hcl_documents: &HclDocuments,
+    document: &CcoDocument,
+    path: &[hcl::Identifier],
+    value: &Value,
+) -> HashMap<String, String> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    value
+        .entries()
+        .filter_map(|(key, _)| {
+            let mut child = path.to_vec();
+            child.push(hcl::Identifier::sanitized(key));
+            let info = document.get(&child)?;
+            let source = info.source?;
+            let line = hcl_documents.source_line(Some(source), info.span?.start)?;
+            let file = source.strip_prefix(&cwd).unwrap_or(source);
+            Some((key.to_string(), format!("from {}:{line}", file.display())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn expression(expression: &str) -> hcl::Expression {
+        expression
+            .parse::<hcl_edit::expr::Expression>()
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn paths() {
+        let path = |text| {
+            expression_path(&expression(text))
+                .map(|path| path.iter().map(|segment| segment.to_string()).collect())
+        };
+        assert_eq!(path("service"), Some(vec!["service".to_string()]));
+        assert_eq!(
+            path("service.api"),
+            Some(vec!["service".to_string(), "api".to_string()])
+        );
+        assert_eq!(path("service.api[0]"), None);
+        assert_eq!(path("{ a = service }"), None);
+    }
+
+    #[test]
+    fn comments() {
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(
+                "data service api {\n  port = 80\n}\n\ndata service web {\n  port = 81\n}\n",
+                Some("envs/prod.cco.hcl".into()),
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+
+        let path = expression_path(&expression("service")).unwrap();
+        let value = document.evaluate_path(&["service"]).unwrap();
+        let comments = sources(&documents, &document, &path, &value);
+        assert_eq!(comments["api"], "from envs/prod.cco.hcl:1");
+        assert_eq!(comments["web"], "from envs/prod.cco.hcl:5");
+
+        let path = expression_path(&expression("service.web")).unwrap();
+        let value = document.evaluate_path(&["service", "web"]).unwrap();
+        let comments = sources(&documents, &document, &path, &value);
+        assert_eq!(comments["port"], "from envs/prod.cco.hcl:6");
+
+        let path = expression_path(&expression("service")).unwrap();
+        let value = document.evaluate_path(&["service"]).unwrap();
+        let annotations = annotations(&documents, &document, &path, &value);
+        assert_eq!(annotations.keys["web"], "from envs/prod.cco.hcl:5");
+        assert_eq!(
+            annotations.of("web").keys["port"],
+            "from envs/prod.cco.hcl:6"
+        );
+        assert!(annotations.of("db").keys.is_empty());
+    }
+}
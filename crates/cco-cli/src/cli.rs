@@ -0,0 +1,859 @@
+//! cco cli interface
+
+use clap::{Parser, Subcommand, ValueEnum};
+use std::fmt::Formatter;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Cli {
+    /// Change the work directory
+    ///
+    /// Can be specified multiple times. Note that all
+    /// paths on the way to the final path must exist.
+    ///
+    /// This is equivalent to running { cd <directory>; cco ... }
+    #[clap(short = 'C', long = "directory", global(true))]
+    pub directory: Vec<PathBuf>,
+
+    /// Color diagnostics and diffs, `auto` colors terminals unless NO_COLOR is set
+    #[clap(long = "color", global(true), default_value_t)]
+    pub color: crate::style::ColorChoice,
+
+    /// Format of logs and diagnostics on stderr, `json` writes one object per line
+    #[clap(
+        long = "log-format",
+        env = "CCO_LOG_FORMAT",
+        global(true),
+        default_value_t
+    )]
+    pub log_format: crate::log::LogFormat,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Evaluate hcl expression
+    ///
+    /// Reads HCL from stdin unless any other source is provided (via --input-*)
+    #[command(alias = "eval")]
+    Evaluate(Box<EvaluateCommand>),
+
+    /// Print the value at a dotted path, e.g. `HOST=$(cco get db.primary.host)`
+    ///
+    /// Strings are printed without quotes, other scalars as JSON. Lists and objects are printed in
+    /// the --output-format (default: json). Exits with an error if the path is not defined,
+    /// unless --default is given.
+    Get(GetCommand),
+
+    /// Run a command with evaluated values as environment variables
+    ///
+    /// Nested keys are joined with `_` and uppercased: `{ db = { host = "x" } }` sets `DB_HOST=x`.
+    Env(EnvCommand),
+
+    /// Run a command with `{expression}` placeholders replaced by evaluated values
+    ///
+    /// Each argument stays a single argument, no shell is involved. Use `{{` and `}}` for
+    /// literal braces.
+    Exec(ExecCommand),
+
+    /// Write evaluated values to external systems
+    #[cfg(any(feature = "consul", feature = "etcd"))]
+    Push(PushCommand),
+
+    /// List the references of an element, or with `--reverse` the elements depending on it
+    ///
+    /// `cco deps --reverse service.api.port` answers "what would change if I edit it?".
+    /// `cco deps --unused --entry app` lists the elements neither `app` nor anything else uses.
+    Deps(DepsCommand),
+
+    /// List the elements whose path or expression matches a pattern
+    ///
+    /// `cco search 'service.*.port'` matches paths, `*` within a segment and `**` across
+    /// segments. The pattern also matches anywhere in the expressions of attributes as written:
+    /// `cco search '*.internal'` finds `host = "db.internal"`.
+    Search(SearchCommand),
+
+    /// Show how the value of an element came about
+    ///
+    /// `cco explain --merges service.api.cpu` lists the `type` block, the partials, the data block
+    /// and the blocks of `--overlay`s defining the attribute, lowest precedence first, and marks the
+    /// definition in effect.
+    Explain(ExplainCommand),
+
+    /// Rename an element and rewrite every reference to it in the input files
+    ///
+    /// `cco rename service.api service.public_api` relabels a data block, `cco rename service svc`
+    /// a data group and `cco rename service.api.port service.api.listen` an attribute.
+    Rename(RenameCommand),
+
+    /// Change the expression of an attribute in the file defining it
+    ///
+    /// `cco set service.api.port 9090` keeps comments and formatting of the file. Attributes with
+    /// a default of the `type` block or none at all are added to the data block.
+    Set(SetCommand),
+
+    /// Copy the expression of an attribute from one profile directory to another
+    ///
+    /// `cco promote staging prod service.api.image` sets `service.api.image` in the files of
+    /// `prod` to the expression written in `staging`, keeping comments and formatting.
+    /// `--dry-run` prints the diff for review.
+    Promote(PromoteCommand),
+
+    /// Print `data` blocks evaluating to the contents of a YAML or JSON file
+    ///
+    /// `cco import services.yaml --group service --split > services.cco.hcl` turns each
+    /// top-level key into a block `data service <key> { .. }`.
+    Import(ImportCommand),
+
+    /// Render the outputs of the packages declared in `cco.workspace.hcl`
+    ///
+    /// Each package loads the shared layers, its own layers and its directory, then writes its
+    /// outputs. `cco build api worker` builds only those packages.
+    Build(BuildCommand),
+
+    /// Check `expect` blocks of test files against the evaluated values
+    ///
+    /// `expect "service.api" { value = { port = 8080 } }` compares with an HCL expression,
+    /// `expect "service.api" { file = "golden/api.yaml" }` with a YAML or JSON file relative to the
+    /// test file. `--update` writes the actual values to fixture files that differ.
+    Test(TestCommand),
+
+    /// Print a JSON Schema for the value of a data group
+    ///
+    /// Properties are derived from the attributes of the `type` block of the group.
+    Schema(SchemaCommand),
+
+    /// Serve evaluated values over HTTP
+    ///
+    /// `GET /eval?expr=<expression>&format=json|yaml|ndjson` evaluates an expression. Documents
+    /// are loaded once and evaluated values are shared between requests. With `--watch`
+    /// documents are reloaded when they change.
+    Serve(ServeCommand),
+
+    /// Print debug information for development
+    ///
+    /// Reads HCL from stdin unless any other source is provided (via --input-*), e.g.
+    /// `cco dev -d config tree`.
+    Dev(DevCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct EvaluateCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    #[clap(flatten)]
+    pub output: OutputArgs,
+
+    /// Cache parsed documents and evaluation results in this directory
+    ///
+    /// Documents are keyed by a hash of their contents, unchanged files skip parsing.
+    /// Results are keyed by a hash of all inputs, the expression and the evaluation options,
+    /// unchanged inputs skip evaluation.
+    #[clap(long = "cache-dir", env = "CCO_CACHE_DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Only output these paths (repeatable), `*` matches any key, e.g. `services.*.url`
+    #[clap(long = "only", value_name = "PATH")]
+    pub only: Vec<crate::filter::PathPattern>,
+
+    /// Remove these paths from the output (repeatable), `*` matches any key, e.g. `*.secrets`
+    #[clap(long = "exclude", value_name = "PATH")]
+    pub exclude: Vec<crate::filter::PathPattern>,
+
+    /// Transform the result with a jq-like query, e.g. `.services[] | select(.enabled) | .url`
+    ///
+    /// Supports paths (`.a.b`, `.["a b"]`, `.[0]`, `.[]`), pipes, `select(...)` with comparisons,
+    /// `keys` and `length`. The results are output as a list.
+    #[clap(long = "query", short = 'q')]
+    pub query: Option<crate::query::Query>,
+
+    /// Output a single result of `--query` as is instead of a list of one
+    #[clap(long = "unwrap", requires = "query")]
+    pub unwrap: bool,
+
+    /// Sort object keys lexicographically instead of keeping source order
+    #[clap(long = "sort-keys")]
+    pub sort_keys: bool,
+
+    /// Canonical output for stable diffs: sorted keys, integral decimals as integers
+    #[clap(long = "normalize")]
+    pub normalize: bool,
+
+    /// Serialize the result while evaluating it
+    ///
+    /// Keeps memory usage bounded for very large results at the cost of
+    /// evaluating shared values multiple times.
+    #[clap(long = "stream", conflicts_with_all(["cache_dir", "only", "exclude", "query", "sort_keys", "normalize", "each"]))]
+    pub stream: bool,
+
+    /// YAML: comment each top-level key with the file and line defining it
+    ///
+    /// Only results of paths (`service.api`) can be annotated, keys of computed values are not.
+    /// With --query, results of key paths (`.api`) with --unwrap. --each and --output-dir
+    /// annotate the keys of each document.
+    #[clap(long = "annotate-sources", conflicts_with_all(["stream", "yaml_documents"]))]
+    pub annotate_sources: bool,
+
+    /// Fail on warnings: deprecated references, unused type blocks, sanitized labels
+    #[clap(long = "strict")]
+    pub strict: bool,
+
+    /// Fail on these diagnostics instead of printing them (`--deny warnings` is `--strict`)
+    #[clap(long = "deny", value_enum)]
+    pub deny: Vec<Lint>,
+
+    /// Fail if labels only differ in case (`Prod` and `prod`)
+    #[clap(long = "check-label-case")]
+    pub check_label_case: bool,
+
+    /// Maximum length of dependency chains
+    #[clap(long = "max-depth", default_value_t = cco::cco_document::DEFAULT_MAX_DEPTH)]
+    pub max_depth: usize,
+
+    /// Maximum number of evaluation steps (default: unlimited)
+    #[clap(long = "max-steps")]
+    pub max_steps: Option<usize>,
+
+    /// Abort evaluation after this long, e.g. `500ms`, `30s` or `2m`
+    #[clap(long = "timeout", value_parser = parse_duration)]
+    pub timeout: Option<std::time::Duration>,
+
+    /// What to do when integer arithmetic overflows
+    #[clap(long = "overflow", value_enum, default_value_t = Overflow::Error)]
+    pub overflow: Overflow,
+
+    /// Write a JSON record of the run to this file: sources with hashes, resolved addressables,
+    /// unresolved references, warnings and timings
+    ///
+    /// Written whether the evaluation succeeds or not.
+    #[clap(long = "report", value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    /// Print the addressables the evaluation resolved to stderr, dependencies first
+    #[clap(long = "explain")]
+    pub explain: bool,
+
+    /// Print how long each phase took and the slowest addressables to stderr
+    #[clap(long = "timings")]
+    pub timings: bool,
+
+    /// Number of addressables listed by --timings
+    #[clap(long = "timings-top", default_value_t = 10, requires("timings"))]
+    pub timings_top: usize,
+
+    /// Read the expression from this file instead, `-` for stdin
+    ///
+    /// For expressions too long to quote on a command line, e.g. `for` expressions building
+    /// whole manifests. There is no short flag: `-e` is an inline expression (`cco env -e`) and
+    /// `-f` an input file.
+    #[clap(
+        long = "expression-file",
+        value_name = "FILE",
+        conflicts_with = "expression"
+    )]
+    pub expression_file: Option<PathBuf>,
+
+    /// HCL expression to evaluate
+    #[clap(required_unless_present = "expression_file")]
+    pub expression: Option<String>,
+}
+
+impl EvaluateCommand {
+    /// The expression argument, or the contents of --expression-file once `evaluate` read it
+    pub fn expression(&self) -> &str {
+        self.expression.as_deref().unwrap_or_default()
+    }
+
+    /// `--strict` or `--deny warnings`
+    pub fn deny_warnings(&self) -> bool {
+        self.strict || self.deny.contains(&Lint::Warnings)
+    }
+}
+
+/// Diagnostics for `--deny`
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+pub enum Lint {
+    Warnings,
+}
+
+#[derive(Parser, Debug)]
+pub struct GetCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Print this if nothing is defined at the path
+    #[clap(long = "default")]
+    pub default: Option<String>,
+
+    /// Format of every value, including strings and other scalars
+    #[arg(short = 'F', long = "output-format")]
+    pub format: Option<OutputFormat>,
+
+    /// Dotted path, segments after an element select keys and list elements of its value
+    pub path: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct EnvCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// HCL expression evaluating to an object
+    #[clap(short = 'e', long = "expression", default_value = "env")]
+    pub expression: String,
+
+    /// Command and arguments to run
+    #[clap(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExecCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Print the shell-quoted command instead of running it
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Command and arguments to run, e.g. `docker run {app.image}:{app.tag}`
+    #[clap(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+#[cfg(any(feature = "consul", feature = "etcd"))]
+#[derive(Parser, Debug)]
+pub struct PushCommand {
+    #[command(subcommand)]
+    pub command: PushSubCommand,
+}
+
+#[cfg(any(feature = "consul", feature = "etcd"))]
+#[derive(Subcommand, Debug)]
+pub enum PushSubCommand {
+    /// Write the result as key/value pairs to Consul or etcd
+    ///
+    /// Nested keys are joined with `/` below --prefix. Only new and changed keys are written,
+    /// keys that are not part of the result are left alone.
+    Kv(PushKvCommand),
+}
+
+#[cfg(any(feature = "consul", feature = "etcd"))]
+#[derive(Parser, Debug)]
+pub struct PushKvCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    #[clap(long = "backend")]
+    pub backend: crate::kv::Backend,
+
+    /// HTTP(S) address of the store (default: local agent of the backend)
+    #[clap(long = "address", env = "CCO_KV_ADDRESS")]
+    pub address: Option<String>,
+
+    /// ACL token (Consul) or authorization token (etcd)
+    #[clap(long = "token", env = "CCO_KV_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Key prefix, e.g. `app/prod`
+    #[clap(long = "prefix", default_value = "")]
+    pub prefix: String,
+
+    /// Print the changes compared to the current values instead of writing them
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// HCL expression to evaluate
+    pub expression: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct ServeCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Address to listen on, `:8080` listens on all interfaces
+    #[clap(long = "listen", default_value = "127.0.0.1:8080")]
+    pub listen: String,
+
+    /// Require `Authorization: Bearer <token>` on every request
+    #[clap(long = "token", env = "CCO_SERVE_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Reload when input files change, `GET /watch` notifies clients about new values
+    #[clap(long = "watch")]
+    pub watch: bool,
+
+    /// How often to check input files for changes
+    #[clap(long = "poll-interval", value_parser = parse_duration, default_value = "1s", requires = "watch")]
+    pub poll_interval: std::time::Duration,
+
+    /// Also serve the gRPC service `cco.v1.Config` on this address, e.g. `:50051`
+    #[cfg(feature = "grpc")]
+    #[clap(long = "grpc-listen", value_name = "ADDRESS")]
+    pub grpc_listen: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct DepsCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// List the elements referencing the path, directly or through other elements
+    #[clap(long = "reverse")]
+    pub reverse: bool,
+
+    /// List the elements nothing references instead
+    #[clap(long = "unused", conflicts_with_all = ["reverse", "path"])]
+    pub unused: bool,
+
+    /// Expression whose references count as used, e.g. what is passed to `cco eval`
+    #[clap(long = "entry", requires = "unused")]
+    pub entries: Vec<String>,
+
+    /// Dotted path of an element, e.g. `service.api.port`
+    #[clap(required_unless_present = "unused")]
+    pub path: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct SearchCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Glob pattern, `*` and `?` do not match `.` of paths
+    pub pattern: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExplainCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Dotted path of an attribute whose definitions to list, e.g. `service.api.cpu`
+    #[clap(long = "merges", value_name = "PATH")]
+    pub merges: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct RenameCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Print the changes as a diff instead of writing the files
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Dotted path of the element, e.g. `service.api`
+    pub from: String,
+
+    /// New dotted path with the same number of segments
+    pub to: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct SetCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Print the change as a diff instead of writing the file
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Take the value as a string instead of an HCL expression
+    #[clap(long = "string")]
+    pub string: bool,
+
+    /// Dotted path of the attribute, e.g. `service.api.port`
+    pub path: String,
+
+    /// HCL expression, e.g. `9090`, `'"text"'` or `'[1, 2]'`
+    pub expression: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct PromoteCommand {
+    /// Loaded with both profiles, e.g. `-d base`
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Print the change as a diff instead of writing the file
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Directory of the profile to copy from
+    pub from: PathBuf,
+
+    /// Directory of the profile to change
+    pub to: PathBuf,
+
+    /// Dotted path of the attribute, e.g. `service.api.image`
+    pub path: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct ImportCommand {
+    /// Group (first label) of the generated blocks
+    #[clap(long = "group", value_parser = parse_identifier)]
+    pub group: hcl::Identifier,
+
+    /// Further labels of the generated blocks, repeat for several
+    #[clap(long = "label")]
+    pub labels: Vec<String>,
+
+    /// One block per top-level key, labelled with the key after --label
+    #[clap(long = "split")]
+    pub split: bool,
+
+    /// YAML or JSON file, `-` for stdin
+    pub file: PathBuf,
+}
+
+#[derive(Parser, Debug)]
+pub struct BuildCommand {
+    /// Loaded by every package before its layers, nothing by default
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Workspace manifest, paths in it are relative to its directory
+    #[clap(long = "workspace", default_value = crate::workspace::MANIFEST)]
+    pub workspace: PathBuf,
+
+    /// Compare with the output files instead of writing them and print a diff
+    ///
+    /// Exits with 0 if the files are up to date, 1 if any differs and 2 on errors.
+    #[clap(long = "check")]
+    pub check: bool,
+
+    /// Packages to build (default: all)
+    pub packages: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct TestCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Write the actual values to fixture files that differ or do not exist
+    #[clap(long = "update")]
+    pub update: bool,
+
+    /// Test files, or directories with `*.test.hcl` files
+    #[clap(default_value = ".")]
+    pub tests: Vec<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct SchemaCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Name of the data group (first label of `type` and `data` blocks)
+    pub group: String,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct InputArgs {
+    /// Load files from work directory
+    #[clap(short = 'w', long = "input-workdir")]
+    pub workdir: bool,
+
+    /// Load a file
+    #[clap(short = 'f', long = "input-file")]
+    pub files: Vec<PathBuf>,
+
+    /// Load files from given directory
+    #[clap(short = 'd', long = "input-dir")]
+    pub directories: Vec<PathBuf>,
+
+    /// Load files from work directory and up
+    ///
+    /// Load each directory walking up the tree.
+    /// Stops when it no longer matches any files.
+    /// Empty files are permitted.
+    /// Stops after a directory containing a `.cco-root` file.
+    #[clap(short = 'c', long = "input-chain", conflicts_with("workdir"))]
+    pub chain: bool,
+
+    /// Last directory `--input-chain` loads, even if its parent has files
+    ///
+    /// Has to be the work directory or one of its parents.
+    #[clap(long = "chain-stop-at", value_name = "DIR", requires = "chain")]
+    pub chain_stop_at: Option<PathBuf>,
+
+    /// What to do with symbolic links to `cco.hcl` files in loaded directories
+    #[clap(long = "symlinks", value_enum, default_value_t = Symlinks::Ignore)]
+    pub symlinks: Symlinks,
+
+    /// Also load files excluded by `.gitignore` or `.ccoignore` files
+    #[cfg(feature = "ignore-files")]
+    #[clap(long = "no-ignore")]
+    pub no_ignore: bool,
+
+    /// Verify the content hashes of all inputs and data sources against this file
+    #[clap(long = "lockfile", value_name = "FILE", env = "CCO_LOCKFILE")]
+    pub lockfile: Option<PathBuf>,
+
+    /// Write the content hashes of the current inputs and data sources to `--lockfile` instead of
+    /// verifying them
+    #[clap(long = "update-lockfile", requires = "lockfile")]
+    pub update_lockfile: bool,
+
+    /// Nest data groups of files in subdirectories of ROOT under their directory path
+    ///
+    /// `data "service" "api"` in `ROOT/prod/eu/` is addressed as `prod.eu.service.api`. Within
+    /// those files, references to groups of the same directory resolve there.
+    #[clap(long = "namespace-dirs", value_name = "ROOT")]
+    pub namespace_dirs: Option<PathBuf>,
+
+    /// Load `base/` and then `overlays/<PROFILE>/`, repeat to apply several overlays in order
+    ///
+    /// Data blocks of later files override the attributes of blocks with the same labels.
+    #[clap(long = "overlay", value_name = "PROFILE")]
+    pub overlays: Vec<String>,
+
+    /// Directory containing `base/` and `overlays/` (default: work directory)
+    #[clap(long = "overlay-root", requires = "overlays")]
+    pub overlay_root: Option<PathBuf>,
+
+    /// Load a document from a URL (http://, s3://bucket/key, gs://bucket/object)
+    ///
+    /// Append `#sha256=<hex>` to reject any other content.
+    #[cfg(feature = "remote")]
+    #[clap(short = 'u', long = "input-url")]
+    pub urls: Vec<String>,
+
+    /// Keep downloaded documents in this directory
+    #[cfg(feature = "remote")]
+    #[clap(long = "input-url-cache-dir", env = "CCO_URL_CACHE_DIR")]
+    pub url_cache_dir: Option<PathBuf>,
+
+    /// Load the plugin `cco-plugin-<NAME>` from PATH for its functions and block types
+    #[clap(long = "plugin", value_name = "NAME")]
+    pub plugins: Vec<String>,
+
+    /// Load all `cco-plugin-*` executables found on PATH
+    #[clap(long = "all-plugins", conflicts_with = "plugins")]
+    pub all_plugins: bool,
+
+    /// Allow `external` blocks to run their commands
+    #[clap(long = "allow-exec")]
+    pub allow_exec: bool,
+
+    /// Make root attributes addressable as `global.<name>` instead of rejecting them
+    #[clap(long = "globals")]
+    pub globals: bool,
+
+    /// Do not fetch `http` blocks or `tfstate` URLs, referencing one is an error
+    #[clap(long = "offline")]
+    pub offline: bool,
+
+    /// Load functions from a WebAssembly module (sandboxed, no file system or network access)
+    #[cfg(feature = "wasm")]
+    #[clap(long = "wasm", value_name = "PATH")]
+    pub wasm: Vec<PathBuf>,
+
+    /// Allow WebAssembly modules to read this environment variable
+    #[cfg(feature = "wasm")]
+    #[clap(long = "wasm-allow-env", value_name = "VAR")]
+    pub wasm_allow_env: Vec<String>,
+
+    /// Fuel (roughly instructions) a WebAssembly function may use per call
+    #[cfg(feature = "wasm")]
+    #[clap(long = "wasm-fuel", default_value_t = 100_000_000)]
+    pub wasm_fuel: u64,
+
+    /// Memory (MiB) a WebAssembly module may use
+    #[cfg(feature = "wasm")]
+    #[clap(long = "wasm-max-memory", value_name = "MIB", default_value_t = 64)]
+    pub wasm_max_memory: usize,
+}
+
+/// See [cco::hcl_documents::SymlinkPolicy]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Symlinks {
+    /// Load their targets
+    Follow,
+    /// Skip them
+    Ignore,
+    /// Fail
+    Error,
+}
+
+impl From<Symlinks> for cco::hcl_documents::SymlinkPolicy {
+    fn from(value: Symlinks) -> Self {
+        match value {
+            Symlinks::Follow => Self::Follow,
+            Symlinks::Ignore => Self::Ignore,
+            Symlinks::Error => Self::Error,
+        }
+    }
+}
+
+/// See [cco::cco_document::OverflowPolicy]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Overflow {
+    /// Fail naming the operation
+    Error,
+    /// Clamp to the smallest or largest integer
+    Saturate,
+}
+
+impl From<Overflow> for cco::cco_document::OverflowPolicy {
+    fn from(value: Overflow) -> Self {
+        match value {
+            Overflow::Error => Self::Error,
+            Overflow::Saturate => Self::Saturate,
+        }
+    }
+}
+
+impl InputArgs {
+    /// `--input-url` values, empty without the `remote` feature
+    pub fn urls(&self) -> &[String] {
+        #[cfg(feature = "remote")]
+        return &self.urls;
+        #[cfg(not(feature = "remote"))]
+        return &[];
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(group(clap::ArgGroup::new("destination").args(["output_file", "output_dir"])))]
+#[clap(group(clap::ArgGroup::new("per_key").args(["split_by_key", "each"])))]
+pub struct OutputArgs {
+    #[arg(short = 'F', long = "output-format", default_value_t)]
+    pub format: OutputFormat,
+
+    /// YAML: indentation width of nested mappings
+    #[arg(long = "yaml-indent", default_value_t = 2, value_parser = clap::value_parser!(u8).range(1..=16))]
+    pub yaml_indent: u8,
+
+    /// YAML: style of strings spanning multiple lines
+    #[arg(long = "yaml-multiline", default_value_t)]
+    pub yaml_multiline: crate::yaml::Multiline,
+
+    /// YAML: write each element (list) or value (object) as its own `---` document
+    #[arg(long = "yaml-documents")]
+    pub yaml_documents: bool,
+
+    /// Round decimals to at most this many digits after the decimal point
+    #[arg(long = "decimal-precision", value_name = "DIGITS")]
+    pub decimal_precision: Option<u8>,
+
+    /// Write whole decimals with `.0`, e.g. `1.0` instead of `1`
+    #[arg(long = "decimal-whole-point")]
+    pub decimal_whole_point: bool,
+
+    /// Write decimals without exponent, e.g. `10000000000000000.0` instead of `1e16`
+    #[arg(long = "decimal-plain")]
+    pub decimal_plain: bool,
+
+    /// Write the output to this file instead of stdout
+    #[clap(short = 'O', long = "output-file")]
+    pub output_file: Option<PathBuf>,
+
+    /// Write one file per top-level key of the result to --output-dir
+    ///
+    /// Files are named `<key>.<format>`, e.g. `out/app.yaml`.
+    #[clap(long = "split-by-key", requires("output_dir"))]
+    pub split_by_key: bool,
+
+    /// Output one document per top-level key of the result, e.g. per service of `service`
+    ///
+    /// YAML documents are separated by `---`, JSON documents by a line break and NDJSON has a
+    /// line per key. With --output-dir one file is written per key, like --split-by-key.
+    #[clap(long = "each", conflicts_with("yaml_documents"))]
+    pub each: bool,
+
+    /// Directory for --split-by-key and --each, created if missing
+    #[clap(long = "output-dir", requires("per_key"))]
+    pub output_dir: Option<PathBuf>,
+
+    /// Compare with the output file(s) instead of writing them and print a diff
+    ///
+    /// Exits with 0 if the files are up to date, 1 if any differs and 2 on errors.
+    #[clap(long = "check", requires("destination"))]
+    pub check: bool,
+}
+
+/// Parse a duration like `30s` or `1h30m`, the same as the `parse_duration` function
+pub fn parse_duration(value: &str) -> Result<std::time::Duration, String> {
+    let seconds = cco::quantity::parse_duration(value)?;
+    std::time::Duration::try_from_secs_f64(seconds)
+        .map_err(|_| format!("invalid duration '{value}', it must not be negative"))
+}
+
+#[derive(ValueEnum, Clone, Default, Debug, PartialEq)]
+pub enum OutputFormat {
+    Json,
+    #[default]
+    Yaml,
+    /// Newline delimited JSON, one line per element if the result is a list
+    Ndjson,
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Json => f.write_str("json"),
+            OutputFormat::Yaml => f.write_str("yaml"),
+            OutputFormat::Ndjson => f.write_str("ndjson"),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct DevCommand {
+    #[clap(flatten)]
+    pub input: InputArgs,
+
+    /// Print expressions marked with `cco_sensitive` instead of `<sensitive>`, and the expressions
+    /// of `documents`
+    #[clap(long = "show-sensitive", global(true))]
+    pub show_sensitive: bool,
+
+    #[command(subcommand)]
+    pub command: DevSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DevSubCommand {
+    Documents,
+    Hcl,
+    /// Print the paths of all elements as a tree with kind and substitution id of each
+    Tree,
+    /// Print counts of blocks, elements, path depth, expression sizes and references
+    Stats(DevStatsCommand),
+    /// List references to paths nothing provides, with the location of the referencing element
+    ///
+    /// Fails if there are any, whether the referencing elements are evaluated or not.
+    Unresolved,
+    /// List attributes of `type` blocks every data block overrides or nothing reads
+    ///
+    /// Fails if there are any.
+    Defaults(DevDefaultsCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct DevDefaultsCommand {
+    /// Expression whose references count as read, e.g. what is passed to `cco eval`
+    #[clap(long = "entry")]
+    pub entries: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct DevStatsCommand {
+    /// Print JSON instead, e.g. to track growth over time
+    #[clap(long = "json")]
+    pub json: bool,
+}
+
+/// Parse a data group name, which has to be a valid identifier
+pub fn parse_identifier(value: &str) -> Result<hcl::Identifier, String> {
+    hcl::Identifier::new(value).map_err(|_| format!("'{value}' is not a valid identifier"))
+}
@@ -0,0 +1,209 @@
+//! Formatting of decimals in JSON and YAML output
+//!
+//! By default decimals are written the way `serde_json`/`serde_yaml` write them: the shortest
+//! representation that reads back as the same number, with an exponent for very large and small
+//! numbers (`1e16`). Rounding, a fixed `.0` for whole decimals and plain notation keep generated
+//! files stable when the serializers change their mind.
+use std::io;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DecimalFormat {
+    /// Round to at most this many digits after the decimal point, ties of the exact binary
+    /// value go to the even digit (`0.125` becomes `0.12`)
+    pub max_precision: Option<usize>,
+    /// Write whole decimals with `.0` (`1.0`, `1.0e16`)
+    pub whole_point: bool,
+    /// Never use an exponent (`10000000000000000`)
+    pub plain: bool,
+}
+
+impl DecimalFormat {
+    /// The decimal as written to output, `None` leaves it to the serializer
+    ///
+    /// Non-finite decimals are always left to the serializer.
+    pub fn format(&self, value: f64) -> Option<String> {
+        if *self == Self::default() || !value.is_finite() {
+            return None;
+        }
+
+        let mut text = match self.max_precision {
+            Some(precision) => {
+                let rounded = format!("{value:.precision$}");
+                match rounded.contains('.') {
+                    true => rounded
+                        .trim_end_matches('0')
+                        .trim_end_matches('.')
+                        .to_string(),
+                    false => rounded,
+                }
+            }
+            None if self.plain => format!("{value}"),
+            None => format!("{value:?}"),
+        };
+        if text == "-0" {
+            text = "0".to_string();
+        }
+
+        if self.whole_point {
+            let mantissa_end = text.find(['e', 'E']).unwrap_or(text.len());
+            if !text[..mantissa_end].contains('.') {
+                text.insert_str(mantissa_end, ".0");
+            }
+        } else if let Some(whole) = text.strip_suffix(".0") {
+            text = whole.to_string();
+        }
+        Some(text)
+    }
+}
+
+/// Pretty printed JSON
+pub fn to_writer_pretty(
+    writer: impl io::Write,
+    value: &impl serde::Serialize,
+    decimals: DecimalFormat,
+) -> serde_json::Result<()> {
+    let formatter = Formatter {
+        inner: serde_json::ser::PrettyFormatter::new(),
+        decimals,
+    };
+    value.serialize(&mut serde_json::Serializer::with_formatter(
+        writer, formatter,
+    ))
+}
+
+/// JSON on a single line
+pub fn to_writer(
+    writer: impl io::Write,
+    value: &impl serde::Serialize,
+    decimals: DecimalFormat,
+) -> serde_json::Result<()> {
+    let formatter = Formatter {
+        inner: serde_json::ser::CompactFormatter,
+        decimals,
+    };
+    value.serialize(&mut serde_json::Serializer::with_formatter(
+        writer, formatter,
+    ))
+}
+
+/// Writes decimals in [DecimalFormat], everything else like `inner`
+struct Formatter<F> {
+    inner: F,
+    decimals: DecimalFormat,
+}
+
+impl<F: serde_json::ser::Formatter> serde_json::ser::Formatter for Formatter<F> {
+    fn write_f64<W: ?Sized + io::Write>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        match self.decimals.format(value) {
+            Some(text) => writer.write_all(text.as_bytes()),
+            None => self.inner.write_f64(writer, value),
+        }
+    }
+
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.inner.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.inner.begin_object_key(writer, first)
+    }
+
+    fn end_object_key<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object_key(writer)
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object_value(writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format() {
+        let format = |max_precision, whole_point, plain, value| {
+            let decimals = DecimalFormat {
+                max_precision,
+                whole_point,
+                plain,
+            };
+            decimals.format(value)
+        };
+
+        assert_eq!(format(None, false, false, 1.5), None);
+        assert_eq!(format(None, true, false, 1.0).as_deref(), Some("1.0"));
+        assert_eq!(format(None, true, false, 1e16).as_deref(), Some("1.0e16"));
+        assert_eq!(
+            format(None, false, true, 1e16).as_deref(),
+            Some("10000000000000000")
+        );
+        assert_eq!(format(None, true, true, 1e-7).as_deref(), Some("0.0000001"));
+        assert_eq!(
+            format(Some(2), false, false, 0.1 + 0.2).as_deref(),
+            Some("0.3")
+        );
+        assert_eq!(format(Some(2), false, false, 2.0).as_deref(), Some("2"));
+        assert_eq!(format(Some(2), true, false, 1.999).as_deref(), Some("2.0"));
+        assert_eq!(format(Some(0), false, false, 2.5).as_deref(), Some("2"));
+        assert_eq!(format(Some(1), true, false, -0.01).as_deref(), Some("0.0"));
+        assert_eq!(format(Some(1), true, false, f64::NAN), None);
+    }
+
+    #[test]
+    fn json() {
+        let value = serde_json::json!({ "a": [1.0, 0.30000000000000004, 1e20], "b": 1 });
+        let decimals = DecimalFormat {
+            max_precision: Some(3),
+            whole_point: true,
+            plain: true,
+        };
+
+        let mut compact = vec![];
+        to_writer(&mut compact, &value, decimals).unwrap();
+        assert_eq!(
+            String::from_utf8(compact).unwrap(),
+            r#"{"a":[1.0,0.3,100000000000000000000.0],"b":1}"#
+        );
+
+        let mut pretty = vec![];
+        to_writer_pretty(&mut pretty, &value, DecimalFormat::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(pretty).unwrap(),
+            serde_json::to_string_pretty(&value).unwrap()
+        );
+    }
+}
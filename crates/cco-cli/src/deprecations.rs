@@ -0,0 +1,192 @@
+//! Reporting of warnings: references to deprecated elements and questionable input
+use crate::style;
+use cco::cco_document::CcoDocument;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Print the warnings found while building `document` to stderr
+pub fn report_warnings(document: &CcoDocument) {
+    print(&build_warnings(document));
+}
+
+/// The warnings found while building `document`
+pub fn build_warnings(document: &CcoDocument) -> Vec<Warning> {
+    document
+        .warnings()
+        .messages()
+        .iter()
+        .map(|message| Warning::Build(message.to_string()))
+        .collect()
+}
+
+/// Print `warnings` to stderr, e.g. those stored with a cached result
+pub fn print(warnings: &[Warning]) {
+    for warning in warnings {
+        warning.print();
+    }
+}
+
+/// A warning as printed, cached results keep them to print them again
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub enum Warning {
+    /// Found while building the document
+    Build(String),
+    /// A reference to a deprecated element, see [DeprecatedUse]
+    Deprecated {
+        path: String,
+        message: String,
+        referenced_by: Option<String>,
+        /// File defining the referencing element
+        file: Option<PathBuf>,
+    },
+}
+
+impl Warning {
+    /// The only place warnings are written
+    fn print(&self) {
+        match self {
+            Warning::Build(message) => {
+                if crate::log::json() {
+                    let phase = Some("build".to_string());
+                    let target = module_path!();
+                    crate::log::diagnostic(
+                        tracing::Level::WARN,
+                        target,
+                        message,
+                        &[("phase", phase)],
+                    );
+                    return;
+                }
+                eprintln!(
+                    "{} {message}",
+                    style::WARNING.paint(style::Stream::Stderr, "warning:")
+                );
+            }
+            Warning::Deprecated {
+                path,
+                message,
+                referenced_by,
+                file,
+            } => {
+                if crate::log::json() {
+                    crate::log::diagnostic(
+                        tracing::Level::WARN,
+                        module_path!(),
+                        &format!("deprecated: {message}"),
+                        &[
+                            ("phase", Some("evaluate".to_string())),
+                            ("path", Some(path.clone())),
+                            ("referenced_by", referenced_by.clone()),
+                            ("file", file.as_ref().map(|file| file.display().to_string())),
+                        ],
+                    );
+                    return;
+                }
+                let consumer = match (referenced_by, file) {
+                    (None, _) => "the evaluated expression".to_string(),
+                    (Some(path), Some(file)) => format!("{path} ({})", file.display()),
+                    (Some(path), None) => path.clone(),
+                };
+                eprintln!(
+                    "{} {path} is deprecated: {message} (referenced by {consumer})",
+                    style::WARNING.paint(style::Stream::Stderr, "warning:"),
+                );
+            }
+        }
+    }
+}
+
+/// Collects deprecated references while evaluating
+#[derive(Debug, Default)]
+pub struct Deprecations {
+    uses: Mutex<Vec<DeprecatedUse>>,
+}
+
+#[derive(Debug)]
+struct DeprecatedUse {
+    path: Vec<hcl::Identifier>,
+    message: String,
+    referenced_by: Option<Vec<hcl::Identifier>>,
+}
+
+impl DeprecatedUse {
+    /// The warning to print, `document` names the referencing file
+    fn warning(&self, document: Option<&CcoDocument>) -> Warning {
+        let file = self
+            .referenced_by
+            .as_ref()
+            .zip(document)
+            .and_then(|(path, document)| document.get(path)?.source);
+        Warning::Deprecated {
+            path: self.path.join("."),
+            message: self.message.clone(),
+            referenced_by: self.referenced_by.as_ref().map(|path| path.join(".")),
+            file: file.map(|file| file.to_path_buf()),
+        }
+    }
+}
+
+impl Deprecations {
+    /// Print a warning per collected reference to stderr
+    ///
+    /// Fails in `strict` mode if there is at least one.
+    pub fn report(&self, document: &CcoDocument, strict: bool) -> anyhow::Result<()> {
+        let warnings = self.warnings(document);
+        print(&warnings);
+
+        anyhow::ensure!(
+            !strict || warnings.is_empty(),
+            "{} reference(s) to deprecated elements",
+            warnings.len()
+        );
+        Ok(())
+    }
+
+    /// A warning per collected reference
+    pub fn warnings(&self, document: &CcoDocument) -> Vec<Warning> {
+        self.uses
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .iter()
+            .map(|deprecated| deprecated.warning(Some(document)))
+            .collect()
+    }
+}
+
+impl cco::observer::EvalObserver for Deprecations {
+    fn on_deprecated(
+        &self,
+        path: &[hcl::Identifier],
+        message: &str,
+        referenced_by: Option<&[hcl::Identifier]>,
+    ) {
+        self.uses
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(DeprecatedUse {
+                path: path.to_vec(),
+                message: message.to_string(),
+                referenced_by: referenced_by.map(<[_]>::to_vec),
+            });
+    }
+}
+
+/// Warns about deprecated references right away, for commands that keep evaluating (`cco serve`)
+#[derive(Debug, Default)]
+pub struct Immediate;
+
+impl cco::observer::EvalObserver for Immediate {
+    fn on_deprecated(
+        &self,
+        path: &[hcl::Identifier],
+        message: &str,
+        referenced_by: Option<&[hcl::Identifier]>,
+    ) {
+        let deprecated = DeprecatedUse {
+            path: path.to_vec(),
+            message: message.to_string(),
+            referenced_by: referenced_by.map(<[_]>::to_vec),
+        };
+        deprecated.warning(None).print();
+    }
+}
@@ -0,0 +1,188 @@
+//! Dependencies between addressable elements
+//!
+//! `cco deps service.api.url` lists the paths the element references, `--reverse` lists the
+//! elements that depend on it: what would change if it is edited. `--unused` lists the elements
+//! nothing references, the expressions passed with `--entry` are what is used from outside.
+use cco::cco_document::CcoDocument;
+use cco::hcl_documents::HclDocuments;
+
+/// Dotted path of an element, e.g. `service.api.port`
+pub fn parse_path(path: &str) -> anyhow::Result<Vec<hcl::Identifier>> {
+    path.split('.')
+        .map(|segment| {
+            anyhow::ensure!(!segment.is_empty(), "invalid path '{path}'");
+            Ok(hcl::Identifier::sanitized(segment))
+        })
+        .collect()
+}
+
+/// Lines of `path` with the location of the element defining it
+///
+/// Paths below an element (`service.api.tags` of a block `service.api`) show the location of the
+/// element, paths nothing provides only the path.
+pub fn references(
+    document: &CcoDocument,
+    documents: &HclDocuments,
+    path: &[hcl::Identifier],
+) -> anyhow::Result<Vec<String>> {
+    let references = document
+        .references(path)
+        .ok_or_else(|| anyhow::anyhow!("nothing defined at {}", path.join(".")))?;
+
+    Ok(references
+        .iter()
+        .map(|reference| {
+            let defined = (1..=reference.len())
+                .rev()
+                .find_map(|len| document.get(&reference[..len]));
+            match defined {
+                Some(info) => format!(
+                    "{}  {}",
+                    reference.join("."),
+                    location(documents, info.source, info.span)
+                ),
+                None => reference.join("."),
+            }
+        })
+        .collect())
+}
+
+/// Lines of the elements depending on `path`, indirect ones name the element they depend on
+pub fn dependents(
+    document: &CcoDocument,
+    documents: &HclDocuments,
+    path: &[hcl::Identifier],
+) -> anyhow::Result<Vec<String>> {
+    anyhow::ensure!(
+        (1..=path.len()).any(|len| document.get(&path[..len]).is_some()),
+        "nothing defined at {}",
+        path.join(".")
+    );
+
+    Ok(document
+        .dependents(path)
+        .into_iter()
+        .map(|dependent| {
+            let mut line = format!(
+                "{}  {}",
+                dependent.info.path.join("."),
+                location(documents, dependent.info.source, dependent.info.span)
+            );
+            if let Some(via) = dependent.via {
+                line.push_str(&format!(" (via {})", via.join(".")));
+            }
+            line
+        })
+        .collect())
+}
+
+/// Lines of the elements neither other elements nor the `entries` expressions reference
+pub fn unused(
+    document: &CcoDocument,
+    documents: &HclDocuments,
+    entries: &[String],
+) -> anyhow::Result<Vec<String>> {
+    Ok(document
+        .unused(&entry_points(document, entries)?)
+        .into_iter()
+        .map(|info| {
+            format!(
+                "{}  {}",
+                info.path.join("."),
+                location(documents, info.source, info.span)
+            )
+        })
+        .collect())
+}
+
+/// Paths referenced by the `--entry` expressions
+pub fn entry_points(
+    document: &CcoDocument,
+    entries: &[String],
+) -> anyhow::Result<Vec<Vec<hcl::Identifier>>> {
+    let mut entry_points = vec![];
+    for entry in entries {
+        let info = document
+            .check_expression(entry)
+            .and_then(|info| info.ensure_resolved())
+            .map_err(|err| anyhow::anyhow!("--entry {entry}: {err}"))?;
+        entry_points.extend(info.references.into_iter().map(|reference| reference.path));
+    }
+    Ok(entry_points)
+}
+
+fn location(
+    documents: &HclDocuments,
+    source: Option<&std::path::Path>,
+    span: Option<std::ops::Range<usize>>,
+) -> String {
+    documents.source_location(source, span.map(|span| span.start))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reverse() {
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(
+                r#"data service api {
+  port = 80
+  url  = "http://localhost:${self.port}"
+}
+data service web {
+  backend = service.api.url
+  api     = service.api
+  name    = "web"
+}
+data monitor api {
+  target = service.web.backend
+}
+"#,
+                None,
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        let path = parse_path("service.api.port").unwrap();
+
+        assert_eq!(
+            dependents(&document, &documents, &path).unwrap(),
+            [
+                "service.api.url  <input>:3:3",
+                "service.web.api  <input>:7:3",
+                "service.web.backend  <input>:6:3 (via service.api.url)",
+                "monitor.api.target  <input>:11:3 (via service.web.backend)",
+            ]
+        );
+        assert_eq!(
+            references(
+                &document,
+                &documents,
+                &parse_path("service.web.backend").unwrap()
+            )
+            .unwrap(),
+            ["service.api.url  <input>:3:3"]
+        );
+        assert!(dependents(&document, &documents, &parse_path("other.x").unwrap()).is_err());
+        assert!(parse_path("service..api").is_err());
+
+        assert_eq!(
+            unused(&document, &documents, &["monitor".to_string()]).unwrap(),
+            [
+                "service.web.api  <input>:7:3",
+                "service.web.name  <input>:8:3"
+            ]
+        );
+        assert_eq!(
+            unused(&document, &documents, &[]).unwrap(),
+            [
+                "service.web.api  <input>:7:3",
+                "service.web.name  <input>:8:3",
+                "monitor.api  <input>:10:1",
+            ]
+        );
+        assert!(unused(&document, &documents, &["missing.path".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! `cco dev` views for debugging documents
+use cco::cco_document::{CcoDocument, Kind, Node};
+use cco::hcl_documents::HclDocuments;
+use hcl_edit::Span;
+use std::collections::BTreeMap;
+
+/// Render the path tree of `document`, each node with the kind and substitution id of its element
+///
+/// ```text
+/// service
+/// └── api (block cco__block_0123456789abcdef)
+///     └── port (attribute cco__attribute_0123456789abcdef)
+/// ```
+pub fn tree(document: &CcoDocument, mut writer: impl std::io::Write) -> std::io::Result<()> {
+    for (ident, node) in &document.tree().root {
+        write_node(document, &mut writer, ident, node, "", None)?;
+    }
+    Ok(())
+}
+
+/// `last` is `None` for root nodes, which are not indented
+fn write_node(
+    document: &CcoDocument,
+    writer: &mut impl std::io::Write,
+    ident: &hcl::Identifier,
+    node: &Node,
+    prefix: &str,
+    last: Option<bool>,
+) -> std::io::Result<()> {
+    let (branch, indent) = match last {
+        None => ("", ""),
+        Some(false) => ("├── ", "│   "),
+        Some(true) => ("└── ", "    "),
+    };
+    write!(writer, "{prefix}{branch}{ident}")?;
+    match node.value.and_then(|index| document.addressable_at(index)) {
+        Some(addressable) => writeln!(writer, " ({} {})", addressable.kind, addressable.subst)?,
+        None => writeln!(writer)?,
+    }
+
+    let prefix = format!("{prefix}{indent}");
+    let count = node.children.len();
+    for (index, (ident, child)) in node.children.iter().enumerate() {
+        write_node(
+            document,
+            writer,
+            ident,
+            child,
+            &prefix,
+            Some(index + 1 == count),
+        )?;
+    }
+    Ok(())
+}
+
+/// Counts describing the size and shape of a document, see [stats]
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Stats {
+    sources: usize,
+    /// Root blocks by block type
+    blocks: BTreeMap<String, usize>,
+    /// Data blocks by data group
+    groups: BTreeMap<String, usize>,
+    /// Elements by [Kind]
+    addressables: BTreeMap<String, usize>,
+    /// Segments of the longest path
+    max_depth: usize,
+    /// Length of the formatted expressions, blocks and data groups have none
+    expression_size: Summary,
+    /// Paths referenced by an element
+    fan_out: Summary,
+    /// Elements referencing an element
+    fan_in: Summary,
+}
+
+/// Total and maximum of a count over all elements
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Summary {
+    total: usize,
+    max: usize,
+    /// Element with the maximum, `None` if it is 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_path: Option<String>,
+}
+
+impl Summary {
+    fn add(&mut self, path: &str, count: usize) {
+        self.total += count;
+        if count > self.max {
+            self.max = count;
+            self.max_path = Some(path.to_string());
+        }
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "total {}, max {}", self.total, self.max)?;
+        match &self.max_path {
+            Some(path) => write!(f, " ({path})"),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn stats(hcl_documents: &HclDocuments, document: &CcoDocument) -> Stats {
+    let mut stats = Stats {
+        sources: hcl_documents.source_count(),
+        ..Default::default()
+    };
+
+    for (index, _, block) in hcl_documents.blocks() {
+        let kind = block.ident.value().to_string();
+        if let (Some(label), "data") = (block.labels.first(), kind.as_str()) {
+            let mut group = hcl_documents.block_namespace(index).to_vec();
+            group.push(hcl::Identifier::sanitized(label.as_str()));
+            *stats.groups.entry(group.join(".")).or_default() += 1;
+        }
+        *stats.blocks.entry(kind).or_default() += 1;
+    }
+
+    let addressables: Vec<_> = (0..)
+        .map_while(|index| document.addressable_at(index))
+        .collect();
+    let mut fan_in: BTreeMap<String, usize> = BTreeMap::new();
+    for addressable in &addressables {
+        let path = addressable.path.join(".");
+        *stats
+            .addressables
+            .entry(addressable.kind.to_string())
+            .or_default() += 1;
+        stats.max_depth = stats.max_depth.max(addressable.path.len());
+
+        if !matches!(addressable.kind, Kind::Block | Kind::Virtual) {
+            let size = hcl::format::to_string(&addressable.expression).map_or(0, |e| e.len());
+            stats.expression_size.add(&path, size);
+        }
+
+        let references = document.references(&addressable.path).unwrap_or_default();
+        stats.fan_out.add(&path, references.len());
+        for reference in references {
+            let target = document
+                .get_most_specific_node(&reference)
+                .and_then(|(subst, _)| document.get_by_subst(subst));
+            if let Some(target) = target {
+                *fan_in.entry(target.path.join(".")).or_default() += 1;
+            }
+        }
+    }
+    for (path, count) in fan_in {
+        stats.fan_in.add(&path, count);
+    }
+
+    stats
+}
+
+impl Stats {
+    pub fn report(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        let total = |counts: &BTreeMap<String, usize>| counts.values().sum::<usize>();
+
+        writeln!(writer, "sources: {}", self.sources)?;
+        writeln!(writer, "blocks: {}", total(&self.blocks))?;
+        for (kind, count) in &self.blocks {
+            writeln!(writer, "  {kind}: {count}")?;
+        }
+        writeln!(writer, "data groups: {}", self.groups.len())?;
+        for (group, count) in &self.groups {
+            writeln!(writer, "  {group}: {count}")?;
+        }
+        writeln!(writer, "addressables: {}", total(&self.addressables))?;
+        for (kind, count) in &self.addressables {
+            writeln!(writer, "  {kind}: {count}")?;
+        }
+        writeln!(writer, "max depth: {}", self.max_depth)?;
+        writeln!(writer, "expression size: {}", self.expression_size)?;
+        writeln!(writer, "fan-out: {}", self.fan_out)?;
+        writeln!(writer, "fan-in: {}", self.fan_in)
+    }
+}
+
+/// Lines of the references nothing provides: element, its location and the referenced path
+pub fn unresolved(document: &CcoDocument, documents: &HclDocuments) -> Vec<String> {
+    document
+        .unresolved()
+        .into_iter()
+        .map(|unresolved| {
+            let info = unresolved.info;
+            format!(
+                "{}  {}  {}",
+                info.path.join("."),
+                documents.source_location(info.source, info.span.map(|span| span.start)),
+                unresolved.reference.join(".")
+            )
+        })
+        .collect()
+}
+
+/// Lines of the attributes of `type` blocks that never take effect: location and reason
+///
+/// A default is dead if every data block of the group defines the attribute itself. An attribute
+/// is unread if no element or `entry_points` path references it in any data block.
+pub fn defaults(
+    document: &CcoDocument,
+    documents: &HclDocuments,
+    entry_points: &[Vec<hcl::Identifier>],
+) -> Vec<String> {
+    let mut references = entry_points.to_vec();
+    for info in document.addressables() {
+        references.extend(document.references(info.path).unwrap_or_default());
+    }
+    let is_read = |path: &[hcl::Identifier]| {
+        references
+            .iter()
+            .any(|reference| reference.starts_with(path) || path.starts_with(reference))
+    };
+
+    let mut lines = vec![];
+    for (index, source, block) in documents.blocks() {
+        if block.ident.value().as_str() != "type" {
+            continue;
+        }
+        let Some(label) = block.labels.first() else {
+            continue;
+        };
+        let mut group = documents.block_namespace(index).to_vec();
+        group.push(hcl::Identifier::sanitized(label.as_str()));
+        let data_blocks: Vec<_> = document
+            .addressables()
+            .filter(|info| info.kind == Kind::Block && info.path.starts_with(&group))
+            .map(|info| info.path)
+            .collect();
+        // a type block without data blocks is reported while building
+        if data_blocks.is_empty() {
+            continue;
+        }
+
+        for attribute in block.body.attributes() {
+            let name = hcl::Identifier::sanitized(attribute.key.value());
+            let paths: Vec<_> = data_blocks
+                .iter()
+                .map(|data_block| {
+                    let mut path = data_block.to_vec();
+                    path.push(name.clone());
+                    path
+                })
+                .collect();
+
+            let overridden = paths.iter().all(|path| {
+                document
+                    .get(path)
+                    .is_some_and(|info| info.kind == Kind::Attribute)
+            });
+            let reason = if overridden {
+                "overridden by every data block"
+            } else if !paths.iter().any(|path| is_read(path)) {
+                "never read"
+            } else {
+                continue;
+            };
+            lines.push(format!(
+                "{}.{name}  {}  {reason}",
+                group.join("."),
+                documents.source_location(
+                    source.as_deref(),
+                    documents
+                        .block_span(index, attribute.span())
+                        .map(|span| span.start)
+                ),
+            ));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render_tree() {
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(
+                "type service {\n  replicas = 1\n}\n\
+                 data service api {\n  port = 80\n}\n\
+                 data service web {}\n",
+                None,
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+
+        let mut out = vec![];
+        tree(&document, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        // substitution ids are hashes, only compare the structure
+        let lines: Vec<_> = out
+            .lines()
+            .map(|line| line.split(" cco__").next().unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "service (virtual",
+                "├── api (block",
+                "│   ├── port (attribute",
+                "│   └── replicas (defaultattribute",
+                "└── web (block",
+                "    └── replicas (defaultattribute",
+            ]
+        );
+        let service = &document.tree().root[&hcl::Identifier::unchecked("service")];
+        let api = service.children[&hcl::Identifier::unchecked("api")].value;
+        let subst = &document.addressable_at(api.unwrap()).unwrap().subst;
+        assert!(out.contains(&format!("├── api (block {subst})")));
+    }
+
+    #[test]
+    fn count_stats() {
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(
+                "type service {\n  replicas = 1\n}\n\
+                 data service api {\n  port = 80\n}\n\
+                 data service web {\n  url = \"http://${service.api.port}/${self.replicas}\"\n}\n\
+                 data db main {\n  port = service.api.port\n}\n",
+                None,
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+
+        let stats = stats(&documents, &document);
+        let counts = |pairs: &[(&str, usize)]| -> BTreeMap<String, usize> {
+            pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+        };
+        assert_eq!(stats.sources, 1);
+        assert_eq!(stats.blocks, counts(&[("data", 3), ("type", 1)]));
+        assert_eq!(stats.groups, counts(&[("db", 1), ("service", 2)]));
+        assert_eq!(
+            stats.addressables,
+            counts(&[
+                ("attribute", 3),
+                ("block", 3),
+                ("defaultattribute", 2),
+                ("virtual", 2)
+            ])
+        );
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(
+            stats.expression_size.max_path.as_deref(),
+            Some("service.web.url")
+        );
+        assert_eq!(
+            stats.fan_out.to_string(),
+            "total 3, max 2 (service.web.url)"
+        );
+        assert_eq!(
+            stats.fan_in.to_string(),
+            "total 3, max 2 (service.api.port)"
+        );
+
+        let mut out = vec![];
+        stats.report(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("sources: 1\nblocks: 4\n  data: 3\n  type: 1\n"));
+        assert!(out.contains("addressables: 10\n"));
+    }
+
+    #[test]
+    fn unresolved_references() {
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(
+                "data service api {\n  port = 80\n  url  = \"http://${self.hots}:${self.port}\"\n}\n",
+                Some("a.cco.hcl".into()),
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+
+        assert_eq!(
+            unresolved(&document, &documents),
+            ["service.api.url  a.cco.hcl:3:3  service.api.hots"]
+        );
+    }
+
+    #[test]
+    fn dead_defaults() {
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(
+                "type service {\n  port = 80\n  debug = false\n  name = \"x\"\n}\n\
+                 data service api {\n  port = 8080\n}\n\
+                 data service web {\n  port = 8081\n  name = \"web\"\n}\n\
+                 data proxy main {\n  upstream = service.api.name\n}\n",
+                Some("a.cco.hcl".into()),
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+
+        assert_eq!(
+            defaults(&document, &documents, &[]),
+            [
+                "service.port  a.cco.hcl:2:3  overridden by every data block",
+                "service.debug  a.cco.hcl:3:3  never read",
+            ]
+        );
+        let entry = vec!["service".into(), "web".into()];
+        assert_eq!(
+            defaults(&document, &documents, &[entry]),
+            ["service.port  a.cco.hcl:2:3  overridden by every data block"]
+        );
+    }
+}
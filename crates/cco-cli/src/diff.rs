@@ -0,0 +1,222 @@
+//! Line based diff in unified format
+//!
+//! Uses the Myers algorithm, which finds a shortest edit script in `O((n + m) * d)` time.
+use crate::style::{Stream, ADDED, BOLD, HUNK, REMOVED};
+
+/// Number of unchanged lines shown around changes
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    /// Line `.0` of old equals line `.1` of new
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Unified diff from `old` to `new`, `None` if both are equal
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let edits = edits(&old_lines, &new_lines);
+
+    let mut out = format!("--- {old_name}\n+++ {new_name}\n");
+    for hunk in hunks(&edits) {
+        let (old_start, old_len, new_start, new_len) = hunk_range(hunk);
+        out.push_str(&format!(
+            "@@ -{old_start},{old_len} +{new_start},{new_len} @@\n"
+        ));
+        for edit in hunk {
+            let (prefix, line) = match *edit {
+                Edit::Equal(index, _) => (' ', old_lines[index]),
+                Edit::Delete(index) => ('-', old_lines[index]),
+                Edit::Insert(index) => ('+', new_lines[index]),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+
+    // only line breaks at the end of the file differ
+    if out.lines().count() == 2 {
+        out.push_str("@@ line endings differ @@\n");
+    }
+    Some(out)
+}
+
+/// Color the lines of a [unified] diff
+pub fn paint(diff: &str, stream: Stream) -> String {
+    let mut out = String::with_capacity(diff.len());
+    for line in diff.lines() {
+        let style = match line.as_bytes().first() {
+            _ if line.starts_with("---") || line.starts_with("+++") => Some(BOLD),
+            Some(b'@') => Some(HUNK),
+            Some(b'-') => Some(REMOVED),
+            Some(b'+') => Some(ADDED),
+            _ => None,
+        };
+        match style {
+            Some(style) => out.push_str(&style.paint(stream, line)),
+            None => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Shortest edit script from `a` to `b`
+fn edits(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let offset = n + m + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = vec![];
+
+    'search: for d in 0..=n + m {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let (mut x, mut y) = (n, m);
+    let mut edits = vec![];
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let index = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert(prev_y as usize));
+            } else {
+                edits.push(Edit::Delete(prev_x as usize));
+            }
+        }
+        (x, y) = (prev_x, prev_y);
+    }
+
+    edits.reverse();
+    edits
+}
+
+/// Changes with surrounding context, close changes share a hunk
+fn hunks(edits: &[Edit]) -> Vec<&[Edit]> {
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(..)))
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for change in changes {
+        let start = change.saturating_sub(CONTEXT);
+        let end = (change + CONTEXT + 1).min(edits.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    hunks
+        .into_iter()
+        .map(|(start, end)| &edits[start..end])
+        .collect()
+}
+
+/// Start (1-based) and length of a hunk in old and new
+fn hunk_range(hunk: &[Edit]) -> (usize, usize, usize, usize) {
+    let (mut old_start, mut old_len, mut new_start, mut new_len) = (None, 0, None, 0);
+    for edit in hunk {
+        match *edit {
+            Edit::Equal(old, new) => {
+                old_start.get_or_insert(old);
+                new_start.get_or_insert(new);
+                old_len += 1;
+                new_len += 1;
+            }
+            Edit::Delete(old) => {
+                old_start.get_or_insert(old);
+                old_len += 1;
+            }
+            Edit::Insert(new) => {
+                new_start.get_or_insert(new);
+                new_len += 1;
+            }
+        }
+    }
+
+    // an empty range names the line before it
+    let start = |start: Option<usize>, len: usize| match start {
+        Some(start) if len > 0 => start + 1,
+        Some(start) => start,
+        None => 0,
+    };
+    (
+        start(old_start, old_len),
+        old_len,
+        start(new_start, new_len),
+        new_len,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn equal() {
+        assert_eq!(unified("a\nb\n", "a\nb\n", "old", "new"), None);
+    }
+
+    #[test]
+    fn changed_lines() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n";
+        assert_eq!(
+            unified(old, new, "old", "new").unwrap(),
+            "--- old\n+++ new\n\
+             @@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+             @@ -8,3 +8,4 @@\n 8\n 9\n 10\n+11\n"
+        );
+    }
+
+    #[test]
+    fn from_empty() {
+        assert_eq!(
+            unified("", "a\n", "old", "new").unwrap(),
+            "--- old\n+++ new\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+    }
+}
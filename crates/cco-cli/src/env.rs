@@ -0,0 +1,81 @@
+//! Environment variables from evaluated values
+use cco::value::Value;
+
+/// Flatten an object into environment variables
+///
+/// Nested object keys are joined with `_` and uppercased (`{db: {host: ..}}` becomes `DB_HOST`).
+/// Strings are used as they are, other scalars in their JSON form and lists as JSON.
+pub fn flatten(value: &Value) -> anyhow::Result<Vec<(String, String)>> {
+    anyhow::ensure!(
+        value.as_object().is_some(),
+        "expected an object to convert into environment variables, got {}",
+        value.type_name()
+    );
+
+    let mut variables = vec![];
+    flatten_into(value, String::new(), &mut variables)?;
+    Ok(variables)
+}
+
+fn flatten_into(
+    value: &Value,
+    name: String,
+    variables: &mut Vec<(String, String)>,
+) -> anyhow::Result<()> {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let key = key.to_uppercase();
+                let name = if name.is_empty() {
+                    key
+                } else {
+                    format!("{name}_{key}")
+                };
+                flatten_into(value, name, variables)?;
+            }
+        }
+        value => variables.push((name, to_string(value)?)),
+    }
+    Ok(())
+}
+
+/// Strings as they are, bytes as base64, anything else as JSON
+pub fn to_string(value: &Value) -> anyhow::Result<String> {
+    Ok(match value {
+        Value::String(string) => string.clone(),
+        Value::Bytes(bytes) => cco::base64::encode(bytes),
+        value => serde_json::to_string(value)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nested() {
+        let value: Value = serde_json::json!({
+            "db": { "host": "localhost", "port": 5432 },
+            "DEBUG": true,
+            "hosts": ["a", "b"],
+        })
+        .try_into()
+        .unwrap();
+
+        assert_eq!(
+            flatten(&value).unwrap(),
+            [
+                ("DB_HOST", "localhost"),
+                ("DB_PORT", "5432"),
+                ("DEBUG", "true"),
+                ("HOSTS", r#"["a","b"]"#),
+            ]
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn not_an_object() {
+        assert!(flatten(&Value::Integer(1)).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+//! `{expression}` placeholders in command lines
+//!
+//! `{{` and `}}` stand for literal braces. Braces inside a placeholder must be balanced, so
+//! object expressions (`{ {a = 1}.a }`) work as well.
+
+/// A piece of a templated argument
+#[derive(Debug, Clone, PartialEq)]
+pub enum Part {
+    Text(String),
+    Placeholder(String),
+}
+
+/// Split an argument into text and placeholders
+pub fn parse(arg: &str) -> anyhow::Result<Vec<Part>> {
+    let mut parts = vec![];
+    let mut text = String::new();
+    let mut chars = arg.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut depth = 1;
+                let mut expression = String::new();
+                for c in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    expression.push(c);
+                }
+                anyhow::ensure!(depth == 0, "unclosed placeholder in '{arg}'");
+                anyhow::ensure!(
+                    !expression.trim().is_empty(),
+                    "empty placeholder in '{arg}'"
+                );
+
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Placeholder(expression.trim().to_string()));
+            }
+            '}' => anyhow::bail!("unmatched '}}' in '{arg}' (use '}}}}' for a literal brace)"),
+            c => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Quote an argument for POSIX shells, for printing command lines
+pub fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=@,+%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            parse("{app.image}:{ app.tag }").unwrap(),
+            [
+                Part::Placeholder("app.image".into()),
+                Part::Text(":".into()),
+                Part::Placeholder("app.tag".into()),
+            ]
+        );
+        assert_eq!(
+            parse("{{literal}} { {a = 1}.a }").unwrap(),
+            [
+                Part::Text("{literal} ".into()),
+                Part::Placeholder("{a = 1}.a".into()),
+            ]
+        );
+        assert!(parse("{unclosed").is_err());
+        assert!(parse("a}b").is_err());
+        assert!(parse("{}").is_err());
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(shell_quote("nginx:1.25"), "nginx:1.25");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
@@ -0,0 +1,233 @@
+//! Expectations checked by `cco test`
+//!
+//! Test files (`*.test.hcl`) contain `expect` blocks labeled with the expression to check. The
+//! expected value is either an HCL expression or a fixture file relative to the test file:
+//!
+//! ```hcl
+//! expect "service.api.port" {
+//!   value = 8080
+//! }
+//!
+//! expect "service.api" {
+//!   file = "golden/api.yaml"
+//! }
+//! ```
+use cco::cco_document::CcoDocument;
+use cco::testing::FixtureCheck;
+use std::path::{Path, PathBuf};
+
+/// Suffix of test files in directories
+const TEST_FILE_SUFFIX: &str = ".test.hcl";
+
+#[derive(Debug)]
+pub struct Expectation {
+    pub expression: String,
+    pub expected: Expected,
+}
+
+#[derive(Debug)]
+pub enum Expected {
+    Value(hcl::Expression),
+    /// YAML or JSON file, by extension
+    File(PathBuf),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Passed,
+    /// The fixture file was written with the actual value
+    Updated,
+    Failed(String),
+}
+
+/// Test files named by `paths`, directories contribute their `*.test.hcl` files
+pub fn test_files(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let mut found: Vec<_> = std::fs::read_dir(path)
+            .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", path.display()))?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|file| {
+                let name = file.file_name().and_then(|name| name.to_str());
+                name.is_some_and(|name| name.ends_with(TEST_FILE_SUFFIX)) && file.is_file()
+            })
+            .collect();
+        found.sort();
+        files.extend(found);
+    }
+    anyhow::ensure!(
+        !files.is_empty(),
+        "no test files (*{TEST_FILE_SUFFIX}) found"
+    );
+    Ok(files)
+}
+
+/// `expect` blocks of a test file, fixture paths are resolved relative to `path`
+pub fn parse(path: &Path, contents: &str) -> anyhow::Result<Vec<Expectation>> {
+    let display = path.display();
+    let body: hcl::Body =
+        hcl::parse(contents).map_err(|err| anyhow::anyhow!("failed to parse {display}: {err}"))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut expectations = vec![];
+    for structure in body.into_inner() {
+        let hcl::Structure::Block(block) = structure else {
+            anyhow::bail!("{display}: only `expect` blocks are allowed");
+        };
+        anyhow::ensure!(
+            block.identifier.as_str() == "expect",
+            "{display}: unknown block `{}`, only `expect` blocks are allowed",
+            block.identifier
+        );
+        let [label] = &block.labels[..] else {
+            anyhow::bail!("{display}: `expect` needs the expression to check as its only label");
+        };
+        let expression = label.as_str().to_string();
+
+        let mut expected = None;
+        for attribute in block.body.attributes() {
+            let value = match (attribute.key(), attribute.expr()) {
+                ("value", expr) => Expected::Value(expr.clone()),
+                ("file", hcl::Expression::String(file)) => Expected::File(dir.join(file)),
+                ("file", _) => {
+                    anyhow::bail!("{display}: expect \"{expression}\": `file` must be a string")
+                }
+                (key, _) => {
+                    anyhow::bail!("{display}: expect \"{expression}\": unknown attribute `{key}`")
+                }
+            };
+            anyhow::ensure!(
+                expected.replace(value).is_none(),
+                "{display}: expect \"{expression}\": set either `value` or `file`, not both"
+            );
+        }
+        let expected = expected.ok_or_else(|| {
+            anyhow::anyhow!("{display}: expect \"{expression}\": `value` or `file` is missing")
+        })?;
+        expectations.push(Expectation {
+            expression,
+            expected,
+        });
+    }
+    Ok(expectations)
+}
+
+/// Compare the value of the expression with the expectation, `update` rewrites fixture files
+pub fn check(document: &CcoDocument, expectation: &Expectation, update: bool) -> Outcome {
+    let expression = &expectation.expression;
+    match &expectation.expected {
+        Expected::File(fixture) => {
+            match cco::testing::check_fixture(document, expression, fixture, update) {
+                Ok(FixtureCheck::Matched) => Outcome::Passed,
+                Ok(FixtureCheck::Updated) => Outcome::Updated,
+                Err(message) => Outcome::Failed(message),
+            }
+        }
+        Expected::Value(value) => {
+            let evaluate = || -> anyhow::Result<_> {
+                let actual = serde_json::to_value(cco::testing::evaluate(document, expression)?)?;
+                let expected = serde_json::to_value(document.evaluate_in_context(value.clone())?)?;
+                Ok((expected, actual))
+            };
+            match evaluate() {
+                Ok((expected, actual)) if expected == actual => Outcome::Passed,
+                Ok((expected, actual)) => Outcome::Failed(format!(
+                    "value of {expression} differs\n  expected: {expected}\n  actual:   {actual}"
+                )),
+                Err(err) => Outcome::Failed(format!("failed to evaluate {expression}: {err:#}")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn document() -> CcoDocument {
+        cco::testing::load_str([(
+            "a.cco.hcl",
+            "data service api {\n  port = 8080\n  name = \"api\"\n}\n",
+        )])
+        .unwrap()
+    }
+
+    #[test]
+    fn values() {
+        let expectations = parse(
+            Path::new("tests/a.test.hcl"),
+            r#"
+expect "service.api.port" {
+  value = 8080
+}
+expect "service.api" {
+  value = { name = "api", port = 8000 + 80 }
+}
+expect "service.api.name" {
+  value = "web"
+}
+expect "service.web" {
+  value = 1
+}
+"#,
+        )
+        .unwrap();
+        let outcomes: Vec<_> = expectations
+            .iter()
+            .map(|expectation| check(&document(), expectation, false))
+            .collect();
+
+        assert_eq!(outcomes[0], Outcome::Passed);
+        assert_eq!(outcomes[1], Outcome::Passed);
+        assert!(matches!(&outcomes[2], Outcome::Failed(message) if message.contains("\"web\"")));
+        assert!(
+            matches!(&outcomes[3], Outcome::Failed(message) if message.contains("failed to evaluate"))
+        );
+    }
+
+    #[test]
+    fn files() {
+        let dir = std::env::temp_dir().join(format!("cco-expect-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("golden")).unwrap();
+        std::fs::write(dir.join("golden/api.yaml"), "port: 1\nname: api\n").unwrap();
+        let test_file = dir.join("api.test.hcl");
+        let contents = "expect \"service.api\" {\n  file = \"golden/api.yaml\"\n}\n";
+        std::fs::write(&test_file, contents).unwrap();
+        std::fs::write(dir.join("other.hcl"), "").unwrap();
+
+        let found = test_files(std::slice::from_ref(&dir)).unwrap();
+        assert_eq!(found, std::slice::from_ref(&test_file));
+        let expectations = parse(&test_file, contents).unwrap();
+        assert!(matches!(
+            &expectations[0].expected,
+            Expected::File(file) if file == &dir.join("golden/api.yaml")
+        ));
+
+        let document = document();
+        assert!(matches!(
+            check(&document, &expectations[0], false),
+            Outcome::Failed(_)
+        ));
+        assert_eq!(check(&document, &expectations[0], true), Outcome::Updated);
+        assert_eq!(check(&document, &expectations[0], false), Outcome::Passed);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid() {
+        let error = |contents| {
+            parse(Path::new("a.test.hcl"), contents)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error("x = 1").contains("only `expect` blocks"));
+        assert!(error("expect {\n value = 1\n}").contains("only label"));
+        assert!(error("expect \"a\" {}").contains("missing"));
+        assert!(error("expect \"a\" {\n value = 1\n file = \"a.yaml\"\n}").contains("not both"));
+        assert!(error("expect \"a\" {\n file = 1\n}").contains("must be a string"));
+    }
+}
@@ -0,0 +1,209 @@
+//! `--explain` report of the addressables an evaluation resolved and `cco explain`
+use cco::cco_document::{CcoDocument, Kind};
+use cco::hcl_documents::HclDocuments;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Collects resolved addressables in the order they were resolved, dependencies first
+#[derive(Debug, Default)]
+pub struct Explain {
+    resolved: Mutex<Vec<(Vec<hcl::Identifier>, Kind)>>,
+}
+
+impl Explain {
+    pub fn report(
+        &self,
+        hcl_documents: &HclDocuments,
+        document: &CcoDocument,
+        mut writer: impl std::io::Write,
+    ) -> std::io::Result<()> {
+        let resolved = self
+            .resolved
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if resolved.is_empty() {
+            return writeln!(writer, "resolved no addressables");
+        }
+
+        writeln!(writer, "resolved {} addressables:", resolved.len())?;
+        let width = resolved.len().to_string().len();
+        for (index, (path, kind)) in resolved.iter().enumerate() {
+            let source = document
+                .get(path)
+                .and_then(|info| Some((info.source?, info.span)))
+                .map(|(source, span)| {
+                    hcl_documents.source_location(Some(source), span.map(|span| span.start))
+                });
+            write!(
+                writer,
+                "  {:>width$}. {} ({kind})",
+                index + 1,
+                path.join(".")
+            )?;
+            match source {
+                Some(source) => writeln!(writer, " {source}")?,
+                None => writeln!(writer)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl cco::observer::EvalObserver for Explain {
+    fn on_resolve(&self, path: &[hcl::Identifier], kind: Kind, _duration: Duration) {
+        self.resolved
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push((path.to_vec(), kind));
+    }
+}
+
+/// Lines of the layers defining the attribute at `path`, in order of precedence with the one in
+/// effect last
+pub fn merges(
+    document: &CcoDocument,
+    documents: &HclDocuments,
+    sources: &[(Option<PathBuf>, String)],
+    path: &[hcl::Identifier],
+) -> anyhow::Result<Vec<String>> {
+    let definitions = document
+        .definitions(path)
+        .ok_or_else(|| anyhow::anyhow!("nothing defined at {}", path.join(".")))?;
+    anyhow::ensure!(
+        !definitions.is_empty(),
+        "{} is not an attribute of a data block",
+        path.join(".")
+    );
+
+    let count = definitions.len();
+    Ok(definitions
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, definition)| {
+            let origin = &definition.origin;
+            let span = origin.span.clone();
+            let mut line = format!(
+                "{}. {}  {}",
+                index + 1,
+                definition.layer,
+                documents.source_location(
+                    origin.source.as_deref(),
+                    span.as_ref().map(|span| span.start)
+                )
+            );
+            if let Some(expression) =
+                crate::search::expression_text(sources, origin.source.as_deref(), span)
+            {
+                line.push_str(&format!("  {expression}"));
+            }
+            if index + 1 == count {
+                line.push_str("  (in effect)");
+            }
+            line
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn resolution_order() {
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(
+                "data service api {\n  port = 80\n  url  = \"http://api:${self.port}\"\n}\n",
+                Some("a.cco.hcl".into()),
+            )
+            .unwrap();
+        let explain = Arc::new(Explain::default());
+        let document = CcoDocument::builder()
+            .observer(explain.clone())
+            .build(&documents)
+            .unwrap();
+
+        let mut report = vec![];
+        explain.report(&documents, &document, &mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "resolved no addressables\n"
+        );
+
+        document.evaluate_path(&["service", "api", "url"]).unwrap();
+        let mut report = vec![];
+        explain.report(&documents, &document, &mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "resolved 2 addressables:\n  \
+             1. service.api.port (attribute) a.cco.hcl:2:3\n  \
+             2. service.api.url (attribute) a.cco.hcl:3:3\n"
+        );
+    }
+
+    #[test]
+    fn merged_layers() {
+        let text = "partial limits {\n  cpu = 2\n}\n\
+                    type service {\n  cpu = 1\n}\n\
+                    data service api {\n  include_partials = [\"limits\"]\n  cpu = 4\n}\n\
+                    data service web {\n  include_partials = [\"limits\"]\n}\n";
+        let mut documents = HclDocuments::default();
+        documents.insert_str(text, None).unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        let sources = [(None, text.to_string())];
+        let path = |path: &str| crate::deps::parse_path(path).unwrap();
+
+        assert_eq!(
+            merges(&document, &documents, &sources, &path("service.api.cpu")).unwrap(),
+            [
+                "1. type block  <input>:5:3  1",
+                "2. partial limits  <input>:2:3  2",
+                "3. data block  <input>:9:3  4  (in effect)",
+            ]
+        );
+        assert_eq!(
+            merges(&document, &documents, &sources, &path("service.web.cpu")).unwrap(),
+            [
+                "1. type block  <input>:5:3  1",
+                "2. partial limits  <input>:2:3  2  (in effect)",
+            ]
+        );
+        assert!(merges(&document, &documents, &sources, &path("service.api")).is_err());
+        assert!(merges(&document, &documents, &sources, &path("service.x")).is_err());
+    }
+
+    #[test]
+    fn merged_overlays() {
+        let base = "data service api {\n  include_partials = [\"limits\"]\n  cpu = 2\n}\n\
+                    partial limits {\n  cpu = 1\n}\n";
+        let prod = "data service api {\n  cpu = 4\n}\n";
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(base, Some("base/a.cco.hcl".into()))
+            .unwrap();
+        documents
+            .insert_str(prod, Some("overlays/prod/a.cco.hcl".into()))
+            .unwrap();
+        let document = CcoDocument::builder()
+            .merge_policy(cco::cco_document::MergePolicy::Override)
+            .build(&documents)
+            .unwrap();
+        let sources = [
+            (Some("base/a.cco.hcl".into()), base.to_string()),
+            (Some("overlays/prod/a.cco.hcl".into()), prod.to_string()),
+        ];
+        let path = crate::deps::parse_path("service.api.cpu").unwrap();
+
+        assert_eq!(
+            merges(&document, &documents, &sources, &path).unwrap(),
+            [
+                "1. partial limits  base/a.cco.hcl:6:3  1",
+                "2. data block  base/a.cco.hcl:3:3  2",
+                "3. data block  overlays/prod/a.cco.hcl:2:3  4  (in effect)",
+            ]
+        );
+    }
+}
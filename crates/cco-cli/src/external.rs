@@ -0,0 +1,132 @@
+//! `external` blocks running a command (`--allow-exec`)
+//!
+//! ```hcl
+//! external git_info {
+//!   program = ["git", "rev-parse", "HEAD"]
+//! }
+//! ```
+//!
+//! `external.git_info` is the output of the command: parsed if it is JSON, otherwise the text
+//! without the trailing newline. The command runs when the block is referenced, at most once.
+use cco::cco_document::CcoDocumentBuilder;
+use std::process::{Command, Stdio};
+
+/// Block type of commands
+pub const KIND: &str = "external";
+
+/// Handle `external` blocks, commands only run if `allow` is set
+pub fn register(builder: CcoDocumentBuilder, allow: bool) -> CcoDocumentBuilder {
+    builder.data_source(KIND, move |_labels, body| match allow {
+        true => run(body),
+        false => Err("running commands requires --allow-exec".to_string()),
+    })
+}
+
+fn run(body: hcl::Value) -> Result<hcl::Value, String> {
+    let hcl::Value::Object(mut body) = body else {
+        return Err("invalid block".to_string());
+    };
+    let program = match body.shift_remove("program") {
+        Some(hcl::Value::Array(program)) => program
+            .into_iter()
+            .map(|arg| match arg {
+                hcl::Value::String(arg) => Ok(arg),
+                other => Err(format!("program arguments must be strings, got {other}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(_) => return Err("program must be a list of strings".to_string()),
+        None => return Err("missing attribute program".to_string()),
+    };
+    let working_dir = match body.shift_remove("working_dir") {
+        Some(hcl::Value::String(dir)) => Some(dir),
+        Some(_) => return Err("working_dir must be a string".to_string()),
+        None => None,
+    };
+    if let Some(name) = body.keys().next() {
+        return Err(format!("unknown attribute {name}"));
+    }
+    let Some((name, args)) = program.split_first() else {
+        return Err("program must not be empty".to_string());
+    };
+
+    let mut command = Command::new(name);
+    command.args(args).stdin(Stdio::null());
+    if let Some(dir) = working_dir {
+        command.current_dir(dir);
+    }
+    tracing::debug!(?program, "running external command");
+    let output = command
+        .output()
+        .map_err(|err| format!("failed to run {name}: {err}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "{name} failed ({}): {}",
+            output.status,
+            stderr.trim()
+        ));
+    }
+
+    let stdout =
+        String::from_utf8(output.stdout).map_err(|_| format!("{name} printed invalid UTF-8"))?;
+    parse_output(&stdout)
+}
+
+/// JSON output parsed, any other text without the trailing newline
+pub fn parse_output(text: &str) -> Result<hcl::Value, String> {
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(value) => crate::plugin::to_hcl(value),
+        Err(_) => Ok(hcl::Value::from(text.strip_suffix('\n').unwrap_or(text))),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    fn evaluate(source: &str, allow: bool) -> Result<serde_json::Value, String> {
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents.insert_str(source, None).unwrap();
+        let document = register(cco::cco_document::CcoDocument::builder(), allow)
+            .build(&documents)
+            .unwrap();
+        document
+            .evaluate_path(&["external", "out"])
+            .map(|value| serde_json::to_value(value).unwrap())
+            .map_err(|err| format!("{err:#}"))
+    }
+
+    #[test]
+    fn output() {
+        let json = r#"external out { program = ["echo", "{\"a\": [1, 2]}"] }"#;
+        assert_eq!(evaluate(json, true), Ok(serde_json::json!({ "a": [1, 2] })));
+
+        let text = r#"external out { program = ["echo", "hello world"] }"#;
+        assert_eq!(evaluate(text, true), Ok("hello world".into()));
+
+        let dir = r#"external out {
+  program = ["pwd"]
+  working_dir = "/"
+}"#;
+        assert_eq!(evaluate(dir, true), Ok("/".into()));
+    }
+
+    #[test]
+    fn errors() {
+        let text = r#"external out { program = ["echo", "hi"] }"#;
+        assert!(evaluate(text, false)
+            .unwrap_err()
+            .contains("requires --allow-exec"));
+
+        let failing = r#"external out { program = ["sh", "-c", "echo nope >&2; exit 3"] }"#;
+        assert!(evaluate(failing, true).unwrap_err().contains("nope"));
+
+        let unknown = r#"external out {
+  program = ["echo"]
+  shell = true
+}"#;
+        assert!(evaluate(unknown, true)
+            .unwrap_err()
+            .contains("unknown attribute shell"));
+    }
+}
@@ -0,0 +1,178 @@
+//! `--only` and `--exclude` output filtering
+use cco::value::Value;
+
+/// A dot separated path where `*` matches any single key or index
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathPattern(Vec<String>);
+
+impl std::str::FromStr for PathPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let segments: Vec<String> = s.split('.').map(str::to_string).collect();
+        if segments.iter().any(String::is_empty) {
+            return Err(format!("invalid path pattern '{s}'"));
+        }
+        Ok(Self(segments))
+    }
+}
+
+fn matches(segment: &str, key: &str) -> bool {
+    segment == "*" || segment == key
+}
+
+/// Apply `--only` and then `--exclude`
+///
+/// Without `only` patterns everything is kept.
+pub fn apply(value: Value, only: &[PathPattern], exclude: &[PathPattern]) -> Value {
+    let mut value = if only.is_empty() {
+        value
+    } else {
+        let patterns: Vec<&[String]> = only.iter().map(|p| p.0.as_slice()).collect();
+        select(value, &patterns).unwrap_or_else(|| Value::Object(Default::default()))
+    };
+
+    let patterns: Vec<&[String]> = exclude.iter().map(|p| p.0.as_slice()).collect();
+    remove(&mut value, &patterns);
+    value
+}
+
+/// Keep everything matched by at least one pattern, `None` if nothing matched
+fn select(value: Value, patterns: &[&[String]]) -> Option<Value> {
+    if patterns.iter().any(|pattern| pattern.is_empty()) {
+        return Some(value);
+    }
+
+    let descend = |key: &str| -> Vec<&[String]> {
+        patterns
+            .iter()
+            .filter(|pattern| matches(&pattern[0], key))
+            .map(|pattern| &pattern[1..])
+            .collect()
+    };
+
+    match value {
+        Value::Object(object) => {
+            let object = object
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    let patterns = descend(&key);
+                    if patterns.is_empty() {
+                        return None;
+                    }
+                    select(value, &patterns).map(|value| (key, value))
+                })
+                .collect();
+            let object = Value::Object(object);
+            let is_empty = object.entries().next().is_none();
+            (!is_empty).then_some(object)
+        }
+        Value::Array(array) => {
+            let array: Vec<_> = array
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, value)| {
+                    let patterns = descend(&index.to_string());
+                    if patterns.is_empty() {
+                        return None;
+                    }
+                    select(value, &patterns)
+                })
+                .collect();
+            (!array.is_empty()).then_some(Value::Array(array))
+        }
+        _ => None,
+    }
+}
+
+/// Remove everything matched by any pattern
+fn remove(value: &mut Value, patterns: &[&[String]]) {
+    if patterns.is_empty() {
+        return;
+    }
+
+    let is_removed = |key: &str| {
+        patterns
+            .iter()
+            .any(|pattern| pattern.len() == 1 && matches(&pattern[0], key))
+    };
+    let descend = |key: &str| -> Vec<&[String]> {
+        patterns
+            .iter()
+            .filter(|pattern| pattern.len() > 1 && matches(&pattern[0], key))
+            .map(|pattern| &pattern[1..])
+            .collect()
+    };
+
+    match value {
+        Value::Object(object) => {
+            object.retain(|key, _| !is_removed(key));
+            for (key, value) in object.iter_mut() {
+                remove(value, &descend(key));
+            }
+        }
+        Value::Array(array) => {
+            *array = std::mem::take(array)
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _)| !is_removed(&index.to_string()))
+                .map(|(index, mut value)| {
+                    remove(&mut value, &descend(&index.to_string()));
+                    value
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn value() -> Value {
+        serde_json::json!({
+            "app": { "port": 80, "secrets": { "token": "x" } },
+            "db": { "port": 5432, "secrets": { "password": "y" } },
+            "list": [1, 2, 3],
+        })
+        .try_into()
+        .unwrap()
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<PathPattern> {
+        patterns.iter().map(|p| p.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn only() {
+        let filtered = apply(value(), &patterns(&["app.port", "db", "list.1"]), &[]);
+        assert_eq!(
+            serde_json::Value::from(filtered),
+            serde_json::json!({
+                "app": { "port": 80 },
+                "db": { "port": 5432, "secrets": { "password": "y" } },
+                "list": [2],
+            })
+        );
+    }
+
+    #[test]
+    fn exclude() {
+        let only = patterns(&["app", "db"]);
+        let filtered = apply(value(), &only, &patterns(&["*.secrets", "list.0"]));
+        assert_eq!(
+            serde_json::Value::from(filtered),
+            serde_json::json!({
+                "app": { "port": 80 },
+                "db": { "port": 5432 },
+            })
+        );
+
+        let filtered = apply(value(), &[], &patterns(&["list.0", "list.2", "app", "db"]));
+        assert_eq!(
+            serde_json::Value::from(filtered),
+            serde_json::json!({ "list": [2] })
+        );
+    }
+}
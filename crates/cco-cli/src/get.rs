@@ -0,0 +1,64 @@
+//! Value of a single dotted path for `cco get`
+use cco::cco_document::CcoDocument;
+use cco::value::Value;
+
+/// Value at `path`, `None` if nothing is defined there
+///
+/// The longest prefix naming an element is evaluated, the remaining segments select keys and
+/// elements of its value like [Value::get]. Failing evaluations are errors, not absent values.
+pub fn lookup(document: &CcoDocument, path: &str) -> anyhow::Result<Option<Value>> {
+    let segments: Vec<&str> = path.split('.').collect();
+    anyhow::ensure!(
+        segments.iter().all(|segment| !segment.is_empty()),
+        "invalid path '{path}'"
+    );
+    let identifiers: Vec<_> = segments.iter().map(hcl::Identifier::sanitized).collect();
+
+    let Some(len) = (1..=segments.len())
+        .rev()
+        .find(|len| document.get(&identifiers[..*len]).is_some())
+    else {
+        return Ok(None);
+    };
+
+    let value = document.evaluate_path(&segments[..len])?;
+    if len == segments.len() {
+        return Ok(Some(value));
+    }
+    Ok(value.get(&segments[len..].join(".")).cloned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lookup_paths() {
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents
+            .insert_str(
+                r#"data db primary {
+  host  = "db.local"
+  ports = [5432, 5433]
+  tags  = { "team name" = "core" }
+}
+"#,
+                None,
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        let get = |path| {
+            let value = lookup(&document, path).unwrap();
+            value.map(|value| crate::env::to_string(&value).unwrap())
+        };
+
+        assert_eq!(get("db.primary.host").as_deref(), Some("db.local"));
+        assert_eq!(get("db.primary.ports").as_deref(), Some("[5432,5433]"));
+        assert_eq!(get("db.primary.ports.1").as_deref(), Some("5433"));
+        assert_eq!(get("db.primary.tags.team name").as_deref(), Some("core"));
+        assert_eq!(get("db.primary.port"), None);
+        assert_eq!(get("db.primary.host.x"), None);
+        assert_eq!(get("cache"), None);
+        assert!(lookup(&document, "db..primary").is_err());
+    }
+}
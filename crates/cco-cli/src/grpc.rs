@@ -0,0 +1,315 @@
+//! gRPC service of `cco serve --grpc-listen` (feature `grpc`)
+//!
+//! Implements `cco.v1.Config` of `proto/cco/v1/config.proto` next to the HTTP [Server]. Both share
+//! the session, the token and the reloads of `--watch`.
+use crate::cli::OutputFormat;
+use crate::serve::{EvalError, Server};
+use cco::session::EvalSession;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tonic::Status;
+
+mod proto {
+    tonic::include_proto!("cco.v1");
+}
+
+use proto::config_server::{Config, ConfigServer};
+use proto::{EvaluateRequest, EvaluateResponse};
+
+/// How often a watch without changes checks whether its client is still connected
+const WATCH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Watches served at the same time, each one holds a blocking thread. Further ones fail with
+/// RESOURCE_EXHAUSTED.
+const MAX_WATCHES: usize = 64;
+
+struct Service {
+    server: Arc<Server>,
+    watches: Arc<Semaphore>,
+}
+
+impl Service {
+    fn new(server: Arc<Server>) -> Self {
+        Self {
+            server,
+            watches: Arc::new(Semaphore::new(MAX_WATCHES)),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Config for Service {
+    async fn evaluate(
+        &self,
+        request: tonic::Request<EvaluateRequest>,
+    ) -> Result<tonic::Response<EvaluateResponse>, Status> {
+        let expression = request.into_inner().expression;
+        let server = self.server.clone();
+        tokio::task::spawn_blocking(move || evaluate(&server, &server.current().0, &expression))
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?
+            .map(tonic::Response::new)
+    }
+
+    type WatchStream = tokio_stream::wrappers::ReceiverStream<Result<EvaluateResponse, Status>>;
+
+    async fn watch(
+        &self,
+        request: tonic::Request<EvaluateRequest>,
+    ) -> Result<tonic::Response<Self::WatchStream>, Status> {
+        let permit = self
+            .watches
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| Status::resource_exhausted("too many watches"))?;
+        let expression = request.into_inner().expression;
+        let server = self.server.clone();
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let mut last_etag = None;
+            loop {
+                let (session, generation) = server.current();
+                let response = match evaluate(&server, &session, &expression) {
+                    Ok(response) => response,
+                    // the stream continues, the value may evaluate after the next reload
+                    Err(status) => EvaluateResponse {
+                        error: status.message().to_string(),
+                        ..Default::default()
+                    },
+                };
+                if !response.error.is_empty() || last_etag.as_ref() != Some(&response.etag) {
+                    last_etag = Some(response.etag.clone());
+                    if sender.blocking_send(Ok(response)).is_err() {
+                        return;
+                    }
+                }
+                while server.wait_for_change(generation, WATCH_CHECK_INTERVAL) == generation {
+                    if sender.is_closed() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(tonic::Response::new(
+            tokio_stream::wrappers::ReceiverStream::new(receiver),
+        ))
+    }
+}
+
+/// Evaluate through [Server::evaluate], so values, ETags and metrics match `GET /eval`
+fn evaluate(
+    server: &Server,
+    session: &EvalSession,
+    expression: &str,
+) -> Result<EvaluateResponse, Status> {
+    let evaluated = server
+        .evaluate(session, expression, &OutputFormat::Json)
+        .map_err(|err| match err {
+            EvalError::Invalid(err) => Status::invalid_argument(err),
+            EvalError::Failed(err) => Status::failed_precondition(err),
+            EvalError::Limit(err) => Status::resource_exhausted(err),
+            EvalError::Internal(err) => Status::internal(err),
+        })?;
+    let value =
+        serde_json::to_value(&evaluated.value).map_err(|err| Status::internal(err.to_string()))?;
+    Ok(EvaluateResponse {
+        value: Some(to_proto(value)),
+        json: String::from_utf8_lossy(&evaluated.body).into_owned(),
+        etag: evaluated.etag,
+        error: String::new(),
+    })
+}
+
+fn to_proto(value: serde_json::Value) -> prost_types::Value {
+    use prost_types::value::Kind;
+    let kind = match value {
+        serde_json::Value::Null => Kind::NullValue(prost_types::NullValue::NullValue.into()),
+        serde_json::Value::Bool(value) => Kind::BoolValue(value),
+        serde_json::Value::Number(number) => Kind::NumberValue(number.as_f64().unwrap_or(f64::NAN)),
+        serde_json::Value::String(value) => Kind::StringValue(value),
+        serde_json::Value::Array(items) => Kind::ListValue(prost_types::ListValue {
+            values: items.into_iter().map(to_proto).collect(),
+        }),
+        serde_json::Value::Object(object) => Kind::StructValue(prost_types::Struct {
+            fields: object
+                .into_iter()
+                .map(|(key, value)| (key, to_proto(value)))
+                .collect(),
+        }),
+    };
+    prost_types::Value { kind: Some(kind) }
+}
+
+/// The gRPC server, bound but not serving yet
+pub struct Grpc {
+    server: Arc<Server>,
+    runtime: tokio::runtime::Runtime,
+    listener: tokio::net::TcpListener,
+}
+
+/// Set up serving `server` over gRPC on `listener`, failures show before anything is served
+pub fn bind(server: Arc<Server>, listener: TcpListener) -> anyhow::Result<Grpc> {
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let listener = {
+        let _runtime = runtime.enter();
+        tokio::net::TcpListener::from_std(listener)?
+    };
+    Ok(Grpc {
+        server,
+        runtime,
+        listener,
+    })
+}
+
+impl Grpc {
+    /// Serve until the process exits or the server fails
+    pub fn serve(self) -> anyhow::Result<()> {
+        let Grpc {
+            server,
+            runtime,
+            listener,
+        } = self;
+        let auth = server.clone();
+        let service = ConfigServer::with_interceptor(Service::new(server), move |request| {
+            authorize(&auth, request)
+        });
+        let incoming = tokio_stream::wrappers::TcpListenerStream::new(listener);
+        runtime.block_on(
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming(incoming),
+        )?;
+        Ok(())
+    }
+}
+
+/// The `--token` of the HTTP API as `authorization` metadata
+fn authorize(server: &Server, request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+    let authorization = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok());
+    match server.authorized(authorization) {
+        true => Ok(request),
+        false => Err(Status::unauthenticated("missing or invalid token")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tonic::Code;
+
+    fn server(token: Option<&str>) -> Arc<Server> {
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents
+            .insert_str("data service api {\n  port = 8080\n}\n", None)
+            .unwrap();
+        let document = cco::cco_document::CcoDocument::new(&documents).unwrap();
+        Arc::new(Server::new(
+            EvalSession::new(document),
+            token.map(str::to_string),
+        ))
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn request(expression: &str) -> tonic::Request<EvaluateRequest> {
+        tonic::Request::new(EvaluateRequest {
+            expression: expression.to_string(),
+        })
+    }
+
+    #[test]
+    fn evaluation() {
+        let server = server(None);
+        let service = Service::new(server.clone());
+
+        let response = block_on(service.evaluate(request("service.api"))).unwrap();
+        let response = response.into_inner();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&response.json).unwrap(),
+            serde_json::json!({ "port": 8080 })
+        );
+        let http = server
+            .evaluate(&server.current().0, "service.api", &OutputFormat::Json)
+            .unwrap();
+        assert_eq!(response.etag, http.etag);
+
+        let code = |expression| {
+            block_on(service.evaluate(request(expression)))
+                .unwrap_err()
+                .code()
+        };
+        assert_eq!(code("service.api["), Code::InvalidArgument);
+        assert_eq!(code("service.x"), Code::FailedPrecondition);
+    }
+
+    #[test]
+    fn token() {
+        let server = server(Some("secret"));
+        let code = |authorization: Option<&str>| {
+            let mut request = tonic::Request::new(());
+            if let Some(authorization) = authorization {
+                request
+                    .metadata_mut()
+                    .insert("authorization", authorization.parse().unwrap());
+            }
+            authorize(&server, request)
+                .err()
+                .map(|status| status.code())
+        };
+        assert_eq!(code(None), Some(Code::Unauthenticated));
+        assert_eq!(code(Some("Bearer wrong")), Some(Code::Unauthenticated));
+        assert_eq!(code(Some("Bearer secret")), None);
+    }
+
+    #[test]
+    fn watch_limit() {
+        let service = Service::new(server(None));
+        let permits = service
+            .watches
+            .clone()
+            .try_acquire_many_owned(MAX_WATCHES as u32)
+            .unwrap();
+        let status = block_on(service.watch(request("service.api"))).unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+
+        drop(permits);
+        assert_eq!(service.watches.available_permits(), MAX_WATCHES);
+    }
+
+    #[test]
+    fn values() {
+        let value = to_proto(serde_json::json!({"port": 80, "tags": ["a", null], "tls": true}));
+        let Some(prost_types::value::Kind::StructValue(object)) = value.kind else {
+            panic!("not a struct: {value:?}");
+        };
+        assert_eq!(
+            object.fields["port"].kind,
+            Some(prost_types::value::Kind::NumberValue(80.0))
+        );
+        assert_eq!(
+            object.fields["tls"].kind,
+            Some(prost_types::value::Kind::BoolValue(true))
+        );
+        let Some(prost_types::value::Kind::ListValue(tags)) = &object.fields["tags"].kind else {
+            panic!("not a list");
+        };
+        assert_eq!(tags.values.len(), 2);
+    }
+}
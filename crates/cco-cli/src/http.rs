@@ -0,0 +1,169 @@
+//! Blocking HTTP client for the APIs of key/value stores, object storage and data sources
+//!
+//! `https://` is verified with rustls against the Mozilla root certificates. Credentials
+//! (`Authorization`, `X-Consul-Token`, ...) are only sent over plain `http://` to loopback
+//! addresses, e.g. a local Consul agent or a storage emulator.
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Headers that must not be sent in cleartext to other hosts
+const CREDENTIAL_HEADERS: [&str; 3] = ["authorization", "x-consul-token", "x-amz-security-token"];
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Fail with the status and body unless the status is 2xx
+    pub fn error_for_status(self) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            self.is_success(),
+            "HTTP {}: {}",
+            self.status,
+            String::from_utf8_lossy(&self.body).trim()
+        );
+        Ok(self)
+    }
+}
+
+/// Send a request and read the complete response
+pub fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<&[u8]>,
+) -> anyhow::Result<Response> {
+    request_timeout(method, url, headers, body, TIMEOUT)
+}
+
+/// [request] giving up when the request takes longer than `timeout`
+pub fn request_timeout(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<&[u8]>,
+    timeout: Duration,
+) -> anyhow::Result<Response> {
+    let parsed = check_url(url, headers)?;
+
+    let client = reqwest::blocking::Client::builder()
+        .use_rustls_tls()
+        .timeout(timeout)
+        .user_agent(concat!("cco/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let mut request = client.request(reqwest::Method::from_bytes(method.as_bytes())?, parsed);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    if let Some(body) = body {
+        request = request.body(body.to_vec());
+    }
+
+    let response = request
+        .send()
+        .map_err(|err| anyhow::anyhow!("request to {url} failed: {err}"))?;
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect();
+    let body = response.bytes()?.to_vec();
+    Ok(Response {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Parse `url`, refusing schemes other than HTTP(S) and credentials in cleartext to other hosts
+fn check_url(url: &str, headers: &[(&str, &str)]) -> anyhow::Result<reqwest::Url> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|err| anyhow::anyhow!("invalid URL '{url}': {err}"))?;
+    match parsed.scheme() {
+        "https" => {}
+        "http" => {
+            let credentials = headers.iter().any(|(name, _)| {
+                CREDENTIAL_HEADERS
+                    .iter()
+                    .any(|header| name.eq_ignore_ascii_case(header))
+            });
+            anyhow::ensure!(
+                !credentials || is_loopback(&parsed),
+                "refusing to send credentials in cleartext to '{url}', use https://"
+            );
+        }
+        _ => anyhow::bail!("unsupported URL '{url}', expected http:// or https://"),
+    }
+    anyhow::ensure!(parsed.has_host(), "missing host in URL '{url}'");
+    Ok(parsed)
+}
+
+fn is_loopback(url: &reqwest::Url) -> bool {
+    let host = url.host_str().unwrap_or_default();
+    host == "localhost"
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Percent-encode everything but unreserved characters and `/`
+pub fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn urls() {
+        let token = [("X-Consul-Token", "secret")];
+        assert!(check_url("https://consul.example:8501/v1/kv", &token).is_ok());
+        assert!(check_url("http://127.0.0.1:8500/v1/kv", &token).is_ok());
+        assert!(check_url("http://[::1]:8500/v1/kv", &token).is_ok());
+        assert!(check_url("http://localhost:8500/v1/kv", &token).is_ok());
+        assert!(check_url("http://example.com/state.json", &[]).is_ok());
+        assert!(check_url("http://consul.example:8500/v1/kv", &token)
+            .unwrap_err()
+            .to_string()
+            .contains("cleartext"));
+        assert!(check_url("http://example.com", &[("authorization", "Bearer a")]).is_err());
+        assert!(check_url("ftp://example.com/file", &[]).is_err());
+    }
+
+    #[test]
+    fn path_encoding() {
+        assert_eq!(encode_path("app/prod/a b"), "app/prod/a%20b");
+    }
+}
@@ -0,0 +1,188 @@
+//! `http` blocks fetching a URL
+//!
+//! ```hcl
+//! http metadata {
+//!   url     = "http://169.254.169.254/latest/meta-data/instance-id"
+//!   headers = { "X-Token" = "..." } # optional
+//!   method  = "GET"                 # optional
+//!   body    = ""                    # optional
+//!   timeout = "2s"                  # optional, default 10s
+//! }
+//! ```
+//!
+//! `http.metadata` is the response body: parsed if it is JSON, otherwise the text. The URL is
+//! fetched when the block is referenced, at most once. Responses other than 2xx are errors. With a
+//! `--lockfile` the body has to match the one locked for the URL.
+use crate::lockfile::Sources;
+use cco::cco_document::CcoDocumentBuilder;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Block type of URLs
+pub const KIND: &str = "http";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handle `http` blocks, with `offline` referencing one is an error
+///
+/// `fetched` is set once a block was fetched, its value may differ the next time. Bodies are
+/// verified against `lock`.
+pub fn register(
+    builder: CcoDocumentBuilder,
+    offline: bool,
+    fetched: Arc<AtomicBool>,
+    lock: Option<Arc<Sources>>,
+) -> CcoDocumentBuilder {
+    builder.data_source(KIND, move |_labels, body| {
+        if offline {
+            return Err("not fetched in --offline mode".to_string());
+        }
+        fetched.store(true, Ordering::Relaxed);
+        fetch(body, lock.as_deref())
+    })
+}
+
+fn fetch(body: hcl::Value, lock: Option<&Sources>) -> Result<hcl::Value, String> {
+    let hcl::Value::Object(mut attributes) = body else {
+        return Err("invalid block".to_string());
+    };
+    let string = |value: hcl::Value, name: &str| match value {
+        hcl::Value::String(value) => Ok(value),
+        _ => Err(format!("{name} must be a string")),
+    };
+
+    let url = match attributes.shift_remove("url") {
+        Some(url) => string(url, "url")?,
+        None => return Err("missing attribute url".to_string()),
+    };
+    let method = match attributes.shift_remove("method") {
+        Some(method) => string(method, "method")?.to_uppercase(),
+        None => "GET".to_string(),
+    };
+    let headers = match attributes.shift_remove("headers") {
+        Some(hcl::Value::Object(headers)) => headers
+            .into_iter()
+            .map(|(name, value)| {
+                let value = string(value, &format!("header {name}"))?;
+                Ok((name, value))
+            })
+            .collect::<Result<Vec<_>, String>>()?,
+        Some(_) => return Err("headers must be an object".to_string()),
+        None => vec![],
+    };
+    let body = match attributes.shift_remove("body") {
+        Some(body) => Some(string(body, "body")?),
+        None => None,
+    };
+    let timeout = match attributes.shift_remove("timeout") {
+        Some(timeout) => crate::cli::parse_duration(&string(timeout, "timeout")?)?,
+        None => DEFAULT_TIMEOUT,
+    };
+    if let Some(name) = attributes.keys().next() {
+        return Err(format!("unknown attribute {name}"));
+    }
+
+    let headers: Vec<_> = headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    tracing::debug!(%method, %url, "fetching http block");
+    let response = crate::http::request_timeout(
+        &method,
+        &url,
+        &headers,
+        body.as_deref().map(str::as_bytes),
+        timeout,
+    )
+    .and_then(crate::http::Response::error_for_status)
+    .map_err(|err| format!("{url}: {err:#}"))?;
+
+    if let Some(lock) = lock {
+        lock.verify(&url, &response.body)?;
+    }
+    let text = String::from_utf8(response.body).map_err(|_| format!("{url}: invalid UTF-8"))?;
+    crate::external::parse_output(&text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serve one request with `body`, returns the URL and the received request head
+    fn server(body: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/meta", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut head = String::new();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            head
+        });
+        (url, handle)
+    }
+
+    fn evaluate(source: &str, offline: bool) -> Result<serde_json::Value, String> {
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents.insert_str(source, None).unwrap();
+        let fetched = Arc::new(AtomicBool::new(false));
+        let document = register(
+            cco::cco_document::CcoDocument::builder(),
+            offline,
+            fetched,
+            None,
+        )
+        .build(&documents)
+        .unwrap();
+        document
+            .evaluate_path(&["http", "meta"])
+            .map(|value| serde_json::to_value(value).unwrap())
+            .map_err(|err| format!("{err:#}"))
+    }
+
+    #[test]
+    fn fetch() {
+        let (url, handle) = server(r#"{"id": "i-123"}"#);
+        let source =
+            format!("http meta {{\n  url = \"{url}\"\n  headers = {{ X-Token = \"t\" }}\n}}");
+        assert_eq!(
+            evaluate(&source, false),
+            Ok(serde_json::json!({ "id": "i-123" }))
+        );
+        let head = handle.join().unwrap();
+        assert!(head.starts_with("GET /meta HTTP/1.1"), "{head}");
+        // header names are sent in lowercase
+        assert!(head.to_ascii_lowercase().contains("x-token: t"), "{head}");
+
+        let (url, handle) = server("plain\n");
+        let source = format!("http meta {{\n  url = \"{url}\"\n  timeout = \"1s\"\n}}");
+        assert_eq!(evaluate(&source, false), Ok("plain".into()));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn offline() {
+        let source = "http meta {\n  url = \"http://127.0.0.1:1/\"\n}";
+        assert!(evaluate(source, true).unwrap_err().contains("--offline"));
+        let source = "http meta {\n  url = \"http://127.0.0.1:1/\"\n  retries = 3\n}";
+        assert!(evaluate(source, false)
+            .unwrap_err()
+            .contains("unknown attribute retries"));
+    }
+}
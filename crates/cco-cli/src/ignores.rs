@@ -0,0 +1,98 @@
+//! `.gitignore` and `.ccoignore` files exclude files from loaded directories (feature `ignore-files`)
+//!
+//! Ignore files of the directory and its parents count, up to the root of the git repository (or
+//! of the file system). The closest file with a matching pattern decides, `.ccoignore` before
+//! `.gitignore` of the same directory, so `!pattern` in a `.ccoignore` loads a file git ignores.
+use ignore::gitignore::Gitignore;
+use ignore::Match;
+use std::path::{Path, PathBuf};
+
+/// Ignore files in the order they are consulted
+const IGNORE_FILES: [&str; 2] = [".ccoignore", ".gitignore"];
+
+pub struct Ignores {
+    dir: PathBuf,
+    /// Closest first
+    matchers: Vec<Gitignore>,
+}
+
+impl Ignores {
+    /// The ignore files applying to files in `dir`
+    pub fn for_directory(dir: &Path) -> std::io::Result<Self> {
+        // patterns are relative to the directory of their file, compare like paths
+        let dir = dir.canonicalize()?;
+        let mut matchers = vec![];
+        for ancestor in dir.ancestors() {
+            for name in IGNORE_FILES {
+                let path = ancestor.join(name);
+                if !path.is_file() {
+                    continue;
+                }
+                let (matcher, err) = Gitignore::new(&path);
+                if let Some(err) = err {
+                    tracing::warn!(phase = "load", file = %path.display(), %err, "invalid ignore pattern");
+                }
+                matchers.push(matcher);
+            }
+            if ancestor.join(".git").exists() {
+                break;
+            }
+        }
+        Ok(Self { dir, matchers })
+    }
+
+    /// Whether the file `name` in the directory is ignored
+    pub fn is_ignored(&self, name: &std::ffi::OsStr) -> bool {
+        let path = self.dir.join(name);
+        for matcher in &self.matchers {
+            match matcher.matched_path_or_any_parents(&path, false) {
+                Match::None => continue,
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+        false
+    }
+}
+
+/// `files` of `dir` without the ignored ones
+pub fn without_ignored(dir: &Path, mut files: Vec<PathBuf>) -> std::io::Result<Vec<PathBuf>> {
+    let ignores = Ignores::for_directory(dir)?;
+    files.retain(|file| {
+        let ignored = file
+            .file_name()
+            .is_some_and(|name| ignores.is_ignored(name));
+        if ignored {
+            tracing::debug!(phase = "load", file = %file.display(), "ignored");
+        }
+        !ignored
+    });
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ignore_files() {
+        let root = std::env::temp_dir().join(format!("cco-ignores-{}", std::process::id()));
+        let dir = root.join("services");
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("generated")).unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(root.join(".gitignore"), "generated/\n*.local.cco.hcl\n").unwrap();
+        std::fs::write(dir.join(".ccoignore"), ".#*\n!keep.local.cco.hcl\n").unwrap();
+
+        let ignores = Ignores::for_directory(&dir).unwrap();
+        assert!(!ignores.is_ignored("api.cco.hcl".as_ref()));
+        assert!(ignores.is_ignored(".#api.cco.hcl".as_ref()));
+        assert!(ignores.is_ignored("dev.local.cco.hcl".as_ref()));
+        assert!(!ignores.is_ignored("keep.local.cco.hcl".as_ref()));
+
+        let ignores = Ignores::for_directory(&root.join("generated")).unwrap();
+        assert!(ignores.is_ignored("api.cco.hcl".as_ref()));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+//! `cco import`: YAML or JSON as `data` blocks
+//!
+//! Converts existing configuration files so a repository can adopt cco one file at a time. The
+//! generated blocks evaluate to the imported value.
+use hcl::format::Format;
+use std::collections::BTreeSet;
+
+/// `data <group> <labels..> { .. }` for an object, with `split` one block per top-level key
+/// labelled with the key
+pub fn import(
+    value: &serde_json::Value,
+    group: &hcl::Identifier,
+    labels: &[String],
+    split: bool,
+) -> anyhow::Result<String> {
+    let block = |labels: Vec<&str>, value: &serde_json::Value| {
+        let serde_json::Value::Object(object) = value else {
+            let labels = labels.join(".");
+            anyhow::bail!("{group}.{labels} must be an object to become a data block");
+        };
+        let invalid: BTreeSet<_> = object
+            .keys()
+            .filter(|key| hcl::Identifier::new(key.as_str()).is_err())
+            .collect();
+        anyhow::ensure!(
+            invalid.is_empty(),
+            "keys are not valid attribute names: {}, rename them or nest them in an object",
+            invalid.into_iter().cloned().collect::<Vec<_>>().join(", ")
+        );
+
+        let mut builder = hcl::Block::builder("data").add_label(group.clone());
+        for label in labels {
+            builder = builder.add_label(label_for(label));
+        }
+        for (key, value) in object {
+            builder = builder.add_attribute((key.as_str(), hcl::to_expression(value)?));
+        }
+        Ok(builder.build())
+    };
+
+    let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+    let mut body = hcl::Body::builder();
+    match (split, value) {
+        (true, serde_json::Value::Object(object)) => {
+            for (key, value) in object {
+                let mut labels = labels.clone();
+                labels.push(key);
+                body = body.add_block(block(labels, value)?);
+            }
+        }
+        (true, _) => anyhow::bail!("--split needs an object, its keys become labels"),
+        (false, value) => body = body.add_block(block(labels, value)?),
+    }
+
+    let mut formatter = hcl::format::Formatter::builder()
+        .prefer_ident_keys(true)
+        .build_vec();
+    body.build().format(&mut formatter)?;
+    Ok(align(&String::from_utf8(formatter.into_inner())?))
+}
+
+/// Bare labels where possible, `data service api` instead of `data "service" "api"`
+fn label_for(label: &str) -> hcl::structure::BlockLabel {
+    match hcl::Identifier::new(label) {
+        Ok(ident) => hcl::structure::BlockLabel::Identifier(ident),
+        Err(_) => hcl::structure::BlockLabel::String(label.to_string()),
+    }
+}
+
+/// Align the `=` of consecutive attributes with the same indentation
+fn align(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    // indentation and key length of attribute lines
+    let keys: Vec<Option<(usize, usize)>> = lines
+        .iter()
+        .map(|line| {
+            let indent = line.len() - line.trim_start().len();
+            let (key, _) = line[indent..].split_once(" = ")?;
+            let key = key.trim_end();
+            let simple = hcl::Identifier::new(key).is_ok()
+                || (key.starts_with('"') && key.ends_with('"') && key.len() > 1);
+            simple.then_some((indent, key.len()))
+        })
+        .collect();
+
+    let mut aligned = String::with_capacity(text.len());
+    let mut group_start = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let same_group = |other: usize| {
+            keys[other]
+                .zip(keys[index])
+                .is_some_and(|(a, b)| a.0 == b.0)
+        };
+        if index == 0 || !same_group(index - 1) {
+            group_start = index;
+        }
+        let Some((indent, key_len)) = keys[index] else {
+            aligned += line;
+            aligned.push('\n');
+            continue;
+        };
+        let width = (group_start..lines.len())
+            .take_while(|other| *other == group_start || same_group(*other))
+            .filter_map(|other| keys[other])
+            .map(|(_, len)| len)
+            .max()
+            .unwrap_or(key_len);
+        let (key, value) = line[indent..].split_once(" = ").expect("attribute line");
+        aligned += &format!("{}{key:width$} = {value}\n", &line[..indent]);
+    }
+    aligned
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn group() -> hcl::Identifier {
+        hcl::Identifier::new("service").unwrap()
+    }
+
+    #[test]
+    fn blocks() {
+        let value = serde_json::json!({
+            "port": 80,
+            "hostname": "api ${not_interpolated}",
+            "labels": { "app.kubernetes.io/name": "api", "tier": "web" },
+            "ports": [80, 443],
+        });
+        let text = import(&value, &group(), &["api".to_string()], false).unwrap();
+        assert_eq!(
+            text,
+            r#"data service api {
+  port     = 80
+  hostname = "api $${not_interpolated}"
+  labels   = {
+    "app.kubernetes.io/name" = "api"
+    tier                     = "web"
+  }
+  ports = [
+    80,
+    443
+  ]
+}
+"#
+        );
+
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents.insert_str(&text, None).unwrap();
+        let document = cco::cco_document::CcoDocument::new(&documents).unwrap();
+        let imported = document.evaluate_path(&["service", "api"]).unwrap();
+        assert_eq!(serde_json::to_value(imported).unwrap(), value);
+    }
+
+    #[test]
+    fn split() {
+        let value = serde_json::json!({
+            "api": { "port": 80 },
+            "my web": { "port": 81 },
+        });
+        let text = import(&value, &group(), &[], true).unwrap();
+        assert_eq!(
+            text,
+            "data service api {\n  port = 80\n}\n\ndata service \"my web\" {\n  port = 81\n}\n"
+        );
+
+        let error = import(&serde_json::json!([1]), &group(), &[], true).unwrap_err();
+        assert!(error.to_string().contains("needs an object"));
+        let error = import(&serde_json::json!({"a.b": 1}), &group(), &[], false).unwrap_err();
+        assert!(error.to_string().contains("a.b"));
+    }
+}
@@ -0,0 +1,309 @@
+//! Export of evaluated values to key/value stores (Consul, etcd)
+//!
+//! Addresses can be `https://`, tokens are only sent over `http://` to loopback addresses (see
+//! [crate::http]).
+use crate::http;
+use cco::base64;
+use cco::value::Value;
+use std::collections::BTreeMap;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Backend {
+    #[cfg(feature = "consul")]
+    Consul,
+    #[cfg(feature = "etcd")]
+    Etcd,
+}
+
+impl Backend {
+    pub fn default_address(self) -> &'static str {
+        match self {
+            #[cfg(feature = "consul")]
+            Backend::Consul => "http://127.0.0.1:8500",
+            #[cfg(feature = "etcd")]
+            Backend::Etcd => "http://127.0.0.1:2379",
+        }
+    }
+}
+
+/// Connection to a key/value store
+pub struct Store {
+    backend: Backend,
+    address: String,
+    token: Option<String>,
+}
+
+impl Store {
+    pub fn new(backend: Backend, address: Option<String>, token: Option<String>) -> Self {
+        let address = address.unwrap_or_else(|| backend.default_address().to_string());
+        Self {
+            backend,
+            address: address.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    /// All keys and values below `prefix`
+    pub fn list(&self, prefix: &str) -> anyhow::Result<BTreeMap<String, String>> {
+        match self.backend {
+            #[cfg(feature = "consul")]
+            Backend::Consul => {
+                let url = format!(
+                    "{}/v1/kv/{}?recurse=true",
+                    self.address,
+                    http::encode_path(prefix)
+                );
+                let response = http::request("GET", &url, &self.headers(), None)?;
+                if response.status == 404 {
+                    return Ok(Default::default());
+                }
+
+                #[derive(serde::Deserialize)]
+                struct Entry {
+                    #[serde(rename = "Key")]
+                    key: String,
+                    #[serde(rename = "Value")]
+                    value: Option<String>,
+                }
+                let entries: Vec<Entry> =
+                    serde_json::from_slice(&response.error_for_status()?.body)?;
+                entries
+                    .into_iter()
+                    .map(|entry| {
+                        let value = base64::decode(entry.value.as_deref().unwrap_or_default())?;
+                        Ok((entry.key, String::from_utf8_lossy(&value).into_owned()))
+                    })
+                    .collect()
+            }
+            #[cfg(feature = "etcd")]
+            Backend::Etcd => {
+                let response = self.etcd("/v3/kv/range", &range_request(prefix))?;
+
+                #[derive(serde::Deserialize)]
+                struct Range {
+                    #[serde(default)]
+                    kvs: Vec<Entry>,
+                }
+                #[derive(serde::Deserialize)]
+                struct Entry {
+                    key: String,
+                    #[serde(default)]
+                    value: String,
+                }
+                let range: Range = serde_json::from_slice(&response.body)?;
+                range
+                    .kvs
+                    .into_iter()
+                    .map(|entry| {
+                        let key = base64::decode(&entry.key)?;
+                        let value = base64::decode(&entry.value)?;
+                        Ok((
+                            String::from_utf8_lossy(&key).into_owned(),
+                            String::from_utf8_lossy(&value).into_owned(),
+                        ))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub fn put(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        match self.backend {
+            #[cfg(feature = "consul")]
+            Backend::Consul => {
+                let url = format!("{}/v1/kv/{}", self.address, http::encode_path(key));
+                http::request("PUT", &url, &self.headers(), Some(value.as_bytes()))?
+                    .error_for_status()?;
+            }
+            #[cfg(feature = "etcd")]
+            Backend::Etcd => {
+                let body = serde_json::json!({
+                    "key": base64::encode(key.as_bytes()),
+                    "value": base64::encode(value.as_bytes()),
+                });
+                self.etcd("/v3/kv/put", &body)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn headers(&self) -> Vec<(&str, &str)> {
+        let Some(token) = &self.token else {
+            return vec![];
+        };
+        match self.backend {
+            #[cfg(feature = "consul")]
+            Backend::Consul => vec![("X-Consul-Token", token)],
+            #[cfg(feature = "etcd")]
+            Backend::Etcd => vec![("Authorization", token)],
+        }
+    }
+
+    #[cfg(feature = "etcd")]
+    fn etcd(&self, path: &str, body: &serde_json::Value) -> anyhow::Result<http::Response> {
+        let mut headers = self.headers();
+        headers.push(("Content-Type", "application/json"));
+        let url = format!("{}{path}", self.address);
+        http::request("POST", &url, &headers, Some(body.to_string().as_bytes()))?.error_for_status()
+    }
+}
+
+/// Range of the keys starting with `prefix`
+///
+/// etcd rejects an empty key, all keys are the range from `\0` to `\0`.
+#[cfg(feature = "etcd")]
+fn range_request(prefix: &str) -> serde_json::Value {
+    let (key, range_end) = match prefix.is_empty() {
+        true => (vec![0], vec![0]),
+        false => (prefix.as_bytes().to_vec(), prefix_end(prefix.as_bytes())),
+    };
+    serde_json::json!({
+        "key": base64::encode(&key),
+        "range_end": base64::encode(&range_end),
+    })
+}
+
+/// First key after all keys starting with `prefix` (etcd range end)
+#[cfg(feature = "etcd")]
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < 0xff {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    // all keys
+    vec![0]
+}
+
+/// Keys are object paths joined with `/` below `prefix`, values as in [crate::env::to_string]
+pub fn flatten(value: &Value, prefix: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut pairs = BTreeMap::new();
+    flatten_into(value, prefix.trim_end_matches('/').to_string(), &mut pairs)?;
+    Ok(pairs)
+}
+
+fn flatten_into(
+    value: &Value,
+    key: String,
+    pairs: &mut BTreeMap<String, String>,
+) -> anyhow::Result<()> {
+    match value {
+        Value::Object(object) => {
+            for (name, value) in object {
+                let key = match key.is_empty() {
+                    true => name.clone(),
+                    false => format!("{key}/{name}"),
+                };
+                flatten_into(value, key, pairs)?;
+            }
+        }
+        value => {
+            anyhow::ensure!(!key.is_empty(), "expected an object or a --prefix");
+            pairs.insert(key, crate::env::to_string(value)?);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Change<'a> {
+    Add {
+        key: &'a str,
+        value: &'a str,
+    },
+    Update {
+        key: &'a str,
+        old: &'a str,
+        new: &'a str,
+    },
+}
+
+/// Writes needed to get from `current` to `desired`, keys missing in `desired` are kept
+pub fn changes<'a>(
+    desired: &'a BTreeMap<String, String>,
+    current: &'a BTreeMap<String, String>,
+) -> Vec<Change<'a>> {
+    desired
+        .iter()
+        .filter_map(|(key, value)| match current.get(key) {
+            None => Some(Change::Add { key, value }),
+            Some(old) if old != value => Some(Change::Update {
+                key,
+                old,
+                new: value,
+            }),
+            Some(_) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flattened_keys() {
+        let value: Value = serde_json::json!({
+            "db": { "host": "localhost", "port": 5432 },
+            "hosts": ["a"],
+        })
+        .try_into()
+        .unwrap();
+
+        let pairs = flatten(&value, "app/prod/").unwrap();
+        assert_eq!(
+            pairs.into_iter().collect::<Vec<_>>(),
+            [
+                ("app/prod/db/host", "localhost"),
+                ("app/prod/db/port", "5432"),
+                ("app/prod/hosts", r#"["a"]"#),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+        assert!(flatten(&Value::Integer(1), "").is_err());
+    }
+
+    #[test]
+    fn changed_keys() {
+        let map = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+        let desired = map(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        let current = map(&[("a", "1"), ("b", "0"), ("d", "4")]);
+        assert_eq!(
+            changes(&desired, &current),
+            [
+                Change::Update {
+                    key: "b",
+                    old: "0",
+                    new: "2"
+                },
+                Change::Add {
+                    key: "c",
+                    value: "3"
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "etcd")]
+    #[test]
+    fn etcd_prefix_end() {
+        assert_eq!(prefix_end(b"app/"), b"app0");
+        assert_eq!(prefix_end(b"a\xff"), b"b");
+
+        assert_eq!(
+            range_request("app/"),
+            serde_json::json!({ "key": "YXBwLw==", "range_end": "YXBwMA==" })
+        );
+        assert_eq!(
+            range_request(""),
+            serde_json::json!({ "key": "AA==", "range_end": "AA==" })
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! `--lockfile`: content hashes of all inputs, written with `--update-lockfile` and verified on
+//! every other run
+//!
+//! One `<sha256>  <path>` line per input like `sha256sum` writes them, so `sha256sum -c cco.lock`
+//! checks the files too. Paths are relative to the lockfile, documents loaded with `--input-url`
+//! are listed by URL and `stdin` as `-`.
+//!
+//! Data sources follow a `# data sources` line: `tfstate` files and URLs, `http` URLs and `--wasm`
+//! modules. They are verified when they are read, [Sources] adds them with `--update-lockfile`.
+use cco::hcl_documents::HclDocuments;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Line starting the data sources
+const SOURCES_HEADER: &str = "# data sources";
+
+/// Hash per input and data source, by path as written to the lockfile
+#[derive(Debug, Default, PartialEq)]
+pub struct Lock {
+    inputs: BTreeMap<String, String>,
+    sources: BTreeMap<String, String>,
+}
+
+impl Lock {
+    /// The inputs of `documents`, paths relative to `dir`
+    pub fn of<'a>(dir: &Path, documents: impl IntoIterator<Item = &'a HclDocuments>) -> Self {
+        let mut lock = Self::default();
+        for documents in documents {
+            for (source, hash) in documents.source_hashes() {
+                let path = match source {
+                    Some(path) => path
+                        .strip_prefix(dir)
+                        .unwrap_or(path)
+                        .to_string_lossy()
+                        .into_owned(),
+                    None => "-".to_string(),
+                };
+                lock.inputs.insert(path, hash.to_string());
+            }
+        }
+        lock
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut lock = Self::default();
+        let mut sources = false;
+        for (number, line) in text.lines().enumerate() {
+            if line.trim() == SOURCES_HEADER {
+                sources = true;
+                continue;
+            }
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = line
+                .split_once(' ')
+                .map(|(hash, path)| (hash, path.trim_start_matches([' ', '*'])))
+                .filter(|(hash, path)| {
+                    hash.len() == 64
+                        && hash.bytes().all(|b| b.is_ascii_hexdigit())
+                        && !path.is_empty()
+                });
+            let Some((hash, path)) = entry else {
+                anyhow::bail!("line {}: expected `<sha256>  <path>`", number + 1);
+            };
+            let entries = match sources {
+                true => &mut lock.sources,
+                false => &mut lock.inputs,
+            };
+            entries.insert(path.to_string(), hash.to_ascii_lowercase());
+        }
+        Ok(lock)
+    }
+
+    /// Inputs that were changed, added or are missing in `current`
+    pub fn differences(&self, current: &Lock) -> Vec<String> {
+        let mut differences = vec![];
+        for (path, hash) in &current.inputs {
+            match self.inputs.get(path) {
+                Some(locked) if locked == hash => {}
+                Some(_) => differences.push(format!("changed: {path}")),
+                None => differences.push(format!("added: {path}")),
+            }
+        }
+        for path in self.inputs.keys() {
+            if !current.inputs.contains_key(path) {
+                differences.push(format!("missing: {path}"));
+            }
+        }
+        differences
+    }
+}
+
+impl std::fmt::Display for Lock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (path, hash) in &self.inputs {
+            writeln!(f, "{hash}  {path}")?;
+        }
+        if !self.sources.is_empty() {
+            writeln!(f, "{SOURCES_HEADER}")?;
+        }
+        for (path, hash) in &self.sources {
+            writeln!(f, "{hash}  {path}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Verify the inputs, or write them with `update` keeping the data sources
+pub fn check<'a>(
+    lockfile: &Path,
+    update: bool,
+    documents: impl IntoIterator<Item = &'a HclDocuments>,
+) -> anyhow::Result<()> {
+    let current = Lock::of(&lockfile_dir(lockfile), documents);
+
+    if update {
+        let sources = match lockfile.exists() {
+            true => read(lockfile)?.sources,
+            false => BTreeMap::new(),
+        };
+        write(lockfile, &Lock { sources, ..current })?;
+        eprintln!("wrote {}", lockfile.display());
+        return Ok(());
+    }
+
+    anyhow::ensure!(
+        lockfile.exists(),
+        "{} does not exist (--update-lockfile writes it)",
+        lockfile.display()
+    );
+    let differences = read(lockfile)?.differences(&current);
+    anyhow::ensure!(
+        differences.is_empty(),
+        "inputs do not match {} (--update-lockfile accepts them):\n{}",
+        lockfile.display(),
+        differences.join("\n")
+    );
+    Ok(())
+}
+
+fn read(lockfile: &Path) -> anyhow::Result<Lock> {
+    let text = std::fs::read_to_string(lockfile)
+        .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", lockfile.display()))?;
+    Lock::parse(&text).map_err(|err| anyhow::anyhow!("{}: {err}", lockfile.display()))
+}
+
+fn write(lockfile: &Path, lock: &Lock) -> anyhow::Result<()> {
+    std::fs::write(lockfile, lock.to_string())
+        .map_err(|err| anyhow::anyhow!("failed to write {}: {err}", lockfile.display()))
+}
+
+/// Data sources of a `--lockfile`, verified or (with `update`) added when they are read
+pub struct Sources {
+    lockfile: PathBuf,
+    dir: PathBuf,
+    update: bool,
+    /// Held while the lockfile is read and written
+    writing: Mutex<()>,
+}
+
+#[cfg_attr(
+    not(any(feature = "http-source", feature = "tfstate", feature = "wasm")),
+    allow(dead_code)
+)]
+impl Sources {
+    pub fn new(lockfile: &Path, update: bool) -> Self {
+        Self {
+            lockfile: lockfile.to_path_buf(),
+            dir: lockfile_dir(lockfile),
+            update,
+            writing: Mutex::new(()),
+        }
+    }
+
+    /// Name of a local file in the lockfile, relative to it like the inputs
+    pub fn file_name(&self, path: &Path) -> String {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        path.strip_prefix(&self.dir)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Whether `contents` of the data source `name` (a path of [Sources::file_name] or a URL) are
+    /// the locked ones
+    pub fn verify(&self, name: &str, contents: &[u8]) -> Result<(), String> {
+        let hash = cco::cache::content_hash(contents);
+        let _writing = self.writing.lock().unwrap_or_else(|err| err.into_inner());
+        let mut lock = match self.update && !self.lockfile.exists() {
+            true => Lock::default(),
+            false => read(&self.lockfile).map_err(|err| format!("{err:#}"))?,
+        };
+        if self.update {
+            lock.sources.insert(name.to_string(), hash);
+            return write(&self.lockfile, &lock).map_err(|err| format!("{err:#}"));
+        }
+        match lock.sources.get(name) {
+            Some(locked) if *locked == hash => Ok(()),
+            Some(_) => Err(format!(
+                "{name} changed since it was locked in {} (--update-lockfile accepts it)",
+                self.lockfile.display()
+            )),
+            None => Err(format!(
+                "{name} is not locked in {} (--update-lockfile adds it)",
+                self.lockfile.display()
+            )),
+        }
+    }
+}
+
+/// Loaded files have canonical paths, compare them with the canonical lockfile directory
+fn lockfile_dir(lockfile: &Path) -> PathBuf {
+    let dir = match lockfile.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    dir.canonicalize().unwrap_or(dir)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lock() {
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str("data a {}\n", Some("/repo/a.cco.hcl".into()))
+            .unwrap();
+        documents
+            .insert_str("", Some("http://example.com/b.cco.hcl".into()))
+            .unwrap();
+        documents.insert_str("", None).unwrap();
+
+        let lock = Lock::of(Path::new("/repo"), [&documents]);
+        let text = lock.to_string();
+        assert_eq!(
+            text,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  -\n\
+             746f37b2c4c02e266416aa9f4d53811c53a9b041096159450c4dc0e82555c19b  a.cco.hcl\n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  http://example.com/b.cco.hcl\n"
+        );
+        assert_eq!(Lock::parse(&text).unwrap(), lock);
+        assert!(Lock::parse("abc  a.cco.hcl").is_err());
+
+        let locked = Lock::parse(
+            "746f37b2c4c02e266416aa9f4d53811c53a9b041096159450c4dc0e82555c19c  a.cco.hcl\n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 *-\n\
+             e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  old.cco.hcl\n",
+        )
+        .unwrap();
+        assert_eq!(
+            locked.differences(&lock),
+            [
+                "changed: a.cco.hcl",
+                "added: http://example.com/b.cco.hcl",
+                "missing: old.cco.hcl"
+            ]
+        );
+    }
+
+    #[test]
+    fn sources() {
+        let dir = std::env::temp_dir().join(format!("cco-lockfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let lockfile = dir.join("cco.lock");
+        let documents = HclDocuments::default();
+
+        // nothing to verify against
+        assert!(check(&lockfile, false, [&documents])
+            .unwrap_err()
+            .to_string()
+            .contains("does not exist"));
+        check(&lockfile, true, [&documents]).unwrap();
+
+        let state = dir.join("network.tfstate");
+        std::fs::write(&state, "{}").unwrap();
+        let sources = Sources::new(&lockfile, false);
+        let name = sources.file_name(&state);
+        assert_eq!(name, "network.tfstate");
+        assert!(sources
+            .verify(&name, b"{}")
+            .unwrap_err()
+            .contains("is not locked"));
+
+        Sources::new(&lockfile, true).verify(&name, b"{}").unwrap();
+        let text = std::fs::read_to_string(&lockfile).unwrap();
+        assert_eq!(
+            text,
+            "# data sources\n\
+             44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a  network.tfstate\n"
+        );
+        assert_eq!(sources.verify(&name, b"{}"), Ok(()));
+        assert!(sources
+            .verify(&name, b"{ }")
+            .unwrap_err()
+            .contains("changed since it was locked"));
+
+        // updating the inputs keeps the data sources
+        check(&lockfile, true, [&documents]).unwrap();
+        assert_eq!(std::fs::read_to_string(&lockfile).unwrap(), text);
+        check(&lockfile, false, [&documents]).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
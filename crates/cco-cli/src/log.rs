@@ -0,0 +1,179 @@
+//! Logging and diagnostics on stderr
+//!
+//! `--log-format json` writes one JSON object per line instead of text, including warnings and
+//! the final error. Events use stable fields: `phase` (`load`, `parse`, `build`, `evaluate`, ...),
+//! `path` for dotted element paths and `file` for input files.
+//!
+//! Warnings and the final error are diagnostics, not logs: text mode always prints them, so they
+//! are written as [diagnostic]s that `CCO_LOG` does not filter.
+use cco::hcl_documents::LoadError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tracing_subscriber::prelude::*;
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogFormat::Text => f.write_str("text"),
+            LogFormat::Json => f.write_str("json"),
+        }
+    }
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static PHASE: Mutex<Option<&'static str>> = Mutex::new(None);
+
+/// Held for the whole run, flushes exported traces when dropped
+pub struct Guard {
+    #[cfg(feature = "otel")]
+    _telemetry: Option<crate::otel::Telemetry>,
+}
+
+impl Guard {
+    /// Flush exported traces, `std::process::exit` skips destructors
+    pub fn finish(self) {}
+}
+
+/// Install the subscriber writing to stderr, filtered by `CCO_LOG` (default: errors only)
+pub fn init(format: LogFormat) -> Guard {
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+
+    let filter = tracing_subscriber::EnvFilter::builder()
+        .with_default_directive(tracing_subscriber::filter::LevelFilter::ERROR.into())
+        .with_env_var("CCO_LOG")
+        .from_env_lossy();
+    let layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_ansi(crate::style::enabled(crate::style::Stream::Stderr))
+            .with_writer(std::io::stderr)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(false)
+            .with_writer(std::io::stderr)
+            .boxed(),
+    };
+    let registry = tracing_subscriber::registry().with(layer.with_filter(filter));
+
+    #[cfg(feature = "otel")]
+    let (otel_layer, telemetry) = crate::otel::layer().unzip();
+    #[cfg(feature = "otel")]
+    let registry = registry.with(otel_layer);
+    registry.init();
+
+    Guard {
+        #[cfg(feature = "otel")]
+        _telemetry: telemetry,
+    }
+}
+
+/// Diagnostics are logged as JSON instead of printed
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Remember `phase` as the one the run is in, see [phase]
+pub fn enter_phase(phase: &'static str) {
+    *PHASE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(phase);
+}
+
+/// The phase entered last, reported with the final error
+pub fn phase() -> Option<&'static str> {
+    *PHASE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Log the error ending the run, with the input file if it is known
+pub fn error(err: &anyhow::Error) {
+    let file = err
+        .chain()
+        .find_map(|cause| match cause.downcast_ref::<LoadError>()? {
+            LoadError::IoError { path, .. }
+            | LoadError::HclParseFailed {
+                path: Some(path), ..
+            } => Some(path.display().to_string()),
+            _ => None,
+        });
+    diagnostic(
+        tracing::Level::ERROR,
+        "cco",
+        &format!("{err:#}"),
+        &[("phase", phase().map(str::to_string)), ("file", file)],
+    );
+}
+
+/// Write a warning or error as a JSON line to stderr, regardless of `CCO_LOG`
+///
+/// The object has the fields of JSON logs, `fields` that are `None` are left out.
+pub fn diagnostic(
+    level: tracing::Level,
+    target: &str,
+    message: &str,
+    fields: &[(&str, Option<String>)],
+) {
+    use std::io::Write;
+
+    let line = diagnostic_line(level, target, message, fields);
+    let _ = writeln!(std::io::stderr().lock(), "{line}");
+}
+
+fn diagnostic_line(
+    level: tracing::Level,
+    target: &str,
+    message: &str,
+    fields: &[(&str, Option<String>)],
+) -> serde_json::Value {
+    use tracing_subscriber::fmt::time::FormatTime;
+
+    let mut timestamp = String::new();
+    let _ = tracing_subscriber::fmt::time::SystemTime.format_time(
+        &mut tracing_subscriber::fmt::format::Writer::new(&mut timestamp),
+    );
+
+    let mut object = serde_json::Map::new();
+    object.insert("timestamp".into(), timestamp.into());
+    object.insert("level".into(), level.as_str().into());
+    object.insert("message".into(), message.into());
+    for (name, value) in fields {
+        if let Some(value) = value {
+            object.insert(name.to_string(), value.as_str().into());
+        }
+    }
+    object.insert("target".into(), target.into());
+    serde_json::Value::Object(object)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diagnostics() {
+        let line = diagnostic_line(
+            tracing::Level::WARN,
+            "cco::deprecations",
+            "a.b is deprecated",
+            &[("phase", Some("evaluate".into())), ("file", None)],
+        );
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["message"], "a.b is deprecated");
+        assert_eq!(line["phase"], "evaluate");
+        assert_eq!(line["target"], "cco::deprecations");
+        assert!(line.get("file").is_none());
+        assert!(line["timestamp"]
+            .as_str()
+            .is_some_and(|time| time.ends_with('Z')));
+    }
+}
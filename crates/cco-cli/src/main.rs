@@ -0,0 +1,1707 @@
+mod annotate;
+mod cli;
+mod decimal;
+mod deprecations;
+mod deps;
+mod dev;
+mod diff;
+mod env;
+mod exec;
+mod expect;
+mod explain;
+mod external;
+mod filter;
+mod get;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "http-source")]
+mod http_source;
+#[cfg(feature = "ignore-files")]
+mod ignores;
+mod import;
+#[cfg(any(feature = "consul", feature = "etcd"))]
+mod kv;
+mod lockfile;
+mod log;
+mod metrics;
+#[cfg(feature = "otel")]
+mod otel;
+mod plugin;
+mod query;
+#[cfg(feature = "remote")]
+mod remote;
+mod rename;
+mod report;
+mod schema;
+mod search;
+mod serve;
+mod set;
+mod style;
+#[cfg(feature = "tfstate")]
+mod tfstate;
+mod timings;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod workspace;
+mod yaml;
+
+use cco::hcl_documents::{directory_files_with, LoadError};
+use cco::value::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use timings::{timed, Timings};
+
+fn main() {
+    use clap::Parser;
+    let cli = cli::Cli::parse();
+    style::init(cli.color);
+
+    let logging = log::init(cli.log_format);
+
+    for new_path in cli.directory.iter() {
+        match new_path.canonicalize() {
+            Err(e) => {
+                eprintln!(
+                    "Failed to resolve path for -C/--directory {}\n{}",
+                    new_path.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+            Ok(cwd) => {
+                if let Err(err) = std::env::set_current_dir(&cwd) {
+                    eprintln!("Failed to set work directory to {}\n{}", cwd.display(), err,);
+                    std::process::exit(1);
+                }
+
+                tracing::info!(directory=%cwd.display(), "Changed working directory");
+            }
+        }
+    }
+
+    let check = match &cli.command {
+        cli::Command::Evaluate(evaluate) => evaluate.output.check,
+        cli::Command::Build(build) => build.check,
+        _ => false,
+    };
+    let command_result = match cli.command {
+        cli::Command::Evaluate(out_cli) => evaluate(*out_cli),
+        cli::Command::Get(get_cli) => get(get_cli),
+        cli::Command::Env(env_cli) => env(env_cli),
+        cli::Command::Exec(exec_cli) => exec(exec_cli),
+        #[cfg(any(feature = "consul", feature = "etcd"))]
+        cli::Command::Push(push_cli) => push(push_cli),
+        cli::Command::Deps(deps_cli) => deps(deps_cli),
+        cli::Command::Search(search_cli) => search(search_cli),
+        cli::Command::Explain(explain_cli) => explain(explain_cli),
+        cli::Command::Rename(rename_cli) => rename(rename_cli),
+        cli::Command::Schema(schema_cli) => schema(schema_cli),
+        cli::Command::Set(set_cli) => set(set_cli),
+        cli::Command::Promote(promote_cli) => promote(promote_cli),
+        cli::Command::Import(import_cli) => import(import_cli),
+        cli::Command::Build(build_cli) => build(build_cli),
+        cli::Command::Test(test_cli) => test(test_cli),
+        cli::Command::Serve(serve_cli) => serve(serve_cli),
+        cli::Command::Dev(dev_cli) => dev(dev_cli),
+    };
+    logging.finish();
+
+    if let Err(e) = command_result {
+        if log::json() {
+            log::error(&e);
+        } else {
+            let mut chain = e.chain();
+            if let Some(error) = chain.next() {
+                eprintln!("{}", style::ERROR.paint(style::Stream::Stderr, error));
+            }
+            for error in chain {
+                eprintln!("{error}")
+            }
+        }
+
+        let code = match e.downcast_ref::<OutOfDate>() {
+            Some(_) => 1,
+            None if check => 2,
+            None => 1,
+        };
+        std::process::exit(code);
+    }
+}
+
+/// `--check` found differences
+#[derive(Debug)]
+struct OutOfDate(Vec<PathBuf>);
+
+impl std::fmt::Display for OutOfDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let paths: Vec<_> = self
+            .0
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        match paths.as_slice() {
+            [path] => write!(f, "{path} is out of date"),
+            paths => write!(f, "{} are out of date", paths.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for OutOfDate {}
+
+pub fn evaluate(mut cli: cli::EvaluateCommand) -> anyhow::Result<()> {
+    if let Some(path) = &cli.expression_file {
+        cli.expression = Some(read_expression(path, &cli.input, std::io::stdin())?);
+    }
+    let timings = (cli.timings || cli.report.is_some()).then(|| Arc::new(Timings::default()));
+    let report = cli
+        .report
+        .is_some()
+        .then(|| Arc::new(report::Report::new(cli.expression())));
+    let result = evaluate_timed(&cli, timings.as_ref(), report.as_ref());
+
+    if let Some(timings) = timings.as_ref().filter(|_| cli.timings) {
+        timings.report(cli.timings_top, std::io::stderr())?;
+    }
+    if let (Some(report), Some(path)) = (report, &cli.report) {
+        let phases = timings.map(|timings| timings.phases()).unwrap_or_default();
+        report.write(path, &phases, &result)?;
+    }
+
+    result
+}
+
+fn evaluate_timed(
+    cli: &cli::EvaluateCommand,
+    timings: Option<&Arc<Timings>>,
+    report: Option<&Arc<report::Report>>,
+) -> anyhow::Result<()> {
+    let phase = timings.map(|timings| &**timings);
+    let sources = timed(phase, "load", || read_sources(&cli.input))?;
+    if let Some(report) = report {
+        report.sources(
+            sources
+                .iter()
+                .map(|(path, contents)| (path.as_deref(), contents.as_str())),
+        );
+    }
+
+    // strict runs always evaluate, plugins may return something else every time
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    // annotations, explanations and reports need the source positions cached documents lack
+    // a cached result does not write the lockfile
+    let cache = (!cli.deny_warnings()
+        && !cli.input.update_lockfile
+        && plugins.is_empty()
+        && !cli.annotate_sources
+        && !cli.explain
+        && cli.report.is_none())
+    .then_some(cli.cache_dir.as_ref())
+    .flatten()
+    .map(cco::cache::Cache::new);
+    let cache_key = cache.as_ref().map(|_| cache_key(cli, &sources));
+
+    if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+        if let Some((value, warnings)) = timed(phase, "cache", || cache.get(key)) {
+            deprecations::print(&warnings);
+            let value = post_process(cli, value)?;
+            return timed(phase, "serialize", || {
+                output(&cli.output, &value, false, &Default::default())
+            });
+        }
+    }
+
+    let hcl_documents = timed(phase, "parse", || {
+        parse_sources_cached(sources, &cli.input, cache.as_ref())
+    })?;
+    let explain = cli.explain.then(|| Arc::new(explain::Explain::default()));
+    let deprecations = Arc::new(deprecations::Deprecations::default());
+    let documents = timed(phase, "build", || {
+        let mut builder = document_builder(&cli.input)
+            .observer(deprecations.clone())
+            .label_case_collisions(cli.check_label_case)
+            .deny_warnings(cli.deny_warnings())
+            .max_depth(cli.max_depth)
+            .max_steps(cli.max_steps)
+            .timeout(cli.timeout)
+            .overflow(cli.overflow.into());
+        if let Some(timings) = timings {
+            builder = builder.observer(timings.clone());
+        }
+        if let Some(explain) = &explain {
+            builder = builder.observer(explain.clone());
+        }
+        if let Some(report) = report {
+            builder = builder.observer(report.clone());
+        }
+        #[cfg(feature = "otel")]
+        {
+            builder = otel::observe(builder);
+        }
+        anyhow::Ok(plugins.register(builder)?.build(&hcl_documents)?)
+    })?;
+    let mut warnings = deprecations::build_warnings(&documents);
+    deprecations::print(&warnings);
+
+    let expr = cco::hcl_documents::parse_expression(cli.expression())?;
+    anyhow::ensure!(
+        !cli.annotate_sources || matches!(cli.output.format, cli::OutputFormat::Yaml),
+        "--annotate-sources requires YAML output"
+    );
+    if cli.stream {
+        let result = timed(phase, "evaluate+serialize", || {
+            output(
+                &cli.output,
+                &documents.evaluate_streaming(expr),
+                true,
+                &Default::default(),
+            )
+        });
+        if let Some(explain) = &explain {
+            explain.report(&hcl_documents, &documents, std::io::stderr())?;
+        }
+        if let Some(report) = report {
+            report.inspect(&hcl_documents, &documents);
+        }
+        result?;
+        return deprecations.report(&documents, cli.deny_warnings());
+    }
+
+    let value = timed(phase, "evaluate", || {
+        documents.evaluate_in_context(expr.clone())
+    });
+    // also explains how far a failing evaluation got
+    if let Some(explain) = &explain {
+        explain.report(&hcl_documents, &documents, std::io::stderr())?;
+    }
+    if let Some(report) = report {
+        report.inspect(&hcl_documents, &documents);
+    }
+    let value = value?;
+    deprecations.report(&documents, cli.deny_warnings())?;
+
+    // fetched values may be different next time
+    if let (Some(cache), Some(key), false) = (&cache, &cache_key, plugins.fetched()) {
+        warnings.extend(deprecations.warnings(&documents));
+        if let Err(err) = cache.put(key, &value, plugins.inputs(), &warnings) {
+            tracing::warn!(%err, "failed to write cache entry");
+        }
+    }
+
+    let value = post_process(cli, value)?;
+    // the annotated keys are those of the query result, e.g. of `service.api` for `service`
+    // queried with `.api --unwrap`
+    let path = match (annotate::expression_path(&expr), &cli.query) {
+        (Some(path), None) => Some(path),
+        (Some(mut path), Some(query)) if cli.unwrap => query.key_path().map(|keys| {
+            path.extend(keys.into_iter().map(hcl::Identifier::sanitized));
+            path
+        }),
+        _ => None,
+    };
+    let annotations = match path {
+        Some(path) if cli.annotate_sources => {
+            annotate::annotations(&hcl_documents, &documents, &path, &value)
+        }
+        _ => Default::default(),
+    };
+    timed(phase, "serialize", || {
+        output(&cli.output, &value, false, &annotations)
+    })
+}
+
+/// Key of the result of `cli` for `sources`, covering every option the evaluation depends on
+fn cache_key(cli: &cli::EvaluateCommand, sources: &[SourceText]) -> cco::cache::CacheKey {
+    let input = &cli.input;
+    let namespace_root = input
+        .namespace_dirs
+        .as_ref()
+        .map(|root| root.canonicalize().unwrap_or_else(|_| root.clone()));
+    // a result is only cached once the lockfile check passed, a hit needs the same lockfile
+    let lockfile = input.lockfile.as_ref().map(|path| {
+        std::fs::read(path)
+            .map(|contents| cco::cache::content_hash(&contents))
+            .unwrap_or_default()
+    });
+    sources
+        .iter()
+        .fold(cco::cache::CacheKey::builder(), |key, (path, contents)| {
+            key.source(path.as_deref(), contents)
+        })
+        .expression(cli.expression())
+        .option("globals", &input.globals.to_string())
+        .option("namespace-dirs", &format!("{namespace_root:?}"))
+        .option("merge", &format!("{:?}", merge_policy(input)))
+        .option("lockfile", &format!("{lockfile:?}"))
+        .option("check-label-case", &cli.check_label_case.to_string())
+        .option("max-depth", &cli.max_depth.to_string())
+        .option("max-steps", &format!("{:?}", cli.max_steps))
+        .option("timeout", &format!("{:?}", cli.timeout))
+        .option("overflow", &format!("{:?}", cli.overflow))
+        .build()
+}
+
+/// Apply `--query`, output filters and key ordering
+fn post_process(cli: &cli::EvaluateCommand, value: Value) -> anyhow::Result<Value> {
+    let value = match &cli.query {
+        Some(query) => query.apply(value, cli.unwrap)?,
+        None => value,
+    };
+    let mut value = filter::apply(value, &cli.only, &cli.exclude);
+
+    if cli.normalize {
+        value.normalize();
+    } else if cli.sort_keys {
+        value.sort_keys();
+    }
+
+    Ok(value)
+}
+
+/// Contents of `--expression-file` without surrounding whitespace, `-` reads `stdin`
+fn read_expression(
+    path: &Path,
+    input: &cli::InputArgs,
+    stdin: impl std::io::Read,
+) -> anyhow::Result<String> {
+    let expression = if path == Path::new("-") {
+        anyhow::ensure!(
+            !is_stdin(input),
+            "--expression-file - needs input files, stdin already provides the documents"
+        );
+        std::io::read_to_string(stdin)?
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("failed to read expression {}: {err}", path.display()))?
+    };
+    Ok(expression.trim().to_string())
+}
+
+/// A source document that has been read but not parsed yet
+type SourceText = (Option<PathBuf>, String);
+
+/// No input files, directories or URLs were given
+fn is_stdin(input: &cli::InputArgs) -> bool {
+    !input.chain
+        && input.files.is_empty()
+        && input.directories.is_empty()
+        && input.overlays.is_empty()
+        && input.urls().is_empty()
+}
+
+/// `--chain-stop-at` as canonical path, it has to be `cwd` or one of its parents
+fn chain_stop_at(input: &cli::InputArgs, cwd: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let Some(dir) = &input.chain_stop_at else {
+        return Ok(None);
+    };
+    let stop_at = dir
+        .canonicalize()
+        .map_err(|err| anyhow::anyhow!("--chain-stop-at {}: {err}", dir.display()))?;
+    anyhow::ensure!(
+        cwd.starts_with(&stop_at),
+        "--chain-stop-at {} is neither the work directory nor one of its parents",
+        dir.display()
+    );
+    Ok(Some(stop_at))
+}
+
+/// The `cco.hcl` files of a directory, without those `.gitignore` or `.ccoignore` exclude
+fn directory_files(dir_path: &Path, input: &cli::InputArgs) -> Result<Vec<PathBuf>, LoadError> {
+    let files = directory_files_with(dir_path, input.symlinks.into())?;
+    #[cfg(feature = "ignore-files")]
+    let files = match input.no_ignore {
+        true => files,
+        false => ignores::without_ignored(dir_path, files).map_err(LoadError::io(dir_path))?,
+    };
+    if files.is_empty() {
+        return Err(LoadError::NoFilesFound(dir_path.to_owned()));
+    }
+    Ok(files)
+}
+
+/// File marking the last directory `--input-chain` loads
+const CHAIN_ROOT_MARKER: &str = ".cco-root";
+
+fn read_sources(input: &cli::InputArgs) -> anyhow::Result<Vec<SourceText>> {
+    if is_stdin(input) {
+        let stdin = std::io::read_to_string(std::io::stdin())?;
+        return Ok(vec![(None, stdin)]);
+    }
+
+    let mut sources = vec![];
+
+    if input.workdir {
+        for file_path in directory_files(&std::env::current_dir()?, input)? {
+            sources.push(read_file(&file_path)?);
+        }
+    }
+
+    if input.chain {
+        let mut current_dir = std::env::current_dir()?.canonicalize()?;
+        let stop_at = chain_stop_at(input, &current_dir)?;
+        loop {
+            match directory_files(&current_dir, input) {
+                Ok(file_paths) => {
+                    for file_path in file_paths {
+                        sources.push(read_file(&file_path)?);
+                    }
+
+                    if current_dir.join(CHAIN_ROOT_MARKER).is_file()
+                        || stop_at.as_ref() == Some(&current_dir)
+                    {
+                        tracing::debug!(dir = %current_dir.display(), "chain stops");
+                        break;
+                    }
+
+                    let Some(parent) = current_dir.parent() else {
+                        break;
+                    };
+
+                    current_dir = parent.to_owned();
+                }
+                Err(LoadError::NoFilesFound(_)) => break,
+                Err(e) => Err(e)?,
+            }
+        }
+    }
+
+    for file_path in &input.files {
+        sources.push(read_file(file_path)?);
+    }
+
+    for dir_path in &input.directories {
+        for file_path in directory_files(dir_path, input)? {
+            sources.push(read_file(&file_path)?);
+        }
+    }
+
+    for dir_path in overlay_directories(input)? {
+        for file_path in directory_files(&dir_path, input)? {
+            sources.push(read_file(&file_path)?);
+        }
+    }
+
+    #[cfg(feature = "remote")]
+    for url in &input.urls {
+        let contents = remote::fetch(url, input.url_cache_dir.as_deref())?;
+        sources.push((Some(PathBuf::from(url)), contents));
+    }
+
+    // a file reached through several inputs or symbolic links is loaded once
+    let mut loaded = std::collections::HashSet::new();
+    sources.retain(|(path, _)| path.as_ref().is_none_or(|path| loaded.insert(path.clone())));
+
+    anyhow::ensure!(!sources.is_empty(), "No files loaded");
+
+    Ok(sources)
+}
+
+/// Builder of the document of `input`: with `--overlay` blocks of later files override the
+/// attributes of blocks with the same labels in earlier ones
+fn document_builder(input: &cli::InputArgs) -> cco::cco_document::CcoDocumentBuilder {
+    cco::cco_document::CcoDocument::builder()
+        .globals(input.globals)
+        .merge_policy(merge_policy(input))
+}
+
+/// Merge policy of the document of `input`, see [document_builder]
+fn merge_policy(input: &cli::InputArgs) -> cco::cco_document::MergePolicy {
+    match input.overlays.is_empty() {
+        true => cco::cco_document::MergePolicy::Reject,
+        false => cco::cco_document::MergePolicy::Override,
+    }
+}
+
+/// `base/` followed by `overlays/<profile>/` for each `--overlay`, none without overlays
+fn overlay_directories(input: &cli::InputArgs) -> anyhow::Result<Vec<PathBuf>> {
+    if input.overlays.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let root = match &input.overlay_root {
+        Some(root) => root.clone(),
+        None => std::env::current_dir()?,
+    };
+    let mut directories = vec![root.join("base")];
+    for profile in &input.overlays {
+        anyhow::ensure!(
+            !profile.is_empty() && !profile.contains(['/', '\\']) && profile != "..",
+            "invalid overlay name '{profile}'"
+        );
+        directories.push(root.join("overlays").join(profile));
+    }
+
+    for directory in &directories {
+        anyhow::ensure!(
+            directory.is_dir(),
+            "overlay directory {} not found",
+            directory.display()
+        );
+    }
+    Ok(directories)
+}
+
+fn read_file(file_path: &Path) -> anyhow::Result<SourceText> {
+    let file_path = file_path.canonicalize().map_err(LoadError::io(file_path))?;
+    tracing::info!(phase = "load", file = %file_path.display(), "loading file");
+
+    let contents = std::fs::read_to_string(&file_path).map_err(LoadError::io(&file_path))?;
+    Ok((Some(file_path), contents))
+}
+
+/// [parse_documents] checked against `--lockfile`
+fn parse_sources(
+    sources: Vec<SourceText>,
+    input: &cli::InputArgs,
+) -> anyhow::Result<cco::hcl_documents::HclDocuments> {
+    parse_sources_cached(sources, input, None)
+}
+
+/// [parse_sources] with the parsed documents of unchanged files taken from `cache`
+fn parse_sources_cached(
+    sources: Vec<SourceText>,
+    input: &cli::InputArgs,
+    cache: Option<&cco::cache::Cache>,
+) -> anyhow::Result<cco::hcl_documents::HclDocuments> {
+    let documents = parse_documents(sources, input, cache)?;
+    if let Some(lockfile) = &input.lockfile {
+        lockfile::check(lockfile, input.update_lockfile, [&documents])?;
+    }
+
+    Ok(documents)
+}
+
+fn parse_documents(
+    sources: Vec<SourceText>,
+    input: &cli::InputArgs,
+    cache: Option<&cco::cache::Cache>,
+) -> anyhow::Result<cco::hcl_documents::HclDocuments> {
+    let mut documents = cco::hcl_documents::HclDocuments::default();
+    for (path, contents) in sources {
+        match cache {
+            Some(cache) => documents.insert_str_cached(&contents, path, cache)?,
+            None => documents.insert_str(&contents, path)?,
+        }
+    }
+    if let Some(root) = &input.namespace_dirs {
+        documents.nest(|path| dir_namespace(root, path));
+    }
+
+    Ok(documents)
+}
+
+/// `prod.eu` for files in `<root>/prod/eu/`, none for files outside of subdirectories of `root`
+fn dir_namespace(root: &Path, path: &Path) -> Vec<hcl::Identifier> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let (root, path) = (canonical(root), canonical(path));
+    let Some(dir) = path.parent().and_then(|dir| dir.strip_prefix(&root).ok()) else {
+        return vec![];
+    };
+    dir.components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(name) => {
+                Some(hcl::Identifier::sanitized(name.to_string_lossy()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Write to stdout, `--output-file`/`--output-dir` or compare with them (`--check`)
+/// Write `value` to stdout or the output files, `streamed` values are serialized only once
+///
+/// `annotations` are written above keys of YAML output, see [yaml::to_annotated_string]
+fn output(
+    output: &cli::OutputArgs,
+    value: &impl serde::Serialize,
+    streamed: bool,
+    annotations: &annotate::Annotations,
+) -> anyhow::Result<()> {
+    // a failing evaluation must not leave truncated files behind, render everything first
+    let files = if let Some(dir) = &output.output_dir {
+        let Value::Object(object) = Value::try_from(serde_json::to_value(value)?)? else {
+            let flag = if output.each {
+                "--each"
+            } else {
+                "--split-by-key"
+            };
+            anyhow::bail!("{flag} requires the result to be an object");
+        };
+
+        let mut files = vec![];
+        for (key, value) in object {
+            anyhow::ensure!(
+                !key.is_empty() && key != "." && key != ".." && !key.contains(['/', '\\']),
+                "key '{key}' can not be used as a file name"
+            );
+            let mut rendered = vec![];
+            let comments = annotations.of(&key).keys;
+            write_output(output, &mut rendered, &value, false, &comments)?;
+            files.push((dir.join(format!("{key}.{}", output.format)), rendered));
+        }
+        files
+    } else if output.each {
+        let Value::Object(object) = Value::try_from(serde_json::to_value(value)?)? else {
+            anyhow::bail!("--each requires the result to be an object");
+        };
+        let mut rendered = vec![];
+        match output.format {
+            cli::OutputFormat::Ndjson => {
+                let values = Value::Array(object.into_values().collect());
+                write_output(output, &mut rendered, &values, false, &HashMap::new())?;
+            }
+            _ => {
+                for (index, (key, value)) in object.iter().enumerate() {
+                    match output.format {
+                        cli::OutputFormat::Yaml => rendered.extend(b"---\n"),
+                        _ if index > 0 => rendered.extend(b"\n"),
+                        _ => {}
+                    }
+                    let comments = annotations.of(key).keys;
+                    write_output(output, &mut rendered, value, false, &comments)?;
+                }
+            }
+        }
+        match &output.output_file {
+            Some(path) => vec![(path.clone(), rendered)],
+            None => {
+                return Ok(std::io::Write::write_all(
+                    &mut std::io::stdout(),
+                    &rendered,
+                )?)
+            }
+        }
+    } else if let Some(path) = &output.output_file {
+        let mut rendered = vec![];
+        write_output(output, &mut rendered, value, streamed, &annotations.keys)?;
+        vec![(path.clone(), rendered)]
+    } else {
+        let stdout = std::io::stdout().lock();
+        return write_output(output, stdout, value, streamed, &annotations.keys);
+    };
+
+    if !output.check {
+        if let Some(dir) = &output.output_dir {
+            std::fs::create_dir_all(dir)?;
+        }
+        for (path, rendered) in files {
+            std::fs::write(path, rendered)?;
+        }
+        return Ok(());
+    }
+
+    let mut out_of_date = vec![];
+    for (path, rendered) in files {
+        let existing = match std::fs::read_to_string(&path) {
+            Ok(existing) => existing,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let rendered = String::from_utf8(rendered)?;
+        let name = path.display().to_string();
+        if let Some(diff) = diff::unified(&existing, &rendered, &name, "(evaluated)") {
+            print!("{}", diff::paint(&diff, style::Stream::Stdout));
+            out_of_date.push(path);
+        }
+    }
+
+    match out_of_date.is_empty() {
+        true => Ok(()),
+        false => Err(OutOfDate(out_of_date).into()),
+    }
+}
+
+fn write_output(
+    output: &cli::OutputArgs,
+    mut writer: impl std::io::Write,
+    value: &impl serde::Serialize,
+    streamed: bool,
+    comments: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+    let decimals = decimal::DecimalFormat {
+        max_precision: output.decimal_precision.map(Into::into),
+        whole_point: output.decimal_whole_point,
+        plain: output.decimal_plain,
+    };
+    let style = yaml::Style {
+        indent: output.yaml_indent.into(),
+        multiline: output.yaml_multiline,
+        decimals,
+    };
+
+    match &output.format {
+        // keeps memory bounded, but multi-line strings serde_yaml can not write as literal blocks
+        // are quoted
+        cli::OutputFormat::Yaml
+            if streamed && !output.yaml_documents && style == yaml::Style::default() =>
+        {
+            serde_yaml::to_writer(writer, value)?
+        }
+        cli::OutputFormat::Yaml => {
+            let value = Value::try_from(serde_json::to_value(value)?)?;
+            let yaml = if output.yaml_documents {
+                yaml::to_documents(&value, style)?
+            } else if !comments.is_empty() {
+                yaml::to_annotated_string(&value, style, comments)?
+            } else {
+                yaml::to_string(&value, style)?
+            };
+            writer.write_all(yaml.as_bytes())?;
+        }
+        format => write_format_with(format, writer, value, decimals)?,
+    };
+
+    Ok(())
+}
+
+/// Write `value` in `format` with default style options
+fn write_format(
+    format: &cli::OutputFormat,
+    writer: impl std::io::Write,
+    value: &impl serde::Serialize,
+) -> anyhow::Result<()> {
+    write_format_with(format, writer, value, Default::default())
+}
+
+fn write_format_with(
+    format: &cli::OutputFormat,
+    mut writer: impl std::io::Write,
+    value: &impl serde::Serialize,
+    decimals: decimal::DecimalFormat,
+) -> anyhow::Result<()> {
+    match format {
+        cli::OutputFormat::Yaml => {
+            let value = Value::try_from(serde_json::to_value(value)?)?;
+            let style = yaml::Style {
+                decimals,
+                ..Default::default()
+            };
+            writer.write_all(yaml::to_string(&value, style)?.as_bytes())?;
+        }
+        cli::OutputFormat::Json => decimal::to_writer_pretty(writer, value, decimals)?,
+        cli::OutputFormat::Ndjson => match serde_json::to_value(value)? {
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    decimal::to_writer(&mut writer, &item, decimals)?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            value => {
+                decimal::to_writer(&mut writer, &value, decimals)?;
+                writer.write_all(b"\n")?;
+            }
+        },
+    };
+
+    Ok(())
+}
+
+pub fn get(cli: cli::GetCommand) -> anyhow::Result<()> {
+    let documents = parse_sources(read_sources(&cli.input)?, &cli.input)?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let deprecations = Arc::new(deprecations::Deprecations::default());
+    let builder = document_builder(&cli.input).observer(deprecations.clone());
+    let document = plugins.register(builder)?.build(&documents)?;
+    deprecations::report_warnings(&document);
+
+    let Some(value) = get::lookup(&document, &cli.path)? else {
+        let default = cli.default.as_deref();
+        let default = default.ok_or_else(|| anyhow::anyhow!("nothing defined at {}", cli.path))?;
+        println!("{default}");
+        return Ok(());
+    };
+    deprecations.report(&document, false)?;
+
+    let stdout = std::io::stdout().lock();
+    match (&cli.format, &value) {
+        (Some(format), value) => write_format(format, stdout, value)?,
+        (None, Value::Array(_) | Value::Object(_)) => {
+            write_format(&cli::OutputFormat::Json, stdout, &value)?;
+            println!();
+        }
+        (None, value) => println!("{}", env::to_string(value)?),
+    }
+    Ok(())
+}
+
+pub fn env(cli: cli::EnvCommand) -> anyhow::Result<()> {
+    let value = evaluate_expression(&cli.input, &cli.expression)?;
+    let variables = env::flatten(&value)?;
+
+    let mut command = command(&cli.command);
+    command.envs(variables);
+    run(command)
+}
+
+pub fn exec(cli: cli::ExecCommand) -> anyhow::Result<()> {
+    let args = cli
+        .command
+        .iter()
+        .map(|arg| exec::parse(arg))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let placeholders: Vec<&str> = args
+        .iter()
+        .flatten()
+        .filter_map(|part| match part {
+            exec::Part::Placeholder(expression) => Some(expression.as_str()),
+            exec::Part::Text(_) => None,
+        })
+        .collect();
+    for placeholder in &placeholders {
+        cco::hcl_documents::parse_expression(placeholder)
+            .map_err(|err| anyhow::anyhow!("invalid placeholder {{{placeholder}}}: {err}"))?;
+    }
+
+    // a single evaluation for all placeholders
+    let values = if placeholders.is_empty() {
+        vec![]
+    } else {
+        let list = format!("[{}]", placeholders.join(",\n"));
+        match evaluate_expression(&cli.input, &list)? {
+            Value::Array(values) => values,
+            value => unreachable!("list evaluated to {}", value.type_name()),
+        }
+    };
+
+    let mut values = values.iter();
+    let mut command_line = vec![];
+    for parts in args {
+        let mut arg = String::new();
+        for part in parts {
+            match part {
+                exec::Part::Text(text) => arg.push_str(&text),
+                exec::Part::Placeholder(_) => {
+                    let value = values.next().expect("a value per placeholder");
+                    arg.push_str(&env::to_string(value)?);
+                }
+            }
+        }
+        command_line.push(arg);
+    }
+
+    if cli.dry_run {
+        let quoted: Vec<String> = command_line
+            .iter()
+            .map(|arg| exec::shell_quote(arg))
+            .collect();
+        println!("{}", quoted.join(" "));
+        return Ok(());
+    }
+
+    run(command(&command_line))
+}
+
+fn command(command_line: &[String]) -> std::process::Command {
+    let (program, args) = command_line
+        .split_first()
+        .expect("clap requires at least one value");
+    let mut command = std::process::Command::new(program);
+    command.args(args);
+    command
+}
+
+/// Replace this process with `command` (or wait for it and exit with its code)
+fn run(mut command: std::process::Command) -> anyhow::Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // only returns if the command could not be started
+        let err = command.exec();
+        Err(anyhow::Error::new(err).context(format!("failed to run {program}")))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = command
+            .status()
+            .map_err(|err| anyhow::Error::new(err).context(format!("failed to run {program}")))?;
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
+/// Load the inputs and evaluate a single expression, warning about deprecated references
+fn evaluate_expression(input: &cli::InputArgs, expression: &str) -> anyhow::Result<Value> {
+    let documents = parse_sources(read_sources(input)?, input)?;
+    let plugins = plugin::Plugins::load(input)?;
+    let deprecations = Arc::new(deprecations::Deprecations::default());
+    let builder = document_builder(input).observer(deprecations.clone());
+    let documents = plugins.register(builder)?.build(&documents)?;
+    deprecations::report_warnings(&documents);
+
+    let expr = cco::hcl_documents::parse_expression(expression)?;
+    let value = documents.evaluate_in_context(expr)?;
+    deprecations.report(&documents, false)?;
+    Ok(value)
+}
+
+#[cfg(any(feature = "consul", feature = "etcd"))]
+pub fn push(cli: cli::PushCommand) -> anyhow::Result<()> {
+    match cli.command {
+        cli::PushSubCommand::Kv(cli) => push_kv(cli),
+    }
+}
+
+#[cfg(any(feature = "consul", feature = "etcd"))]
+fn push_kv(cli: cli::PushKvCommand) -> anyhow::Result<()> {
+    let value = evaluate_expression(&cli.input, &cli.expression)?;
+    let desired = kv::flatten(&value, &cli.prefix)?;
+
+    let store = kv::Store::new(cli.backend, cli.address, cli.token);
+    // `app/prod/` does not match `app/production`
+    let prefix = cli.prefix.trim_end_matches('/');
+    let current = match prefix.is_empty() {
+        true => store.list("")?,
+        false => store.list(&format!("{prefix}/"))?,
+    };
+
+    let changes = kv::changes(&desired, &current);
+    for change in &changes {
+        let line = match change {
+            kv::Change::Add { key, value } => {
+                style::ADDED.paint(style::Stream::Stdout, format!("+ {key} = {value}"))
+            }
+            kv::Change::Update { key, old, new } => {
+                style::HUNK.paint(style::Stream::Stdout, format!("~ {key} = {old} -> {new}"))
+            }
+        };
+        println!("{line}");
+    }
+
+    if cli.dry_run {
+        return Ok(());
+    }
+
+    for change in &changes {
+        let (kv::Change::Add { key, value }
+        | kv::Change::Update {
+            key, new: value, ..
+        }) = change;
+        store.put(key, value)?;
+    }
+    tracing::info!(
+        written = changes.len(),
+        unchanged = desired.len() - changes.len(),
+        "pushed"
+    );
+    Ok(())
+}
+
+pub fn deps(cli: cli::DepsCommand) -> anyhow::Result<()> {
+    let documents = parse_sources(read_sources(&cli.input)?, &cli.input)?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let builder = document_builder(&cli.input);
+    let document = plugins.register(builder)?.build(&documents)?;
+
+    let lines = match &cli.path {
+        _ if cli.unused => deps::unused(&document, &documents, &cli.entries)?,
+        Some(path) if cli.reverse => {
+            deps::dependents(&document, &documents, &deps::parse_path(path)?)?
+        }
+        Some(path) => deps::references(&document, &documents, &deps::parse_path(path)?)?,
+        None => unreachable!("required by clap"),
+    };
+    for line in lines {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+pub fn search(cli: cli::SearchCommand) -> anyhow::Result<()> {
+    let sources = read_sources(&cli.input)?;
+    let documents = parse_sources(sources.clone(), &cli.input)?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let builder = document_builder(&cli.input);
+    let document = plugins.register(builder)?.build(&documents)?;
+
+    for line in search::search(&document, &documents, &sources, &cli.pattern) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+pub fn explain(cli: cli::ExplainCommand) -> anyhow::Result<()> {
+    let sources = read_sources(&cli.input)?;
+    let documents = parse_sources(sources.clone(), &cli.input)?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let builder = document_builder(&cli.input);
+    let document = plugins.register(builder)?.build(&documents)?;
+
+    let path = deps::parse_path(&cli.merges)?;
+    for line in explain::merges(&document, &documents, &sources, &path)? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+pub fn rename(cli: cli::RenameCommand) -> anyhow::Result<()> {
+    let sources = read_sources(&cli.input)?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let document = build_document(&cli.input, &plugins, sources.clone())?;
+
+    let (from, to) = (deps::parse_path(&cli.from)?, deps::parse_path(&cli.to)?);
+    let changed = rename::rename(&document, &sources, &from, &to)?;
+    write_sources(
+        &cli.input,
+        &plugins,
+        &sources,
+        &changed,
+        cli.dry_run,
+        |_| Ok(()),
+    )
+}
+
+pub fn set(cli: cli::SetCommand) -> anyhow::Result<()> {
+    let sources = read_sources(&cli.input)?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let document = build_document(&cli.input, &plugins, sources.clone())?;
+
+    let path = deps::parse_path(&cli.path)?;
+    let expression = match cli.string {
+        true => hcl::format::to_string(&hcl::Expression::String(cli.expression))?,
+        false => cli.expression,
+    };
+    let changed = set::set(&document, &sources, &path, &expression)?;
+    write_sources(
+        &cli.input,
+        &plugins,
+        &sources,
+        &[changed],
+        cli.dry_run,
+        |document| document.evaluate_path(&path).map(|_| ()),
+    )
+}
+
+pub fn promote(cli: cli::PromoteCommand) -> anyhow::Result<()> {
+    let profile = |dir: &Path| {
+        let mut input = cli.input.clone();
+        input.directories.push(dir.to_path_buf());
+        input
+    };
+    let (from_input, to_input) = (profile(&cli.from), profile(&cli.to));
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let path = deps::parse_path(&cli.path)?;
+
+    let from_sources = read_sources(&from_input)?;
+    let from = build_document(&from_input, &plugins, from_sources.clone())?;
+    let expression = set::expression(&from, &from_sources, &path, &cli.from)?;
+
+    let to_sources = read_sources(&to_input)?;
+    let to = build_document(&to_input, &plugins, to_sources.clone())?;
+    let changed = set::set(&to, &to_sources, &path, &expression)?;
+    // sources are read with canonical paths
+    let to_dir = cli
+        .to
+        .canonicalize()
+        .map_err(|err| anyhow::anyhow!("{}: {err}", cli.to.display()))?;
+    anyhow::ensure!(
+        changed.0.starts_with(&to_dir),
+        "{} is defined in {}, not in {}",
+        cli.path,
+        changed.0.display(),
+        cli.to.display()
+    );
+    write_sources(
+        &to_input,
+        &plugins,
+        &to_sources,
+        &[changed],
+        cli.dry_run,
+        |document| document.evaluate_path(&path).map(|_| ()),
+    )
+}
+
+pub fn import(cli: cli::ImportCommand) -> anyhow::Result<()> {
+    let contents = match cli.file.as_path() == Path::new("-") {
+        true => std::io::read_to_string(std::io::stdin())?,
+        false => std::fs::read_to_string(&cli.file).map_err(LoadError::io(&cli.file))?,
+    };
+    // JSON is YAML as well
+    let value: serde_json::Value = serde_yaml::from_str(&contents)
+        .map_err(|err| anyhow::anyhow!("failed to parse {}: {err}", cli.file.display()))?;
+    print!(
+        "{}",
+        import::import(&value, &cli.group, &cli.labels, cli.split)?
+    );
+    Ok(())
+}
+
+pub fn build(cli: cli::BuildCommand) -> anyhow::Result<()> {
+    use anyhow::Context;
+    let workspace = workspace::Workspace::load(&cli.workspace)?;
+    let root = cli.workspace.parent().unwrap_or(Path::new(""));
+    let packages = workspace.packages(root, &cli.packages)?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+
+    let mut parsed = vec![];
+    for package in packages {
+        let context = || format!("package {}", package.name);
+        let mut sources = match is_stdin(&cli.input) {
+            true => vec![],
+            false => read_sources(&cli.input)?,
+        };
+        for dir in &package.dirs {
+            for file_path in directory_files(dir, &cli.input).with_context(context)? {
+                sources.push(read_file(&file_path)?);
+            }
+        }
+
+        let documents = parse_documents(sources, &cli.input, None).with_context(context)?;
+        parsed.push((package, documents));
+    }
+    // one lockfile covers the inputs of all packages
+    if let Some(lockfile) = &cli.input.lockfile {
+        let documents = parsed.iter().map(|(_, documents)| documents);
+        lockfile::check(lockfile, cli.input.update_lockfile, documents)?;
+    }
+
+    let mut out_of_date = vec![];
+    for (package, documents) in parsed {
+        let context = || format!("package {}", package.name);
+        let deprecations = Arc::new(deprecations::Deprecations::default());
+        let builder = document_builder(&cli.input).observer(deprecations.clone());
+        let document = plugins
+            .register(builder)?
+            .build(&documents)
+            .with_context(context)?;
+        deprecations::report_warnings(&document);
+        // outputs of a package often share values, evaluate each addressable once
+        let session = cco::session::EvalSession::new(document);
+
+        for target in &package.outputs {
+            let expression = cco::hcl_documents::parse_expression(&target.expression)
+                .map_err(|err| anyhow::anyhow!("{}: {err}", target.file.display()))
+                .with_context(context)?;
+            let value = session
+                .evaluate(expression)
+                .with_context(|| format!("package {}: {}", package.name, target.file.display()))?;
+
+            // the defaults of `cco eval`
+            let mut args = <cli::OutputArgs as clap::Parser>::parse_from(["cco"]);
+            args.format = target.format.clone();
+            args.output_file = Some(target.file.clone());
+            args.check = cli.check;
+            if let Some(dir) = target.file.parent().filter(|_| !cli.check) {
+                std::fs::create_dir_all(dir)?;
+            }
+            match output(&args, &value, false, &Default::default()) {
+                Err(err) => match err.downcast::<OutOfDate>() {
+                    Ok(OutOfDate(paths)) => out_of_date.extend(paths),
+                    Err(err) => return Err(err),
+                },
+                Ok(()) if !cli.check => eprintln!("wrote {}", target.file.display()),
+                Ok(()) => {}
+            }
+        }
+        deprecations.report(session.document(), false)?;
+    }
+
+    match out_of_date.is_empty() {
+        true => Ok(()),
+        false => Err(OutOfDate(out_of_date).into()),
+    }
+}
+
+fn build_document(
+    input: &cli::InputArgs,
+    plugins: &plugin::Plugins,
+    sources: Vec<SourceText>,
+) -> anyhow::Result<cco::cco_document::CcoDocument> {
+    let builder = document_builder(input);
+    Ok(plugins
+        .register(builder)?
+        .build(&parse_sources(sources, input)?)?)
+}
+
+/// Write the `changed` sources, with `dry_run` print a diff instead
+///
+/// Nothing is written unless the documents with the changes applied are valid and pass `check`.
+fn write_sources(
+    input: &cli::InputArgs,
+    plugins: &plugin::Plugins,
+    sources: &[SourceText],
+    changed: &[(PathBuf, String)],
+    dry_run: bool,
+    check: impl FnOnce(&cco::cco_document::CcoDocument) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    use anyhow::Context;
+    let contents_of = |path: &Path| {
+        changed
+            .iter()
+            .find(|(changed, _)| changed == path)
+            .map(|(_, text)| text)
+    };
+    let updated = sources
+        .iter()
+        .map(|(path, text)| {
+            let text = path.as_deref().and_then(contents_of).unwrap_or(text);
+            (path.clone(), text.clone())
+        })
+        .collect();
+    build_document(input, plugins, updated)
+        .and_then(|document| check(&document))
+        .map_err(|err| err.context("the changed documents are invalid"))?;
+
+    for (path, original) in sources {
+        let Some((path, text)) = path
+            .as_deref()
+            .and_then(|path| Some((path, contents_of(path)?)))
+        else {
+            continue;
+        };
+        let name = path.display().to_string();
+        if dry_run {
+            if let Some(diff) = diff::unified(original, text, &name, &name) {
+                print!("{}", diff::paint(&diff, style::Stream::Stdout));
+            }
+        } else if original != text {
+            replace_file(path, text).with_context(|| format!("failed to write {name}"))?;
+            eprintln!("updated {name}");
+        }
+    }
+    Ok(())
+}
+
+/// Write `contents` to a temporary file next to `path` first, then move it over `path`
+///
+/// An interrupted write leaves the original file intact. The permissions of `path` are kept.
+fn replace_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = PathBuf::from(tmp_path);
+
+    std::fs::write(&tmp_path, contents)?;
+    let replaced = std::fs::metadata(path)
+        .and_then(|metadata| std::fs::set_permissions(&tmp_path, metadata.permissions()))
+        .and_then(|()| std::fs::rename(&tmp_path, path));
+    if replaced.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    replaced
+}
+
+pub fn schema(cli: cli::SchemaCommand) -> anyhow::Result<()> {
+    let documents = parse_sources(read_sources(&cli.input)?, &cli.input)?;
+    // reject documents that could not be evaluated anyway
+    document_builder(&cli.input).build(&documents)?;
+
+    let schema = schema::generate(&documents, &cli.group)?;
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+pub fn test(cli: cli::TestCommand) -> anyhow::Result<()> {
+    let documents = parse_sources(read_sources(&cli.input)?, &cli.input)?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let deprecations = Arc::new(deprecations::Deprecations::default());
+    let builder = document_builder(&cli.input).observer(deprecations.clone());
+    let document = plugins.register(builder)?.build(&documents)?;
+    deprecations::report_warnings(&document);
+
+    let (mut passed, mut updated, mut failed) = (0, 0, 0);
+    for file in expect::test_files(&cli.tests)? {
+        let contents = std::fs::read_to_string(&file).map_err(LoadError::io(&file))?;
+        for expectation in expect::parse(&file, &contents)? {
+            let expression = &expectation.expression;
+            match expect::check(&document, &expectation, cli.update) {
+                expect::Outcome::Passed => passed += 1,
+                expect::Outcome::Updated => {
+                    updated += 1;
+                    println!("updated {expression}");
+                }
+                expect::Outcome::Failed(message) => {
+                    failed += 1;
+                    println!("FAILED  {expression} ({})\n{message}\n", file.display());
+                }
+            }
+        }
+    }
+    deprecations.report(&document, false)?;
+
+    println!("{passed} passed, {updated} updated, {failed} failed");
+    anyhow::ensure!(
+        failed == 0,
+        "{failed} of {} expectations failed",
+        passed + updated + failed
+    );
+    Ok(())
+}
+
+pub fn serve(cli: cli::ServeCommand) -> anyhow::Result<()> {
+    let sources = timed(None, "load", || read_sources(&cli.input))?;
+    let mut fingerprint = fingerprint(&sources);
+    let documents = timed(None, "parse", || parse_sources(sources, &cli.input))?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let document = timed(None, "build", || {
+        anyhow::Ok(
+            plugins
+                .register(serve_builder(&cli.input))?
+                .build(&documents)?,
+        )
+    })?;
+    let server = Arc::new(serve::Server::new(document.into(), cli.token));
+
+    if cli.watch {
+        anyhow::ensure!(
+            !is_stdin(&cli.input),
+            "--watch needs input files, stdin can not be watched"
+        );
+        let server = server.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(cli.poll_interval);
+            match reload(&cli.input, &plugins, fingerprint) {
+                Ok(None) => {}
+                Ok(Some((next, document))) => {
+                    fingerprint = next;
+                    server.replace(document.into());
+                    tracing::info!("sources changed, reloaded");
+                }
+                Err(err) => {
+                    // keep serving the last good state until the sources are fixed
+                    tracing::warn!("reload failed: {err:#}");
+                    server.reload_failed();
+                }
+            }
+        });
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_listen) = &cli.grpc_listen {
+        let address = serve::listen_address(grpc_listen);
+        let listener = std::net::TcpListener::bind(&address)
+            .map_err(|err| anyhow::anyhow!("failed to listen on {address}: {err}"))?;
+        eprintln!("gRPC listening on {}", listener.local_addr()?);
+        let grpc = grpc::bind(server.clone(), listener)?;
+        std::thread::spawn(move || {
+            if let Err(err) = grpc.serve() {
+                // serving only HTTP would hide the failure from gRPC clients
+                eprintln!("gRPC server failed: {err:#}");
+                std::process::exit(1);
+            }
+        });
+    }
+
+    let address = serve::listen_address(&cli.listen);
+    let listener = std::net::TcpListener::bind(&address)
+        .map_err(|err| anyhow::anyhow!("failed to listen on {address}: {err}"))?;
+    eprintln!("listening on http://{}", listener.local_addr()?);
+    server.run(listener)
+}
+
+/// Read the sources again, build a new document if they differ from `fingerprint`
+fn reload(
+    input: &cli::InputArgs,
+    plugins: &plugin::Plugins,
+    fingerprint: u64,
+) -> anyhow::Result<Option<(u64, cco::cco_document::CcoDocument)>> {
+    let sources = read_sources(input)?;
+    let next = self::fingerprint(&sources);
+    if next == fingerprint {
+        return Ok(None);
+    }
+    #[cfg(feature = "otel")]
+    let _span = tracing::info_span!("reload").entered();
+    let documents = timed(None, "parse", || parse_sources(sources, input))?;
+    let document = timed(None, "build", || {
+        anyhow::Ok(plugins.register(serve_builder(input))?.build(&documents)?)
+    })?;
+    Ok(Some((next, document)))
+}
+
+fn serve_builder(input: &cli::InputArgs) -> cco::cco_document::CcoDocumentBuilder {
+    // clients must not read files of the server
+    let builder = document_builder(input)
+        .file_functions(false)
+        .observer(deprecations::Immediate);
+    #[cfg(feature = "otel")]
+    let builder = otel::observe(builder);
+    builder
+}
+
+/// Hash of paths and contents, detects added, removed and changed files
+fn fingerprint(sources: &[SourceText]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    sources.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// (cco-)developer utilities
+///
+/// A quick way to expose internal structures for debugging purposes
+pub fn dev(cli: cli::DevCommand) -> anyhow::Result<()> {
+    use cli::DevSubCommand::*;
+
+    let documents = parse_sources(read_sources(&cli.input)?, &cli.input)?;
+    let plugins = plugin::Plugins::load(&cli.input)?;
+    let builder = document_builder(&cli.input);
+    let cco_document = plugins.register(builder)?.build(&documents)?;
+
+    match cli.command {
+        Documents if cli.show_sensitive => {
+            cco::cco_document::reveal_sensitive(|| println!("{documents:#?}"))
+        }
+        Documents => println!("{documents:#?}"),
+        Hcl if cli.show_sensitive => {
+            cco::cco_document::reveal_sensitive(|| println!("{cco_document:#?}"))
+        }
+        Hcl => println!("{cco_document:#?}"),
+        Tree => dev::tree(&cco_document, std::io::stdout().lock())?,
+        Stats(stats_cli) => {
+            let stats = dev::stats(&documents, &cco_document);
+            if stats_cli.json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                stats.report(std::io::stdout().lock())?;
+            }
+        }
+        Unresolved => {
+            let lines = dev::unresolved(&cco_document, &documents);
+            for line in &lines {
+                println!("{line}");
+            }
+            anyhow::ensure!(lines.is_empty(), "{} unresolved references", lines.len());
+        }
+        Defaults(defaults_cli) => {
+            let entry_points = deps::entry_points(&cco_document, &defaults_cli.entries)?;
+            let lines = dev::defaults(&cco_document, &documents, &entry_points);
+            for line in &lines {
+                println!("{line}");
+            }
+            anyhow::ensure!(lines.is_empty(), "{} ineffective defaults", lines.len());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overlays() {
+        let root = std::env::temp_dir().join(format!("cco-overlays-{}", std::process::id()));
+        let prod = root.join("overlays").join("prod");
+        std::fs::create_dir_all(root.join("base")).unwrap();
+        std::fs::create_dir_all(&prod).unwrap();
+        std::fs::write(
+            root.join("base").join("app.cco.hcl"),
+            "data app main {\n  replicas = 1\n  image = \"app:1\"\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            prod.join("app.cco.hcl"),
+            "data app main {\n  replicas = 3\n}\n",
+        )
+        .unwrap();
+
+        let input = |args: &[&std::ffi::OsStr]| {
+            let mut all = vec![std::ffi::OsStr::new("cco")];
+            all.extend(args);
+            <cli::InputArgs as clap::Parser>::parse_from(all)
+        };
+        let build = |input: &cli::InputArgs| {
+            let documents = parse_sources(read_sources(input)?, input)?;
+            anyhow::Ok(document_builder(input).build(&documents)?)
+        };
+
+        let overlaid = input(&[
+            "--overlay".as_ref(),
+            "prod".as_ref(),
+            "--overlay-root".as_ref(),
+            root.as_os_str(),
+        ]);
+        let document = build(&overlaid).unwrap();
+        assert_eq!(
+            serde_json::to_value(document.evaluate_path(&["app", "main"]).unwrap()).unwrap(),
+            serde_json::json!({ "replicas": 3, "image": "app:1" })
+        );
+
+        // other inputs still must not redefine blocks
+        let directories = input(&[
+            "-d".as_ref(),
+            root.join("base").as_os_str(),
+            "-d".as_ref(),
+            prod.as_os_str(),
+        ]);
+        assert!(build(&directories).is_err());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn durations() {
+        let duration = |value| cli::parse_duration(value).map(|duration| duration.as_secs_f64());
+        assert_eq!(duration("1h30m"), Ok(5400.0));
+        assert_eq!(duration("250ms"), Ok(0.25));
+        assert_eq!(duration("1d"), Ok(86400.0));
+        assert!(duration("-1s").unwrap_err().contains("negative"));
+        assert!(duration("10").is_err());
+    }
+
+    #[test]
+    fn replaced_files() {
+        let dir = std::env::temp_dir().join(format!("cco-replace-{}", std::process::id()));
+        let path = dir.join("a b.cco.hcl");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "data a {}\n").unwrap();
+
+        replace_file(&path, "data b {}\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data b {}\n");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        assert!(replace_file(&dir.join("missing").join("a.cco.hcl"), "").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn annotated_files() {
+        let dir = std::env::temp_dir().join(format!("cco-annotated-{}", std::process::id()));
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents
+            .insert_str(
+                "data service api {\n  port = 80\n}\n",
+                Some("prod.cco.hcl".into()),
+            )
+            .unwrap();
+        let document = cco::cco_document::CcoDocument::new(&documents).unwrap();
+        let path = [hcl::Identifier::sanitized("service")];
+        let value = document.evaluate_path(&["service"]).unwrap();
+        let annotations = annotate::annotations(&documents, &document, &path, &value);
+
+        let args = <cli::OutputArgs as clap::Parser>::parse_from([
+            "cco".as_ref(),
+            "--split-by-key".as_ref(),
+            "--output-dir".as_ref(),
+            dir.as_os_str(),
+        ]);
+        output(&args, &value, false, &annotations).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("api.yaml")).unwrap(),
+            "# from prod.cco.hcl:2\nport: 80\n"
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn expression_files() {
+        use clap::Parser;
+
+        let parse = |args: &[&str]| cli::Cli::try_parse_from([&["cco"][..], args].concat());
+        let Ok(cli::Cli {
+            command: cli::Command::Evaluate(eval),
+            ..
+        }) = parse(&["eval", "--expression-file", "-", "-f", "a.cco.hcl"])
+        else {
+            panic!("expected cco eval");
+        };
+        assert_eq!(eval.expression_file.as_deref(), Some(Path::new("-")));
+        // `-e` is an inline expression wherever it exists
+        assert!(parse(&["eval", "-e", "expression.hcl"]).is_err());
+        assert!(parse(&["env", "-e", "service.api.env", "--", "true"]).is_ok());
+
+        let stdin = "\n  { for k, v in service : k => v.port }\n".as_bytes();
+        assert_eq!(
+            read_expression(Path::new("-"), &eval.input, stdin).unwrap(),
+            "{ for k, v in service : k => v.port }"
+        );
+        // without input files the documents come from stdin
+        let Ok(cli::Cli {
+            command: cli::Command::Evaluate(eval),
+            ..
+        }) = parse(&["eval", "--expression-file", "-"])
+        else {
+            panic!("expected cco eval");
+        };
+        assert!(read_expression(Path::new("-"), &eval.input, "a".as_bytes())
+            .unwrap_err()
+            .to_string()
+            .contains("needs input files"));
+
+        let path = std::env::temp_dir().join(format!("cco-expression-{}.hcl", std::process::id()));
+        std::fs::write(&path, "service.api\n").unwrap();
+        assert_eq!(
+            read_expression(&path, &eval.input, std::io::empty()).unwrap(),
+            "service.api"
+        );
+        let _ = std::fs::remove_file(&path);
+        assert!(read_expression(&path, &eval.input, std::io::empty()).is_err());
+    }
+
+    #[test]
+    fn cache_keys() {
+        use clap::Parser;
+
+        let key = |args: &[&str]| {
+            let cli = cli::Cli::parse_from([&["cco", "eval"][..], args, &["service"]].concat());
+            let cli::Command::Evaluate(eval) = cli.command else {
+                panic!("expected cco eval");
+            };
+            cache_key(&eval, &[(None, "data service api {}".to_string())])
+        };
+        let default = key(&[]);
+        assert_eq!(default, key(&[]));
+        for options in [
+            &["--overflow", "saturate"][..],
+            &["--globals"],
+            &["--namespace-dirs", "."],
+            &["--overlay", "prod"],
+            &["--max-steps", "10"],
+            &["--check-label-case"],
+        ] {
+            assert_ne!(key(options), default, "{options:?}");
+        }
+    }
+
+    #[test]
+    fn each_output_dir() {
+        let dir = std::env::temp_dir().join(format!("cco-each-{}", std::process::id()));
+        let args = <cli::OutputArgs as clap::Parser>::parse_from([
+            "cco".as_ref(),
+            "--each".as_ref(),
+            "--output-dir".as_ref(),
+            dir.as_os_str(),
+        ]);
+        assert_eq!(
+            output(&args, &Value::Array(vec![]), false, &Default::default())
+                .unwrap_err()
+                .to_string(),
+            "--each requires the result to be an object"
+        );
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn chain_stops() {
+        let root = std::env::temp_dir().join(format!("cco-chain-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("envs").join("prod")).unwrap();
+        std::fs::create_dir_all(root.join("other")).unwrap();
+        // the work directory is canonical, like in `read_sources`
+        let root = root.canonicalize().unwrap();
+        let work = root.join("envs").join("prod");
+
+        let stop_at = |dir: &Path| {
+            let input = <cli::InputArgs as clap::Parser>::parse_from([
+                "cco".as_ref(),
+                "-c".as_ref(),
+                "--chain-stop-at".as_ref(),
+                dir.as_os_str(),
+            ]);
+            chain_stop_at(&input, &work).map_err(|err| err.to_string())
+        };
+        assert_eq!(stop_at(&root), Ok(Some(root.clone())));
+        assert_eq!(stop_at(&work.join("..")), Ok(Some(root.join("envs"))));
+        assert_eq!(stop_at(&work), Ok(Some(work.clone())));
+        assert!(stop_at(&root.join("other"))
+            .unwrap_err()
+            .contains("neither the work directory nor one of its parents"));
+        assert!(stop_at(&root.join("missing")).is_err());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,230 @@
+//! Server metrics in the Prometheus text format (`GET /metrics`)
+use cco::session::CacheStats;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds (seconds) of the evaluation duration histogram
+const BUCKETS: [f64; 9] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// responses by path and status
+    requests: Mutex<BTreeMap<(&'static str, u16), u64>>,
+    evaluation_errors: AtomicU64,
+    durations: Mutex<Histogram>,
+    reloads: AtomicU64,
+    reload_errors: AtomicU64,
+    /// cache lookups of sessions that were replaced by a reload
+    retired: Mutex<CacheStats>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// not cumulative, one more than [BUCKETS] for `+Inf`
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+}
+
+impl Metrics {
+    pub fn request(&self, path: &str, status: u16) {
+        // unknown paths share one label to keep the number of series bounded
+        let path = match path {
+            "/eval" => "/eval",
+            "/watch" => "/watch",
+            "/healthz" => "/healthz",
+            "/metrics" => "/metrics",
+            _ => "other",
+        };
+        *lock(&self.requests).entry((path, status)).or_default() += 1;
+    }
+
+    /// An expression was evaluated (successfully or not) in `duration`
+    pub fn evaluation(&self, duration: Duration, success: bool) {
+        if !success {
+            self.evaluation_errors.fetch_add(1, Relaxed);
+        }
+
+        let seconds = duration.as_secs_f64();
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(BUCKETS.len());
+        let mut durations = lock(&self.durations);
+        durations.counts[bucket] += 1;
+        durations.sum += seconds;
+    }
+
+    /// The sources were reloaded, `previous` are the cache lookups of the replaced session
+    pub fn reload(&self, previous: CacheStats) {
+        self.reloads.fetch_add(1, Relaxed);
+        let mut retired = lock(&self.retired);
+        retired.hits += previous.hits;
+        retired.misses += previous.misses;
+    }
+
+    pub fn reload_error(&self) {
+        self.reload_errors.fetch_add(1, Relaxed);
+    }
+
+    /// All metrics, `current` and `memoized` describe the session in use
+    pub fn render(&self, current: CacheStats, memoized: usize) -> String {
+        let mut out = String::new();
+        header(
+            &mut out,
+            "cco_requests_total",
+            "counter",
+            "HTTP responses by path and status.",
+        );
+        for ((path, status), count) in lock(&self.requests).iter() {
+            let _ = writeln!(
+                out,
+                "cco_requests_total{{path=\"{path}\",status=\"{status}\"}} {count}"
+            );
+        }
+
+        let durations = lock(&self.durations);
+        let evaluations: u64 = durations.counts.iter().sum();
+        header(
+            &mut out,
+            "cco_evaluations_total",
+            "counter",
+            "Evaluated expressions.",
+        );
+        let _ = writeln!(out, "cco_evaluations_total {evaluations}");
+        header(
+            &mut out,
+            "cco_evaluation_errors_total",
+            "counter",
+            "Expressions that failed to evaluate.",
+        );
+        let _ = writeln!(
+            out,
+            "cco_evaluation_errors_total {}",
+            self.evaluation_errors.load(Relaxed)
+        );
+
+        header(
+            &mut out,
+            "cco_evaluation_duration_seconds",
+            "histogram",
+            "Time to evaluate and render an expression.",
+        );
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(&durations.counts) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "cco_evaluation_duration_seconds_bucket{{le=\"{bound}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "cco_evaluation_duration_seconds_bucket{{le=\"+Inf\"}} {evaluations}\n\
+             cco_evaluation_duration_seconds_sum {}\n\
+             cco_evaluation_duration_seconds_count {evaluations}",
+            durations.sum
+        );
+
+        let retired = *lock(&self.retired);
+        header(
+            &mut out,
+            "cco_cache_hits_total",
+            "counter",
+            "References answered with an already evaluated addressable.",
+        );
+        let _ = writeln!(out, "cco_cache_hits_total {}", retired.hits + current.hits);
+        header(
+            &mut out,
+            "cco_cache_misses_total",
+            "counter",
+            "References that required evaluating an addressable.",
+        );
+        let _ = writeln!(
+            out,
+            "cco_cache_misses_total {}",
+            retired.misses + current.misses
+        );
+        header(
+            &mut out,
+            "cco_memoized_addressables",
+            "gauge",
+            "Evaluated addressables kept by the current session.",
+        );
+        let _ = writeln!(out, "cco_memoized_addressables {memoized}");
+
+        header(
+            &mut out,
+            "cco_reloads_total",
+            "counter",
+            "Successful reloads of changed sources.",
+        );
+        let _ = writeln!(out, "cco_reloads_total {}", self.reloads.load(Relaxed));
+        header(
+            &mut out,
+            "cco_reload_errors_total",
+            "counter",
+            "Reloads that failed, the previous state is kept.",
+        );
+        let _ = writeln!(
+            out,
+            "cco_reload_errors_total {}",
+            self.reload_errors.load(Relaxed)
+        );
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rendered() {
+        let metrics = Metrics::default();
+        metrics.request("/eval", 200);
+        metrics.request("/eval", 200);
+        metrics.request("/nope", 404);
+        metrics.evaluation(Duration::from_millis(3), true);
+        metrics.evaluation(Duration::from_secs(10), false);
+        metrics.reload(CacheStats { hits: 5, misses: 2 });
+        metrics.reload_error();
+
+        let text = metrics.render(CacheStats { hits: 1, misses: 1 }, 4);
+        for line in [
+            "# TYPE cco_requests_total counter",
+            "cco_requests_total{path=\"/eval\",status=\"200\"} 2",
+            "cco_requests_total{path=\"other\",status=\"404\"} 1",
+            "cco_evaluations_total 2",
+            "cco_evaluation_errors_total 1",
+            "cco_evaluation_duration_seconds_bucket{le=\"0.001\"} 0",
+            "cco_evaluation_duration_seconds_bucket{le=\"0.005\"} 1",
+            "cco_evaluation_duration_seconds_bucket{le=\"5\"} 1",
+            "cco_evaluation_duration_seconds_bucket{le=\"+Inf\"} 2",
+            "cco_evaluation_duration_seconds_count 2",
+            "cco_cache_hits_total 6",
+            "cco_cache_misses_total 3",
+            "cco_memoized_addressables 4",
+            "cco_reloads_total 1",
+            "cco_reload_errors_total 1",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing '{line}' in\n{text}"
+            );
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! OpenTelemetry trace export (feature `otel`)
+//!
+//! Enabled by the standard `OTEL_EXPORTER_OTLP_ENDPOINT` (or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`)
+//! variable, spans are sent via OTLP/HTTP. Phases (`load`, `parse`, `build`, `evaluate`, ...) and
+//! requests of `cco serve` are tracing spans, evaluated addressables are reported by [Addressables].
+use cco::cco_document::{CcoDocumentBuilder, Kind};
+use opentelemetry::trace::{Span as _, Tracer as _, TracerProvider as _};
+use opentelemetry::KeyValue;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+static TRACER: OnceLock<opentelemetry_sdk::trace::Tracer> = OnceLock::new();
+
+/// Flushes pending spans when dropped
+pub struct Telemetry(opentelemetry_sdk::trace::SdkTracerProvider);
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.shutdown() {
+            eprintln!("failed to export traces: {err}");
+        }
+    }
+}
+
+/// The export layer, `None` if no endpoint is configured
+pub fn layer<S>() -> Option<(impl Layer<S>, Telemetry)>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    let configured = [
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    ]
+    .iter()
+    .any(|name| std::env::var_os(name).is_some());
+    if !configured {
+        return None;
+    }
+
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(err) => {
+            eprintln!("traces are not exported: {err}");
+            return None;
+        }
+    };
+    let mut resource = opentelemetry_sdk::Resource::builder();
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name("cco");
+    }
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+
+    let tracer = provider.tracer("cco");
+    let _ = TRACER.set(tracer.clone());
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(tracing_subscriber::filter::LevelFilter::INFO);
+    Some((layer, Telemetry(provider)))
+}
+
+/// Traces are exported
+pub fn enabled() -> bool {
+    TRACER.get().is_some()
+}
+
+/// Report evaluated addressables if traces are exported
+pub fn observe(builder: CcoDocumentBuilder) -> CcoDocumentBuilder {
+    match enabled() {
+        true => builder.observer(Addressables),
+        false => builder,
+    }
+}
+
+/// One span per evaluated addressable, children of the current tracing span
+///
+/// Evaluation is not recursive, so spans are recorded once an addressable is resolved. Their
+/// duration includes dependencies, which are siblings and not children.
+pub struct Addressables;
+
+impl cco::observer::EvalObserver for Addressables {
+    fn on_resolve(&self, path: &[hcl::Identifier], kind: Kind, duration: Duration) {
+        let Some(tracer) = TRACER.get() else {
+            return;
+        };
+        let path = path.join(".");
+        let end = SystemTime::now();
+        let start = end.checked_sub(duration).unwrap_or(end);
+        tracer
+            .span_builder(format!("resolve {path}"))
+            .with_start_time(start)
+            .with_attributes([
+                KeyValue::new("cco.path", path),
+                KeyValue::new("cco.kind", format!("{kind:?}")),
+            ])
+            .start_with_context(tracer, &tracing::Span::current().context())
+            .end_with_timestamp(end);
+    }
+}
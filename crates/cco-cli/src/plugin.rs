@@ -0,0 +1,404 @@
+//! External plugins providing functions and data source blocks
+//!
+//! A plugin is an executable named `cco-plugin-<name>` on `PATH`. It is started once and talks
+//! JSON over stdio, one message per line:
+//!
+//! - `{"method": "describe"}` is sent first, the result names the functions and block types the
+//!   plugin provides: `{"functions": ["lookup"], "blocks": ["vault"]}`
+//! - `{"method": "call", "function": "lookup", "args": [...]}` calls a function
+//! - `{"method": "data_source", "kind": "vault", "labels": ["db"], "body": {...}}` evaluates a block
+//!   `vault db { ... }` with its evaluated attributes
+//!
+//! Every message is answered with `{"result": <value>}` or `{"error": "<message>"}`. Stdin is
+//! closed when `cco` is done, the plugin should exit then.
+use cco::cco_document::CcoDocumentBuilder;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+const PREFIX: &str = "cco-plugin-";
+
+pub struct Plugin {
+    name: String,
+    functions: Vec<String>,
+    blocks: Vec<String>,
+    process: Mutex<Process>,
+}
+
+struct Process {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+#[derive(serde::Deserialize)]
+struct Description {
+    #[serde(default)]
+    functions: Vec<String>,
+    #[serde(default)]
+    blocks: Vec<String>,
+}
+
+/// `{"result": ..}` or `{"error": ".."}`
+#[derive(serde::Deserialize)]
+pub struct Reply {
+    result: Option<serde_json::Value>,
+    error: Option<String>,
+}
+
+impl Reply {
+    pub fn into_result(self) -> Result<serde_json::Value, String> {
+        match (self.result, self.error) {
+            (_, Some(error)) => Err(error),
+            (Some(result), None) => Ok(result),
+            (None, None) => Ok(serde_json::Value::Null),
+        }
+    }
+}
+
+/// Plugins and modules given with `--plugin`, `--all-plugins` and `--wasm`, and `external` blocks
+#[derive(Default)]
+pub struct Plugins {
+    processes: Vec<Arc<Plugin>>,
+    allow_exec: bool,
+    #[cfg_attr(
+        not(any(feature = "http-source", feature = "tfstate")),
+        allow(dead_code)
+    )]
+    offline: bool,
+    /// set once an `http` block or `tfstate` URL was fetched
+    fetched: Arc<std::sync::atomic::AtomicBool>,
+    /// files read by `tfstate` blocks, with the hashes of their contents
+    inputs: Arc<std::sync::Mutex<cco::cache::Inputs>>,
+    /// data sources of `--lockfile`
+    #[cfg_attr(
+        not(any(feature = "http-source", feature = "tfstate", feature = "wasm")),
+        allow(dead_code)
+    )]
+    lock: Option<Arc<crate::lockfile::Sources>>,
+    #[cfg(feature = "wasm")]
+    modules: Vec<Arc<crate::wasm::Module>>,
+}
+
+impl Plugins {
+    pub fn load(input: &crate::cli::InputArgs) -> anyhow::Result<Self> {
+        let lock = input.lockfile.as_deref().map(|lockfile| {
+            Arc::new(crate::lockfile::Sources::new(
+                lockfile,
+                input.update_lockfile,
+            ))
+        });
+        Ok(Self {
+            processes: start(&input.plugins, input.all_plugins)?,
+            allow_exec: input.allow_exec,
+            offline: input.offline,
+            fetched: Arc::default(),
+            inputs: Arc::default(),
+            #[cfg(feature = "wasm")]
+            modules: crate::wasm::load(input, lock.as_deref())?,
+            lock,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "wasm")]
+        if !self.modules.is_empty() {
+            return false;
+        }
+        self.processes.is_empty() && !self.allow_exec
+    }
+
+    /// Whether values were fetched from the network during the evaluation
+    pub fn fetched(&self) -> bool {
+        self.fetched.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Files the evaluation read besides the sources, a cached result depends on them too
+    pub fn inputs(&self) -> cco::cache::Inputs {
+        self.inputs.lock().expect("not poisoned").clone()
+    }
+
+    /// Make the functions and blocks of all plugins available to the document
+    pub fn register(&self, mut builder: CcoDocumentBuilder) -> anyhow::Result<CcoDocumentBuilder> {
+        let mut provided: Vec<(String, String)> = vec![];
+        let mut provide = |function: &str, owner: &str| {
+            if let Some((_, other)) = provided.iter().find(|(name, _)| name == function) {
+                anyhow::bail!("function {function} is provided by {other} and {owner}");
+            }
+            provided.push((function.to_string(), owner.to_string()));
+            hcl::Identifier::new(function)
+                .map_err(|_| anyhow::anyhow!("{owner}: invalid function name '{function}'"))
+        };
+
+        for plugin in &self.processes {
+            for function in &plugin.functions {
+                let name = provide(function, &plugin.name)?;
+                let (plugin, function) = (plugin.clone(), function.clone());
+                builder = builder.external_function(name, move |args| plugin.call(&function, args));
+            }
+
+            for kind in &plugin.blocks {
+                anyhow::ensure!(
+                    !cco::cco_document::BLOCK_KINDS.contains(&kind.as_str())
+                        && ![crate::external::KIND, "http", "tfstate"].contains(&kind.as_str()),
+                    "plugin {}: block type {kind} is reserved",
+                    plugin.name
+                );
+                let (plugin, kind) = (plugin.clone(), kind.clone());
+                builder = builder.data_source(kind.clone(), move |labels, body| {
+                    plugin.data_source(&kind, labels, body)
+                });
+            }
+        }
+
+        #[cfg(feature = "wasm")]
+        for module in &self.modules {
+            for function in module.functions() {
+                let name = provide(function, module.name())?;
+                let (module, function) = (module.clone(), function.clone());
+                builder = builder.external_function(name, move |args| module.call(&function, args));
+            }
+        }
+
+        #[cfg(feature = "http-source")]
+        let builder = crate::http_source::register(
+            builder,
+            self.offline,
+            self.fetched.clone(),
+            self.lock.clone(),
+        );
+        #[cfg(feature = "tfstate")]
+        let builder = crate::tfstate::register(
+            builder,
+            self.offline,
+            self.fetched.clone(),
+            self.inputs.clone(),
+            self.lock.clone(),
+        );
+        Ok(crate::external::register(builder, self.allow_exec))
+    }
+}
+
+impl Plugin {
+    /// Start the plugin and ask what it provides
+    pub fn spawn(name: &str, path: &Path) -> anyhow::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| anyhow::anyhow!("failed to start plugin {}: {err}", path.display()))?;
+        let process = Process {
+            stdin: child.stdin.take(),
+            stdout: BufReader::new(child.stdout.take().expect("stdout is piped")),
+            child,
+        };
+
+        let mut plugin = Self {
+            name: name.to_string(),
+            functions: vec![],
+            blocks: vec![],
+            process: Mutex::new(process),
+        };
+        let description = plugin
+            .request(&serde_json::json!({ "method": "describe" }))
+            .map_err(|err| anyhow::anyhow!("{err}"))?;
+        let description: Description = serde_json::from_value(description)
+            .map_err(|err| anyhow::anyhow!("plugin {name}: invalid description: {err}"))?;
+        plugin.functions = description.functions;
+        plugin.blocks = description.blocks;
+        tracing::debug!(
+            plugin = name,
+            functions = ?plugin.functions,
+            blocks = ?plugin.blocks,
+            "loaded plugin"
+        );
+        Ok(plugin)
+    }
+
+    /// Send one message and wait for the reply
+    fn request(&self, message: &serde_json::Value) -> Result<serde_json::Value, String> {
+        let mut process = self
+            .process
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let failed = |err: std::io::Error| format!("plugin {}: {err}", self.name);
+
+        let stdin = process.stdin.as_mut().ok_or("plugin was stopped")?;
+        writeln!(stdin, "{message}").map_err(failed)?;
+        stdin.flush().map_err(failed)?;
+
+        let mut line = String::new();
+        if process.stdout.read_line(&mut line).map_err(failed)? == 0 {
+            return Err(format!("plugin {} exited unexpectedly", self.name));
+        }
+        let reply: Reply = serde_json::from_str(&line)
+            .map_err(|err| format!("plugin {}: invalid reply: {err}", self.name))?;
+        reply.into_result()
+    }
+
+    fn call(&self, function: &str, args: &[hcl::Value]) -> Result<hcl::Value, String> {
+        let message = serde_json::json!({ "method": "call", "function": function, "args": args });
+        to_hcl(self.request(&message)?)
+    }
+
+    fn data_source(
+        &self,
+        kind: &str,
+        labels: &[String],
+        body: hcl::Value,
+    ) -> Result<hcl::Value, String> {
+        let message = serde_json::json!({
+            "method": "data_source",
+            "kind": kind,
+            "labels": labels,
+            "body": body,
+        });
+        to_hcl(self.request(&message)?)
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        let process = self
+            .process
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        // closing stdin asks the plugin to exit
+        drop(process.stdin.take());
+        let _ = process.child.wait();
+    }
+}
+
+pub fn to_hcl(value: serde_json::Value) -> Result<hcl::Value, String> {
+    serde_json::from_value(value).map_err(|err| err.to_string())
+}
+
+/// Names and paths of all plugins on `PATH`, the first one of each name wins
+pub fn discover() -> Vec<(String, PathBuf)> {
+    let mut plugins: Vec<(String, PathBuf)> = vec![];
+    let Some(path) = std::env::var_os("PATH") else {
+        return plugins;
+    };
+
+    for dir in std::env::split_paths(&path) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut found: Vec<_> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let file_name = entry.file_name().into_string().ok()?;
+                let name = file_name.strip_prefix(PREFIX)?;
+                let name = name.strip_suffix(".exe").unwrap_or(name).to_string();
+                is_executable(&entry.path()).then(|| (name, entry.path()))
+            })
+            .collect();
+        found.sort();
+        for (name, path) in found {
+            if !plugins.iter().any(|(existing, _)| *existing == name) {
+                plugins.push((name, path));
+            }
+        }
+    }
+    plugins
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Start the plugins named in `names`, or all plugins on `PATH` with `all`
+fn start(names: &[String], all: bool) -> anyhow::Result<Vec<Arc<Plugin>>> {
+    if names.is_empty() && !all {
+        return Ok(vec![]);
+    }
+
+    let available = discover();
+    let selected: Vec<_> = match all {
+        true => available,
+        false => names
+            .iter()
+            .map(|name| {
+                available
+                    .iter()
+                    .find(|(available, _)| available == name)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("plugin {PREFIX}{name} not found on PATH"))
+            })
+            .collect::<anyhow::Result<_>>()?,
+    };
+
+    selected
+        .iter()
+        .map(|(name, path)| Plugin::spawn(name, path).map(Arc::new))
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    /// Plugin answering every call with the number of the request
+    fn script(dir: &Path) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join("cco-plugin-test");
+        std::fs::write(
+            &path,
+            r#"#!/bin/sh
+read -r describe
+echo '{"result": {"functions": ["answer"], "blocks": ["fixed"]}}'
+while read -r line; do
+  case "$line" in
+    *'"data_source"'*) echo '{"result": {"port": 8080}}' ;;
+    *'"fail"'*) echo '{"error": "failed on purpose"}' ;;
+    *) echo '{"result": 42}' ;;
+  esac
+done
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn protocol() {
+        let dir = std::env::temp_dir().join(format!("cco-plugin-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plugin = Arc::new(Plugin::spawn("test", &script(&dir)).unwrap());
+        assert_eq!(plugin.functions, ["answer"]);
+        assert_eq!(plugin.blocks, ["fixed"]);
+
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents
+            .insert_str(
+                "data app {\n  answer = answer()\n  port = fixed.db.port\n}\nfixed db {\n}\n",
+                None,
+            )
+            .unwrap();
+        let plugins = Plugins {
+            processes: vec![plugin.clone()],
+            ..Default::default()
+        };
+        let builder = plugins.register(cco::cco_document::CcoDocument::builder());
+        let document = builder.unwrap().build(&documents).unwrap();
+        let value = document.evaluate_path(&["app"]).unwrap();
+        assert_eq!(
+            serde_json::to_value(value).unwrap(),
+            serde_json::json!({ "answer": 42, "port": 8080 })
+        );
+
+        assert_eq!(plugin.call("fail", &[]), Err("failed on purpose".into()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,475 @@
+//! `--query`: a small subset of jq
+//!
+//! Supported:
+//! - paths: `.`, `.a.b`, `.["a b"]`, `.[0]`, `.[]`, `.a[].b`
+//! - pipes: `.services[] | .url`
+//! - `select(<query> <op> <query>)` with `==`, `!=`, `<`, `<=`, `>`, `>=`, or `select(<query>)`
+//! - `keys` (sorted like jq), `length`
+//! - literals: numbers, `"strings"`, `true`, `false`
+//!
+//! Unlike jq, missing keys and out of range indices produce no output instead of `null`. The
+//! results are output as a list, however many there are.
+use cco::value::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query(Vec<Filter>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Path(Vec<Segment>),
+    Select(Box<Condition>),
+    Keys,
+    Length,
+    Literal(serde_json::Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Iterate,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Truthy(Query),
+    Compare(Query, Comparison, Query),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl std::str::FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let query = parser.query()?;
+        parser.skip_whitespace();
+        if parser.pos != s.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(query)
+    }
+}
+
+impl Query {
+    /// Run the query, the results are returned as an array
+    ///
+    /// With `unwrap` a single result is returned as is.
+    pub fn apply(&self, value: Value, unwrap: bool) -> anyhow::Result<Value> {
+        let mut results = self.run(value)?;
+        Ok(if unwrap && results.len() == 1 {
+            results.remove(0)
+        } else {
+            Value::Array(results)
+        })
+    }
+
+    /// Keys a query only descends into (`.a.b`, `.a | select(.b)`), its result is the value at
+    /// that path
+    pub fn key_path(&self) -> Option<Vec<String>> {
+        let mut path = vec![];
+        for filter in &self.0 {
+            match filter {
+                Filter::Path(segments) => {
+                    for segment in segments {
+                        let Segment::Key(key) = segment else {
+                            return None;
+                        };
+                        path.push(key.clone());
+                    }
+                }
+                Filter::Select(_) => {}
+                _ => return None,
+            }
+        }
+        Some(path)
+    }
+
+    fn run(&self, value: Value) -> anyhow::Result<Vec<Value>> {
+        self.0.iter().try_fold(vec![value], |values, filter| {
+            values.into_iter().try_fold(vec![], |mut results, value| {
+                results.extend(filter.run(value)?);
+                Ok(results)
+            })
+        })
+    }
+}
+
+impl Filter {
+    fn run(&self, value: Value) -> anyhow::Result<Vec<Value>> {
+        Ok(match self {
+            Filter::Path(segments) => {
+                segments.iter().try_fold(vec![value], |values, segment| {
+                    values.into_iter().try_fold(vec![], |mut results, value| {
+                        results.extend(segment.run(value)?);
+                        anyhow::Ok(results)
+                    })
+                })?
+            }
+            Filter::Select(condition) => {
+                if condition.holds(&value)? {
+                    vec![value]
+                } else {
+                    vec![]
+                }
+            }
+            Filter::Keys => match value {
+                Value::Object(object) => {
+                    let mut keys: Vec<String> = object.into_keys().collect();
+                    keys.sort();
+                    vec![Value::Array(keys.into_iter().map(Value::String).collect())]
+                }
+                Value::Array(array) => vec![Value::Array(
+                    (0..array.len() as i64).map(Value::Integer).collect(),
+                )],
+                other => anyhow::bail!("{} has no keys", other.type_name()),
+            },
+            Filter::Length => vec![Value::Integer(match &value {
+                Value::Object(object) => object.len(),
+                Value::Array(array) => array.len(),
+                Value::String(string) => string.chars().count(),
+                Value::Bytes(bytes) => bytes.len(),
+                other => anyhow::bail!("{} has no length", other.type_name()),
+            } as i64)],
+            Filter::Literal(literal) => vec![Value::try_from(literal.clone())?],
+        })
+    }
+}
+
+impl Segment {
+    fn run(&self, value: Value) -> anyhow::Result<Vec<Value>> {
+        Ok(match (self, value) {
+            (Segment::Key(key), Value::Object(mut object)) => {
+                object.shift_remove(key).into_iter().collect()
+            }
+            (Segment::Index(index), Value::Array(mut array)) if *index < array.len() => {
+                vec![array.swap_remove(*index)]
+            }
+            (Segment::Index(_), Value::Array(_)) => vec![],
+            (Segment::Iterate, Value::Array(array)) => array,
+            (Segment::Iterate, Value::Object(object)) => object.into_values().collect(),
+            (segment, value) => anyhow::bail!("cannot apply {segment:?} to {}", value.type_name()),
+        })
+    }
+}
+
+impl Condition {
+    fn holds(&self, value: &Value) -> anyhow::Result<bool> {
+        match self {
+            Condition::Truthy(query) => Ok(query
+                .run(value.clone())?
+                .iter()
+                .any(|value| !matches!(value, Value::Boolean(false)))),
+            Condition::Compare(lhs, comparison, rhs) => {
+                let lhs = lhs.run(value.clone())?;
+                let rhs = rhs.run(value.clone())?;
+                for lhs in &lhs {
+                    for rhs in &rhs {
+                        if comparison.holds(lhs, rhs) {
+                            return Ok(true);
+                        }
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl Comparison {
+    fn holds(self, lhs: &Value, rhs: &Value) -> bool {
+        use std::cmp::Ordering;
+
+        let ordering = match (lhs, rhs) {
+            (Value::String(lhs), Value::String(rhs)) => Some(lhs.cmp(rhs)),
+            (lhs, rhs) => match (lhs.as_f64(), rhs.as_f64()) {
+                (Some(lhs), Some(rhs)) => lhs.partial_cmp(&rhs),
+                _ => None,
+            },
+        };
+        let equal = match ordering {
+            Some(ordering) => ordering == Ordering::Equal,
+            None => serde_json::Value::from(lhs.clone()) == serde_json::Value::from(rhs.clone()),
+        };
+
+        match self {
+            Comparison::Eq => equal,
+            Comparison::Ne => !equal,
+            Comparison::Lt => ordering == Some(Ordering::Less),
+            Comparison::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Comparison::Gt => ordering == Some(Ordering::Greater),
+            Comparison::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{message} at position {} of query", self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{token}'")))
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, String> {
+        let mut filters = vec![self.filter()?];
+        while self.eat("|") {
+            filters.push(self.filter()?);
+        }
+        Ok(Query(filters))
+    }
+
+    fn filter(&mut self) -> Result<Filter, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+
+        if rest.starts_with('.') {
+            return self.path().map(Filter::Path);
+        }
+        if self.eat("select(") {
+            let condition = self.condition()?;
+            self.expect(")")?;
+            return Ok(Filter::Select(Box::new(condition)));
+        }
+
+        let word_len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        let filter = match &rest[..word_len] {
+            "keys" => Filter::Keys,
+            "length" => Filter::Length,
+            "true" => Filter::Literal(true.into()),
+            "false" => Filter::Literal(false.into()),
+            _ if rest.starts_with('"') => return self.string().map(|s| Filter::Literal(s.into())),
+            number if !number.is_empty() => Filter::Literal(
+                serde_json::from_str(number).map_err(|_| self.error("invalid number"))?,
+            ),
+            _ => return Err(self.error("expected a filter")),
+        };
+        self.pos += word_len;
+        Ok(filter)
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let lhs = self.query()?;
+        for (token, comparison) in [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ] {
+            if self.eat(token) {
+                return Ok(Condition::Compare(lhs, comparison, self.query()?));
+            }
+        }
+        Ok(Condition::Truthy(lhs))
+    }
+
+    fn path(&mut self) -> Result<Vec<Segment>, String> {
+        self.expect(".")?;
+        let mut segments = vec![];
+        if let Some(key) = self.key() {
+            segments.push(Segment::Key(key));
+        }
+
+        loop {
+            if self.rest().starts_with('[') {
+                self.pos += 1;
+                segments.push(self.bracket()?);
+            } else if self.rest().starts_with(".[") {
+                self.pos += 2;
+                segments.push(self.bracket()?);
+            } else if self.rest().starts_with('.') {
+                self.pos += 1;
+                let key = self.key().ok_or_else(|| self.error("expected a key"))?;
+                segments.push(Segment::Key(key));
+            } else {
+                return Ok(segments);
+            }
+        }
+    }
+
+    fn key(&mut self) -> Option<String> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '-')
+            .unwrap_or(rest.len());
+        self.pos += len;
+        (len > 0).then(|| rest[..len].to_string())
+    }
+
+    /// Contents of `[...]` after the opening bracket
+    fn bracket(&mut self) -> Result<Segment, String> {
+        if self.eat("]") {
+            return Ok(Segment::Iterate);
+        }
+
+        self.skip_whitespace();
+        let segment = if self.rest().starts_with('"') {
+            Segment::Key(self.string()?)
+        } else {
+            let rest = self.rest();
+            let digits = &rest[..rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len())];
+            let index = digits
+                .parse()
+                .map_err(|_| self.error("expected an index"))?;
+            self.pos += digits.len();
+            Segment::Index(index)
+        };
+        self.expect("]")?;
+        Ok(segment)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let rest = self.rest();
+        let mut escaped = false;
+        for (index, c) in rest.char_indices().skip(1) {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    let literal = &rest[..=index];
+                    self.pos += literal.len();
+                    return serde_json::from_str(literal).map_err(|_| self.error("invalid string"));
+                }
+                _ => escaped = false,
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn query(query: &str, input: serde_json::Value) -> serde_json::Value {
+        let query: Query = query.parse().unwrap();
+        query.apply(input.try_into().unwrap(), true).unwrap().into()
+    }
+
+    #[test]
+    fn paths() {
+        let input = serde_json::json!({ "a": { "b c": [1, 2, 3] } });
+        assert_eq!(query(".", input.clone()), input);
+        assert_eq!(query(".a[\"b c\"][1]", input.clone()), serde_json::json!(2));
+        assert_eq!(
+            query(".a.[\"b c\"][]", input.clone()),
+            serde_json::json!([1, 2, 3])
+        );
+        assert_eq!(query(".missing", input), serde_json::json!([]));
+
+        let path = |query: &str| query.parse::<Query>().unwrap().key_path();
+        assert_eq!(path("."), Some(vec![]));
+        assert_eq!(
+            path(".a | select(.b) | .[\"b c\"]"),
+            Some(vec!["a".to_string(), "b c".to_string()])
+        );
+        assert_eq!(path(".a[0]"), None);
+        assert_eq!(path(".a | keys"), None);
+    }
+
+    #[test]
+    fn lists() {
+        let input = serde_json::json!({ "services": [{ "port": 80 }, { "port": 443 }] });
+        let query = |query: &str, input: &serde_json::Value| {
+            let query: Query = query.parse().unwrap();
+            serde_json::Value::from(
+                query
+                    .apply(input.clone().try_into().unwrap(), false)
+                    .unwrap(),
+            )
+        };
+        assert_eq!(
+            query(".services[].port", &input),
+            serde_json::json!([80, 443])
+        );
+        assert_eq!(
+            query(".services[] | select(.port > 80) | .port", &input),
+            serde_json::json!([443])
+        );
+        assert_eq!(query(".services[5]", &input), serde_json::json!([]));
+    }
+
+    #[test]
+    fn pipes_and_select() {
+        let input = serde_json::json!({
+            "services": [
+                { "name": "a", "enabled": true, "port": 80 },
+                { "name": "b", "enabled": false, "port": 443 },
+                { "name": "c", "enabled": true, "port": 8080 },
+            ]
+        });
+        assert_eq!(
+            query(".services[] | select(.enabled) | .name", input.clone()),
+            serde_json::json!(["a", "c"])
+        );
+        assert_eq!(
+            query(".services[] | select(.port >= 443) | .name", input.clone()),
+            serde_json::json!(["b", "c"])
+        );
+        assert_eq!(
+            query(
+                ".services[] | select(.name == \"b\") | .port",
+                input.clone()
+            ),
+            serde_json::json!(443)
+        );
+        assert_eq!(
+            query(".services | length", input.clone()),
+            serde_json::json!(3)
+        );
+        assert_eq!(
+            query(".services[0] | keys", input),
+            serde_json::json!(["enabled", "name", "port"])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!("services".parse::<Query>().is_err());
+        assert!(".a[".parse::<Query>().is_err());
+        assert!("select(.a".parse::<Query>().is_err());
+    }
+}
@@ -0,0 +1,496 @@
+//! Rename an element and every reference to it
+//!
+//! `cco rename service.api service.public_api` relabels the data block and rewrites all
+//! traversals starting with the old path. Only the spans of labels, keys and traversal segments
+//! are replaced in the source text, formatting and comments stay as they are.
+//!
+//! Data groups, data blocks, aliases and attributes of data blocks can be renamed, attributes
+//! only within their block. `self.<key>` in the `type` block of the group is rewritten along with
+//! the attribute, unless other blocks of the group define the old or new key as well. References
+//! built from strings (e.g. `include_partials`) or through variables of `for` expressions
+//! (`[for s in service: s.api]`) are not rewritten, nor are references a `for` variable of the
+//! new name would shadow.
+use cco::cco_document::{CcoDocument, Kind};
+use hcl_edit::expr::{Expression, ForExpr, Traversal, TraversalOperator};
+use hcl_edit::structure::{Block, BlockLabel};
+use hcl_edit::template::ForTemplateExpr;
+use hcl_edit::visit::{self, Visit};
+use hcl_edit::Span;
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// Block types whose labels are (part of) the path of an element
+const LABELED_KINDS: &[&str] = &["data", "type", "alias"];
+
+/// New contents of the sources that change when renaming `from` to `to`
+///
+/// Both paths must have the same length. Sources without a path can not be rewritten.
+pub fn rename(
+    document: &CcoDocument,
+    sources: &[(Option<PathBuf>, String)],
+    from: &[hcl::Identifier],
+    to: &[hcl::Identifier],
+) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let (from_str, to_str) = (from.join("."), to.join("."));
+    anyhow::ensure!(
+        from.len() == to.len(),
+        "{from_str} and {to_str} must have the same number of segments"
+    );
+    anyhow::ensure!(from != to, "{from_str} and {to_str} are the same");
+    let info = document
+        .get(from)
+        .ok_or_else(|| anyhow::anyhow!("nothing defined at {from_str}"))?;
+    anyhow::ensure!(document.get(to).is_none(), "{to_str} is defined already");
+
+    let target = match info.kind {
+        Kind::Virtual | Kind::Block | Kind::Alias => Target::Labels,
+        Kind::Attribute if from[..from.len() - 1] == to[..to.len() - 1] => Target::AttributeKey,
+        Kind::Attribute => anyhow::bail!("attributes can only be renamed within their block"),
+        Kind::DefaultAttribute => {
+            anyhow::bail!("{from_str} is a default of a type block, it can not be renamed")
+        }
+        Kind::DataSource => anyhow::bail!("{from_str} is a data source, it can not be renamed"),
+    };
+
+    // `self.<key>` in a type block refers to the attribute of every data block of the group
+    let shared = match (target, from.split_last(), to.last()) {
+        (Target::AttributeKey, Some((key, block)), Some(new_key)) => {
+            document.paths().any(|path| match path.split_last() {
+                Some((last, other)) => {
+                    other.len() == block.len()
+                        && other != block
+                        && other.first() == block.first()
+                        && (last == key || last == new_key)
+                }
+                None => false,
+            })
+        }
+        _ => false,
+    };
+
+    let mut definitions = 0;
+    let mut changed = vec![];
+    for (path, text) in sources {
+        let (body, spans) = cco::hcl_documents::parse_body(text)?;
+        let mut renamer = Renamer {
+            from,
+            to,
+            target,
+            edits: vec![],
+            definitions: 0,
+            block_path: None,
+            type_group: None,
+            shared,
+            locals: vec![],
+            conflicts: vec![],
+        };
+        renamer.visit_body(&body);
+        if let Some(conflict) = renamer.conflicts.first() {
+            anyhow::bail!("can not rename {from_str} to {to_str}: {conflict}");
+        }
+        if renamer.edits.is_empty() {
+            continue;
+        }
+
+        let Some(path) = path else {
+            anyhow::bail!("rename needs input files, stdin can not be rewritten");
+        };
+        definitions += renamer.definitions;
+        let edits = renamer
+            .edits
+            .into_iter()
+            .map(|(span, replacement)| (spans.span(span), replacement))
+            .collect();
+        changed.push((path.clone(), apply(text, edits)));
+    }
+    anyhow::ensure!(
+        definitions > 0,
+        "the definition of {from_str} was not found"
+    );
+
+    Ok(changed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    /// Labels of `data`, `type` and `alias` blocks
+    Labels,
+    /// The key of an attribute in a data block
+    AttributeKey,
+}
+
+/// Collects the replacements in one document
+struct Renamer<'a> {
+    from: &'a [hcl::Identifier],
+    to: &'a [hcl::Identifier],
+    target: Target,
+    edits: Vec<(Range<usize>, String)>,
+    /// Number of renamed blocks and attributes
+    definitions: usize,
+    /// Path of the data block `self` refers to
+    block_path: Option<Vec<hcl::Identifier>>,
+    /// Group of the type block whose `self` refers to each of its data blocks
+    type_group: Option<hcl::Identifier>,
+    /// Whether other data blocks of the group define the renamed key or the new one
+    shared: bool,
+    /// Variables of enclosing `for` expressions
+    locals: Vec<String>,
+    /// References that can not be rewritten
+    conflicts: Vec<String>,
+}
+
+impl Renamer<'_> {
+    fn edit(&mut self, span: Option<Range<usize>>, replacement: String) {
+        if let Some(span) = span {
+            self.edits.push((span, replacement));
+        }
+    }
+
+    fn rename_definition(&mut self, block: &Block) {
+        let kind = block.ident.as_str();
+        let labels: Vec<_> = block
+            .labels
+            .iter()
+            .map(|label| hcl::Identifier::sanitized(label.as_str()))
+            .collect();
+
+        match self.target {
+            Target::Labels if LABELED_KINDS.contains(&kind) && labels.starts_with(self.from) => {
+                for (index, label) in block.labels.iter().enumerate().take(self.from.len()) {
+                    if self.from[index] == self.to[index] {
+                        continue;
+                    }
+                    let (span, replacement) = match label {
+                        BlockLabel::Ident(ident) => (ident.span(), self.to[index].to_string()),
+                        BlockLabel::String(string) => {
+                            (string.span(), format!("\"{}\"", self.to[index]))
+                        }
+                    };
+                    self.edit(span, replacement);
+                }
+                self.definitions += 1;
+            }
+            Target::AttributeKey
+                if kind == "data" && labels[..] == self.from[..self.from.len() - 1] =>
+            {
+                let key = &self.from[self.from.len() - 1];
+                for attribute in block.body.attributes() {
+                    if attribute.key.as_str() == key.as_str() {
+                        let replacement = self.to[self.to.len() - 1].to_string();
+                        self.edit(attribute.key.span(), replacement);
+                        self.definitions += 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Rewrite the segments of a path, `segments` are the root variable and following attributes
+    fn rename_reference(&mut self, segments: &[&hcl_edit::Decorated<hcl_edit::Ident>]) {
+        let root = segments[0].as_str();
+        if self.locals.iter().any(|local| local == root) {
+            return;
+        }
+
+        // `self` stands for the path of the block, the following segments continue it
+        let (mut path, first) = match (&self.block_path, &self.type_group) {
+            (Some(block_path), _) if root == "self" => (block_path.clone(), 1),
+            (None, Some(_)) if root == "self" => return self.rename_type_reference(segments),
+            _ if root == "self" => return,
+            _ => (vec![], 0),
+        };
+        let implicit = path.len();
+        path.extend(
+            segments[first..]
+                .iter()
+                .map(|segment| hcl::Identifier::sanitized(segment.as_str())),
+        );
+        if !path.starts_with(self.from) {
+            return;
+        }
+        if implicit == 0 && self.from[0] != self.to[0] {
+            let new_root = self.to[0].as_str();
+            if self.locals.iter().any(|local| local == new_root) {
+                self.conflicts.push(format!(
+                    "`for` variable {new_root} shadows the reference to {}",
+                    self.from.join(".")
+                ));
+                return;
+            }
+        }
+
+        for index in implicit..self.from.len() {
+            if self.from[index] != self.to[index] {
+                let segment = segments[first + index - implicit];
+                self.edit(segment.span(), self.to[index].to_string());
+            }
+        }
+    }
+
+    /// Rewrite `self.<key>` in the type block of the group of a renamed attribute
+    fn rename_type_reference(&mut self, segments: &[&hcl_edit::Decorated<hcl_edit::Ident>]) {
+        let (Target::AttributeKey, Some(group), Some(segment)) =
+            (self.target, &self.type_group, segments.get(1))
+        else {
+            return;
+        };
+        let key = &self.from[self.from.len() - 1];
+        if *group != self.from[0] || segment.as_str() != key.as_str() {
+            return;
+        }
+        if self.shared {
+            self.conflicts.push(format!(
+                "type {group} refers to self.{key}, other blocks of {group} define {key} or {}",
+                self.to[self.to.len() - 1]
+            ));
+            return;
+        }
+        self.edit(segment.span(), self.to[self.to.len() - 1].to_string());
+    }
+}
+
+impl Visit for Renamer<'_> {
+    fn visit_block(&mut self, node: &Block) {
+        // nested blocks are part of an attribute-like body, not definitions
+        if self.block_path.is_some() || self.type_group.is_some() {
+            return visit::visit_block(self, node);
+        }
+
+        self.rename_definition(node);
+        let labels = node
+            .labels
+            .iter()
+            .map(|label| hcl::Identifier::sanitized(label.as_str()));
+        match node.ident.as_str() {
+            "data" => self.block_path = Some(labels.collect()),
+            "type" => self.type_group = labels.take(1).next(),
+            _ => {}
+        }
+        self.visit_body(&node.body);
+        self.block_path = None;
+        self.type_group = None;
+    }
+
+    fn visit_expr(&mut self, node: &Expression) {
+        match node {
+            Expression::Variable(variable) => self.rename_reference(&[variable]),
+            _ => visit::visit_expr(self, node),
+        }
+    }
+
+    fn visit_traversal(&mut self, node: &Traversal) {
+        match &node.expr {
+            Expression::Variable(variable) => {
+                let mut segments = vec![variable];
+                for operator in &node.operators {
+                    match operator.value() {
+                        TraversalOperator::GetAttr(ident) => segments.push(ident),
+                        _ => break,
+                    }
+                }
+                self.rename_reference(&segments);
+            }
+            expr => self.visit_expr(expr),
+        }
+        for operator in &node.operators {
+            self.visit_traversal_operator(operator);
+        }
+    }
+
+    fn visit_for_expr(&mut self, node: &ForExpr) {
+        self.visit_expr(&node.intro.collection_expr);
+        let outer = self.locals.len();
+        self.locals.extend(
+            node.intro
+                .key_var
+                .iter()
+                .map(|var| var.as_str().to_string()),
+        );
+        self.locals.push(node.intro.value_var.as_str().to_string());
+        if let Some(key_expr) = &node.key_expr {
+            self.visit_expr(key_expr);
+        }
+        self.visit_expr(&node.value_expr);
+        if let Some(cond) = &node.cond {
+            self.visit_for_cond(cond);
+        }
+        self.locals.truncate(outer);
+    }
+
+    fn visit_for_template_expr(&mut self, node: &ForTemplateExpr) {
+        self.visit_expr(&node.collection_expr);
+        let outer = self.locals.len();
+        self.locals
+            .extend(node.key_var.iter().map(|var| var.as_str().to_string()));
+        self.locals.push(node.value_var.as_str().to_string());
+        self.visit_template(&node.template);
+        self.locals.truncate(outer);
+    }
+}
+
+/// Replace the spans in `text`, last one first so earlier spans stay valid
+fn apply(text: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+    edits.dedup_by_key(|(span, _)| span.start);
+
+    let mut text = text.to_string();
+    for (span, replacement) in edits {
+        text.replace_range(span, &replacement);
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = r#"# services
+data service api {
+  port = 80 # default
+  url  = "http://localhost:${self.port}"
+}
+
+data service "web" {
+  backend = service.api.url
+  ports   = [for port in [service.api.port]: port]
+  all     = { for name, svc in service : name => svc.port }
+  text    = "%{ for port in [service.api.port] }${port}%{ endfor }"
+}
+
+alias service legacy {
+  target = service.api
+}
+"#;
+
+    fn rename_in(from: &str, to: &str) -> anyhow::Result<String> {
+        rename_source(SOURCE, from, to)
+    }
+
+    fn rename_source(source: &str, from: &str, to: &str) -> anyhow::Result<String> {
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents.insert_str(source, None)?;
+        let document = CcoDocument::new(&documents)?;
+        let sources = [(Some(PathBuf::from("a.cco.hcl")), source.to_string())];
+        let mut changed = rename(
+            &document,
+            &sources,
+            &crate::deps::parse_path(from)?,
+            &crate::deps::parse_path(to)?,
+        )?;
+        assert_eq!(changed.len(), 1);
+        Ok(changed.remove(0).1)
+    }
+
+    #[test]
+    fn block() {
+        let renamed = rename_in("service.api", "service.public_api").unwrap();
+        assert_eq!(
+            renamed,
+            SOURCE
+                .replace("data service api", "data service public_api")
+                .replace("service.api", "service.public_api")
+        );
+    }
+
+    #[test]
+    fn group_and_attribute() {
+        let renamed = rename_in("service", "svc").unwrap();
+        assert!(renamed.contains(r#"data svc "web" {"#), "{renamed}");
+        assert!(renamed.contains("alias svc legacy"), "{renamed}");
+        assert!(
+            renamed.contains("[for port in [svc.api.port]: port]"),
+            "{renamed}"
+        );
+        assert!(
+            renamed.contains("for name, svc in svc : name => svc.port"),
+            "{renamed}"
+        );
+
+        let renamed = rename_in("service.api.port", "service.api.listen").unwrap();
+        assert_eq!(
+            renamed,
+            SOURCE
+                .replace("port = 80", "listen = 80")
+                .replace("self.port", "self.listen")
+                .replace("service.api.port", "service.api.listen")
+        );
+    }
+
+    #[test]
+    fn slices() {
+        let source = "data service api {\n  hosts = [\"a\", \"b\"]\n}\n\n\
+                      data service web {\n  upstream = service.api.hosts[1:]\n  first = service.api.hosts[0]\n}\n";
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents.insert_str(source, None).unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        let sources = [(Some(PathBuf::from("a.cco.hcl")), source.to_string())];
+        let path = |path| crate::deps::parse_path(path).unwrap();
+        let changed = rename(
+            &document,
+            &sources,
+            &path("service.api"),
+            &path("service.backend"),
+        )
+        .unwrap();
+        assert_eq!(
+            changed[0].1,
+            source
+                .replace("data service api", "data service backend")
+                .replace("service.api.hosts", "service.backend.hosts")
+        );
+    }
+
+    #[test]
+    fn type_defaults() {
+        let source = "type service {\n  url = \"http://localhost:${self.port}\"\n}\n\n\
+                      data service api {\n  port = 80\n}\n\n\
+                      data service web {\n  name = \"web\"\n}\n";
+        let renamed = rename_source(source, "service.api.port", "service.api.listen").unwrap();
+        assert_eq!(
+            renamed,
+            source
+                .replace("self.port", "self.listen")
+                .replace("port = 80", "listen = 80")
+        );
+
+        let shared = source.replace("name = \"web\"", "port = 8080");
+        let error = rename_source(&shared, "service.api.port", "service.api.listen").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("type service refers to self.port"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn shadowing_for_variables() {
+        let source = "data service api {\n  hosts = [\"a\"]\n}\n\n\
+                      data web {\n  hosts = [for service in service.api.hosts : service]\n}\n";
+        let renamed = rename_source(source, "service", "backend").unwrap();
+        assert_eq!(
+            renamed,
+            source
+                .replace("data service api", "data backend api")
+                .replace("in service.api", "in backend.api")
+        );
+
+        let source = "data service api {\n  port = 80\n}\n\n\
+                      data web {\n  ports = [for svc in [1] : service.api.port]\n}\n";
+        let error = rename_source(source, "service", "svc").unwrap_err();
+        assert!(
+            error.to_string().contains("`for` variable svc shadows"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let error = |from, to| rename_in(from, to).unwrap_err().to_string();
+        assert!(error("service.api", "service.web").contains("defined already"));
+        assert!(error("service.api", "api").contains("same number"));
+        assert!(error("service.api.port", "service.web.port").contains("within their block"));
+        assert!(error("service.nope", "service.other").contains("nothing defined"));
+    }
+}
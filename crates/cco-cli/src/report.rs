@@ -0,0 +1,219 @@
+//! `--report`: machine-readable record of an evaluation
+//!
+//! Written as JSON for audit trails and build systems deciding whether outputs are up to date:
+//! which sources were loaded (with content hashes), which addressables were resolved, which
+//! references did not resolve, warnings and how long each phase took.
+use cco::cco_document::{CcoDocument, Kind};
+use cco::hcl_documents::HclDocuments;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Collects the record while evaluating, see [Report::write]
+#[derive(Debug, Default)]
+pub struct Report {
+    record: Mutex<Record>,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+struct Record {
+    version: &'static str,
+    expression: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    sources: Vec<Source>,
+    /// In the order they were resolved, dependencies first
+    resolved: Vec<Resolved>,
+    /// Resolved attributes whose value is a `default`
+    defaults: Vec<String>,
+    /// Referenced paths nothing provides a value for, e.g. first arguments of `try`
+    unresolved: Vec<String>,
+    warnings: Vec<String>,
+    /// Milliseconds per phase
+    timings: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Source {
+    /// `null` for stdin
+    path: Option<PathBuf>,
+    sha256: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Resolved {
+    path: String,
+    kind: String,
+    /// `path:line:column` of the definition
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    /// Including dependencies
+    duration_ms: f64,
+    #[serde(skip)]
+    segments: Vec<hcl::Identifier>,
+}
+
+impl Report {
+    pub fn new(expression: &str) -> Self {
+        Self {
+            record: Mutex::new(Record {
+                version: env!("CARGO_PKG_VERSION"),
+                expression: expression.to_string(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn record(&self) -> std::sync::MutexGuard<'_, Record> {
+        self.record
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Record the loaded sources
+    pub fn sources<'a>(&self, sources: impl IntoIterator<Item = (Option<&'a Path>, &'a str)>) {
+        self.record().sources = sources
+            .into_iter()
+            .map(|(path, contents)| Source {
+                path: path.map(Path::to_path_buf),
+                sha256: format!("{:x}", Sha256::digest(contents.as_bytes())),
+            })
+            .collect();
+    }
+
+    /// Record what only the documents know: definitions, unresolved references and warnings
+    ///
+    /// Call after evaluating, successful or not.
+    pub fn inspect(&self, hcl_documents: &HclDocuments, document: &CcoDocument) {
+        let mut record = self.record();
+        let record = &mut *record;
+
+        let mut references: Vec<Vec<hcl::Identifier>> = vec![];
+        if let Ok(info) = document.check_expression(&record.expression) {
+            references.extend(info.references.into_iter().map(|reference| reference.path));
+        }
+        for resolved in &mut record.resolved {
+            if let Some(info) = document.get(&resolved.segments) {
+                resolved.source = info.source.map(|source| {
+                    hcl_documents.source_location(Some(source), info.span.map(|span| span.start))
+                });
+            }
+            references.extend(document.references(&resolved.segments).unwrap_or_default());
+        }
+
+        for reference in references {
+            let path = reference.join(".");
+            let unresolved = document
+                .check_expression(&path)
+                .is_ok_and(|info| info.unresolved().next().is_some());
+            if unresolved && !record.unresolved.contains(&path) {
+                record.unresolved.push(path);
+            }
+        }
+
+        let messages = document.warnings().messages().iter().cloned();
+        record.warnings.splice(0..0, messages);
+    }
+
+    /// Write the record as JSON, with the outcome of the evaluation
+    pub fn write(
+        &self,
+        path: &Path,
+        phases: &[(&'static str, Duration)],
+        result: &anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut record = self.record();
+        record.success = result.is_ok();
+        record.error = result.as_ref().err().map(|err| format!("{err:#}"));
+        record.timings = phases
+            .iter()
+            .map(|(phase, duration)| (phase.to_string(), milliseconds(*duration).into()))
+            .collect();
+
+        let json = serde_json::to_string_pretty(&*record)? + "\n";
+        std::fs::write(path, json)
+            .map_err(|err| anyhow::anyhow!("failed to write report {}: {err}", path.display()))
+    }
+}
+
+impl cco::observer::EvalObserver for Report {
+    fn on_resolve(&self, path: &[hcl::Identifier], kind: Kind, duration: Duration) {
+        let mut record = self.record();
+        if kind == Kind::DefaultAttribute {
+            record.defaults.push(path.join("."));
+        }
+        record.resolved.push(Resolved {
+            path: path.join("."),
+            kind: kind.to_string(),
+            source: None,
+            duration_ms: milliseconds(duration),
+            segments: path.to_vec(),
+        });
+    }
+
+    fn on_deprecated(
+        &self,
+        path: &[hcl::Identifier],
+        message: &str,
+        _referenced_by: Option<&[hcl::Identifier]>,
+    ) {
+        let warning = format!("{} is deprecated: {message}", path.join("."));
+        self.record().warnings.push(warning);
+    }
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn record() {
+        let source = r#"data service api {
+  port = 80
+  url  = "http://api:${self.port}${try(proxy.path, "/")}"
+}
+"#;
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(source, Some("a.cco.hcl".into()))
+            .unwrap();
+        let report = Arc::new(Report::new("service.api.url"));
+        report.sources([(Some(Path::new("a.cco.hcl")), source)]);
+        let document = CcoDocument::builder()
+            .observer(report.clone())
+            .build(&documents)
+            .unwrap();
+
+        document.evaluate_path(&["service", "api", "url"]).unwrap();
+        report.inspect(&documents, &document);
+
+        let path = std::env::temp_dir().join(format!("cco-report-{}.json", std::process::id()));
+        report
+            .write(&path, &[("evaluate", Duration::from_millis(2))], &Ok(()))
+            .unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(json["success"], true);
+        assert_eq!(json["expression"], "service.api.url");
+        assert_eq!(json["sources"][0]["path"], "a.cco.hcl");
+        assert_eq!(
+            json["sources"][0]["sha256"],
+            format!("{:x}", Sha256::digest(source.as_bytes()))
+        );
+        assert_eq!(json["resolved"][0]["path"], "service.api.port");
+        assert_eq!(json["resolved"][0]["source"], "a.cco.hcl:2:3");
+        assert_eq!(json["resolved"][1]["path"], "service.api.url");
+        assert_eq!(json["unresolved"], serde_json::json!(["proxy.path"]));
+        assert_eq!(json["timings"]["evaluate"], 2.0);
+        assert!(json.get("error").is_none());
+    }
+}
@@ -0,0 +1,127 @@
+//! JSON Schema for the output of a data group
+//!
+//! The schema is derived from the `type` block of the group: every attribute becomes a property,
+//! literal defaults also define the property type and `default`. Defaults that need evaluation
+//! (references, function calls) leave the property unconstrained. Labels of the data blocks
+//! become nested objects with arbitrary keys.
+use cco::hcl_documents::HclDocuments;
+use serde_json::{json, Map, Value};
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Schema of the value of `group`
+pub fn generate(documents: &HclDocuments, group: &str) -> anyhow::Result<Value> {
+    let blocks_of = |kind: &'static str| {
+        documents.blocks().filter(move |(_, _, block)| {
+            block.ident.as_str() == kind
+                && block.labels.first().map(|label| label.as_str()) == Some(group)
+        })
+    };
+
+    let type_block = blocks_of("type").next().map(|(_, _, block)| block);
+    let label_count = blocks_of("data")
+        .map(|(_, _, block)| block.labels.len())
+        .next();
+    anyhow::ensure!(
+        type_block.is_some() || label_count.is_some(),
+        "no type or data blocks for '{group}'"
+    );
+
+    let mut properties = Map::new();
+    for attribute in type_block.iter().flat_map(|block| block.body.attributes()) {
+        let expression: hcl::Expression = attribute.value.clone().into();
+        properties.insert(attribute.key.to_string(), property(&expression)?);
+    }
+
+    let mut schema = json!({ "type": "object", "properties": properties });
+    for _ in 1..label_count.unwrap_or(1) {
+        schema = json!({ "type": "object", "additionalProperties": schema });
+    }
+
+    let Value::Object(mut schema) = schema else {
+        unreachable!("schema is an object")
+    };
+    let mut document = Map::new();
+    document.insert("$schema".into(), DIALECT.into());
+    document.insert("title".into(), group.into());
+    document.append(&mut schema);
+    Ok(Value::Object(document))
+}
+
+/// Property with the type and default of a literal default value
+fn property(default: &hcl::Expression) -> anyhow::Result<Value> {
+    use hcl::eval::Evaluate;
+
+    let Ok(value) = default.evaluate(&hcl::eval::Context::new()) else {
+        return Ok(json!({}));
+    };
+
+    let type_name = match &value {
+        hcl::Value::Null => return Ok(json!({})),
+        hcl::Value::Bool(_) => "boolean",
+        hcl::Value::Number(number) if number.is_f64() => "number",
+        hcl::Value::Number(_) => "integer",
+        hcl::Value::String(_) => "string",
+        hcl::Value::Array(_) => "array",
+        hcl::Value::Object(_) => "object",
+    };
+
+    Ok(json!({ "type": type_name, "default": serde_json::to_value(value)? }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn documents(hcl: &str) -> HclDocuments {
+        let mut documents = HclDocuments::default();
+        documents.insert_str(hcl, None).unwrap();
+        documents
+    }
+
+    #[test]
+    fn nested_labels() {
+        let documents = documents(
+            r#"
+            type service {
+              port    = 80
+              ratio   = 0.5
+              tags    = ["a"]
+              enabled = true
+              url     = "http://${self.host}:${self.port}"
+              host    = other.x.host
+            }
+            data service app { host = "localhost" }
+            "#,
+        );
+
+        assert_eq!(
+            generate(&documents, "service").unwrap(),
+            json!({
+                "$schema": DIALECT,
+                "title": "service",
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "properties": {
+                        "port": { "type": "integer", "default": 80 },
+                        "ratio": { "type": "number", "default": 0.5 },
+                        "tags": { "type": "array", "default": ["a"] },
+                        "enabled": { "type": "boolean", "default": true },
+                        "url": {},
+                        "host": {},
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_group() {
+        let documents = documents("data service app {}");
+        assert!(generate(&documents, "other").is_err());
+
+        let schema = generate(&documents, "service").unwrap();
+        assert_eq!(schema["additionalProperties"]["properties"], json!({}));
+    }
+}
@@ -0,0 +1,191 @@
+//! `cco search` over element paths and expressions
+//!
+//! `*` matches within a path segment, `**` across segments and `?` a single character:
+//! `service.*.port` matches `service.api.port`. Patterns are also matched against the source text
+//! of attribute expressions, anywhere in the text: `*.internal` finds `"db.internal"`.
+use cco::cco_document::{CcoDocument, Kind};
+use cco::hcl_documents::HclDocuments;
+use std::path::PathBuf;
+
+/// Lines of the matching elements: path, location and expression as written
+pub fn search(
+    document: &CcoDocument,
+    documents: &HclDocuments,
+    sources: &[(Option<PathBuf>, String)],
+    pattern: &str,
+) -> Vec<String> {
+    // labels are addressed by their sanitized form, `my api` as `my_api`
+    let path_pattern: Vec<String> = pattern
+        .split('.')
+        .map(|segment| {
+            if segment.contains(['*', '?']) {
+                segment.to_string()
+            } else {
+                hcl::Identifier::sanitized(segment).to_string()
+            }
+        })
+        .collect();
+    let path_pattern = path_pattern.join(".");
+    let text_pattern = format!("**{pattern}**");
+
+    let mut lines = vec![];
+    for info in document.addressables() {
+        let expression = match info.kind {
+            Kind::Attribute | Kind::DefaultAttribute => {
+                expression_text(sources, info.source, info.span.clone())
+            }
+            _ => None,
+        };
+        let path = info.path.join(".");
+        let text_matches = expression
+            .as_deref()
+            .is_some_and(|text| glob(&text_pattern, text, None));
+        if !glob(&path_pattern, &path, Some('.')) && !text_matches {
+            continue;
+        }
+
+        let location = documents.source_location(info.source, info.span.map(|span| span.start));
+        lines.push(match expression {
+            Some(expression) => format!("{path}  {location}  {expression}"),
+            None => format!("{path}  {location}"),
+        });
+    }
+    lines
+}
+
+/// Expression of the attribute defined at `span`, on a single line
+pub fn expression_text(
+    sources: &[(Option<PathBuf>, String)],
+    source: Option<&std::path::Path>,
+    span: Option<std::ops::Range<usize>>,
+) -> Option<String> {
+    let (_, text) = sources.iter().find(|(path, _)| path.as_deref() == source)?;
+    let attribute = text.get(span?)?;
+    let (_key, expression) = attribute.split_once('=')?;
+    let lines: Vec<_> = expression.lines().map(str::trim).collect();
+    Some(lines.join(" ").trim().to_string())
+}
+
+/// Part of a glob pattern
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    /// `**`
+    Any,
+    /// `*`
+    Segment,
+    /// `?`
+    One,
+    Char(char),
+}
+
+/// Match `text` against a glob, `*` does not match `separator`
+///
+/// Runs the pattern as a set of positions over the text, `O(text × pattern)` for any pattern.
+fn glob(pattern: &str, text: &str, separator: Option<char>) -> bool {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' if chars.next_if_eq(&'*').is_some() => Token::Any,
+            '*' => Token::Segment,
+            '?' => Token::One,
+            c => Token::Char(c),
+        });
+    }
+
+    // positions in `tokens` the text read so far can end at, stars may match nothing
+    let close = |positions: &mut Vec<bool>| {
+        for index in 0..tokens.len() {
+            if positions[index] && matches!(tokens[index], Token::Any | Token::Segment) {
+                positions[index + 1] = true;
+            }
+        }
+    };
+    let mut positions = vec![false; tokens.len() + 1];
+    positions[0] = true;
+    close(&mut positions);
+    for c in text.chars() {
+        let mut next = vec![false; tokens.len() + 1];
+        for (index, token) in tokens.iter().enumerate() {
+            if !positions[index] {
+                continue;
+            }
+            match token {
+                Token::Any => next[index] = true,
+                Token::Segment if Some(c) != separator => next[index] = true,
+                Token::One if Some(c) != separator => next[index + 1] = true,
+                Token::Char(expected) if *expected == c => next[index + 1] = true,
+                _ => {}
+            }
+        }
+        close(&mut next);
+        if !next.contains(&true) {
+            return false;
+        }
+        positions = next;
+    }
+    positions[tokens.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob("service.*.port", "service.api.port", Some('.')));
+        assert!(!glob("service.*.port", "service.a.b.port", Some('.')));
+        assert!(glob("service.**.port", "service.a.b.port", Some('.')));
+        assert!(glob("db.?", "db.a", Some('.')));
+        assert!(!glob("db.?", "db.ab", Some('.')));
+        assert!(glob("**.internal**", "\"db.internal:5432\"", None));
+        assert!(glob("a*b**c", "axb.y.c", Some('.')));
+        assert!(!glob("a*b**c", "a.b.c.d", Some('.')));
+        assert!(glob("", "", None));
+        assert!(!glob("", "a", None));
+
+        // no exponential backtracking on many stars
+        let text = "a".repeat(10_000);
+        assert!(!glob(&format!("{}b", "*a".repeat(50)), &text, None));
+        assert!(!glob(&format!("{}b", "**a".repeat(50)), &text, None));
+    }
+
+    #[test]
+    fn paths_and_expressions() {
+        let text = "data service \"my api\" {\n  port = 8080\n  host = \"api.internal\"\n}\n\
+                    data db main {\n  host = \"db.internal\"\n}\n";
+        let mut documents = HclDocuments::default();
+        documents.insert_str(text, None).unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        let sources = [(None, text.to_string())];
+
+        assert_eq!(
+            search(&document, &documents, &sources, "service.*.port"),
+            ["service.my_api.port  <input>:2:3  8080"]
+        );
+        assert_eq!(
+            search(&document, &documents, &sources, "service.my api"),
+            ["service.my_api  <input>:1:1"]
+        );
+        assert_eq!(
+            search(&document, &documents, &sources, "*.internal"),
+            [
+                "service.my_api.host  <input>:3:3  \"api.internal\"",
+                "db.main.host  <input>:6:3  \"db.internal\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn slices() {
+        let text = "data list main {\n  tail = [1, 2, 3][1:]\n  head = [1, 2, 3][:1]\n}\n";
+        let mut documents = HclDocuments::default();
+        documents.insert_str(text, None).unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        let sources = [(None, text.to_string())];
+        assert_eq!(
+            search(&document, &documents, &sources, "[:1]"),
+            ["list.main.head  <input>:3:3  [1, 2, 3][:1]"]
+        );
+    }
+}
@@ -0,0 +1,704 @@
+//! HTTP server mode (`cco serve`)
+//!
+//! Documents are loaded once. All requests share one [EvalSession], so values of addressables
+//! are only evaluated once no matter how many requests need them.
+//!
+//! - `GET /eval?expr=<expression>&format=json|yaml|ndjson` evaluates an expression (JSON by default)
+//! - `GET /watch?expr=<expression>` streams the value as server-sent events whenever it changes
+//! - `GET /watch?expr=<expression>&etag=<etag>` long-polls: responds once the value no longer has
+//!   the `ETag` of an earlier response, or with 304 after `timeout` seconds (at most 60)
+//! - `GET /healthz` returns `ok`
+//! - `GET /metrics` returns [Metrics] in the Prometheus text format
+//!
+//! [Server::replace] swaps the session after sources changed (`--watch`).
+//!
+//! With a token, every request needs an `Authorization: Bearer <token>` header.
+use crate::cli::OutputFormat;
+use crate::metrics::Metrics;
+use cco::cco_document::LimitExceeded;
+use cco::session::EvalSession;
+use cco::value::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Request head size limit, there is no reason for long requests
+const MAX_HEAD: usize = 64 * 1024;
+
+/// Connections served at the same time, further ones are refused with 503
+const MAX_CONNECTIONS: usize = 256;
+
+pub struct Server {
+    state: Mutex<State>,
+    /// Signaled when the session is replaced
+    changed: Condvar,
+    token: Option<String>,
+    metrics: Metrics,
+    /// Connections currently served
+    connections: AtomicUsize,
+}
+
+/// A connection counted in [Server::connections], released on drop
+struct Slot(Arc<Server>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A value of [Server::evaluate]
+pub struct Evaluated {
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+    pub value: Value,
+    /// The value rendered in the requested format
+    pub body: Vec<u8>,
+    /// Content hash of `body`
+    pub etag: String,
+}
+
+/// Why [Server::evaluate] failed
+#[derive(Debug, PartialEq)]
+pub enum EvalError {
+    /// The expression does not parse
+    Invalid(String),
+    /// The evaluation failed, e.g. on an unknown reference or a failing function
+    Failed(String),
+    /// The evaluation exceeded a limit (depth, steps or time)
+    Limit(String),
+    /// The value could not be rendered
+    Internal(String),
+}
+
+struct State {
+    session: Arc<EvalSession>,
+    /// Incremented on every reload
+    generation: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub etag: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn text(status: u16, body: impl Into<String>) -> Self {
+        let mut body = body.into();
+        body.push('\n');
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            etag: None,
+            body: body.into_bytes(),
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> std::io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        if let Some(etag) = &self.etag {
+            write!(stream, "ETag: \"{etag}\"\r\n")?;
+        }
+        stream.write_all(b"\r\n")?;
+        stream.write_all(&self.body)
+    }
+}
+
+impl Server {
+    pub fn new(session: EvalSession, token: Option<String>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                session: Arc::new(session),
+                generation: 0,
+            }),
+            changed: Condvar::new(),
+            token,
+            metrics: Metrics::default(),
+            connections: AtomicUsize::new(0),
+        }
+    }
+
+    /// Serve `session` from now on and notify watchers
+    pub fn replace(&self, session: EvalSession) {
+        let mut state = self.lock();
+        let previous = std::mem::replace(&mut state.session, Arc::new(session));
+        state.generation += 1;
+        self.metrics.reload(previous.cache_stats());
+        self.changed.notify_all();
+    }
+
+    /// Count a reload that failed (the session is kept)
+    pub fn reload_failed(&self) {
+        self.metrics.reload_error();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// The current session and its generation, incremented on every reload
+    pub fn current(&self) -> (Arc<EvalSession>, u64) {
+        let state = self.lock();
+        (state.session.clone(), state.generation)
+    }
+
+    /// Block until the generation differs from `generation` or `timeout` passed
+    pub fn wait_for_change(&self, generation: u64, timeout: Duration) -> u64 {
+        let state = self.lock();
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(state, timeout, |state| state.generation == generation)
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        state.generation
+    }
+
+    /// A slot for another connection, `None` with [MAX_CONNECTIONS] served already
+    fn slot(self: &Arc<Self>) -> Option<Slot> {
+        self.connections
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                (count < MAX_CONNECTIONS).then_some(count + 1)
+            })
+            .ok()
+            .map(|_| Slot(self.clone()))
+    }
+
+    /// Accept connections forever, one thread per connection up to [MAX_CONNECTIONS]
+    pub fn run(self: Arc<Self>, listener: TcpListener) -> anyhow::Result<()> {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::warn!(%err, "failed to accept connection");
+                    continue;
+                }
+            };
+
+            let Some(slot) = self.slot() else {
+                tracing::warn!("too many connections");
+                let busy = Response::text(503, "too many connections");
+                if let Err(err) = busy.write_to(&mut stream) {
+                    tracing::debug!(%err, "connection failed");
+                }
+                continue;
+            };
+            std::thread::spawn(move || {
+                if let Err(err) = slot.0.connection(stream) {
+                    tracing::debug!(%err, "connection failed");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn connection(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        let request = match read_request(&mut BufReader::new(&stream)) {
+            Ok(request) => request,
+            Err(err) => return Response::text(400, err.to_string()).write_to(&mut stream),
+        };
+
+        #[cfg(feature = "otel")]
+        let _span = tracing::info_span!(
+            "request",
+            otel.name = %format_args!("{} {}", request.method, request.path)
+        )
+        .entered();
+        let is_stream = request.path == "/watch" && request.query("etag").is_none();
+        if is_stream && self.authorize(&request).is_none() && request.method == "GET" {
+            tracing::info!(path = request.path, "event stream");
+            self.metrics.request(&request.path, 200);
+            return self.events(&request, &mut stream);
+        }
+
+        let response = self.handle(&request);
+        tracing::info!(
+            method = request.method,
+            path = request.path,
+            status = response.status,
+            "request"
+        );
+        response.write_to(&mut stream)
+    }
+
+    /// `Some` error response if the request is not authorized
+    pub fn authorize(&self, request: &Request) -> Option<Response> {
+        match self.authorized(request.header("authorization")) {
+            true => None,
+            false => Some(Response::text(401, "missing or invalid token")),
+        }
+    }
+
+    /// Whether an `authorization` header (or gRPC metadata) carries the token, if there is one
+    pub fn authorized(&self, authorization: Option<&str>) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let given = authorization.and_then(|value| value.strip_prefix("Bearer "));
+        given.is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        let response = self.route(request);
+        self.metrics.request(&request.path, response.status);
+        response
+    }
+
+    fn route(&self, request: &Request) -> Response {
+        if let Some(response) = self.authorize(request) {
+            return response;
+        }
+
+        if request.method != "GET" {
+            return Response::text(405, "only GET is supported");
+        }
+
+        match request.path.as_str() {
+            "/healthz" => Response::text(200, "ok"),
+            "/eval" => self.eval(&self.current().0, request),
+            "/watch" => self.long_poll(request),
+            "/metrics" => {
+                let session = self.current().0;
+                Response {
+                    status: 200,
+                    content_type: "text/plain; version=0.0.4",
+                    etag: None,
+                    body: self
+                        .metrics
+                        .render(session.cache_stats(), session.memoized_count())
+                        .into_bytes(),
+                }
+            }
+            _ => Response::text(404, "not found"),
+        }
+    }
+
+    fn eval(&self, session: &EvalSession, request: &Request) -> Response {
+        let Some(expression) = request.query("expr") else {
+            return Response::text(400, "missing query parameter 'expr'");
+        };
+        let format = match request.query("format").unwrap_or("json") {
+            "json" => OutputFormat::Json,
+            "yaml" => OutputFormat::Yaml,
+            "ndjson" => OutputFormat::Ndjson,
+            format => return Response::text(400, format!("unknown format '{format}'")),
+        };
+
+        match self.evaluate(session, expression, &format) {
+            Ok(evaluated) => Response {
+                status: 200,
+                content_type: match format {
+                    OutputFormat::Json => "application/json",
+                    OutputFormat::Yaml => "application/yaml",
+                    OutputFormat::Ndjson => "application/x-ndjson",
+                },
+                etag: Some(evaluated.etag),
+                body: evaluated.body,
+            },
+            Err(EvalError::Invalid(err)) => Response::text(400, err),
+            Err(EvalError::Failed(err) | EvalError::Limit(err)) => Response::text(422, err),
+            Err(EvalError::Internal(err)) => Response::text(500, err),
+        }
+    }
+
+    /// Evaluate `expression` in `session` and render the value in `format`, counted in the
+    /// metrics. Shared by `GET /eval` and gRPC, so both have the same values and ETags.
+    pub fn evaluate(
+        &self,
+        session: &EvalSession,
+        expression: &str,
+        format: &OutputFormat,
+    ) -> Result<Evaluated, EvalError> {
+        let expression = cco::hcl_documents::parse_expression(expression)
+            .map_err(|err| EvalError::Invalid(err.to_string()))?;
+        let started = std::time::Instant::now();
+        let value = match session.evaluate(expression) {
+            Ok(value) => value,
+            Err(err) => {
+                self.metrics.evaluation(started.elapsed(), false);
+                return Err(match err.downcast_ref::<LimitExceeded>() {
+                    Some(_) => EvalError::Limit(format!("{err:#}")),
+                    None => EvalError::Failed(format!("{err:#}")),
+                });
+            }
+        };
+
+        let mut body = vec![];
+        let rendered = crate::write_format(format, &mut body, &value);
+        self.metrics.evaluation(started.elapsed(), rendered.is_ok());
+        rendered.map_err(|err| EvalError::Internal(format!("{err:#}")))?;
+        Ok(Evaluated {
+            etag: etag(&body),
+            value,
+            body,
+        })
+    }
+
+    /// `/watch?etag=..`: respond once the value no longer matches `etag`, 304 after `timeout`
+    fn long_poll(&self, request: &Request) -> Response {
+        let given = request.query("etag").unwrap_or_default().trim_matches('"');
+        let timeout = match request.query("timeout").map(str::parse) {
+            None => LONG_POLL_TIMEOUT,
+            Some(Ok(seconds)) => Duration::from_secs(seconds).min(LONG_POLL_TIMEOUT),
+            Some(Err(_)) => return Response::text(400, "invalid timeout"),
+        };
+        let deadline = std::time::Instant::now() + timeout;
+
+        loop {
+            let (session, generation) = self.current();
+            let response = self.eval(&session, request);
+            if response.etag.as_deref() != Some(given) {
+                return response;
+            }
+
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() || self.wait_for_change(generation, remaining) == generation {
+                return Response {
+                    status: 304,
+                    body: vec![],
+                    ..response
+                };
+            }
+        }
+    }
+
+    /// `/watch`: server-sent events with the value after every change
+    fn events(&self, request: &Request, stream: &mut impl Write) -> std::io::Result<()> {
+        stream.write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+              Connection: close\r\n\r\n",
+        )?;
+
+        let mut last_etag = None;
+        let (_, mut generation) = self.current();
+        loop {
+            let session = self.current().0;
+            let response = self.eval(&session, request);
+            if response.etag.is_none() || response.etag != last_etag {
+                let event = match response.status {
+                    200 => "value",
+                    _ => "error",
+                };
+                write_event(stream, event, response.etag.as_deref(), &response.body)?;
+                last_etag = response.etag;
+            }
+
+            // a keep-alive comment detects closed connections
+            loop {
+                let next = self.wait_for_change(generation, KEEP_ALIVE);
+                if next != generation {
+                    generation = next;
+                    break;
+                }
+                stream.write_all(b": keep-alive\n\n")?;
+                stream.flush()?;
+            }
+        }
+    }
+}
+
+const LONG_POLL_TIMEOUT: Duration = Duration::from_secs(60);
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+fn write_event(
+    stream: &mut impl Write,
+    event: &str,
+    id: Option<&str>,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut message = format!("event: {event}\n");
+    if let Some(id) = id {
+        message.push_str(&format!("id: {id}\n"));
+    }
+    for line in String::from_utf8_lossy(data).trim_end().split('\n') {
+        message.push_str(&format!("data: {line}\n"));
+    }
+    message.push('\n');
+    stream.write_all(message.as_bytes())?;
+    stream.flush()
+}
+
+/// Content hash used as `ETag`
+fn etag(body: &[u8]) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Read the request head, a body is not expected
+pub fn read_request(reader: &mut impl BufRead) -> anyhow::Result<Request> {
+    // one byte more than allowed, so a head that is too large is noticed
+    let mut reader = reader.take(MAX_HEAD as u64 + 1);
+    let mut lines = vec![];
+    let mut size = 0;
+    loop {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line)?;
+        size += read;
+        anyhow::ensure!(size <= MAX_HEAD, "request head too large");
+        let line = line.trim_end_matches(['\r', '\n']);
+        if read == 0 || line.is_empty() {
+            break;
+        }
+        lines.push(line.to_string());
+    }
+
+    let mut lines = lines.into_iter();
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("invalid request line '{request_line}'");
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    Ok(Request {
+        method: method.to_string(),
+        path: percent_decode(path),
+        query: query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (
+                    percent_decode(&key.replace('+', " ")),
+                    percent_decode(&value.replace('+', " ")),
+                )
+            })
+            .collect(),
+        headers: lines
+            .filter_map(|line| {
+                line.split_once(':')
+                    .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            })
+            .collect(),
+    })
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// `:8080` listens on all interfaces
+pub fn listen_address(listen: &str) -> String {
+    match listen.strip_prefix(':') {
+        Some(port) => format!("0.0.0.0:{port}"),
+        None => listen.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn server(token: Option<&str>) -> Server {
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents
+            .insert_str("data service api {\n  port = 8080\n}\n", None)
+            .unwrap();
+        let document = cco::cco_document::CcoDocument::new(&documents).unwrap();
+        Server::new(EvalSession::new(document), token.map(str::to_string))
+    }
+
+    fn get(target: &str, headers: &str) -> Request {
+        let raw = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+        read_request(&mut raw.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn requests() {
+        let request = get("/eval?expr=service.api%5B%22port%22%5D+&format=yaml", "");
+        assert_eq!(request.path, "/eval");
+        assert_eq!(request.query("expr"), Some("service.api[\"port\"] "));
+        assert_eq!(request.query("format"), Some("yaml"));
+        assert_eq!(request.header("host"), Some("localhost"));
+
+        // a line without end is not read beyond the limit
+        let endless = format!("GET /{}", "a".repeat(2 * MAX_HEAD));
+        let err = read_request(&mut endless.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "request head too large");
+    }
+
+    #[test]
+    fn connection_limit() {
+        let server = Arc::new(server(None));
+        let slots: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|_| server.slot().unwrap())
+            .collect();
+        assert!(server.slot().is_none());
+        drop(slots);
+        assert!(server.slot().is_some());
+        assert_eq!(server.connections.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn evaluation() {
+        let server = server(None);
+
+        let response = server.handle(&get("/eval?expr=service.api", ""));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_type, "application/json");
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&response.body).unwrap(),
+            serde_json::json!({ "port": 8080 })
+        );
+
+        assert_eq!(server.handle(&get("/eval", "")).status, 400);
+        assert_eq!(server.handle(&get("/eval?expr=service.x", "")).status, 422);
+        assert_eq!(server.handle(&get("/other", "")).status, 404);
+    }
+
+    #[test]
+    fn long_poll() {
+        let server = Arc::new(server(None));
+        let etag = server
+            .handle(&get("/eval?expr=service.api.port", ""))
+            .etag
+            .unwrap();
+
+        let unchanged = server.handle(&get(
+            &format!("/watch?expr=service.api.port&etag={etag}&timeout=0"),
+            "",
+        ));
+        assert_eq!(unchanged.status, 304);
+
+        let waiting = std::thread::spawn({
+            let server = server.clone();
+            move || {
+                server.handle(&get(
+                    &format!("/watch?expr=service.api.port&etag={etag}"),
+                    "",
+                ))
+            }
+        });
+        std::thread::sleep(Duration::from_millis(50));
+
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents
+            .insert_str("data service api {\n  port = 9090\n}\n", None)
+            .unwrap();
+        let document = cco::cco_document::CcoDocument::new(&documents).unwrap();
+        server.replace(EvalSession::new(document));
+
+        let changed = waiting.join().unwrap();
+        assert_eq!(changed.status, 200);
+        assert_eq!(changed.body, b"9090");
+    }
+
+    #[test]
+    fn metrics() {
+        let server = server(None);
+        server.handle(&get("/eval?expr=service.api.port", ""));
+        server.handle(&get("/eval?expr=service.api.port", ""));
+        server.handle(&get("/eval?expr=service.x", ""));
+
+        let response = server.handle(&get("/metrics", ""));
+        assert_eq!(response.status, 200);
+        let text = String::from_utf8(response.body).unwrap();
+        assert!(text.contains("cco_requests_total{path=\"/eval\",status=\"200\"} 2\n"));
+        assert!(text.contains("cco_evaluations_total 3\n"));
+        assert!(text.contains("cco_evaluation_errors_total 1\n"));
+        assert!(!text.contains("cco_cache_hits_total 0\n"));
+    }
+
+    #[test]
+    fn events() {
+        let mut stream = vec![];
+        write_event(&mut stream, "value", Some("1"), b"{\n  \"a\": 1\n}").unwrap();
+        assert_eq!(
+            String::from_utf8(stream).unwrap(),
+            "event: value\nid: 1\ndata: {\ndata:   \"a\": 1\ndata: }\n\n"
+        );
+    }
+
+    #[test]
+    fn token() {
+        let server = server(Some("secret"));
+        assert_eq!(server.handle(&get("/healthz", "")).status, 401);
+        assert_eq!(
+            server
+                .handle(&get("/healthz", "Authorization: Bearer wrong\r\n"))
+                .status,
+            401
+        );
+        assert_eq!(
+            server
+                .handle(&get("/healthz", "Authorization: Bearer secret\r\n"))
+                .status,
+            200
+        );
+    }
+}
@@ -0,0 +1,337 @@
+//! Change the expression of an attribute in its source file
+//!
+//! `cco set service.api.port 9090` replaces the expression of the attribute that defines
+//! `service.api.port`. Attributes the data block does not define (e.g. with a default of the
+//! `type` block) are added to it. The rest of the file, including comments and formatting, stays
+//! as it is.
+//!
+//! `cco promote staging prod service.api.image` sets the attribute in `prod` to its expression as
+//! written in `staging`.
+use cco::cco_document::{CcoDocument, Kind};
+use cco::hcl_documents::SpanMap;
+use hcl_edit::structure::{Attribute, Structure};
+use std::path::{Path, PathBuf};
+
+/// Path and new contents of the source defining `path` with `expression` as its value
+pub fn set(
+    document: &CcoDocument,
+    sources: &[(Option<PathBuf>, String)],
+    path: &[hcl::Identifier],
+    expression: &str,
+) -> anyhow::Result<(PathBuf, String)> {
+    let path_str = path.join(".");
+    let expression = expression.trim();
+    cco::hcl_documents::parse_expression(expression)
+        .map_err(|err| anyhow::anyhow!("invalid expression '{expression}': {err}"))?;
+
+    let (parent, key) = path.split_at(path.len() - 1);
+    let (info, insert) = match document.get(path) {
+        Some(info) if info.kind == Kind::Attribute => (info, false),
+        Some(info) if info.kind != Kind::DefaultAttribute => {
+            anyhow::bail!("{path_str} is a {}, only attributes can be set", info.kind)
+        }
+        _ => match document.get(parent) {
+            Some(info) if info.kind == Kind::Block => (info, true),
+            _ => anyhow::bail!("{path_str} is neither an attribute nor in a data block"),
+        },
+    };
+
+    let (file, text) = sources
+        .iter()
+        .find_map(|(source, text)| {
+            let source = source.as_ref()?;
+            (Some(source.as_path()) == info.source).then_some((source, text))
+        })
+        .ok_or_else(|| anyhow::anyhow!("{path_str} is not defined in an input file"))?;
+    let span = info
+        .span
+        .ok_or_else(|| anyhow::anyhow!("location of {path_str} is unknown"))?;
+
+    let (body, spans) = cco::hcl_documents::parse_body(text)?;
+    let mut text = text.clone();
+    for structure in body.iter() {
+        match structure {
+            Structure::Block(block) if spans.of(block) == Some(span.clone()) && insert => {
+                let line = format!("{} = {expression}", key[0]);
+                let last = block.body.attributes().last();
+                insert_attribute(&mut text, last.and_then(|last| spans.of(last)), span, &line);
+                return Ok((file.clone(), text));
+            }
+            Structure::Block(block) => {
+                let Some(attribute) = find(block.body.attributes(), &spans, &span) else {
+                    continue;
+                };
+                anyhow::ensure!(
+                    block.ident.as_str() == "data",
+                    "{path_str} is defined in a {} block, edit it there",
+                    block.ident.as_str()
+                );
+                replace_value(&mut text, &spans, attribute, expression)?;
+                return Ok((file.clone(), text));
+            }
+            Structure::Attribute(attribute) if spans.of(attribute) == Some(span.clone()) => {
+                replace_value(&mut text, &spans, attribute, expression)?;
+                return Ok((file.clone(), text));
+            }
+            Structure::Attribute(_) => {}
+        }
+    }
+    anyhow::bail!("definition of {path_str} not found in {}", file.display())
+}
+
+/// Expression of the attribute at `path` as written in a file of `dir`
+///
+/// Sources are read with canonical paths (see [crate::read_sources]), so is `dir`.
+pub fn expression(
+    document: &CcoDocument,
+    sources: &[(Option<PathBuf>, String)],
+    path: &[hcl::Identifier],
+    dir: &Path,
+) -> anyhow::Result<String> {
+    let path_str = path.join(".");
+    let canonical = dir
+        .canonicalize()
+        .map_err(|err| anyhow::anyhow!("{}: {err}", dir.display()))?;
+    let info = document
+        .get(path)
+        .ok_or_else(|| anyhow::anyhow!("nothing defined at {path_str}"))?;
+    anyhow::ensure!(
+        info.kind == Kind::Attribute,
+        "{path_str} is a {}, only attributes can be promoted",
+        info.kind
+    );
+
+    let text = info
+        .source
+        .filter(|source| source.starts_with(&canonical))
+        .and_then(|source| {
+            sources
+                .iter()
+                .find(|(path, _)| path.as_deref() == Some(source))
+        })
+        .map(|(_, text)| text)
+        .ok_or_else(|| anyhow::anyhow!("{path_str} is not defined in {}", dir.display()))?;
+    let span = info
+        .span
+        .ok_or_else(|| anyhow::anyhow!("location of {path_str} is unknown"))?;
+
+    let (body, spans) = cco::hcl_documents::parse_body(text)?;
+    let value = body
+        .iter()
+        .find_map(|structure| match structure {
+            Structure::Block(block) => find(block.body.attributes(), &spans, &span),
+            Structure::Attribute(_) => None,
+        })
+        .and_then(|attribute| spans.of(&attribute.value))
+        .ok_or_else(|| anyhow::anyhow!("definition of {path_str} not found"))?;
+    Ok(text[value].to_string())
+}
+
+fn find<'a>(
+    mut attributes: impl Iterator<Item = &'a Attribute>,
+    spans: &SpanMap,
+    span: &std::ops::Range<usize>,
+) -> Option<&'a Attribute> {
+    attributes.find(|attribute| spans.of(*attribute).as_ref() == Some(span))
+}
+
+fn replace_value(
+    text: &mut String,
+    spans: &SpanMap,
+    attribute: &Attribute,
+    expression: &str,
+) -> anyhow::Result<()> {
+    let span = spans
+        .of(&attribute.value)
+        .ok_or_else(|| anyhow::anyhow!("location of {} is unknown", attribute.key.as_str()))?;
+    text.replace_range(span, expression);
+    Ok(())
+}
+
+/// Add `line` to the block at `block_span`, after its last attribute (at `last`) or before the
+/// closing brace
+fn insert_attribute(
+    text: &mut String,
+    last: Option<std::ops::Range<usize>>,
+    block_span: std::ops::Range<usize>,
+    line: &str,
+) {
+    let line_start = |offset: usize| text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let indentation = |offset: usize| {
+        let start = line_start(offset);
+        let whitespace = text[start..offset].len() - text[start..offset].trim_start().len();
+        text[start..start + whitespace].to_string()
+    };
+
+    match last {
+        Some(span) => {
+            let indent = indentation(span.start);
+            let end = text[span.end..]
+                .find('\n')
+                .map_or(text.len(), |index| span.end + index);
+            text.insert_str(end, &format!("\n{indent}{line}"));
+        }
+        None => {
+            let close = block_span.end - 1;
+            let indent = format!("{}  ", indentation(block_span.start));
+            if text[line_start(close)..close].trim().is_empty() {
+                text.insert_str(line_start(close), &format!("{indent}{line}\n"));
+            } else {
+                let outer = indentation(block_span.start);
+                text.insert_str(close, &format!("\n{indent}{line}\n{outer}"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = r#"type service {
+  port = 80
+}
+
+data service api {
+  # the port
+  port = 8080 # http
+  host = "localhost"
+}
+
+data service web {}
+
+data service db {
+}
+"#;
+
+    fn set_in(path: &str, expression: &str) -> anyhow::Result<String> {
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        let file = PathBuf::from("a.cco.hcl");
+        documents.insert_str(SOURCE, file.clone())?;
+        let document = CcoDocument::new(&documents)?;
+        let sources = [(Some(file), SOURCE.to_string())];
+        let (_, text) = set(
+            &document,
+            &sources,
+            &crate::deps::parse_path(path)?,
+            expression,
+        )?;
+        Ok(text)
+    }
+
+    #[test]
+    fn replace() {
+        assert_eq!(
+            set_in("service.api.port", "9090").unwrap(),
+            SOURCE.replace("port = 8080 # http", "port = 9090 # http")
+        );
+        assert_eq!(
+            set_in("service.api.host", r#" "db.local" "#).unwrap(),
+            SOURCE.replace(r#""localhost""#, r#""db.local""#)
+        );
+    }
+
+    #[test]
+    fn insert() {
+        assert_eq!(
+            set_in("service.api.tls", "true").unwrap(),
+            SOURCE.replace(
+                "  host = \"localhost\"\n",
+                "  host = \"localhost\"\n  tls = true\n"
+            )
+        );
+        assert_eq!(
+            set_in("service.web.port", "1").unwrap(),
+            SOURCE.replace("data service web {}", "data service web {\n  port = 1\n}")
+        );
+        assert_eq!(
+            set_in("service.db.port", "2").unwrap(),
+            SOURCE.replace("data service db {\n", "data service db {\n  port = 2\n")
+        );
+    }
+
+    #[test]
+    fn promote() {
+        let root = std::env::temp_dir().join(format!("cco-promote-{}", std::process::id()));
+        let staging = root.join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::create_dir_all(root.join("prod")).unwrap();
+        std::fs::write(staging.join("a.cco.hcl"), SOURCE).unwrap();
+
+        let input = <crate::cli::InputArgs as clap::Parser>::parse_from([
+            std::ffi::OsStr::new("cco"),
+            std::ffi::OsStr::new("-d"),
+            staging.as_os_str(),
+        ]);
+        let sources = crate::read_sources(&input).unwrap();
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        for (path, text) in &sources {
+            documents.insert_str(text, path.clone()).unwrap();
+        }
+        let document = CcoDocument::new(&documents).unwrap();
+        let expression = |path: &str, dir: PathBuf| {
+            let path = crate::deps::parse_path(path).unwrap();
+            expression(&document, &sources, &path, &dir).map_err(|e| e.to_string())
+        };
+
+        // the directory as given on the command line, not canonical
+        let given = root.join("prod").join("..").join("staging");
+        assert_eq!(
+            expression("service.api.port", given.clone()).unwrap(),
+            "8080"
+        );
+        assert_eq!(
+            expression("service.api.host", given.clone()).unwrap(),
+            r#""localhost""#
+        );
+        assert!(expression("service.api.port", root.join("prod"))
+            .unwrap_err()
+            .contains("not defined in"));
+        assert!(expression("service.web.port", given)
+            .unwrap_err()
+            .contains("only attributes"));
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn slices() {
+        let source = "data service api {\n  hosts = [\"a\", \"b\", \"c\"]\n  primary = self.hosts[1:]\n  port = 8080\n}\n";
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        // absolute like the paths of `read_sources`
+        let file = std::env::current_dir().unwrap().join("a.cco.hcl");
+        documents.insert_str(source, file.clone()).unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        let sources = [(Some(file), source.to_string())];
+        let set = |path: &str, expression: &str| {
+            let path = crate::deps::parse_path(path).unwrap();
+            set(&document, &sources, &path, expression).unwrap().1
+        };
+
+        assert_eq!(
+            set("service.api.port", "9090"),
+            source.replace("8080", "9090")
+        );
+        assert_eq!(
+            set("service.api.primary", "self.hosts[:1]"),
+            source.replace("self.hosts[1:]", "self.hosts[:1]")
+        );
+        assert_eq!(
+            set("service.api.tls", "true"),
+            source.replace("  port = 8080\n", "  port = 8080\n  tls = true\n")
+        );
+        let path = crate::deps::parse_path("service.api.primary").unwrap();
+        assert_eq!(
+            expression(&document, &sources, &path, Path::new(".")).unwrap(),
+            "self.hosts[1:]"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let error = |path, expression| set_in(path, expression).unwrap_err().to_string();
+        assert!(error("service.api", "1").contains("only attributes"));
+        assert!(error("service.api.port", "1 +").contains("invalid expression"));
+        assert!(error("other.x.y", "1").contains("neither"));
+    }
+}
@@ -0,0 +1,81 @@
+//! Terminal colors for diagnostics and diffs
+//!
+//! Colors are enabled per stream by [init]: `auto` colors terminals unless `NO_COLOR` is set
+//! (see <https://no-color.org>) or `TERM=dumb`.
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl std::fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorChoice::Auto => f.write_str("auto"),
+            ColorChoice::Always => f.write_str("always"),
+            ColorChoice::Never => f.write_str("never"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+static STDOUT: AtomicBool = AtomicBool::new(false);
+static STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Decide for both streams whether to color them
+pub fn init(choice: ColorChoice) {
+    let auto = || {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let dumb = std::env::var_os("TERM").is_some_and(|term| term == "dumb");
+        !no_color && !dumb
+    };
+    let (stdout, stderr) = match choice {
+        ColorChoice::Always => (true, true),
+        ColorChoice::Never => (false, false),
+        ColorChoice::Auto => (
+            auto() && std::io::stdout().is_terminal(),
+            auto() && std::io::stderr().is_terminal(),
+        ),
+    };
+    STDOUT.store(stdout, Ordering::Relaxed);
+    STDERR.store(stderr, Ordering::Relaxed);
+}
+
+pub fn enabled(stream: Stream) -> bool {
+    match stream {
+        Stream::Stdout => STDOUT.load(Ordering::Relaxed),
+        Stream::Stderr => STDERR.load(Ordering::Relaxed),
+    }
+}
+
+/// An ANSI SGR sequence
+#[derive(Clone, Copy, Debug)]
+pub struct Style(&'static str);
+
+pub const ERROR: Style = Style("1;31");
+pub const WARNING: Style = Style("1;33");
+pub const ADDED: Style = Style("32");
+pub const REMOVED: Style = Style("31");
+pub const HUNK: Style = Style("36");
+pub const BOLD: Style = Style("1");
+
+impl Style {
+    /// `text` wrapped in this style if `stream` is colored
+    pub fn paint(self, stream: Stream, text: impl std::fmt::Display) -> String {
+        if enabled(stream) {
+            format!("\x1b[{}m{text}\x1b[0m", self.0)
+        } else {
+            text.to_string()
+        }
+    }
+}
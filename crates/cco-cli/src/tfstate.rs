@@ -0,0 +1,209 @@
+//! `tfstate` blocks exposing the outputs of a Terraform state
+//!
+//! ```hcl
+//! tfstate network {
+//!   path = "infra/network/terraform.tfstate"
+//!   # or (feature `remote`): url = "s3://states/network.tfstate"
+//!   include_sensitive = false # optional
+//! }
+//! ```
+//!
+//! `tfstate.network` is an object of the root module outputs, `tfstate.network.vpc_id` the value
+//! of the output `vpc_id`. Outputs marked `sensitive` are left out unless `include_sensitive` is
+//! set. The state is read when the block is referenced, a relative `path` from the directory of
+//! the file declaring the block. With a `--lockfile` the state has to match the locked one.
+use crate::lockfile::Sources;
+use cco::cache::Inputs;
+use cco::cco_document::CcoDocumentBuilder;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Block type of states
+pub const KIND: &str = "tfstate";
+
+#[derive(serde::Deserialize)]
+struct State {
+    /// name to `{"value": .., "type": ..}`
+    #[serde(default)]
+    outputs: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Handle `tfstate` blocks
+///
+/// With `offline` states are only read from files, `fetched` is set once one was downloaded. Files
+/// that were read are added to `inputs`, a cached result is only valid while they are unchanged.
+/// States are verified against `lock`.
+pub fn register(
+    builder: CcoDocumentBuilder,
+    offline: bool,
+    fetched: Arc<AtomicBool>,
+    inputs: Arc<Mutex<Inputs>>,
+    lock: Option<Arc<Sources>>,
+) -> CcoDocumentBuilder {
+    builder.data_source_with_origin(KIND, move |_labels, file, body| {
+        let hcl::Value::Object(mut attributes) = body else {
+            return Err("invalid block".to_string());
+        };
+        let path = attributes.shift_remove("path");
+        let url = attributes.shift_remove("url");
+        let include_sensitive = match attributes.shift_remove("include_sensitive") {
+            None => false,
+            Some(hcl::Value::Bool(include)) => include,
+            Some(_) => return Err("include_sensitive must be a bool".to_string()),
+        };
+        if let Some(name) = attributes.keys().next() {
+            return Err(format!("unknown attribute {name}"));
+        }
+
+        let contents = match (path, url) {
+            (Some(hcl::Value::String(path)), None) => {
+                let path = relative_to(file, &path);
+                let contents = std::fs::read(&path)
+                    .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+                if let Some(lock) = &lock {
+                    lock.verify(&lock.file_name(&path), &contents)?;
+                }
+                let hash = cco::cache::content_hash(&contents);
+                inputs
+                    .lock()
+                    .expect("not poisoned")
+                    .push((path.clone(), hash));
+                String::from_utf8(contents)
+                    .map_err(|_| format!("{} is not valid UTF-8", path.display()))?
+            }
+            (None, Some(hcl::Value::String(url))) if offline => {
+                return Err(format!("{url} not fetched in --offline mode"));
+            }
+            (None, Some(hcl::Value::String(url))) => {
+                fetched.store(true, Ordering::Relaxed);
+                let contents = fetch(&url)?;
+                if let Some(lock) = &lock {
+                    lock.verify(&url, contents.as_bytes())?;
+                }
+                contents
+            }
+            (Some(_), Some(_)) => return Err("path and url are mutually exclusive".to_string()),
+            (None, None) => return Err("missing attribute path or url".to_string()),
+            _ => return Err("path and url must be strings".to_string()),
+        };
+        outputs(&contents, include_sensitive)
+    })
+}
+
+/// `path` in the directory of `file`, documents without a file resolve from the work directory
+fn relative_to(file: Option<&Path>, path: &str) -> PathBuf {
+    match file.and_then(Path::parent) {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+#[cfg(feature = "remote")]
+fn fetch(url: &str) -> Result<String, String> {
+    crate::remote::fetch(url, None).map_err(|err| format!("{url}: {err:#}"))
+}
+
+#[cfg(not(feature = "remote"))]
+fn fetch(url: &str) -> Result<String, String> {
+    Err(format!(
+        "{url}: reading states from URLs requires the remote feature"
+    ))
+}
+
+/// Values of the outputs in a state file, without the sensitive ones unless `include_sensitive`
+fn outputs(contents: &str, include_sensitive: bool) -> Result<hcl::Value, String> {
+    let state: State =
+        serde_json::from_str(contents).map_err(|err| format!("invalid state: {err}"))?;
+    let outputs: serde_json::Map<_, _> = state
+        .outputs
+        .into_iter()
+        .filter(|(_, output)| {
+            include_sensitive || output.get("sensitive") != Some(&serde_json::Value::Bool(true))
+        })
+        .map(|(name, mut output)| {
+            let value = output.get_mut("value").map(serde_json::Value::take);
+            (name, value.unwrap_or_default())
+        })
+        .collect();
+    crate::plugin::to_hcl(outputs.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STATE: &str = r#"{
+      "version": 4,
+      "terraform_version": "1.9.0",
+      "outputs": {
+        "vpc_id": { "value": "vpc-123", "type": "string" },
+        "subnets": { "value": ["a", "b"], "type": ["list", "string"], "sensitive": false },
+        "db_password": { "value": "hunter2", "type": "string", "sensitive": true }
+      },
+      "resources": []
+    }"#;
+
+    fn evaluate(source: &str, file: PathBuf) -> (Result<serde_json::Value, String>, Inputs) {
+        let mut documents = cco::hcl_documents::HclDocuments::default();
+        documents.insert_str(source, file).unwrap();
+        let fetched = Arc::new(AtomicBool::new(false));
+        let inputs = Arc::new(Mutex::new(vec![]));
+        let document = register(
+            cco::cco_document::CcoDocument::builder(),
+            true,
+            fetched.clone(),
+            inputs.clone(),
+            None,
+        )
+        .build(&documents)
+        .unwrap();
+        let value = document
+            .evaluate_path(&["app"])
+            .map(|value| serde_json::to_value(value).unwrap())
+            .map_err(|err| format!("{err:#}"));
+        assert!(!fetched.load(Ordering::Relaxed));
+        let inputs = inputs.lock().unwrap().clone();
+        (value, inputs)
+    }
+
+    #[test]
+    fn outputs_of_state() {
+        let dir = std::env::temp_dir().join(format!("cco-tfstate-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("infra")).unwrap();
+        let path = dir.join("infra").join("network.tfstate");
+        std::fs::write(&path, STATE).unwrap();
+
+        // relative to the declaring file, not the work directory
+        let source = "tfstate network {\n  path = \"infra/network.tfstate\"\n}\n\
+                      data app {\n  vpc = tfstate.network.vpc_id\n  subnet = tfstate.network.subnets[1]\n}\n";
+        let (value, inputs) = evaluate(source, dir.join("a.cco.hcl"));
+        assert_eq!(
+            value,
+            Ok(serde_json::json!({ "vpc": "vpc-123", "subnet": "b" }))
+        );
+        assert_eq!(
+            inputs,
+            [(path.clone(), cco::cache::content_hash(STATE.as_bytes()))]
+        );
+
+        let source = "tfstate network {\n  path = \"infra/network.tfstate\"\n}\n\
+                      data app {\n  password = tfstate.network.db_password\n}\n";
+        let (value, _) = evaluate(source, dir.join("a.cco.hcl"));
+        assert!(value.is_err());
+        let source = "tfstate network {\n  path = \"infra/network.tfstate\"\n  include_sensitive = true\n}\n\
+                      data app {\n  password = tfstate.network.db_password\n}\n";
+        let (value, _) = evaluate(source, dir.join("a.cco.hcl"));
+        assert_eq!(value, Ok(serde_json::json!({ "password": "hunter2" })));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(outputs("{}", false).is_ok());
+        assert!(outputs("not json", false)
+            .unwrap_err()
+            .starts_with("invalid state"));
+    }
+}
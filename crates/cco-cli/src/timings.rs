@@ -0,0 +1,85 @@
+//! `--timings` report
+use cco::cco_document::Kind;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Collects per-phase and per-addressable durations
+#[derive(Debug, Default)]
+pub struct Timings {
+    phases: Mutex<Vec<(&'static str, Duration)>>,
+    addressables: Mutex<Vec<(String, Duration)>>,
+}
+
+impl Timings {
+    /// Run `f`, recording its duration as `phase`
+    pub fn phase<T>(&self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        lock(&self.phases).push((phase, started.elapsed()));
+        result
+    }
+
+    /// Recorded phases in order
+    pub fn phases(&self) -> Vec<(&'static str, Duration)> {
+        lock(&self.phases).clone()
+    }
+
+    pub fn report(&self, top: usize, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        let phases = lock(&self.phases);
+        let total: Duration = phases.iter().map(|(_, duration)| *duration).sum();
+
+        writeln!(writer, "phases:")?;
+        for (phase, duration) in phases.iter() {
+            writeln!(writer, "  {phase:<12} {}", format_duration(*duration))?;
+        }
+        writeln!(writer, "  {:<12} {}", "total", format_duration(total))?;
+
+        let mut addressables = lock(&self.addressables).clone();
+        if addressables.is_empty() {
+            return Ok(());
+        }
+
+        addressables.sort_by(|(_, a), (_, b)| b.cmp(a));
+        writeln!(
+            writer,
+            "slowest addressables ({} of {} evaluated, including dependencies):",
+            top.min(addressables.len()),
+            addressables.len()
+        )?;
+        for (path, duration) in addressables.iter().take(top) {
+            writeln!(writer, "  {} {path}", format_duration(*duration))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl cco::observer::EvalObserver for Timings {
+    fn on_resolve(&self, path: &[hcl::Identifier], _kind: Kind, duration: Duration) {
+        lock(&self.addressables).push((path.join("."), duration));
+    }
+}
+
+/// Run `f` as `phase`, recording its duration if timings are collected
+///
+/// The phase is reported with a failure (see [crate::log::phase]), with `otel` it is a tracing
+/// span.
+pub fn timed<T>(timings: Option<&Timings>, phase: &'static str, f: impl FnOnce() -> T) -> T {
+    crate::log::enter_phase(phase);
+    #[cfg(feature = "otel")]
+    let _span = tracing::info_span!("phase", otel.name = phase).entered();
+    match timings {
+        Some(timings) => timings.phase(phase, f),
+        None => f(),
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:>10.3}ms", duration.as_secs_f64() * 1000.0)
+}
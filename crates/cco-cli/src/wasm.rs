@@ -0,0 +1,307 @@
+//! WebAssembly plugins (`--wasm <module.wasm>`)
+//!
+//! Modules run sandboxed: they get no WASI and therefore no file system, network, clock or
+//! environment. The only import a module may use is `cco.getenv`, which only answers for variables
+//! granted with `--wasm-allow-env`. Modules with any other import fail to load.
+//!
+//! A module exports
+//!
+//! - `memory` and `cco_alloc(len: i32) -> i32`, returning a buffer of `len` bytes
+//! - `cco_fn_<name>(ptr: i32, len: i32) -> i64` for every function `<name>` it provides. The input
+//!   is a JSON array of the arguments, the function returns `ptr << 32 | len` of a JSON reply
+//!   `{"result": <value>}` or `{"error": "<message>"}` (as [crate::plugin] replies)
+//!
+//! `cco.getenv(ptr: i32, len: i32) -> i64` gets the name of a variable and returns `ptr << 32 | len`
+//! of its value (in a buffer from `cco_alloc`) or `-1` if it is not set or not granted.
+//!
+//! Every call gets a fuel budget (`--wasm-fuel`, roughly the number of instructions) and modules
+//! can not grow their memory beyond `--wasm-max-memory`.
+use crate::plugin::Reply;
+use std::path::Path;
+use std::sync::Mutex;
+use wasmtime::{Caller, Extern, Linker, Memory, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+const FUNCTION_PREFIX: &str = "cco_fn_";
+
+/// What a module may do
+#[derive(Debug, Clone)]
+pub struct Grants {
+    /// environment variables readable with `cco.getenv`
+    pub env: Vec<String>,
+    /// fuel per call
+    pub fuel: u64,
+    /// bytes of linear memory
+    pub max_memory: usize,
+}
+
+struct State {
+    grants: Grants,
+    limits: StoreLimits,
+}
+
+pub struct Module {
+    name: String,
+    functions: Vec<String>,
+    instance: Mutex<Instance>,
+}
+
+struct Instance {
+    store: Store<State>,
+    instance: wasmtime::Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+}
+
+impl Module {
+    /// Load the module at `path`, verified against `lock`
+    pub fn load(
+        path: &Path,
+        grants: Grants,
+        lock: Option<&crate::lockfile::Sources>,
+    ) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)
+            .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", path.display()))?;
+        if let Some(lock) = lock {
+            lock.verify(&lock.file_name(path), &bytes)
+                .map_err(|err| anyhow::anyhow!(err))?;
+        }
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::new(name, &bytes, grants)
+    }
+
+    /// Compile and instantiate a module (binary or text format)
+    pub fn new(name: String, bytes: &[u8], grants: Grants) -> anyhow::Result<Self> {
+        let failed = |err: anyhow::Error| anyhow::anyhow!("wasm module {name}: {err:#}");
+
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = wasmtime::Engine::new(&config)?;
+        let module = wasmtime::Module::new(&engine, bytes).map_err(failed)?;
+
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(grants.max_memory)
+            .build();
+        let fuel = grants.fuel;
+        let mut store = Store::new(&engine, State { grants, limits });
+        store.limiter(|state| &mut state.limits);
+        // start functions run during instantiation
+        store.set_fuel(fuel)?;
+
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap("cco", "getenv", getenv)?;
+        let instance = linker.instantiate(&mut store, &module).map_err(failed)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| failed(anyhow::anyhow!("missing export 'memory'")))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "cco_alloc")
+            .map_err(failed)?;
+
+        let functions = module
+            .exports()
+            .filter_map(|export| export.name().strip_prefix(FUNCTION_PREFIX))
+            .map(str::to_string)
+            .collect();
+        Ok(Self {
+            name,
+            functions,
+            instance: Mutex::new(Instance {
+                store,
+                instance,
+                memory,
+                alloc,
+            }),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn functions(&self) -> &[String] {
+        &self.functions
+    }
+
+    pub fn call(&self, function: &str, args: &[hcl::Value]) -> Result<hcl::Value, String> {
+        let mut guard = self
+            .instance
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let Instance {
+            store,
+            instance,
+            memory,
+            alloc,
+        } = &mut *guard;
+        let failed = |err: anyhow::Error| format!("wasm module {}: {err:#}", self.name);
+
+        let func = instance
+            .get_typed_func::<(i32, i32), i64>(&mut *store, &format!("{FUNCTION_PREFIX}{function}"))
+            .map_err(failed)?;
+        let input = serde_json::to_vec(args).map_err(|err| err.to_string())?;
+
+        let fuel = store.data().grants.fuel;
+        store.set_fuel(fuel).map_err(failed)?;
+        let ptr = write(&mut *store, *memory, alloc, &input).map_err(failed)?;
+        let packed = func
+            .call(&mut *store, (ptr, input.len() as i32))
+            .map_err(failed)?;
+
+        let output = read(&*store, *memory, packed).map_err(failed)?;
+        let reply: Reply = serde_json::from_slice(&output)
+            .map_err(|err| format!("wasm module {}: invalid reply: {err}", self.name))?;
+        crate::plugin::to_hcl(reply.into_result()?)
+    }
+}
+
+/// Copy `bytes` into a buffer allocated by the module
+fn write(
+    mut store: impl wasmtime::AsContextMut,
+    memory: Memory,
+    alloc: &TypedFunc<i32, i32>,
+    bytes: &[u8],
+) -> anyhow::Result<i32> {
+    let len = i32::try_from(bytes.len())?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory.write(&mut store, ptr as u32 as usize, bytes)?;
+    Ok(ptr)
+}
+
+/// Bytes at `ptr << 32 | len`
+fn read(store: impl wasmtime::AsContext, memory: Memory, packed: i64) -> anyhow::Result<Vec<u8>> {
+    let (ptr, len) = unpack(packed);
+    // the length comes from the module, allocate only what its memory can hold
+    let size = memory.data_size(&store);
+    if !ptr.checked_add(len).is_some_and(|end| end <= size) {
+        anyhow::bail!("{len} bytes at {ptr} are out of bounds of the memory ({size} bytes)");
+    }
+    let mut bytes = vec![0; len];
+    memory.read(&store, ptr, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn pack(ptr: i32, len: usize) -> i64 {
+    ((ptr as u32 as i64) << 32) | len as u32 as i64
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    (
+        (packed as u64 >> 32) as usize,
+        (packed as u64 & 0xffff_ffff) as usize,
+    )
+}
+
+/// `cco.getenv`, see the module documentation
+fn getenv(mut caller: Caller<'_, State>, ptr: i32, len: i32) -> anyhow::Result<i64> {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+        anyhow::bail!("missing export 'memory'");
+    };
+    let name = read(&caller, memory, pack(ptr, len as u32 as usize))?;
+    let name = String::from_utf8(name)?;
+
+    let granted = caller.data().grants.env.contains(&name);
+    let Some(value) = granted.then(|| std::env::var(&name).ok()).flatten() else {
+        tracing::debug!(name, granted, "wasm module asked for environment variable");
+        return Ok(-1);
+    };
+
+    let Some(Extern::Func(alloc)) = caller.get_export("cco_alloc") else {
+        anyhow::bail!("missing export 'cco_alloc'");
+    };
+    let alloc = alloc.typed::<i32, i32>(&caller)?;
+    let ptr = write(&mut caller, memory, &alloc, value.as_bytes())?;
+    Ok(pack(ptr, value.len()))
+}
+
+/// Load the modules of `--wasm`, verified against the data sources of `--lockfile`
+pub fn load(
+    input: &crate::cli::InputArgs,
+    lock: Option<&crate::lockfile::Sources>,
+) -> anyhow::Result<Vec<std::sync::Arc<Module>>> {
+    let grants = Grants {
+        env: input.wasm_allow_env.clone(),
+        fuel: input.wasm_fuel,
+        max_memory: input.wasm_max_memory * 1024 * 1024,
+    };
+    input
+        .wasm
+        .iter()
+        .map(|path| Module::load(path, grants.clone(), lock).map(std::sync::Arc::new))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MODULE: &str = r#"
+    (module
+      (import "cco" "getenv" (func $getenv (param i32 i32) (result i64)))
+      (memory (export "memory") 1)
+      (global $next (mut i32) (i32.const 1024))
+      (data (i32.const 0) "{\"result\": 42}")
+      (data (i32.const 16) "HOME")
+      (func (export "cco_alloc") (param $len i32) (result i32)
+        (local $ptr i32)
+        (local.set $ptr (global.get $next))
+        (global.set $next (i32.add (global.get $next) (local.get $len)))
+        (local.get $ptr))
+      (func (export "cco_fn_answer") (param i32 i32) (result i64)
+        (i64.const 14))
+      (func (export "cco_fn_huge") (param i32 i32) (result i64)
+        (i64.const 0xffffffff))
+      (func (export "cco_fn_home") (param i32 i32) (result i64)
+        (call $getenv (i32.const 16) (i32.const 4)))
+      (func (export "cco_fn_spin") (param i32 i32) (result i64)
+        (loop $forever (br $forever))
+        (i64.const 0)))
+    "#;
+
+    fn grants(env: &[&str]) -> Grants {
+        Grants {
+            env: env.iter().map(|name| name.to_string()).collect(),
+            fuel: 1_000_000,
+            max_memory: 1024 * 1024,
+        }
+    }
+
+    #[test]
+    fn calls() {
+        let module = Module::new("test".into(), MODULE.as_bytes(), grants(&[])).unwrap();
+        let mut functions = module.functions().to_vec();
+        functions.sort();
+        assert_eq!(functions, ["answer", "home", "huge", "spin"]);
+
+        assert_eq!(module.call("answer", &[]), Ok(hcl::Value::from(42)));
+        // out of fuel
+        assert!(module.call("spin", &[]).is_err());
+        // a length beyond the memory is rejected before anything is allocated
+        assert!(module
+            .call("huge", &[])
+            .unwrap_err()
+            .contains("out of bounds"));
+    }
+
+    #[test]
+    fn capabilities() {
+        // not granted, getenv returns -1 which is no valid reply
+        let module = Module::new("test".into(), MODULE.as_bytes(), grants(&[])).unwrap();
+        assert!(module.call("home", &[]).is_err());
+
+        let wasi = r#"(module (import "wasi_snapshot_preview1" "fd_write"
+            (func (param i32 i32 i32 i32) (result i32))))"#;
+        assert!(Module::new("wasi".into(), wasi.as_bytes(), grants(&["HOME"])).is_err());
+    }
+
+    #[test]
+    fn packing() {
+        assert_eq!(unpack(pack(1024, 14)), (1024, 14));
+        assert_eq!(
+            unpack(pack(i32::MAX, u32::MAX as usize)),
+            (i32::MAX as usize, u32::MAX as usize)
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! `cco.workspace.hcl`: the packages of a repository, rendered by `cco build`
+//!
+//! ```hcl
+//! layers = ["shared"]
+//!
+//! package "api" {
+//!   dir    = "services/api"
+//!   layers = ["shared/http"]
+//!
+//!   output "dist/api.yaml" {
+//!     expression = "service.api"
+//!   }
+//! }
+//! ```
+//!
+//! A package loads the workspace `layers`, its own `layers` and then its `dir` (default: the
+//! package name), like `-d` for each of them. Paths are relative to the manifest.
+use crate::cli::OutputFormat;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File name of the manifest
+pub const MANIFEST: &str = "cco.workspace.hcl";
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Workspace {
+    /// Directories loaded by every package, before its own
+    #[serde(default)]
+    layers: Vec<PathBuf>,
+    #[serde(default)]
+    package: BTreeMap<String, PackageSpec>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PackageSpec {
+    dir: Option<PathBuf>,
+    #[serde(default)]
+    layers: Vec<PathBuf>,
+    #[serde(default)]
+    output: BTreeMap<PathBuf, OutputSpec>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct OutputSpec {
+    expression: String,
+    /// `yaml`, `json` or `ndjson`, default: by file extension
+    format: Option<String>,
+}
+
+/// A package ready to be built
+#[derive(Debug, PartialEq)]
+pub struct Package {
+    pub name: String,
+    /// Directories to load in order: layers, then the package directory
+    pub dirs: Vec<PathBuf>,
+    pub outputs: Vec<Output>,
+}
+
+/// A file written by `cco build`
+#[derive(Debug, PartialEq)]
+pub struct Output {
+    pub file: PathBuf,
+    pub expression: String,
+    pub format: OutputFormat,
+}
+
+impl Workspace {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", path.display()))?;
+        hcl::from_str(&text).map_err(|err| anyhow::anyhow!("{}: {err}", path.display()))
+    }
+
+    /// The packages called `names` (all if empty) with paths relative to `root`
+    pub fn packages(&self, root: &Path, names: &[String]) -> anyhow::Result<Vec<Package>> {
+        let unknown: Vec<&str> = names
+            .iter()
+            .filter(|name| !self.package.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        anyhow::ensure!(
+            unknown.is_empty(),
+            "unknown package(s) {}, the workspace has: {}",
+            unknown.join(", "),
+            self.package.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+
+        let mut packages = vec![];
+        for (name, spec) in &self.package {
+            if !names.is_empty() && !names.contains(name) {
+                continue;
+            }
+
+            let dir = spec.dir.clone().unwrap_or_else(|| PathBuf::from(name));
+            let dirs = self
+                .layers
+                .iter()
+                .chain(&spec.layers)
+                .chain(std::iter::once(&dir))
+                .map(|dir| root.join(dir))
+                .collect();
+
+            let mut outputs = vec![];
+            for (file, output) in &spec.output {
+                let format = match &output.format {
+                    Some(format) => clap::ValueEnum::from_str(format, true).map_err(|_| {
+                        anyhow::anyhow!(
+                            "package {name}: unknown format `{format}` of {}",
+                            file.display()
+                        )
+                    })?,
+                    None => format_of(file),
+                };
+                outputs.push(Output {
+                    file: root.join(file),
+                    expression: output.expression.clone(),
+                    format,
+                });
+            }
+            anyhow::ensure!(!outputs.is_empty(), "package {name} has no outputs");
+
+            packages.push(Package {
+                name: name.clone(),
+                dirs,
+                outputs,
+            });
+        }
+        Ok(packages)
+    }
+}
+
+/// Output format by file extension, YAML unless `.json` or `.ndjson`
+fn format_of(file: &Path) -> OutputFormat {
+    match file.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => OutputFormat::Json,
+        Some("ndjson" | "jsonl") => OutputFormat::Ndjson,
+        _ => OutputFormat::Yaml,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn packages() {
+        let workspace: Workspace = hcl::from_str(
+            r#"
+            layers = ["shared"]
+
+            package "api" {
+              dir    = "services/api"
+              layers = ["shared/http"]
+              output "dist/api.yaml" { expression = "service.api" }
+              output "dist/api.json" { expression = "service.api" }
+            }
+
+            package "worker" {
+              output "dist/worker.conf" {
+                expression = "service.worker"
+                format     = "json"
+              }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let root = Path::new("repo");
+        let packages = workspace.packages(root, &[]).unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(
+            packages[0].dirs,
+            ["repo/shared", "repo/shared/http", "repo/services/api"].map(PathBuf::from)
+        );
+        assert_eq!(
+            packages[0]
+                .outputs
+                .iter()
+                .map(|output| (output.file.to_str().unwrap(), output.format.clone()))
+                .collect::<Vec<_>>(),
+            [
+                ("repo/dist/api.json", OutputFormat::Json),
+                ("repo/dist/api.yaml", OutputFormat::Yaml)
+            ]
+        );
+        assert_eq!(
+            packages[1].dirs,
+            ["repo/shared", "repo/worker"].map(PathBuf::from)
+        );
+        assert_eq!(packages[1].outputs[0].format, OutputFormat::Json);
+
+        let selected = workspace.packages(root, &["worker".to_string()]).unwrap();
+        assert_eq!(selected.len(), 1);
+        let error = workspace.packages(root, &["web".to_string()]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown package(s) web, the workspace has: api, worker"
+        );
+    }
+}
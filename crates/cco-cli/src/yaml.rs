@@ -0,0 +1,364 @@
+//! YAML output with style options
+//!
+//! `serde_yaml` has a fixed style and quotes multi-line strings it can not write as plain literal
+//! blocks (leading spaces, trailing blank lines). This small emitter for [Value] follows the same
+//! conventions (sequences are not indented relative to their key), keeps multi-line strings such
+//! as heredocs literal blocks and only delegates scalar formatting to `serde_yaml`.
+use cco::value::Value;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+pub enum Multiline {
+    /// Literal block scalars (`|`)
+    #[default]
+    Literal,
+    /// Double quoted strings with `\n` escapes
+    Quoted,
+}
+
+impl std::fmt::Display for Multiline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Multiline::Literal => f.write_str("literal"),
+            Multiline::Quoted => f.write_str("quoted"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub indent: usize,
+    pub multiline: Multiline,
+    pub decimals: crate::decimal::DecimalFormat,
+}
+
+impl Default for Style {
+    /// The style of `serde_yaml`
+    fn default() -> Self {
+        Self {
+            indent: 2,
+            multiline: Multiline::Literal,
+            decimals: Default::default(),
+        }
+    }
+}
+
+/// A single YAML document
+pub fn to_string(value: &Value, style: Style) -> anyhow::Result<String> {
+    let mut out = String::new();
+    Emitter {
+        style,
+        out: &mut out,
+    }
+    .value(value, 0, false)?;
+    Ok(out)
+}
+
+/// A single YAML document with a comment above some of the top-level keys
+///
+/// `comments` are looked up by key, a comment may span multiple lines.
+pub fn to_annotated_string(
+    value: &Value,
+    style: Style,
+    comments: &HashMap<String, String>,
+) -> anyhow::Result<String> {
+    let Value::Object(object) = value else {
+        return to_string(value, style);
+    };
+    let mut out = String::new();
+    let mut emitter = Emitter {
+        style,
+        out: &mut out,
+    };
+    for (key, value) in object {
+        for line in comments
+            .get(key)
+            .into_iter()
+            .flat_map(|comment| comment.lines())
+        {
+            writeln!(emitter.out, "# {line}")?;
+        }
+        emitter.entry(key, value, 0)?;
+    }
+    if object.is_empty() {
+        emitter.value(value, 0, false)?;
+    }
+    Ok(out)
+}
+
+/// One document per element of an array or value of an object
+pub fn to_documents(value: &Value, style: Style) -> anyhow::Result<String> {
+    let documents: Vec<&Value> = match value {
+        Value::Array(array) => array.iter().collect(),
+        Value::Object(object) => object.values().collect(),
+        scalar => vec![scalar],
+    };
+
+    let mut out = String::new();
+    for document in documents {
+        out.push_str("---\n");
+        out.push_str(&to_string(document, style)?);
+    }
+    Ok(out)
+}
+
+struct Emitter<'a> {
+    style: Style,
+    out: &'a mut String,
+}
+
+impl Emitter<'_> {
+    fn indent(&mut self, col: usize) {
+        self.out.push_str(&" ".repeat(col));
+    }
+
+    /// Write `value` with block content at `col`
+    ///
+    /// `inline` is set if the cursor is already at `col`, after a `- `.
+    fn value(&mut self, value: &Value, col: usize, inline: bool) -> anyhow::Result<()> {
+        match value {
+            Value::Object(object) if !object.is_empty() => {
+                for (index, (key, value)) in object.iter().enumerate() {
+                    if index > 0 || !inline {
+                        self.indent(col);
+                    }
+                    self.entry(key, value, col)?;
+                }
+            }
+            Value::Array(array) if !array.is_empty() => {
+                for (index, item) in array.iter().enumerate() {
+                    if index > 0 || !inline {
+                        self.indent(col);
+                    }
+                    self.out.push('-');
+                    self.after_dash(item, col)?;
+                }
+            }
+            scalar => self.scalar_line(scalar, col + self.style.indent)?,
+        }
+        Ok(())
+    }
+
+    /// `key: value` of a mapping, the cursor is at `col` already
+    fn entry(&mut self, key: &str, value: &Value, col: usize) -> anyhow::Result<()> {
+        let key = self.scalar(&Value::String(key.to_string()))?;
+        write!(self.out, "{key}:")?;
+        self.after_key(value, col)
+    }
+
+    /// Value of a `key:` at `col`, sequences are not indented (like `serde_yaml`)
+    fn after_key(&mut self, value: &Value, col: usize) -> anyhow::Result<()> {
+        match value {
+            Value::Array(array) if !array.is_empty() => {
+                self.out.push('\n');
+                self.value(value, col, false)
+            }
+            Value::Object(object) if !object.is_empty() => {
+                self.out.push('\n');
+                self.value(value, col + self.style.indent, false)
+            }
+            scalar => {
+                self.out.push(' ');
+                self.scalar_line(scalar, col + self.style.indent)
+            }
+        }
+    }
+
+    /// Item of a sequence with the `-` at `col`
+    fn after_dash(&mut self, value: &Value, col: usize) -> anyhow::Result<()> {
+        self.out.push(' ');
+        match value {
+            Value::Array(array) if !array.is_empty() => self.value(value, col + 2, true),
+            Value::Object(object) if !object.is_empty() => self.value(value, col + 2, true),
+            scalar => self.scalar_line(scalar, col + self.style.indent),
+        }
+    }
+
+    /// A scalar and the line break, block scalar content goes to `col`
+    fn scalar_line(&mut self, value: &Value, col: usize) -> anyhow::Result<()> {
+        match self.literal(value, col) {
+            Some(literal) => self.out.push_str(&literal),
+            None => writeln!(self.out, "{}", self.scalar(value)?)?,
+        }
+        Ok(())
+    }
+
+    /// Multi-line strings as literal block scalar with content at `col`
+    fn literal(&self, value: &Value, col: usize) -> Option<String> {
+        let Value::String(string) = value else {
+            return None;
+        };
+        let representable = string.contains('\n')
+            && !string
+                .chars()
+                .any(|c| c.is_control() && c != '\n' && c != '\t');
+        if self.style.multiline != Multiline::Literal || !representable {
+            return None;
+        }
+
+        // the indentation can not be detected from a first line starting with spaces, content is
+        // always indented by `indent` relative to its parent
+        let indicator = match string.starts_with([' ', '\n']) {
+            true if self.style.indent > 9 => return None,
+            true => self.style.indent.to_string(),
+            false => String::new(),
+        };
+        let trimmed = string.trim_end_matches('\n');
+        let (chomping, content) = match string.len() - trimmed.len() {
+            0 => ("-", trimmed),
+            1 if !trimmed.is_empty() => ("", trimmed),
+            // keep all but the final line break, which is part of the scalar syntax
+            _ => ("+", &string[..string.len() - 1]),
+        };
+
+        let mut literal = format!("|{indicator}{chomping}\n");
+        for line in content.split('\n') {
+            if !line.is_empty() {
+                literal.push_str(&" ".repeat(col));
+                literal.push_str(line);
+            }
+            literal.push('\n');
+        }
+        Some(literal)
+    }
+
+    fn scalar(&self, value: &Value) -> anyhow::Result<String> {
+        Ok(match value {
+            // serde_yaml would use a block scalar
+            Value::String(string) if string.contains('\n') => serde_json::to_string(string)?,
+            Value::Decimal(decimal) => match self.style.decimals.format(*decimal) {
+                Some(decimal) => decimal,
+                None => serde_yaml::to_string(value)?.trim_end().to_string(),
+            },
+            scalar => serde_yaml::to_string(scalar)?.trim_end().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn value() -> Value {
+        serde_json::json!({
+            "s": "x\ny\n",
+            "l": [1, { "a": 1, "b": [1, 2] }, [3]],
+            "m": {},
+            "n": { "o": "yes" },
+        })
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn indent() {
+        let style = Style {
+            indent: 4,
+            multiline: Multiline::Literal,
+            ..Default::default()
+        };
+        let yaml = to_string(&value(), style).unwrap();
+        assert_eq!(
+            yaml,
+            "s: |\n    x\n    y\nl:\n- 1\n- a: 1\n  b:\n  - 1\n  - 2\n- - 3\nm: {}\nn:\n    o: yes\n"
+        );
+
+        let reparsed: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(reparsed, serde_json::Value::from(value()));
+    }
+
+    #[test]
+    fn annotated() {
+        let comments = HashMap::from([
+            ("n".to_string(), "from a.cco.hcl:3".to_string()),
+            ("m".to_string(), "two\nlines".to_string()),
+        ]);
+        let yaml = to_annotated_string(&value(), Style::default(), &comments).unwrap();
+        assert!(yaml.contains("\n# two\n# lines\nm: {}\n# from a.cco.hcl:3\nn:\n  o: yes\n"));
+        assert!(yaml.starts_with("s: |\n"));
+
+        let reparsed: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(reparsed, serde_json::Value::from(value()));
+        assert_eq!(
+            to_annotated_string(&Value::Integer(1), Style::default(), &comments).unwrap(),
+            "1\n"
+        );
+    }
+
+    #[test]
+    fn quoted_multiline() {
+        let style = Style {
+            indent: 2,
+            multiline: Multiline::Quoted,
+            ..Default::default()
+        };
+        let yaml = to_string(&value(), style).unwrap();
+        assert!(yaml.starts_with("s: \"x\\ny\\n\"\n"));
+
+        let reparsed: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(reparsed, serde_json::Value::from(value()));
+    }
+
+    #[test]
+    fn heredocs() {
+        let strings = [
+            "#!/bin/sh\necho \"hi\"\n  indented\n",
+            "  leading\nspaces\n",
+            "\nleading newline",
+            "trailing   \nspaces   ",
+            "blank lines\n\n\n",
+            "\n",
+            "\n\n",
+            "a\n   \nb",
+        ];
+        for indent in [2, 4] {
+            let style = Style {
+                indent,
+                multiline: Multiline::Literal,
+                ..Default::default()
+            };
+            for string in strings {
+                let json = serde_json::json!({ "k": string, "l": [string, { "m": string }] });
+                for value in [json, string.into()] {
+                    let yaml = to_string(&value.clone().try_into().unwrap(), style).unwrap();
+                    assert!(yaml.contains('|'), "not a literal block:\n{yaml}");
+                    let reparsed: serde_json::Value =
+                        serde_yaml::from_str(&yaml).unwrap_or_else(|err| panic!("{err}:\n{yaml}"));
+                    assert_eq!(reparsed, value, "{yaml}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn documents() {
+        let style = Style {
+            indent: 2,
+            multiline: Multiline::Literal,
+            ..Default::default()
+        };
+        let value: Value = serde_json::json!({ "a": { "kind": "A" }, "b": { "kind": "B" } })
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            to_documents(&value, style).unwrap(),
+            "---\nkind: A\n---\nkind: B\n"
+        );
+    }
+
+    #[test]
+    fn decimals() {
+        let style = Style {
+            decimals: crate::decimal::DecimalFormat {
+                max_precision: Some(2),
+                whole_point: true,
+                plain: false,
+            },
+            ..Default::default()
+        };
+        let value = serde_json::json!({ "a": 2.0, "b": 0.126, "c": [1e-9] });
+        let yaml = to_string(&value.try_into().unwrap(), style).unwrap();
+        assert_eq!(yaml, "a: 2.0\nb: 0.13\nc:\n- 0.0\n");
+    }
+}
@@ -0,0 +1,53 @@
+/*
+ * cco - cascading configuration
+ *
+ * C interface to load cco documents and evaluate expressions.
+ * All strings are UTF-8 and NUL terminated.
+ *
+ * Ownership:
+ * - CcoDocument and CcoResult pointers are owned by the caller and must be
+ *   released with cco_document_free / cco_result_free.
+ * - Strings returned by cco_result_* and cco_last_error are borrowed and stay
+ *   valid until the owning object is freed (or, for cco_last_error, until the
+ *   next failing call on the same thread).
+ */
+#ifndef CCO_H
+#define CCO_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct CcoDocument CcoDocument;
+typedef struct CcoResult CcoResult;
+
+/* Load files or directories (only files ending in cco.hcl are loaded from directories).
+ * Returns NULL on failure, see cco_last_error. */
+CcoDocument *cco_load(const char *const *paths, size_t paths_len);
+
+/* Load a document from a string. Returns NULL on failure, see cco_last_error. */
+CcoDocument *cco_load_str(const char *source);
+
+void cco_document_free(CcoDocument *document);
+
+/* Evaluate an HCL expression. Never returns NULL for valid arguments. */
+CcoResult *cco_evaluate(const CcoDocument *document, const char *expression);
+
+/* JSON encoded value, NULL if evaluation failed. */
+const char *cco_result_json(const CcoResult *result);
+
+/* Error message, NULL if evaluation succeeded. */
+const char *cco_result_error(const CcoResult *result);
+
+void cco_result_free(CcoResult *result);
+
+/* Message of the last error from cco_load / cco_load_str on this thread, or NULL. */
+const char *cco_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CCO_H */
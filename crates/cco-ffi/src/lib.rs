@@ -0,0 +1,223 @@
+//! C interface for `cco`
+//!
+//! Exposes loading and evaluation over a C ABI so that non-Rust services get the exact same evaluation
+//! semantics. The C declarations live in `include/cco.h`.
+//!
+//! Errors never unwind across the FFI boundary: panics are caught and reported like any other error.
+use cco::cco_document::CcoDocument as Document;
+use cco::hcl_documents::HclDocuments;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::path::Path;
+
+/// Opaque handle to a loaded document
+pub struct CcoDocument(Document);
+
+/// Opaque handle to an evaluation result
+pub struct CcoResult(Result<CString, CString>);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(to_cstring(message)));
+}
+
+/// Interior NUL bytes can not be represented, replace them
+fn to_cstring(s: String) -> CString {
+    CString::new(s.replace('\0', "\u{FFFD}")).expect("NUL bytes were replaced")
+}
+
+/// Run `f`, turning errors and panics into messages
+fn guarded<T>(f: impl FnOnce() -> anyhow::Result<T>) -> Result<T, String> {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(err)) => Err(format!("{err:#}")),
+        Err(panic) => Err(panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic during evaluation".to_string())),
+    }
+}
+
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> anyhow::Result<&'a str> {
+    anyhow::ensure!(!ptr.is_null(), "{name} must not be NULL");
+    Ok(CStr::from_ptr(ptr).to_str()?)
+}
+
+fn into_document(documents: HclDocuments) -> anyhow::Result<*mut CcoDocument> {
+    let document = Document::new(&documents)?;
+    Ok(Box::into_raw(Box::new(CcoDocument(document))))
+}
+
+/// Load files or directories
+///
+/// # Safety
+/// `paths` must point to `paths_len` valid NUL terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn cco_load(
+    paths: *const *const c_char,
+    paths_len: usize,
+) -> *mut CcoDocument {
+    let result = guarded(|| {
+        anyhow::ensure!(!paths.is_null() || paths_len == 0, "paths must not be NULL");
+
+        let mut documents = HclDocuments::default();
+        for index in 0..paths_len {
+            let path = Path::new(str_arg(*paths.add(index), "path")?);
+            if path.is_dir() {
+                documents.load_directory(path)?;
+            } else {
+                documents.load_file(path)?;
+            }
+        }
+
+        into_document(documents)
+    });
+
+    result.unwrap_or_else(|message| {
+        set_last_error(message);
+        std::ptr::null_mut()
+    })
+}
+
+/// Load a document from a string
+///
+/// # Safety
+/// `source` must be a valid NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn cco_load_str(source: *const c_char) -> *mut CcoDocument {
+    let result = guarded(|| {
+        let mut documents = HclDocuments::default();
+        documents.insert_str(str_arg(source, "source")?, None)?;
+        into_document(documents)
+    });
+
+    result.unwrap_or_else(|message| {
+        set_last_error(message);
+        std::ptr::null_mut()
+    })
+}
+
+/// # Safety
+/// `document` must be NULL or a pointer returned by `cco_load`/`cco_load_str` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn cco_document_free(document: *mut CcoDocument) {
+    if !document.is_null() {
+        drop(Box::from_raw(document));
+    }
+}
+
+/// Evaluate an expression
+///
+/// # Safety
+/// `document` must be a valid document pointer, `expression` a valid NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn cco_evaluate(
+    document: *const CcoDocument,
+    expression: *const c_char,
+) -> *mut CcoResult {
+    let result = guarded(|| {
+        anyhow::ensure!(!document.is_null(), "document must not be NULL");
+        let expression = cco::hcl_documents::parse_expression(str_arg(expression, "expression")?)?;
+        let value = (*document).0.evaluate_in_context(expression)?;
+        Ok(serde_json::to_string(&value)?)
+    });
+
+    Box::into_raw(Box::new(CcoResult(
+        result.map(to_cstring).map_err(to_cstring),
+    )))
+}
+
+/// JSON encoded value or NULL if evaluation failed
+///
+/// # Safety
+/// `result` must be a valid result pointer. The returned string is valid until the result is freed.
+#[no_mangle]
+pub unsafe extern "C" fn cco_result_json(result: *const CcoResult) -> *const c_char {
+    match result.as_ref() {
+        Some(CcoResult(Ok(json))) => json.as_ptr(),
+        _ => std::ptr::null(),
+    }
+}
+
+/// Error message or NULL if evaluation succeeded
+///
+/// # Safety
+/// `result` must be a valid result pointer. The returned string is valid until the result is freed.
+#[no_mangle]
+pub unsafe extern "C" fn cco_result_error(result: *const CcoResult) -> *const c_char {
+    match result.as_ref() {
+        Some(CcoResult(Err(error))) => error.as_ptr(),
+        _ => std::ptr::null(),
+    }
+}
+
+/// # Safety
+/// `result` must be NULL or a pointer returned by `cco_evaluate` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn cco_result_free(result: *mut CcoResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+/// Message of the last failed load on this thread
+///
+/// The returned string is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn cco_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |error| error.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    unsafe fn string(ptr: *const c_char) -> Option<String> {
+        (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn load_and_evaluate() {
+        unsafe {
+            let document = cco_load_str(c("data app {\n port = 1\n}").as_ptr());
+            assert!(!document.is_null());
+
+            let result = cco_evaluate(document, c("app").as_ptr());
+            assert_eq!(
+                string(cco_result_json(result)).as_deref(),
+                Some(r#"{"port":1}"#)
+            );
+            assert_eq!(string(cco_result_error(result)), None);
+            cco_result_free(result);
+
+            let result = cco_evaluate(document, c("unknown").as_ptr());
+            assert_eq!(string(cco_result_json(result)), None);
+            assert!(string(cco_result_error(result)).is_some());
+            cco_result_free(result);
+
+            cco_document_free(document);
+        }
+    }
+
+    #[test]
+    fn load_error() {
+        unsafe {
+            let document = cco_load_str(c("not = valid = hcl").as_ptr());
+            assert!(document.is_null());
+            assert!(string(cco_last_error()).is_some());
+        }
+    }
+}
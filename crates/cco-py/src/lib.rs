@@ -0,0 +1,116 @@
+//! Python bindings for `cco`
+//!
+//! Build with [maturin](https://www.maturin.rs) (`maturin develop` in this directory).
+//!
+//! ```python
+//! import cco
+//!
+//! document = cco.load(["config/"])
+//! services = document.evaluate("service")
+//! ```
+//!
+//! Values map to `bool`, `int`, `float`, `str`, `list` and `dict` (key order is preserved). Errors are
+//! raised as `cco.CcoError`.
+use cco::cco_document::CcoDocument;
+use cco::hcl_documents::HclDocuments;
+use cco::value::Value;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use std::path::PathBuf;
+
+create_exception!(cco, CcoError, PyException);
+
+fn error(err: impl std::fmt::Display) -> PyErr {
+    CcoError::new_err(err.to_string())
+}
+
+/// Loaded cco documents
+#[pyclass(name = "Document", module = "cco", frozen)]
+struct Document {
+    document: CcoDocument,
+}
+
+#[pymethods]
+impl Document {
+    /// Evaluate an HCL expression
+    fn evaluate(&self, py: Python<'_>, expression: &str) -> PyResult<PyObject> {
+        let expression = cco::hcl_documents::parse_expression(expression).map_err(error)?;
+        let value = self
+            .document
+            .evaluate_in_context(expression)
+            .map_err(|err| error(format!("{err:#}")))?;
+
+        to_python(py, value)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<cco.Document with {} paths>",
+            self.document.paths().count()
+        )
+    }
+}
+
+fn to_python(py: Python<'_>, value: Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Boolean(value) => value.into_py(py),
+        Value::Integer(value) => value.into_py(py),
+        Value::Decimal(value) => value.into_py(py),
+        Value::String(value) => value.into_py(py),
+        Value::Bytes(value) => PyBytes::new(py, &value).into_py(py),
+        Value::Array(value) => {
+            let elements = value
+                .into_iter()
+                .map(|element| to_python(py, element))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, elements).into_py(py)
+        }
+        Value::Object(value) => {
+            let dict = PyDict::new(py);
+            for (key, value) in value {
+                dict.set_item(key, to_python(py, value)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+fn build(documents: HclDocuments) -> PyResult<Document> {
+    let document = CcoDocument::new(&documents).map_err(error)?;
+    Ok(Document { document })
+}
+
+/// Load files or directories (only files ending in `cco.hcl` are loaded from directories)
+#[pyfunction]
+fn load(paths: Vec<PathBuf>) -> PyResult<Document> {
+    let mut documents = HclDocuments::default();
+    for path in paths {
+        if path.is_dir() {
+            documents.load_directory(&path).map_err(error)?;
+        } else {
+            documents.load_file(&path).map_err(error)?;
+        }
+    }
+
+    build(documents)
+}
+
+/// Load a document from a string
+#[pyfunction]
+fn loads(source: &str) -> PyResult<Document> {
+    let mut documents = HclDocuments::default();
+    documents.insert_str(source, None).map_err(error)?;
+    build(documents)
+}
+
+#[pymodule]
+#[pyo3(name = "cco")]
+fn cco_py(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("CcoError", py.get_type::<CcoError>())?;
+    m.add_class::<Document>()?;
+    m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_function(wrap_pyfunction!(loads, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,623 @@
+//! checked integer arithmetic
+//!
+//! `hcl` adds, subtracts, multiplies and negates integers without overflow checks: debug builds
+//! panic, release builds wrap around. Before an expression is evaluated [check_overflow] computes
+//! these operations for integer operands itself and applies the [OverflowPolicy] when the result
+//! does not fit an `i64`. Operands may be any HCL integer, literals above `i64::MAX` included.
+//!
+//! Within the arguments of `try` and `can` an overflowing operation becomes a call of
+//! [OVERFLOW_FUNCTION], it fails when evaluated so `try` moves on and `can` is `false`.
+//!
+//! Operands inside `for` expressions and templates are only known while `hcl` evaluates them.
+//! Their operations become calls of [CHECKED_FUNCTION] (or [SATURATING_FUNCTION]) which compute
+//! them the same way. Integers of the final value that do not fit an `i64` (large literals) are
+//! subject to the policy as well, see [check_range].
+//!
+//! The parser nests chained operations to the right as written (`a - b - c` is `a - (b - c)`),
+//! `hcl` only regroups them when it evaluates a whole operation. [check_overflow] regroups them by
+//! precedence before anything is computed.
+use crate::cco_document::OverflowPolicy;
+use hcl::eval::{Context, Evaluate, FuncArgs};
+use hcl::expr::{BinaryOp, BinaryOperator, Operation, TraversalOperator, UnaryOp, UnaryOperator};
+use hcl::template::{Directive, Element, Template};
+use hcl::{Expression, FuncCall, ObjectKey, TemplateExpr, Value};
+
+/// Function failing with the overflowing operation it is called with, see the module documentation
+pub(crate) const OVERFLOW_FUNCTION: &str = "cco__overflow";
+
+/// Function computing an operation of a `for` expression or template, see [deferred]
+pub(crate) const CHECKED_FUNCTION: &str = "cco__checked";
+
+/// [CHECKED_FUNCTION] with [OverflowPolicy::Saturate]
+pub(crate) const SATURATING_FUNCTION: &str = "cco__saturating";
+
+/// Replace integer operations with their result, `Err` describes an overflowing operation
+///
+/// Operands are evaluated in place, operands that can not be evaluated yet (unknown references)
+/// leave the operation to the regular evaluation.
+pub(crate) fn check_overflow(
+    expression: &mut Expression,
+    context: &Context,
+    policy: OverflowPolicy,
+) -> Result<(), String> {
+    regroup(expression);
+    check(expression, context, policy)
+}
+
+fn check(
+    expression: &mut Expression,
+    context: &Context,
+    policy: OverflowPolicy,
+) -> Result<(), String> {
+    match expression {
+        Expression::Operation(operation) => {
+            match operation.as_mut() {
+                Operation::Binary(binop) => {
+                    check(&mut binop.lhs_expr, context, policy)?;
+                    check(&mut binop.rhs_expr, context, policy)?;
+                }
+                Operation::Unary(unop) => check(&mut unop.expr, context, policy)?,
+            }
+            if let Some(result) = integer_operation(operation, context, policy)? {
+                *expression = Expression::Number(result.into());
+            }
+        }
+        Expression::Conditional(cond) => {
+            check(&mut cond.cond_expr, context, policy)?;
+            // only the branch that is taken, the other one may well overflow
+            if cond.cond_expr.evaluate_in_place(context).is_ok() {
+                if let Expression::Bool(taken) = cond.cond_expr {
+                    let branch = match taken {
+                        true => &mut cond.true_expr,
+                        false => &mut cond.false_expr,
+                    };
+                    *expression = std::mem::replace(branch, Expression::Null);
+                    return check(expression, context, policy);
+                }
+            }
+        }
+        Expression::Array(array) => {
+            for expr in array {
+                check(expr, context, policy)?;
+            }
+        }
+        Expression::Object(object) => {
+            for value in object.values_mut() {
+                check(value, context, policy)?;
+            }
+        }
+        Expression::Parenthesis(expr) => check(expr, context, policy)?,
+        Expression::FuncCall(func_call) => {
+            let fallback = matches!(func_call.name.name.as_str(), "try" | "can");
+            for arg in func_call.args.iter_mut() {
+                match check(arg, context, policy) {
+                    Err(operation) if fallback => {
+                        *arg = FuncCall::builder(OVERFLOW_FUNCTION)
+                            .arg(operation)
+                            .build()
+                            .into();
+                    }
+                    result => result?,
+                }
+            }
+        }
+        Expression::Traversal(traversal) => {
+            check(&mut traversal.expr, context, policy)?;
+            for operator in traversal.operators.iter_mut() {
+                if let TraversalOperator::Index(index) = operator {
+                    check(index, context, policy)?;
+                }
+            }
+        }
+        Expression::ForExpr(for_expr) => {
+            check(&mut for_expr.collection_expr, context, policy)?;
+            let function = deferred_function(policy);
+            if let Some(key_expr) = &mut for_expr.key_expr {
+                defer(key_expr, function);
+            }
+            defer(&mut for_expr.value_expr, function);
+            if let Some(cond_expr) = &mut for_expr.cond_expr {
+                defer(cond_expr, function);
+            }
+        }
+        Expression::TemplateExpr(template_expr) => {
+            defer_template(template_expr, deferred_function(policy));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Result of `+`, `-`, `*` and negation if all operands are integers
+fn integer_operation(
+    operation: &mut Operation,
+    context: &Context,
+    policy: OverflowPolicy,
+) -> Result<Option<i64>, String> {
+    match operation {
+        Operation::Binary(binop) => {
+            use BinaryOperator::{Minus, Mul, Plus};
+            if !matches!(binop.operator, Plus | Minus | Mul) {
+                return Ok(None);
+            }
+            let Some(lhs) = integer(&mut binop.lhs_expr, context) else {
+                return Ok(None);
+            };
+            let Some(rhs) = integer(&mut binop.rhs_expr, context) else {
+                return Ok(None);
+            };
+            let result = compute(binop.operator, lhs, rhs);
+            apply(result, policy, || format!("{lhs} {} {rhs}", binop.operator)).map(Some)
+        }
+        Operation::Unary(unop) if unop.operator == UnaryOperator::Neg => {
+            let Some(value) = integer(&mut unop.expr, context) else {
+                return Ok(None);
+            };
+            apply(-value, policy, || format!("-({value})")).map(Some)
+        }
+        Operation::Unary(_) => Ok(None),
+    }
+}
+
+/// `result` if it fits an `i64`, otherwise clamped to it or the `operation` as error
+fn apply(
+    result: i128,
+    policy: OverflowPolicy,
+    operation: impl FnOnce() -> String,
+) -> Result<i64, String> {
+    match (i64::try_from(result), policy) {
+        (Ok(result), _) => Ok(result),
+        (Err(_), OverflowPolicy::Saturate) => {
+            Ok(result.clamp(i64::MIN.into(), i64::MAX.into()) as i64)
+        }
+        (Err(_), OverflowPolicy::Error) => Err(operation()),
+    }
+}
+
+/// `lhs operator rhs` for `+`, `-` and `*`
+fn compute(operator: BinaryOperator, lhs: i128, rhs: i128) -> i128 {
+    match operator {
+        BinaryOperator::Plus => lhs + rhs,
+        BinaryOperator::Minus => lhs - rhs,
+        // a product beyond `i128` has the sign of its operands, far outside of `i64`
+        _ => lhs.checked_mul(rhs).unwrap_or(if (lhs < 0) == (rhs < 0) {
+            i128::MAX
+        } else {
+            i128::MIN
+        }),
+    }
+}
+
+/// Value of an integer operand, also the ones above `i64::MAX`
+fn integer(expression: &mut Expression, context: &Context) -> Option<i128> {
+    expression.evaluate_in_place(context).ok()?;
+    match expression {
+        Expression::Number(number) => number_integer(number),
+        _ => None,
+    }
+}
+
+/// Integer of a number, also the ones above `i64::MAX`
+fn number_integer(number: &hcl::Number) -> Option<i128> {
+    number
+        .as_i64()
+        .map(i128::from)
+        .or_else(|| number.as_u64().map(i128::from))
+}
+
+/// Apply the policy to integers of an evaluated expression that do not fit an `i64`
+///
+/// `Err` is the first integer out of range.
+pub(crate) fn check_range(
+    expression: &mut Expression,
+    policy: OverflowPolicy,
+) -> Result<(), String> {
+    match expression {
+        Expression::Number(number) if !number.is_f64() && number.as_i64().is_none() => match policy
+        {
+            OverflowPolicy::Error => return Err(number.to_string()),
+            OverflowPolicy::Saturate => *number = i64::MAX.into(),
+        },
+        Expression::Array(array) => {
+            for expr in array {
+                check_range(expr, policy)?;
+            }
+        }
+        Expression::Object(object) => {
+            for value in object.values_mut() {
+                check_range(value, policy)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Name of the function computing deferred operations with `policy`
+fn deferred_function(policy: OverflowPolicy) -> &'static str {
+    match policy {
+        OverflowPolicy::Error => CHECKED_FUNCTION,
+        OverflowPolicy::Saturate => SATURATING_FUNCTION,
+    }
+}
+
+/// Compute an operation deferred to [CHECKED_FUNCTION] or [SATURATING_FUNCTION]
+///
+/// The arguments are the operator followed by the operands. Integer operands are computed like
+/// [check_overflow] does, all others are left to `hcl`.
+pub(crate) fn deferred(args: &FuncArgs, policy: OverflowPolicy) -> Result<Value, String> {
+    let operator = args[0].as_str().unwrap_or_default();
+    let operands = &args[1..];
+    let integers: Option<Vec<i128>> = operands
+        .iter()
+        .map(|operand| operand.as_number().and_then(number_integer))
+        .collect();
+
+    let result = match (operands, integers.as_deref()) {
+        (_, Some(&[value])) => apply(-value, policy, || format!("-({value})")),
+        ([operand], None) => {
+            let operation = UnaryOp::new(UnaryOperator::Neg, operand.clone());
+            return evaluate(Operation::Unary(operation));
+        }
+        ([lhs, rhs], integers) => {
+            let operator: BinaryOperator = operator.parse().map_err(|err| format!("{err}"))?;
+            let Some(&[lhs, rhs]) = integers else {
+                let operation = BinaryOp::new(lhs.clone(), operator, rhs.clone());
+                return evaluate(Operation::Binary(operation));
+            };
+            apply(compute(operator, lhs, rhs), policy, || {
+                format!("{lhs} {operator} {rhs}")
+            })
+        }
+        _ => return Err(format!("expected one or two operands of `{operator}`")),
+    };
+    result
+        .map(Value::from)
+        .map_err(|operation| format!("integer overflow in `{operation}`"))
+}
+
+/// Evaluate an operation of operands that are not all integers like `hcl` does
+fn evaluate(operation: Operation) -> Result<Value, String> {
+    Expression::from(operation)
+        .evaluate(&Context::new())
+        .map_err(|err| err.to_string())
+}
+
+/// Replace `+`, `-`, `*` and negation in `expression` with calls of `function`
+fn defer(expression: &mut Expression, function: &str) {
+    for_each_nested(expression, &mut |nested| defer(nested, function));
+    let call = match expression {
+        Expression::TemplateExpr(template_expr) => return defer_template(template_expr, function),
+        Expression::Operation(operation) => match operation.as_mut() {
+            Operation::Binary(binop) => {
+                use BinaryOperator::{Minus, Mul, Plus};
+                if !matches!(binop.operator, Plus | Minus | Mul) {
+                    return;
+                }
+                FuncCall::builder(function)
+                    .arg(binop.operator.as_str())
+                    .arg(std::mem::replace(&mut binop.lhs_expr, Expression::Null))
+                    .arg(std::mem::replace(&mut binop.rhs_expr, Expression::Null))
+                    .build()
+            }
+            Operation::Unary(unop) if unop.operator == UnaryOperator::Neg => {
+                FuncCall::builder(function)
+                    .arg(unop.operator.as_str())
+                    .arg(std::mem::replace(&mut unop.expr, Expression::Null))
+                    .build()
+            }
+            Operation::Unary(_) => return,
+        },
+        _ => return,
+    };
+    *expression = call.into();
+}
+
+/// [defer] the operations of the interpolations and directives of a template
+///
+/// Templates without operations are kept as written.
+fn defer_template(template_expr: &mut TemplateExpr, function: &str) {
+    let Ok(mut template) = Template::from_expr(template_expr) else {
+        return;
+    };
+    let written = template.clone();
+    defer_elements(&mut template, function);
+    if template != written {
+        *template_expr = TemplateExpr::QuotedString(template.to_string());
+    }
+}
+
+fn defer_elements(template: &mut Template, function: &str) {
+    // expressions of templates are only parsed here, they are not regrouped yet
+    let defer = |expression: &mut Expression| {
+        regroup(expression);
+        defer(expression, function);
+    };
+    for element in template.elements_mut() {
+        match element {
+            Element::Literal(_) => {}
+            Element::Interpolation(interpolation) => defer(&mut interpolation.expr),
+            Element::Directive(directive) => match directive {
+                Directive::If(if_directive) => {
+                    defer(&mut if_directive.cond_expr);
+                    defer_elements(&mut if_directive.true_template, function);
+                    if let Some(false_template) = &mut if_directive.false_template {
+                        defer_elements(false_template, function);
+                    }
+                }
+                Directive::For(for_directive) => {
+                    defer(&mut for_directive.collection_expr);
+                    defer_elements(&mut for_directive.template, function);
+                }
+            },
+        }
+    }
+}
+
+/// Regroup chained binary operations by operator precedence, see the module documentation
+fn regroup(expression: &mut Expression) {
+    let Expression::Operation(operation) = expression else {
+        return for_each_nested(expression, &mut regroup);
+    };
+    if !matches!(operation.as_ref(), Operation::Binary(_)) {
+        return for_each_nested(expression, &mut regroup);
+    }
+
+    let mut operands = Vec::new();
+    let mut operators = Vec::new();
+    flatten(
+        std::mem::replace(expression, Expression::Null),
+        &mut operands,
+        &mut operators,
+    );
+    for operand in &mut operands {
+        regroup(operand);
+    }
+    *expression = group(
+        &mut operands.into_iter(),
+        &mut operators.into_iter().peekable(),
+        0,
+    );
+}
+
+/// Operands and operators of chained binary operations in written order
+fn flatten(
+    expression: Expression,
+    operands: &mut Vec<Expression>,
+    operators: &mut Vec<BinaryOperator>,
+) {
+    if let Expression::Operation(operation) = expression {
+        match *operation {
+            Operation::Binary(binop) => {
+                flatten(binop.lhs_expr, operands, operators);
+                operators.push(binop.operator);
+                flatten(binop.rhs_expr, operands, operators);
+            }
+            unary => operands.push(unary.into()),
+        }
+    } else {
+        operands.push(expression);
+    }
+}
+
+/// Operations of operators with at least precedence `min`, left to right
+fn group(
+    operands: &mut impl Iterator<Item = Expression>,
+    operators: &mut std::iter::Peekable<impl Iterator<Item = BinaryOperator>>,
+    min: u8,
+) -> Expression {
+    let mut lhs = operands.next().expect("one operand more than operators");
+    while let Some(operator) = operators.next_if(|operator| operator.precedence() >= min) {
+        let rhs = group(operands, operators, operator.precedence() + 1);
+        lhs = Operation::Binary(BinaryOp::new(lhs, operator, rhs)).into();
+    }
+    lhs
+}
+
+/// Call `f` with each expression nested directly in `expression`, templates aside
+fn for_each_nested(expression: &mut Expression, f: &mut dyn FnMut(&mut Expression)) {
+    match expression {
+        Expression::Array(array) => {
+            for expr in array {
+                f(expr);
+            }
+        }
+        Expression::Object(object) => {
+            // keys can not be changed in place, the object is rebuilt in order
+            *object = std::mem::take(object)
+                .into_iter()
+                .map(|(mut key, mut value)| {
+                    if let ObjectKey::Expression(expr) = &mut key {
+                        f(expr);
+                    }
+                    f(&mut value);
+                    (key, value)
+                })
+                .collect();
+        }
+        Expression::Traversal(traversal) => {
+            f(&mut traversal.expr);
+            for operator in traversal.operators.iter_mut() {
+                if let TraversalOperator::Index(index) = operator {
+                    f(index);
+                }
+            }
+        }
+        Expression::FuncCall(func_call) => {
+            for arg in func_call.args.iter_mut() {
+                f(arg);
+            }
+        }
+        Expression::Parenthesis(expr) => f(expr),
+        Expression::Conditional(cond) => {
+            f(&mut cond.cond_expr);
+            f(&mut cond.true_expr);
+            f(&mut cond.false_expr);
+        }
+        Expression::Operation(operation) => match operation.as_mut() {
+            Operation::Binary(binop) => {
+                f(&mut binop.lhs_expr);
+                f(&mut binop.rhs_expr);
+            }
+            Operation::Unary(unop) => f(&mut unop.expr),
+        },
+        Expression::ForExpr(for_expr) => {
+            f(&mut for_expr.collection_expr);
+            if let Some(key_expr) = &mut for_expr.key_expr {
+                f(key_expr);
+            }
+            f(&mut for_expr.value_expr);
+            if let Some(cond_expr) = &mut for_expr.cond_expr {
+                f(cond_expr);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(expression: &str, policy: OverflowPolicy) -> Result<String, String> {
+        let expression: hcl_edit::expr::Expression = expression.parse().unwrap();
+        let mut expression: Expression = expression.into();
+        let mut context = Context::new();
+        context.declare_var("max", i64::MAX);
+        context.declare_var("min", i64::MIN);
+        crate::functions::declare(&mut context);
+        check_overflow(&mut expression, &context, policy)?;
+        crate::functions::resolve_fallbacks(
+            &mut expression,
+            &context,
+            &|_| false,
+            &Default::default(),
+        )?;
+        expression
+            .evaluate_in_place(&context)
+            .map_err(|err| err.to_string())?;
+        Ok(expression.to_string())
+    }
+
+    #[test]
+    fn overflow() {
+        let error = |expression| check(expression, OverflowPolicy::Error).unwrap_err();
+        assert_eq!(error("max + 1"), "9223372036854775807 + 1");
+        assert_eq!(
+            error("[1, { a = 2 * (max - 1) }]"),
+            "2 * 9223372036854775806"
+        );
+        assert_eq!(error("-min"), "-(-9223372036854775808)");
+        assert_eq!(
+            error("18446744073709551615 - 1"),
+            "18446744073709551615 - 1"
+        );
+        assert_eq!(error("[for x in [max + 1]: x]"), "9223372036854775807 + 1");
+
+        let saturate = |expression| check(expression, OverflowPolicy::Saturate).unwrap();
+        assert_eq!(saturate("max + 1"), "9223372036854775807");
+        assert_eq!(saturate("min * 2 + 0"), "-9223372036854775808");
+        assert_eq!(saturate("-min"), "9223372036854775807");
+        assert_eq!(saturate("true ? 1 : max + 1"), "1");
+    }
+
+    #[test]
+    fn unchanged() {
+        let error = |expression| check(expression, OverflowPolicy::Error).unwrap();
+        assert_eq!(error("(max - 1) + 1"), "9223372036854775807");
+        assert_eq!(error("false ? max + 1 : 1.5 * 2"), "3");
+        assert_eq!(error("7 / 2"), "3.5");
+        assert_eq!(error("\"a\" == \"b\""), "false");
+        // integer literals beyond `i64` are values and operands like any other
+        assert_eq!(error("18446744073709551615"), "18446744073709551615");
+        assert_eq!(error("18446744073709551615 * 0"), "0");
+    }
+
+    #[test]
+    fn fallbacks() {
+        let error = |expression| check(expression, OverflowPolicy::Error);
+        assert_eq!(error("can(max + 1)").as_deref(), Ok("false"));
+        assert_eq!(error("try(max + 1, 0)").as_deref(), Ok("0"));
+        assert!(error("try(max + 1)")
+            .unwrap_err()
+            .contains("integer overflow in `9223372036854775807 + 1`"));
+    }
+
+    #[test]
+    fn precedence() {
+        let error = |expression| check(expression, OverflowPolicy::Error).unwrap();
+        assert_eq!(error("2 * 3 + 1"), "7");
+        assert_eq!(error("10 - 3 - 2"), "5");
+        assert_eq!(error("1 == 1 && 2 < 8"), "true");
+        assert_eq!(error("-2 * (3 + 1) + 1"), "-7");
+        assert_eq!(error("max - 1 - max"), "-1");
+    }
+
+    #[test]
+    fn deferred() {
+        let error = |expression| check(expression, OverflowPolicy::Error);
+        assert_eq!(error("[for x in [1, 2]: x * 2 + 1][1]").as_deref(), Ok("5"));
+        assert_eq!(error("[for x in [1.5]: -x][0]").as_deref(), Ok("-1.5"));
+        assert!(error("[for x in [max]: x + 1]")
+            .unwrap_err()
+            .contains("integer overflow in `9223372036854775807 + 1`"));
+        assert_eq!(
+            error("can([for x in [max]: x + 1])").as_deref(),
+            Ok("false")
+        );
+        assert_eq!(error("\"${1 + 2 * 3}\"").as_deref(), Ok("7"));
+        assert!(error("\"%{ for x in [max] }${x + 1}%{ endfor }\"")
+            .unwrap_err()
+            .contains("integer overflow in `9223372036854775807 + 1`"));
+        assert!(error("[for x in [\"a\"]: x * 2]")
+            .unwrap_err()
+            .contains("not applicable"));
+
+        let saturate = |expression| check(expression, OverflowPolicy::Saturate).unwrap();
+        assert_eq!(
+            saturate("[for x in [max, min]: x * 2][1]"),
+            "-9223372036854775808"
+        );
+        assert_eq!(saturate("\"${-min}\""), "9223372036854775807");
+    }
+
+    #[test]
+    fn templates() {
+        // templates without operations are kept as written
+        let expression: hcl_edit::expr::Expression = "<<EOT\n${max}\nEOT".parse().unwrap();
+        let mut expression: Expression = expression.into();
+        let written = expression.clone();
+        check_overflow(&mut expression, &Context::new(), OverflowPolicy::Error).unwrap();
+        assert_eq!(expression, written);
+    }
+
+    #[test]
+    fn range() {
+        let range = |expression: &str, policy| {
+            let mut expression: Expression = expression
+                .parse::<hcl_edit::expr::Expression>()
+                .unwrap()
+                .into();
+            expression.evaluate_in_place(&Context::new()).unwrap();
+            check_range(&mut expression, policy).map(|_| {
+                expression
+                    .to_string()
+                    .split_whitespace()
+                    .collect::<String>()
+            })
+        };
+        assert_eq!(
+            range("[1, { a = 18446744073709551615 }]", OverflowPolicy::Error),
+            Err("18446744073709551615".to_string())
+        );
+        assert_eq!(
+            range(
+                "[1, { a = 18446744073709551615 }]",
+                OverflowPolicy::Saturate
+            )
+            .as_deref(),
+            Ok("[1,{\"a\"=9223372036854775807}]")
+        );
+        assert_eq!(range("1.5", OverflowPolicy::Error).as_deref(), Ok("1.5"));
+    }
+}
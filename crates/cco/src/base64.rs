@@ -0,0 +1,65 @@
+//! standard base64 (RFC 4648) with padding
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Error decoding text that is not base64
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("invalid base64")]
+pub struct DecodeError;
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let buffer = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, buffer[0], buffer[1], buffer[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Padding is optional, whitespace (e.g. line breaks of PEM bodies) is skipped
+pub fn decode(encoded: &str) -> Result<Vec<u8>, DecodeError> {
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in encoded
+        .bytes()
+        .filter(|c| *c != b'=' && !c.is_ascii_whitespace())
+    {
+        let value = ALPHABET.iter().position(|a| *a == c).ok_or(DecodeError)?;
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            decoded.push((bits >> count) as u8);
+        }
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for input in ["", "f", "fo", "foo", "foob", "fooba", "foobar"] {
+            let encoded = encode(input.as_bytes());
+            assert_eq!(decode(&encoded).unwrap(), input.as_bytes());
+        }
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(&[0xff, 0x00, 0xfe]), "/wD+");
+        assert_eq!(decode("Zm9v\nYmFy\n").unwrap(), b"foobar");
+        assert_eq!(decode("Zm8").unwrap(), b"fo");
+        assert_eq!(decode("Zm9v!"), Err(DecodeError));
+    }
+}
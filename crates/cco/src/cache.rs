@@ -0,0 +1,779 @@
+//! on-disk cache for parsed documents and evaluation results
+//!
+//! Repeated invocations (e.g. in CI) usually evaluate the same expressions against unchanged files.
+//! [Cache] stores the parsed document of each source, keyed by [CacheKey::document], a content hash
+//! of the source alone: a changed file is parsed again, all others are read from the cache. Cached
+//! documents keep their expressions and the comments above blocks and attributes (doc comments),
+//! not the source positions.
+//!
+//! Evaluated [Value]s are stored as well, keyed by a [CacheKey] which is a content hash of
+//! everything that can affect the result:
+//! - the `cco` version
+//! - path and contents of each source (in load order)
+//! - the expression
+//! - every option the evaluation depends on, see [CacheKeyBuilder::option]
+//!
+//! Files that are only known once evaluated (e.g. Terraform states read by a block) are stored
+//! with the entry as [Inputs], the entry is only used while their contents are unchanged. Warnings
+//! printed while evaluating are stored with it too, a hit prints them again.
+//!
+//! A cache hit skips parsing and evaluation entirely. Entries are never invalidated, a change in any
+//! input produces a different key. Clearing the cache is as simple as removing the directory.
+use crate::value::Value;
+use hcl::expr::{
+    BinaryOp, BinaryOperator, Conditional, ForExpr, FuncCall, FuncName, Heredoc, ObjectKey,
+    Operation, TemplateExpr, Traversal, TraversalOperator, UnaryOp, UnaryOperator, Variable,
+};
+use hcl::{Expression, Identifier};
+use hcl_edit::structure::{Attribute, Block, BlockLabel, Body};
+use hcl_edit::{Decorate, Decorated, Ident};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Entry layout, part of the key
+const FORMAT: &str = "tagged-value+inputs+warnings";
+
+/// Layout of parsed documents, part of their key
+const DOCUMENT_FORMAT: &str = "structures";
+
+/// Files read while evaluating, with the SHA-256 (hex) of their contents
+pub type Inputs = Vec<(PathBuf, String)>;
+
+/// SHA-256 (hex) of `contents`, as recorded in [Inputs]
+pub fn content_hash(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    inputs: Inputs,
+    value: Stored,
+    warnings: Vec<serde_json::Value>,
+}
+
+/// [Value] as stored in an [Entry]
+///
+/// The JSON serialization of [Value] writes bytes as base64 string and would read them back as
+/// string, hence every value is tagged with its type.
+#[derive(serde::Serialize, serde::Deserialize)]
+enum Stored {
+    Boolean(bool),
+    Integer(i64),
+    Decimal(f64),
+    String(String),
+    /// base64
+    Bytes(String),
+    Array(Vec<Stored>),
+    Object(Vec<(String, Stored)>),
+}
+
+impl From<&Value> for Stored {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Boolean(value) => Stored::Boolean(*value),
+            Value::Integer(value) => Stored::Integer(*value),
+            Value::Decimal(value) => Stored::Decimal(*value),
+            Value::String(value) => Stored::String(value.clone()),
+            Value::Bytes(value) => Stored::Bytes(crate::base64::encode(value)),
+            Value::Array(values) => Stored::Array(values.iter().map(Stored::from).collect()),
+            Value::Object(values) => Stored::Object(
+                values
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl TryFrom<Stored> for Value {
+    type Error = crate::base64::DecodeError;
+
+    fn try_from(value: Stored) -> Result<Self, Self::Error> {
+        Ok(match value {
+            Stored::Boolean(value) => Value::Boolean(value),
+            Stored::Integer(value) => Value::Integer(value),
+            Stored::Decimal(value) => Value::Decimal(value),
+            Stored::String(value) => Value::String(value),
+            Stored::Bytes(value) => Value::Bytes(crate::base64::decode(&value)?),
+            Stored::Array(values) => Value::Array(
+                values
+                    .into_iter()
+                    .map(Value::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Stored::Object(values) => Value::Object(
+                values
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, value.try_into()?)))
+                    .collect::<Result<_, Self::Error>>()?,
+            ),
+        })
+    }
+}
+
+/// Content hash identifying a cache entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    pub fn builder() -> CacheKeyBuilder {
+        let mut hasher = Sha256::new();
+        hash_field(&mut hasher, env!("CARGO_PKG_VERSION").as_bytes());
+        hash_field(&mut hasher, FORMAT.as_bytes());
+        CacheKeyBuilder { hasher }
+    }
+
+    /// Key of the parsed document of `contents`, the same for every path
+    pub fn document(contents: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hash_field(&mut hasher, env!("CARGO_PKG_VERSION").as_bytes());
+        hash_field(&mut hasher, DOCUMENT_FORMAT.as_bytes());
+        hash_field(&mut hasher, contents.as_bytes());
+        CacheKey(format!("{:x}", hasher.finalize()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+pub struct CacheKeyBuilder {
+    hasher: Sha256,
+}
+
+impl CacheKeyBuilder {
+    /// Add a source document
+    pub fn source(mut self, path: Option<&Path>, contents: &str) -> Self {
+        let path = path.map(|path| path.to_string_lossy()).unwrap_or_default();
+        hash_field(&mut self.hasher, path.as_bytes());
+        hash_field(&mut self.hasher, contents.as_bytes());
+        self
+    }
+
+    /// Add the expression that is evaluated
+    pub fn expression(mut self, expression: &str) -> Self {
+        hash_field(&mut self.hasher, expression.as_bytes());
+        self
+    }
+
+    /// Add an option that can change the result, e.g. `("overflow", "saturate")`
+    pub fn option(mut self, name: &str, value: &str) -> Self {
+        hash_field(&mut self.hasher, name.as_bytes());
+        hash_field(&mut self.hasher, value.as_bytes());
+        self
+    }
+
+    pub fn build(self) -> CacheKey {
+        CacheKey(format!("{:x}", self.hasher.finalize()))
+    }
+}
+
+/// Length prefix each field so that field boundaries can not shift
+fn hash_field(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+/// Directory of cached documents and evaluation results
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn entry_path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+
+    fn document_path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join("documents").join(format!("{key}.json"))
+    }
+
+    /// Look up a cached value and the warnings stored with it
+    ///
+    /// Unreadable or corrupt entries are treated as a cache miss, as are entries whose [Inputs]
+    /// changed.
+    pub fn get<W: serde::de::DeserializeOwned>(&self, key: &CacheKey) -> Option<(Value, Vec<W>)> {
+        let path = self.entry_path(key);
+        let contents = std::fs::read_to_string(&path).ok()?;
+
+        let entry = match serde_json::from_str::<Entry>(&contents) {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::warn!(file = %path.display(), %err, "ignoring corrupt cache entry");
+                return None;
+            }
+        };
+        for (input, hash) in &entry.inputs {
+            let current = std::fs::read(input)
+                .ok()
+                .map(|contents| content_hash(&contents));
+            if current.as_ref() != Some(hash) {
+                tracing::debug!(%key, input = %input.display(), "cache entry outdated");
+                return None;
+            }
+        }
+        let warnings = entry
+            .warnings
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        tracing::debug!(%key, "cache hit");
+        Some((Value::try_from(entry.value).ok()?, warnings))
+    }
+
+    /// Store a value that was evaluated from the sources of `key` and `inputs`, with the warnings
+    /// printed while evaluating it
+    pub fn put<W: serde::Serialize>(
+        &self,
+        key: &CacheKey,
+        value: &Value,
+        inputs: Inputs,
+        warnings: &[W],
+    ) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        let entry = Entry {
+            inputs,
+            value: value.into(),
+            warnings: warnings
+                .iter()
+                .map(serde_json::to_value)
+                .collect::<Result<_, _>>()?,
+        };
+        write(&self.entry_path(key), &serde_json::to_vec(&entry)?)?;
+
+        tracing::debug!(%key, "cache entry written");
+        Ok(())
+    }
+
+    /// Look up a parsed document, see [CacheKey::document]
+    ///
+    /// Unreadable or corrupt documents are treated as a cache miss.
+    pub fn get_document(&self, key: &CacheKey) -> Option<Body> {
+        let path = self.document_path(key);
+        let contents = std::fs::read(&path).ok()?;
+        match serde_json::from_slice::<Vec<Structure>>(&contents) {
+            Ok(structures) => {
+                tracing::debug!(%key, "cached document");
+                Some(structures.into_iter().map(Structure::into_edit).collect())
+            }
+            Err(err) => {
+                tracing::warn!(file = %path.display(), %err, "ignoring corrupt cached document");
+                None
+            }
+        }
+    }
+
+    /// Store the parsed document of the source of `key`
+    ///
+    /// Documents with expressions the cache can not represent are not stored.
+    pub fn put_document(&self, key: &CacheKey, body: &Body) -> std::io::Result<()> {
+        let Some(structures) = body
+            .iter()
+            .map(Structure::from_edit)
+            .collect::<Option<Vec<_>>>()
+        else {
+            tracing::debug!(%key, "document not cacheable");
+            return Ok(());
+        };
+        let path = self.document_path(key);
+        std::fs::create_dir_all(path.parent().expect("documents directory"))?;
+        write(&path, &serde_json::to_vec(&structures)?)?;
+
+        tracing::debug!(%key, "document written to the cache");
+        Ok(())
+    }
+}
+
+/// Write to a temporary file first so concurrent readers never see partial entries
+fn write(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    std::fs::write(&tmp_path, contents)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Block or attribute of a cached document
+///
+/// `hcl_edit` structures can not be serialized, and [hcl::Body] does not read back what it writes
+/// (expressions are written as `${...}` strings). Documents are stored as this mirror instead.
+#[derive(serde::Serialize, serde::Deserialize)]
+enum Structure {
+    Attribute {
+        /// Comments and whitespace before the attribute
+        prefix: Option<String>,
+        key: Identifier,
+        expr: Expr,
+    },
+    Block {
+        prefix: Option<String>,
+        ident: Identifier,
+        labels: Vec<Label>,
+        body: Vec<Structure>,
+    },
+}
+
+impl Structure {
+    fn from_edit(structure: &hcl_edit::structure::Structure) -> Option<Self> {
+        let prefix = structure.decor().prefix().map(|prefix| prefix.to_string());
+        Some(match structure {
+            hcl_edit::structure::Structure::Attribute(attribute) => Structure::Attribute {
+                prefix,
+                key: attribute.key.as_str().into(),
+                expr: Expr::from_hcl(attribute.value.clone().into())?,
+            },
+            hcl_edit::structure::Structure::Block(block) => Structure::Block {
+                prefix,
+                ident: block.ident.as_str().into(),
+                labels: block
+                    .labels
+                    .iter()
+                    .map(|label| match label {
+                        BlockLabel::Ident(ident) => Label::Ident(ident.as_str().into()),
+                        BlockLabel::String(string) => Label::String(string.to_string()),
+                    })
+                    .collect(),
+                body: block
+                    .body
+                    .iter()
+                    .map(Structure::from_edit)
+                    .collect::<Option<_>>()?,
+            },
+        })
+    }
+
+    fn into_edit(self) -> hcl_edit::structure::Structure {
+        let (prefix, mut structure): (_, hcl_edit::structure::Structure) = match self {
+            Structure::Attribute { prefix, key, expr } => {
+                let value = hcl_edit::expr::Expression::from(expr.into_hcl());
+                (prefix, Attribute::new(Ident::from(key), value).into())
+            }
+            Structure::Block {
+                prefix,
+                ident,
+                labels,
+                body,
+            } => {
+                let mut block = Block::new(Ident::from(ident));
+                block.labels = labels
+                    .into_iter()
+                    .map(|label| match label {
+                        Label::Ident(ident) => BlockLabel::Ident(Decorated::new(ident.into())),
+                        Label::String(string) => BlockLabel::String(Decorated::new(string)),
+                    })
+                    .collect();
+                block.body = body.into_iter().map(Structure::into_edit).collect();
+                (prefix, block.into())
+            }
+        };
+        if let Some(prefix) = prefix {
+            structure.decor_mut().set_prefix(prefix);
+        }
+        structure
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+enum Label {
+    Ident(Identifier),
+    String(String),
+}
+
+/// [Expression] of a cached document
+#[derive(serde::Serialize, serde::Deserialize)]
+enum Expr {
+    Null,
+    Bool(bool),
+    Number(hcl::Number),
+    String(String),
+    Array(Vec<Expr>),
+    /// Keys are expressions as well, JSON objects only have string keys
+    Object(Vec<(Key, Expr)>),
+    Template(String),
+    /// Delimiter and template, `<<-` heredocs are dedented by the parser already
+    Heredoc(Identifier, String),
+    Variable(Identifier),
+    Traversal(Box<Expr>, Vec<Operator>),
+    FuncCall {
+        namespace: Vec<Identifier>,
+        name: Identifier,
+        args: Vec<Expr>,
+        expand_final: bool,
+    },
+    Parenthesis(Box<Expr>),
+    Conditional(Box<[Expr; 3]>),
+    Unary(UnaryOperator, Box<Expr>),
+    Binary(Box<[Expr; 2]>, BinaryOperator),
+    For {
+        key_var: Option<Identifier>,
+        value_var: Identifier,
+        collection: Box<Expr>,
+        key: Option<Box<Expr>>,
+        value: Box<Expr>,
+        grouping: bool,
+        cond: Option<Box<Expr>>,
+    },
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+enum Key {
+    Identifier(Identifier),
+    Expression(Expr),
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+enum Operator {
+    AttrSplat,
+    FullSplat,
+    GetAttr(Identifier),
+    Index(Expr),
+    LegacyIndex(u64),
+}
+
+impl Expr {
+    /// `None` for expressions added to hcl after this mirror
+    fn from_hcl(expression: Expression) -> Option<Self> {
+        let boxed = |expression| Expr::from_hcl(expression).map(Box::new);
+        Some(match expression {
+            Expression::Null => Expr::Null,
+            Expression::Bool(bool) => Expr::Bool(bool),
+            Expression::Number(number) => Expr::Number(number),
+            Expression::String(string) => Expr::String(string),
+            Expression::Array(array) => Expr::Array(
+                array
+                    .into_iter()
+                    .map(Expr::from_hcl)
+                    .collect::<Option<_>>()?,
+            ),
+            Expression::Object(object) => Expr::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let key = match key {
+                            ObjectKey::Identifier(identifier) => Key::Identifier(identifier),
+                            ObjectKey::Expression(expression) => {
+                                Key::Expression(Expr::from_hcl(expression)?)
+                            }
+                            _ => return None,
+                        };
+                        Some((key, Expr::from_hcl(value)?))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Expression::TemplateExpr(template) => match *template {
+                TemplateExpr::QuotedString(template) => Expr::Template(template),
+                TemplateExpr::Heredoc(heredoc) => {
+                    Expr::Heredoc(heredoc.delimiter, heredoc.template)
+                }
+            },
+            Expression::Variable(variable) => Expr::Variable(variable.into_inner()),
+            Expression::Traversal(traversal) => {
+                let Traversal { expr, operators } = *traversal;
+                let operators = operators
+                    .into_iter()
+                    .map(|operator| {
+                        Some(match operator {
+                            TraversalOperator::AttrSplat => Operator::AttrSplat,
+                            TraversalOperator::FullSplat => Operator::FullSplat,
+                            TraversalOperator::GetAttr(identifier) => Operator::GetAttr(identifier),
+                            TraversalOperator::Index(index) => {
+                                Operator::Index(Expr::from_hcl(index)?)
+                            }
+                            TraversalOperator::LegacyIndex(index) => Operator::LegacyIndex(index),
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+                Expr::Traversal(boxed(expr)?, operators)
+            }
+            Expression::FuncCall(func_call) => {
+                let FuncCall {
+                    name,
+                    args,
+                    expand_final,
+                } = *func_call;
+                Expr::FuncCall {
+                    namespace: name.namespace,
+                    name: name.name,
+                    args: args
+                        .into_iter()
+                        .map(Expr::from_hcl)
+                        .collect::<Option<_>>()?,
+                    expand_final,
+                }
+            }
+            Expression::Parenthesis(expression) => Expr::Parenthesis(boxed(*expression)?),
+            Expression::Conditional(conditional) => {
+                let Conditional {
+                    cond_expr,
+                    true_expr,
+                    false_expr,
+                } = *conditional;
+                Expr::Conditional(Box::new([
+                    Expr::from_hcl(cond_expr)?,
+                    Expr::from_hcl(true_expr)?,
+                    Expr::from_hcl(false_expr)?,
+                ]))
+            }
+            Expression::Operation(operation) => match *operation {
+                Operation::Unary(UnaryOp { operator, expr }) => Expr::Unary(operator, boxed(expr)?),
+                Operation::Binary(BinaryOp {
+                    lhs_expr,
+                    operator,
+                    rhs_expr,
+                }) => Expr::Binary(
+                    Box::new([Expr::from_hcl(lhs_expr)?, Expr::from_hcl(rhs_expr)?]),
+                    operator,
+                ),
+            },
+            Expression::ForExpr(for_expr) => {
+                let ForExpr {
+                    key_var,
+                    value_var,
+                    collection_expr,
+                    key_expr,
+                    value_expr,
+                    grouping,
+                    cond_expr,
+                } = *for_expr;
+                Expr::For {
+                    key_var,
+                    value_var,
+                    collection: boxed(collection_expr)?,
+                    key: match key_expr {
+                        Some(key) => Some(boxed(key)?),
+                        None => None,
+                    },
+                    value: boxed(value_expr)?,
+                    grouping,
+                    cond: match cond_expr {
+                        Some(cond) => Some(boxed(cond)?),
+                        None => None,
+                    },
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    fn into_hcl(self) -> Expression {
+        match self {
+            Expr::Null => Expression::Null,
+            Expr::Bool(bool) => Expression::Bool(bool),
+            Expr::Number(number) => Expression::Number(number),
+            Expr::String(string) => Expression::String(string),
+            Expr::Array(array) => {
+                Expression::Array(array.into_iter().map(Expr::into_hcl).collect())
+            }
+            Expr::Object(object) => Expression::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let key = match key {
+                            Key::Identifier(identifier) => ObjectKey::Identifier(identifier),
+                            Key::Expression(expression) => {
+                                ObjectKey::Expression(expression.into_hcl())
+                            }
+                        };
+                        (key, value.into_hcl())
+                    })
+                    .collect(),
+            ),
+            Expr::Template(template) => TemplateExpr::QuotedString(template).into(),
+            Expr::Heredoc(delimiter, template) => {
+                TemplateExpr::Heredoc(Heredoc::new(delimiter, template)).into()
+            }
+            Expr::Variable(identifier) => Expression::Variable(Variable::from(identifier)),
+            Expr::Traversal(expr, operators) => Traversal::new(
+                expr.into_hcl(),
+                operators.into_iter().map(|operator| match operator {
+                    Operator::AttrSplat => TraversalOperator::AttrSplat,
+                    Operator::FullSplat => TraversalOperator::FullSplat,
+                    Operator::GetAttr(identifier) => TraversalOperator::GetAttr(identifier),
+                    Operator::Index(index) => TraversalOperator::Index(index.into_hcl()),
+                    Operator::LegacyIndex(index) => TraversalOperator::LegacyIndex(index),
+                }),
+            )
+            .into(),
+            Expr::FuncCall {
+                namespace,
+                name,
+                args,
+                expand_final,
+            } => FuncCall {
+                name: FuncName { namespace, name },
+                args: args.into_iter().map(Expr::into_hcl).collect(),
+                expand_final,
+            }
+            .into(),
+            Expr::Parenthesis(expr) => Expression::Parenthesis(Box::new(expr.into_hcl())),
+            Expr::Conditional(exprs) => {
+                let [cond, true_expr, false_expr] = *exprs;
+                Conditional::new(cond.into_hcl(), true_expr.into_hcl(), false_expr.into_hcl())
+                    .into()
+            }
+            Expr::Unary(operator, expr) => {
+                Operation::Unary(UnaryOp::new(operator, expr.into_hcl())).into()
+            }
+            Expr::Binary(exprs, operator) => {
+                let [lhs, rhs] = *exprs;
+                Operation::Binary(BinaryOp::new(lhs.into_hcl(), operator, rhs.into_hcl())).into()
+            }
+            Expr::For {
+                key_var,
+                value_var,
+                collection,
+                key,
+                value,
+                grouping,
+                cond,
+            } => ForExpr {
+                key_var,
+                value_var,
+                collection_expr: collection.into_hcl(),
+                key_expr: key.map(|key| key.into_hcl()),
+                value_expr: value.into_hcl(),
+                grouping,
+                cond_expr: cond.map(|cond| cond.into_hcl()),
+            }
+            .into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_depends_on_field_boundaries() {
+        let one = CacheKey::builder()
+            .source(None, "ab")
+            .expression("c")
+            .build();
+        let two = CacheKey::builder()
+            .source(None, "a")
+            .expression("bc")
+            .build();
+        assert_ne!(one, two);
+    }
+
+    #[test]
+    fn key_is_stable() {
+        let one = CacheKey::builder()
+            .source(None, "data a {}")
+            .expression("a")
+            .build();
+        let two = CacheKey::builder()
+            .source(None, "data a {}")
+            .expression("a")
+            .build();
+        assert_eq!(one, two);
+
+        let saturate = CacheKey::builder()
+            .source(None, "data a {}")
+            .expression("a")
+            .option("overflow", "saturate")
+            .build();
+        assert_ne!(one, saturate);
+    }
+
+    #[test]
+    fn documents() {
+        let dir = std::env::temp_dir().join(format!("cco-cache-documents-{}", std::process::id()));
+        let cache = Cache::new(&dir);
+        let contents = r#"
+# the api
+data service "api" {
+  port = 80 + 1
+  url = "http://${host}"
+  tags = { for k, v in var.tags : upper(k) => v... if v != null }
+  first = servers[*].name[0]
+  text = <<EOT
+${a ? -b : c}
+EOT
+}
+"#;
+        let key = CacheKey::document(contents);
+        assert_eq!(key, CacheKey::document(contents));
+        assert_ne!(key, CacheKey::document("data a {}"));
+        assert!(cache.get_document(&key).is_none());
+
+        let body = hcl_edit::parser::parse_body(contents).unwrap();
+        cache.put_document(&key, &body).unwrap();
+        let cached = cache.get_document(&key).unwrap();
+        assert_eq!(hcl::Body::from(cached.clone()), hcl::Body::from(body));
+        let block = cached.blocks().next().unwrap();
+        assert_eq!(
+            crate::util::doc_comment(block.decor()).as_deref(),
+            Some("the api")
+        );
+
+        std::fs::write(cache.document_path(&key), "{").unwrap();
+        assert!(cache.get_document(&key).is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn tagged_values() {
+        let dir = std::env::temp_dir().join(format!("cco-cache-values-{}", std::process::id()));
+        let cache = Cache::new(&dir);
+        let key = CacheKey::builder().expression("a").build();
+        let value = Value::Object(indexmap::indexmap! {
+            "cert".to_string() => Value::Bytes(vec![0, 1, 255]),
+            "text".to_string() => Value::String("AAH/".to_string()),
+            "ports".to_string() => Value::Array(vec![Value::Integer(1), Value::Decimal(1.5)]),
+        });
+        cache.put::<String>(&key, &value, vec![], &[]).unwrap();
+
+        let (cached, _) = cache.get::<String>(&key).unwrap();
+        let Value::Object(cached) = cached else {
+            panic!("expected an object");
+        };
+        assert!(matches!(&cached["cert"], Value::Bytes(bytes) if bytes == &[0, 1, 255]));
+        assert!(matches!(&cached["text"], Value::String(text) if text == "AAH/"));
+        assert_eq!(
+            serde_json::to_value(&cached["ports"]).unwrap(),
+            serde_json::json!([1, 1.5])
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn changed_inputs() {
+        let dir = std::env::temp_dir().join(format!("cco-cache-{}", std::process::id()));
+        let cache = Cache::new(dir.join("cache"));
+        let key = CacheKey::builder().expression("a").build();
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = dir.join("network.tfstate");
+        std::fs::write(&state, "1").unwrap();
+
+        let inputs = vec![(state.clone(), content_hash(b"1"))];
+        let warnings = ["a is deprecated".to_string()];
+        cache
+            .put(&key, &Value::Integer(1), inputs, &warnings)
+            .unwrap();
+        let cached = cache
+            .get::<String>(&key)
+            .map(|(value, warnings)| (serde_json::to_value(value).unwrap(), warnings));
+        assert_eq!(cached, Some((serde_json::json!(1), warnings.to_vec())));
+
+        std::fs::write(&state, "2").unwrap();
+        assert!(cache.get::<String>(&key).is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
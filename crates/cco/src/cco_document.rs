@@ -1,8 +1,72 @@
 //! Collection of known [Addressable]s
-use crate::hcl_documents::HclDocuments;
-use crate::util::{AttributeReferenceRewriter, SelfRewriter};
+use crate::hcl_documents::{HclDocuments, Source};
+use crate::observer::EvalObserver;
+use crate::util::{
+    self, AttributeReferenceRewriter, NamespaceRewriter, ResolverRewriter, SelfRewriter,
+    TraversalExt,
+};
 use crate::visit::VisitTraversalsMut;
 use hcl::eval::{ErrorKind, Evaluate};
+use hcl_edit::Decorate;
+use hcl_edit::Span;
+
+/// Root block types known to `cco`
+pub const BLOCK_KINDS: &[&str] = &["data", "type", "alias", "partial"];
+
+/// Prefix of substitution identifiers, see [Addressable::subst]
+///
+/// Root identifiers with this prefix are rejected with [Issue::ReservedIdentifier].
+pub const SUBST_PREFIX: &str = "cco__";
+
+/// Data block attribute listing the `partial` blocks whose attributes the block includes
+///
+/// `include_partials = ["common_limits"]`. Attributes of the block take precedence, later partials
+/// override earlier ones.
+pub const INCLUDE_PARTIALS_ATTRIBUTE: &str = "include_partials";
+
+/// Root identifier of root attributes, see [CcoDocumentBuilder::globals]
+pub const GLOBAL_NAMESPACE: &str = "global";
+
+/// Data block attribute marking the block or some of its attributes as deprecated
+///
+/// - `cco_deprecated = "message"` deprecates the whole block
+/// - `cco_deprecated = { attribute = "message" }` deprecates single attributes
+pub const DEPRECATED_ATTRIBUTE: &str = "cco_deprecated";
+
+/// Data block attribute marking the block or some of its attributes as sensitive
+///
+/// - `cco_sensitive = true` marks every attribute of the block
+/// - `cco_sensitive = ["password"]` marks single attributes
+///
+/// `Debug` output shows `<sensitive>` instead of their expressions, see [reveal_sensitive].
+pub const SENSITIVE_ATTRIBUTE: &str = "cco_sensitive";
+
+thread_local! {
+    /// Set while [reveal_sensitive] runs on this thread
+    static REVEAL_SENSITIVE: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Run `f` with `Debug` output of [Addressable]s including sensitive expressions
+///
+/// ```
+/// # use cco::{cco_document::{reveal_sensitive, CcoDocument}, hcl_documents};
+/// let documents = hcl_documents!("data db {\n cco_sensitive = [\"password\"]\n password = \"hunter2\"\n}");
+/// let document = CcoDocument::new(&documents).unwrap();
+///
+/// assert!(!format!("{document:?}").contains("hunter2"));
+/// assert!(reveal_sensitive(|| format!("{document:?}")).contains("hunter2"));
+/// ```
+pub fn reveal_sensitive<R>(f: impl FnOnce() -> R) -> R {
+    let previous = REVEAL_SENSITIVE.with(|reveal| reveal.replace(true));
+    let result = f();
+    REVEAL_SENSITIVE.with(|reveal| reveal.set(previous));
+    result
+}
+
+/// Whether [reveal_sensitive] runs on this thread
+pub(crate) fn sensitive_revealed() -> bool {
+    REVEAL_SENSITIVE.with(std::cell::Cell::get)
+}
 
 /// Multiple HCL Documents containing valid CCO blocks
 #[derive(Debug)]
@@ -14,44 +78,533 @@ pub struct CcoDocument {
     ///
     /// indices point to self.addressables
     tree: Tree,
+
+    /// Index into self.addressables by substitution identifier
+    substs: std::collections::HashMap<hcl::Identifier, usize>,
+
+    options: Options,
+
+    /// Issues with [Severity::Warning] found while building
+    warnings: CcoParseErrors,
+}
+
+/// Default for [CcoDocumentBuilder::max_depth]
+pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
+/// Settings for building and evaluating a [CcoDocument], see [CcoDocumentBuilder]
+#[derive(Debug, Clone)]
+struct Options {
+    strict: bool,
+    block_kinds: Vec<String>,
+    functions: indexmap::IndexMap<hcl::Identifier, hcl::eval::FuncDef>,
+    variables: indexmap::IndexMap<hcl::Identifier, hcl::Value>,
+    resolver: Option<Resolver>,
+    externals: crate::external::Externals,
+    /// block types handled by [CcoDocumentBuilder::data_source]
+    data_sources: Vec<String>,
+    observer: Option<Observer>,
+    max_depth: Option<usize>,
+    max_steps: Option<usize>,
+    timeout: Option<std::time::Duration>,
+    overflow: OverflowPolicy,
+    merge: MergePolicy,
+    secrets: Secrets,
+    label_case_collisions: bool,
+    deny_warnings: bool,
+    globals: bool,
+    file_functions: bool,
+}
+
+/// Signature of [CcoDocumentBuilder::resolver] callbacks
+pub type ResolverFn = dyn Fn(&[hcl::Identifier]) -> Option<hcl::Expression> + Send + Sync;
+
+/// Signature of [CcoDocumentBuilder::external_function]s, called with the evaluated arguments
+pub type ExternalFn = dyn Fn(&[hcl::Value]) -> Result<hcl::Value, String> + Send + Sync;
+
+/// Signature of [CcoDocumentBuilder::data_source] handlers, called with the block labels and the
+/// evaluated block body
+pub type DataSourceFn = dyn Fn(&[String], hcl::Value) -> Result<hcl::Value, String> + Send + Sync;
+
+/// Signature of [CcoDocumentBuilder::secret_provider]s, called with the key of a secret
+pub type SecretFn = dyn Fn(&str) -> Result<String, String> + Send + Sync;
+
+/// Function looking secrets up, see [CcoDocumentBuilder::secret_provider]
+pub const SECRET_FUNCTION: &str = "secret";
+
+/// [CcoDocumentBuilder::secret_provider]s by name
+#[derive(Clone, Default)]
+struct Secrets(indexmap::IndexMap<String, std::sync::Arc<SecretFn>>);
+
+impl std::fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.0.keys()).finish()
+    }
+}
+
+impl Secrets {
+    /// `secret("<provider>", "<key>")`
+    fn lookup(&self, args: &[hcl::Value]) -> Result<hcl::Value, String> {
+        let [hcl::Value::String(provider), hcl::Value::String(key)] = args else {
+            return Err("expected a provider name and a key".to_string());
+        };
+        let Some(lookup) = self.0.get(provider) else {
+            let known: Vec<&str> = self.0.keys().map(String::as_str).collect();
+            return Err(format!(
+                "unknown secret provider '{provider}', expected one of {}",
+                known.join(", ")
+            ));
+        };
+        lookup(key)
+            .map(hcl::Value::String)
+            .map_err(|err| format!("{provider} {key}: {err}"))
+    }
+}
+
+#[derive(Clone)]
+struct Resolver(std::sync::Arc<ResolverFn>);
+
+impl std::fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Resolver")
+    }
+}
+
+#[derive(Clone)]
+struct Observer(std::sync::Arc<dyn EvalObserver>);
+
+impl std::fmt::Debug for Observer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            strict: true,
+            block_kinds: BLOCK_KINDS.iter().map(|kind| kind.to_string()).collect(),
+            functions: Default::default(),
+            variables: Default::default(),
+            resolver: None,
+            externals: Default::default(),
+            data_sources: vec![],
+            observer: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_steps: None,
+            timeout: None,
+            overflow: OverflowPolicy::default(),
+            merge: MergePolicy::default(),
+            secrets: Secrets::default(),
+            label_case_collisions: false,
+            deny_warnings: false,
+            globals: false,
+            file_functions: true,
+        }
+    }
+}
+
+impl Options {
+    /// Evaluation context with all functions and variables declared
+    fn context(&self) -> hcl::eval::Context<'static> {
+        let mut context = hcl::eval::Context::new();
+        crate::functions::declare(&mut context);
+        #[cfg(feature = "fs")]
+        if !self.file_functions {
+            crate::functions::disable_files(&mut context);
+        }
+        for (name, func) in &self.functions {
+            context.declare_func(name.clone(), func.clone());
+        }
+        for (name, value) in &self.variables {
+            context.declare_var(name.clone(), value.clone());
+        }
+        self.externals.declare(&mut context);
+        context
+    }
+}
+
+/// Configures how a [CcoDocument] is built and evaluated
+///
+/// ```
+/// # use cco::{cco_document::CcoDocument, hcl_documents};
+/// let documents = hcl_documents!("data app {\n env = environment\n}");
+/// let document = CcoDocument::builder()
+///     .variable("environment", "prod")
+///     .build(&documents)
+///     .unwrap();
+///
+/// let env = document.evaluate_path(&["app", "env"]).unwrap();
+/// assert_eq!(env.as_str(), Some("prod"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CcoDocumentBuilder {
+    options: Options,
+}
+
+impl CcoDocumentBuilder {
+    /// Treat questionable input as an error (default: `true`)
+    ///
+    /// When disabled, root attributes and unknown block types are skipped with a warning.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// Restrict which root block types may be used (default: all of [BLOCK_KINDS])
+    pub fn block_kinds<I, S>(mut self, kinds: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.block_kinds = kinds.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Report labels that only differ in case (default: `false`)
+    ///
+    /// `data service Prod {}` and `data service prod {}` are distinct blocks. When enabled, this is
+    /// reported as [Issue::LabelCaseCollision] since it is usually a typo.
+    pub fn label_case_collisions(mut self, enabled: bool) -> Self {
+        self.options.label_case_collisions = enabled;
+        self
+    }
+
+    /// Make root attributes addressable as `global.<name>` (default: `false`)
+    ///
+    /// Otherwise root attributes are reported as [Issue::RootAttribute]. When enabled, data blocks
+    /// named [GLOBAL_NAMESPACE] are rejected with [Issue::ReservedIdentifier].
+    ///
+    /// ```
+    /// # use cco::{cco_document::CcoDocument, hcl_documents};
+    /// let documents = hcl_documents!("region = \"eu\"\ndata app {\n region = global.region\n}");
+    /// let document = CcoDocument::builder().globals(true).build(&documents).unwrap();
+    ///
+    /// let region = document.evaluate_path(&["app", "region"]).unwrap();
+    /// assert_eq!(region.as_str(), Some("eu"));
+    /// ```
+    pub fn globals(mut self, enabled: bool) -> Self {
+        self.options.globals = enabled;
+        self
+    }
+
+    /// Fail on issues with [Severity::Warning] too (default: `false`)
+    ///
+    /// Otherwise they are available as [CcoDocument::warnings] of the built document.
+    pub fn deny_warnings(mut self, deny: bool) -> Self {
+        self.options.deny_warnings = deny;
+        self
+    }
+
+    /// Let functions read files (default: `true`), `filebase64decode` fails otherwise
+    ///
+    /// Disable it where expressions come from untrusted clients, e.g. a server.
+    pub fn file_functions(mut self, enabled: bool) -> Self {
+        self.options.file_functions = enabled;
+        self
+    }
+
+    /// Make a function available to expressions
+    pub fn function(mut self, name: impl Into<hcl::Identifier>, func: hcl::eval::FuncDef) -> Self {
+        self.options.functions.insert(name.into(), func);
+        self
+    }
+
+    /// Make a function implemented by a closure available to expressions
+    ///
+    /// Unlike [CcoDocumentBuilder::function] the function can carry state (e.g. a connection to a
+    /// plugin process). It is called with the evaluated arguments, an `Err` fails the evaluation.
+    pub fn external_function<F>(mut self, name: impl Into<hcl::Identifier>, func: F) -> Self
+    where
+        F: Fn(&[hcl::Value]) -> Result<hcl::Value, String> + Send + Sync + 'static,
+    {
+        self.options
+            .externals
+            .insert(name.into(), std::sync::Arc::new(func));
+        self
+    }
+
+    /// Handle root blocks of type `kind` with a closure
+    ///
+    /// `kind labels... { attributes }` becomes addressable as `kind.labels...`. Its value is
+    /// whatever `handler` returns for the labels and the evaluated attributes. The handler is only
+    /// called when the block is referenced. `data`, `type` and `alias` can not be handled.
+    ///
+    /// ```
+    /// # use cco::{cco_document::CcoDocument, hcl_documents};
+    /// let documents = hcl_documents!("upper greeting {\n text = \"hi\"\n}");
+    /// let document = CcoDocument::builder()
+    ///     .data_source("upper", |_labels, body| {
+    ///         let text = body.as_object().and_then(|body| body.get("text"));
+    ///         let text = text.and_then(hcl::Value::as_str).ok_or("missing text")?;
+    ///         Ok(hcl::Value::from(text.to_uppercase()))
+    ///     })
+    ///     .build(&documents)
+    ///     .unwrap();
+    ///
+    /// let value = document.evaluate_path(&["upper", "greeting"]).unwrap();
+    /// assert_eq!(value.as_str(), Some("HI"));
+    /// ```
+    pub fn data_source<F>(self, kind: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(&[String], hcl::Value) -> Result<hcl::Value, String> + Send + Sync + 'static,
+    {
+        self.data_source_with_origin(kind, move |labels, _file, body| handler(labels, body))
+    }
+
+    /// [CcoDocumentBuilder::data_source] whose handler also gets the file declaring the block
+    ///
+    /// The file is `None` for documents without a path (e.g. stdin). Handlers reading files use it
+    /// to resolve relative paths, like paths in the document are relative to the document.
+    pub fn data_source_with_origin<F>(mut self, kind: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(&[String], Option<&std::path::Path>, hcl::Value) -> Result<hcl::Value, String>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let kind = kind.into();
+        let name = data_source_function(&kind);
+        self.options.data_sources.push(kind.clone());
+        let func = move |args: &[hcl::Value]| {
+            let [hcl::Value::Array(labels), body, file] = args else {
+                return Err("invalid data source arguments".to_string());
+            };
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|label| label.as_str().unwrap_or_default().to_string())
+                .collect();
+            let file = file.as_str().map(std::path::Path::new);
+            handler(&labels, file, body.clone())
+                .map_err(|err| format!("{kind} {}: {err}", labels.join(" ")))
+        };
+        self.options
+            .externals
+            .insert(name, std::sync::Arc::new(func));
+        self
+    }
+
+    /// Make a variable available to expressions
+    ///
+    /// Addressables take precedence over variables of the same name.
+    pub fn variable(
+        mut self,
+        name: impl Into<hcl::Identifier>,
+        value: impl Into<hcl::Value>,
+    ) -> Self {
+        self.options.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Fallback for unknown variables
+    ///
+    /// Consulted when evaluation encounters a variable that is neither an addressable nor a
+    /// declared variable. The resolver is called with the longest known path of each traversal
+    /// starting at that variable (`app.runtime.port` => `[app, runtime, port]`), then with shorter
+    /// prefixes until it returns an expression. That expression replaces the matched part of the
+    /// traversal.
+    pub fn resolver<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&[hcl::Identifier]) -> Option<hcl::Expression> + Send + Sync + 'static,
+    {
+        self.options.resolver = Some(Resolver(std::sync::Arc::new(resolver)));
+        self
+    }
+
+    /// Get notified while expressions are rewritten and evaluated, see [EvalObserver]
+    ///
+    /// Can be called multiple times, observers are notified in the order they were added.
+    pub fn observer(mut self, observer: impl EvalObserver + 'static) -> Self {
+        let observer: std::sync::Arc<dyn EvalObserver> = match self.options.observer.take() {
+            Some(Observer(existing)) => std::sync::Arc::new((existing, observer)),
+            None => std::sync::Arc::new(observer),
+        };
+        self.options.observer = Some(Observer(observer));
+        self
+    }
+
+    /// Limit the length of dependency chains (default: [DEFAULT_MAX_DEPTH])
+    ///
+    /// `a = b`, `b = c`, `c = 1` is a chain of length 3 when evaluating `a`.
+    ///
+    /// Exceeding the limit fails with [LimitExceeded::Depth]. `None` disables the limit.
+    pub fn max_depth(mut self, limit: impl Into<Option<usize>>) -> Self {
+        self.options.max_depth = limit.into();
+        self
+    }
+
+    /// Limit the number of evaluation steps of a single evaluation (default: unlimited)
+    ///
+    /// Every attempt to evaluate an expression counts as one step. Exceeding the limit fails with
+    /// [LimitExceeded::Steps].
+    pub fn max_steps(mut self, limit: impl Into<Option<usize>>) -> Self {
+        self.options.max_steps = limit.into();
+        self
+    }
+
+    /// Abort a single evaluation after `timeout` (default: unlimited)
+    ///
+    /// The deadline is checked between evaluation steps, a single slow function call or
+    /// [CcoDocumentBuilder::resolver] callback is not interrupted. Exceeding the timeout fails with
+    /// [LimitExceeded::Timeout]. Not supported on `wasm32-unknown-unknown`, which has no clock:
+    /// evaluations with a timeout fail there.
+    pub fn timeout(mut self, timeout: impl Into<Option<std::time::Duration>>) -> Self {
+        self.options.timeout = timeout.into();
+        self
+    }
+
+    /// What happens when integer arithmetic (`+`, `-`, `*`, negation) leaves the `i64` range
+    /// (default: [OverflowPolicy::Error])
+    pub fn overflow(mut self, policy: OverflowPolicy) -> Self {
+        self.options.overflow = policy;
+        self
+    }
+
+    /// What happens with data blocks that have the same labels (default: [MergePolicy::Reject])
+    ///
+    /// ```
+    /// # use cco::{cco_document::{CcoDocument, MergePolicy}, hcl_documents};
+    /// let documents = hcl_documents! {
+    ///     "base.cco.hcl" => "data app {\n replicas = 1\n image = \"app:1\"\n}",
+    ///     "prod.cco.hcl" => "data app {\n replicas = 3\n}"
+    /// };
+    /// let document = CcoDocument::builder()
+    ///     .merge_policy(MergePolicy::Override)
+    ///     .build(&documents)
+    ///     .unwrap();
+    ///
+    /// let replicas = document.evaluate_path(&["app", "replicas"]).unwrap();
+    /// assert_eq!(replicas.as_i64(), Some(3));
+    /// let image = document.evaluate_path(&["app", "image"]).unwrap();
+    /// assert_eq!(image.as_str(), Some("app:1"));
+    /// ```
+    pub fn merge_policy(mut self, policy: MergePolicy) -> Self {
+        self.options.merge = policy;
+        self
+    }
+
+    /// Look secrets up with `secret("<name>", "<key>")`
+    ///
+    /// `secret("vault", "db/password")` calls the provider registered as `vault` with
+    /// `db/password`, an `Err` fails the evaluation. Providers are only called when such an
+    /// expression is evaluated. Mark attributes holding secrets with [SENSITIVE_ATTRIBUTE].
+    ///
+    /// ```
+    /// # use cco::{cco_document::CcoDocument, hcl_documents};
+    /// let documents = hcl_documents!("data db {\n password = secret(\"env\", \"DB_PASSWORD\")\n}");
+    /// let document = CcoDocument::builder()
+    ///     .secret_provider("env", |key| match key {
+    ///         "DB_PASSWORD" => Ok("hunter2".to_string()),
+    ///         _ => Err("not set".to_string()),
+    ///     })
+    ///     .build(&documents)
+    ///     .unwrap();
+    ///
+    /// let password = document.evaluate_path(&["db", "password"]).unwrap();
+    /// assert_eq!(password.as_str(), Some("hunter2"));
+    /// ```
+    pub fn secret_provider<F>(mut self, name: impl Into<String>, provider: F) -> Self
+    where
+        F: Fn(&str) -> Result<String, String> + Send + Sync + 'static,
+    {
+        self.options
+            .secrets
+            .0
+            .insert(name.into(), std::sync::Arc::new(provider));
+        let secrets = self.options.secrets.clone();
+        self.options.externals.insert(
+            hcl::Identifier::unchecked(SECRET_FUNCTION),
+            std::sync::Arc::new(move |args: &[hcl::Value]| secrets.lookup(args)),
+        );
+        self
+    }
+
+    pub fn build(self, hcl_documents: &HclDocuments) -> Result<CcoDocument, CcoParseErrors> {
+        CcoDocument::build(self.options, hcl_documents)
+    }
 }
 
 impl CcoDocument {
     pub fn new(hcl_documents: &HclDocuments) -> Result<Self, CcoParseErrors> {
+        Self::builder().build(hcl_documents)
+    }
+
+    pub fn builder() -> CcoDocumentBuilder {
+        CcoDocumentBuilder::default()
+    }
+
+    fn build(options: Options, hcl_documents: &HclDocuments) -> Result<Self, CcoParseErrors> {
         let mut _self = Self {
             tree: Default::default(),
             addressables: Default::default(),
+            substs: Default::default(),
+            options,
+            warnings: CcoParseErrors::new(),
         };
 
         let mut e = CcoParseErrors::new();
-        let mut data_groups: std::collections::HashMap<hcl::Identifier, DataGroup> =
+        // keyed by namespace and group name, see [HclDocuments::nest], in source order
+        let mut data_groups: indexmap::IndexMap<Vec<hcl::Identifier>, DataGroup> =
+            Default::default();
+        let mut type_specs: std::collections::HashMap<Vec<hcl::Identifier>, usize> =
             Default::default();
-        let mut type_specs: std::collections::HashMap<hcl::Identifier, usize> = Default::default();
+        let mut label_cases = LabelCases::default();
+        let mut aliases: Vec<AliasBlock> = vec![];
+        let mut data_sources: Vec<usize> = vec![];
+        let mut globals: Vec<usize> = vec![];
+        let mut partials: std::collections::HashMap<String, usize> = Default::default();
 
-        for (index, _source, _attribute) in hcl_documents.attributes() {
-            e.log(Issue::RootAttribute(index))
+        for (index, _source, attribute) in hcl_documents.attributes() {
+            if _self.options.globals {
+                globals.push(index);
+            } else if _self.options.strict {
+                e.log(Issue::RootAttribute(index))
+            } else {
+                tracing::warn!(
+                    key = attribute.key.value().as_str(),
+                    "ignoring root attribute"
+                );
+            }
         }
 
         for (index, _source, block) in hcl_documents.blocks() {
-            match block.ident.value().as_str() {
+            let kind = block.ident.value().as_str();
+            if BLOCK_KINDS.contains(&kind) && !_self.options.block_kinds.iter().any(|k| k == kind) {
+                e.log(Issue::BlockTypeNotAllowed(index));
+                continue;
+            }
+
+            match kind {
                 "data" => {
                     if block.labels.is_empty() {
                         e.log(Issue::DataBlockLabelMissing(index));
                         break;
                     }
 
-                    let data_block = DataBlock::new(index, block);
+                    let data_block =
+                        DataBlock::new(index, block, hcl_documents.block_namespace(index));
+                    if data_block.identifiers[..=data_block.namespace]
+                        .iter()
+                        .any(|identifier| identifier.starts_with(SUBST_PREFIX))
+                        || (_self.options.globals
+                            && data_block.identifiers[0].as_str() == GLOBAL_NAMESPACE)
+                    {
+                        e.log(Issue::ReservedIdentifier(index));
+                        continue;
+                    }
+                    if data_block.identifiers[data_block.namespace..]
+                        .iter()
+                        .zip(&block.labels)
+                        .any(|(identifier, label)| identifier.as_str() != label.as_str())
+                    {
+                        e.log(Issue::SanitizedLabel(index));
+                    }
 
-                    let group: &mut DataGroup =
-                        if data_groups.contains_key(data_block.identifiers[0].as_str()) {
-                            data_groups.get_mut(&data_block.identifiers[0]).unwrap()
-                        } else {
-                            data_groups.insert(data_block.identifiers[0].clone(), DataGroup::new());
-                            data_groups.get_mut(&data_block.identifiers[0]).unwrap()
-                        };
+                    let group: &mut DataGroup = data_groups
+                        .entry(data_block.group().to_vec())
+                        .or_insert_with(DataGroup::new);
 
                     if let Some(existing_member) = group.data_blocks.first() {
-                        if existing_member.identifiers.len() != block.labels.len() {
+                        if existing_member.identifiers.len() != data_block.identifiers.len() {
                             e.log(Issue::DataBlockLabelMismatch {
                                 existing: existing_member.block_index,
                                 new: index,
@@ -62,17 +615,24 @@ impl CcoDocument {
 
                     if let Some(existing) = group
                         .data_blocks
-                        .iter()
-                        .find(|existing_block| *existing_block == &data_block)
+                        .iter_mut()
+                        .find(|existing_block| **existing_block == data_block)
                     {
-                        e.log(Issue::DataBlockLabelCollision {
-                            existing: existing.block_index,
-                            new: index,
-                        });
+                        match _self.options.merge {
+                            MergePolicy::Reject => e.log(Issue::DataBlockLabelCollision {
+                                existing: existing.block_index,
+                                new: index,
+                            }),
+                            MergePolicy::Override => existing.overrides.push(index),
+                        }
                         continue;
                     }
 
-                    group.data_blocks.push(DataBlock::new(index, block));
+                    if _self.options.label_case_collisions {
+                        label_cases.check(&data_block.identifiers, index, &mut e);
+                    }
+
+                    group.data_blocks.push(data_block);
                 }
                 "type" => {
                     if block.labels.is_empty() {
@@ -86,8 +646,17 @@ impl CcoDocument {
                     }
 
                     let type_name = hcl::Identifier::sanitized(block.labels[0].as_str());
+                    if type_name.starts_with(SUBST_PREFIX) {
+                        e.log(Issue::ReservedIdentifier(index));
+                        continue;
+                    }
+                    if type_name.as_str() != block.labels[0].as_str() {
+                        e.log(Issue::SanitizedLabel(index));
+                    }
 
-                    if let Some(existing_type_spec) = type_specs.get(&type_name) {
+                    let mut type_path = hcl_documents.block_namespace(index).to_vec();
+                    type_path.push(type_name);
+                    if let Some(existing_type_spec) = type_specs.get(&type_path) {
                         e.log(Issue::TypeBlockLabelCollision {
                             new: index,
                             existing: *existing_type_spec,
@@ -95,44 +664,341 @@ impl CcoDocument {
                         continue;
                     }
 
-                    type_specs.insert(type_name, index);
+                    if _self.options.label_case_collisions {
+                        label_cases.check(&type_path, index, &mut e);
+                    }
+
+                    type_specs.insert(type_path, index);
+                }
+                "partial" => {
+                    if block.labels.len() != 1 || block.body.blocks().next().is_some() {
+                        e.log(Issue::PartialBlockInvalid(index));
+                        continue;
+                    }
+
+                    let name = partial_name(hcl_documents.block_namespace(index), &block.labels[0]);
+                    if let Some(existing) = partials.get(&name) {
+                        e.log(Issue::PartialBlockLabelCollision {
+                            existing: *existing,
+                            new: index,
+                        });
+                        continue;
+                    }
+                    partials.insert(name, index);
+                }
+                "alias" => {
+                    if block.labels.is_empty() {
+                        e.log(Issue::AliasBlockLabelMissing(index));
+                        continue;
+                    }
+
+                    match AliasBlock::new(index, block, hcl_documents.block_namespace(index)) {
+                        Some(alias) => aliases.push(alias),
+                        None => e.log(Issue::AliasBlockInvalid(index)),
+                    }
+                }
+                kind if _self.options.data_sources.iter().any(|k| k == kind) => {
+                    if block.labels.is_empty() {
+                        e.log(Issue::DataSourceLabelMissing(index));
+                        continue;
+                    }
+                    if block.body.blocks().next().is_some() {
+                        e.log(Issue::DataSourceInvalid(index));
+                        continue;
+                    }
+                    data_sources.push(index);
+                }
+                _ if !_self.options.strict => {
+                    tracing::warn!(kind, "ignoring unknown block type");
                 }
                 _ => e.log(Issue::UnknownBlockType(index)),
             }
         }
 
-        if !e.issues.is_empty() {
-            return Err(e);
+        // root types also apply to the groups of that name in namespaces
+        let mut unused_types: Vec<usize> = type_specs
+            .iter()
+            .filter(|(type_path, _)| match type_path.as_slice() {
+                [type_name] => !data_groups
+                    .keys()
+                    .any(|group| group.last() == Some(type_name)),
+                type_path => !data_groups.contains_key(type_path),
+            })
+            .map(|(_, index)| *index)
+            .collect();
+        unused_types.sort();
+        for index in unused_types {
+            e.log(Issue::UnusedTypeBlock(index));
+        }
+
+        // a namespace is a path of its own, no data group may end or continue there
+        let mut namespaces: std::collections::HashMap<&[hcl::Identifier], usize> =
+            Default::default();
+        for group in data_groups.values() {
+            let data_block = &group.data_blocks[0];
+            for len in 1..=data_block.namespace {
+                namespaces
+                    .entry(&data_block.identifiers[..len])
+                    .or_insert(data_block.block_index);
+            }
+        }
+        let mut namespace_collisions: Vec<(usize, usize)> = data_groups
+            .iter()
+            .filter_map(|(group, members)| {
+                let namespace = namespaces.get(group.as_slice())?;
+                Some((members.data_blocks[0].block_index, *namespace))
+            })
+            .collect();
+        namespace_collisions.sort();
+        for (group, namespace) in namespace_collisions {
+            e.log(Issue::NamespaceCollision { group, namespace });
+        }
+
+        // references to groups of the own namespace are resolved there
+        let mut namespace_groups: std::collections::HashMap<
+            Vec<hcl::Identifier>,
+            std::collections::HashSet<hcl::Identifier>,
+        > = Default::default();
+        for data_block in data_groups.values().map(|group| &group.data_blocks[0]) {
+            if data_block.namespace > 0 {
+                namespace_groups
+                    .entry(data_block.identifiers[..data_block.namespace].to_vec())
+                    .or_default()
+                    .insert(data_block.identifiers[data_block.namespace].clone());
+            }
+        }
+        for alias in &aliases {
+            if alias.namespace > 0 {
+                namespace_groups
+                    .entry(alias.identifiers[..alias.namespace].to_vec())
+                    .or_default()
+                    .insert(alias.identifiers[alias.namespace].clone());
+            }
+        }
+        let scoped = |mut expression: hcl::Expression,
+                      identifiers: &[hcl::Identifier],
+                      namespace: usize| {
+            if namespace > 0 {
+                let groups = &namespace_groups[&identifiers[..namespace]];
+                expression.visit_traversals_mut(&mut SelfRewriter::new(&identifiers[namespace..]));
+                expression.visit_traversals_mut(&mut NamespaceRewriter::new(
+                    &identifiers[..namespace],
+                    groups,
+                ));
+            }
+            expression
+        };
+
+        let mut global_names: std::collections::HashMap<hcl::Identifier, usize> =
+            Default::default();
+        for index in globals {
+            let (_, source, attribute) = hcl_documents.get_attribute(index);
+            let name = hcl::Identifier::sanitized(attribute.key.value());
+            if let Some(existing) = global_names.get(&name) {
+                e.log(Issue::RootAttributeCollision {
+                    existing: *existing,
+                    new: index,
+                });
+                continue;
+            }
+            global_names.insert(name.clone(), index);
+
+            let path = vec![hcl::Identifier::unchecked(GLOBAL_NAMESPACE), name];
+            let index = _self
+                .insert(
+                    Kind::Attribute,
+                    path,
+                    attribute.value.clone().into(),
+                    Origin::new(
+                        source.clone(),
+                        hcl_documents.attribute_span(index, attribute.span()),
+                    ),
+                )
+                .expect("checked above");
+            _self.addressables[index].doc = util::doc_comment(attribute.decor());
+        }
+
+        if e.fails(_self.options.deny_warnings) {
+            return Err(e.into_failure(_self.options.deny_warnings, hcl_documents));
         };
 
-        for data_block in data_groups.iter().flat_map(|(_, group)| &group.data_blocks) {
+        for data_block in data_groups.values().flat_map(|group| &group.data_blocks) {
             // direct attributes
             let data_block_hcl = hcl_documents.get_block(data_block.block_index);
-            for attribute in data_block_hcl.2.body.attributes() {
-                let mut path = data_block.identifiers.clone();
-                path.push(hcl::Identifier::sanitized(attribute.key.value()));
-
-                tracing::trace!(?path, "add direct attribute");
-                assert!(
-                    _self
-                        .insert(Kind::Attribute, path, attribute.value.clone().into())
-                        .is_ok(),
-                    "attribute collision: {:?}.{:?}",
-                    data_block.identifiers,
-                    attribute.key.value(),
-                );
+            let mut block_deprecation = None;
+            let mut attribute_deprecations = vec![];
+            let mut block_sensitive = false;
+            let mut sensitive_attributes = vec![];
+            let mut included = vec![];
+            // blocks merged with [MergePolicy::Override] in order of precedence, the last first
+            let mut special_attributes = std::collections::HashSet::new();
+            for block_index in data_block.block_indices().rev() {
+                let (_, source, block) = hcl_documents.get_block(block_index);
+                for attribute in block.body.attributes() {
+                    let key = attribute.key.value().as_str();
+                    let special = [
+                        INCLUDE_PARTIALS_ATTRIBUTE,
+                        DEPRECATED_ATTRIBUTE,
+                        SENSITIVE_ATTRIBUTE,
+                    ]
+                    .contains(&key);
+                    if special && !special_attributes.insert(key) {
+                        continue;
+                    }
+
+                    if key == INCLUDE_PARTIALS_ATTRIBUTE {
+                        match parse_include_partials(
+                            attribute.value.clone().into(),
+                            &data_block.identifiers[..data_block.namespace],
+                            &partials,
+                        ) {
+                            Some(indices) => included = indices,
+                            None => e.log(Issue::IncludePartialsInvalid(block_index)),
+                        }
+                        continue;
+                    }
+
+                    if key == DEPRECATED_ATTRIBUTE {
+                        match parse_deprecations(attribute.value.clone().into()) {
+                            Some(Deprecations::Block(message)) => block_deprecation = Some(message),
+                            Some(Deprecations::Attributes(messages)) => {
+                                attribute_deprecations = messages
+                            }
+                            None => e.log(Issue::DeprecatedAttributeInvalid(block_index)),
+                        }
+                        continue;
+                    }
+
+                    if key == SENSITIVE_ATTRIBUTE {
+                        match parse_sensitive(attribute.value.clone().into()) {
+                            Some(Sensitive::Block(sensitive)) => block_sensitive = sensitive,
+                            Some(Sensitive::Attributes(attributes)) => {
+                                sensitive_attributes = attributes
+                            }
+                            None => e.log(Issue::SensitiveAttributeInvalid(block_index)),
+                        }
+                        continue;
+                    }
+
+                    let mut path = data_block.identifiers.clone();
+                    path.push(hcl::Identifier::sanitized(attribute.key.value()));
+                    let origin = Origin::new(
+                        source.clone(),
+                        hcl_documents.block_span(block_index, attribute.span()),
+                    );
+
+                    tracing::trace!(?path, "add direct attribute");
+                    // not being added means that a block loaded later defines it
+                    let index = match _self.insert(
+                        Kind::Attribute,
+                        path,
+                        scoped(
+                            attribute.value.clone().into(),
+                            &data_block.identifiers,
+                            data_block.namespace,
+                        ),
+                        origin.clone(),
+                    ) {
+                        Ok(index) => {
+                            _self.addressables[index].doc = util::doc_comment(attribute.decor());
+                            index
+                        }
+                        Err(existing) => existing,
+                    };
+                    _self.addressables[index]
+                        .definitions
+                        .push(Definition::new(Layer::Data, origin));
+                }
+            }
+
+            // attributes of partials, the last one wins
+            for partial_index in included.into_iter().rev() {
+                let partial_hcl = hcl_documents.get_block(partial_index);
+                let name = partial_hcl.2.labels.first().map(|label| label.as_str());
+                for attribute in partial_hcl.2.body.attributes() {
+                    let mut path = data_block.identifiers.clone();
+                    path.push(hcl::Identifier::sanitized(attribute.key.value()));
+                    let origin = Origin::new(
+                        partial_hcl.1.clone(),
+                        hcl_documents.block_span(partial_index, attribute.span()),
+                    );
+
+                    // not being added means that the block or a later partial defines it
+                    let index = match _self.insert(
+                        Kind::Attribute,
+                        path,
+                        scoped(
+                            attribute.value.clone().into(),
+                            &data_block.identifiers,
+                            data_block.namespace,
+                        ),
+                        origin.clone(),
+                    ) {
+                        Ok(index) => {
+                            _self.addressables[index].doc = util::doc_comment(attribute.decor());
+                            index
+                        }
+                        Err(existing) => existing,
+                    };
+                    let layer = Layer::Partial(name.unwrap_or_default().to_string());
+                    _self.addressables[index]
+                        .definitions
+                        .push(Definition::new(layer, origin));
+                }
             }
 
             // default/fallback attributes
-            if let Some(type_spec_index) = type_specs.get(&data_block.identifiers[0]).copied() {
+            let type_spec = type_specs.get(data_block.group()).or_else(|| {
+                type_specs.get(&data_block.identifiers[data_block.namespace..=data_block.namespace])
+            });
+            if let Some(type_spec_index) = type_spec.copied() {
                 let type_spec_hcl = hcl_documents.get_block(type_spec_index);
                 for attribute in type_spec_hcl.2.body.attributes() {
                     let mut path = data_block.identifiers.clone();
                     path.push(hcl::Identifier::sanitized(attribute.key.value()));
+                    let origin = Origin::new(
+                        type_spec_hcl.1.clone(),
+                        hcl_documents.block_span(type_spec_index, attribute.span()),
+                    );
+
+                    // not being added means that we already have a direct attribute
+                    let index = match _self.insert(
+                        Kind::DefaultAttribute,
+                        path,
+                        scoped(
+                            attribute.value.clone().into(),
+                            &data_block.identifiers,
+                            data_block.namespace,
+                        ),
+                        origin.clone(),
+                    ) {
+                        Ok(index) => {
+                            _self.addressables[index].doc = util::doc_comment(attribute.decor());
+                            index
+                        }
+                        Err(existing) => existing,
+                    };
+                    _self.addressables[index]
+                        .definitions
+                        .push(Definition::new(Layer::Type, origin));
+                }
+            }
+
+            for (attribute, message) in attribute_deprecations {
+                let mut path = data_block.identifiers.clone();
+                path.push(attribute);
+                match _self.tree.node(&path).and_then(|node| node.value) {
+                    Some(index) => _self.addressables[index].deprecation = Some(message),
+                    None => e.log(Issue::DeprecatedAttributeInvalid(data_block.block_index)),
+                }
+            }
 
-                    // not being added means that we already have a direct attribute. ignore.
-                    let _ =
-                        _self.insert(Kind::DefaultAttribute, path, attribute.value.clone().into());
+            for attribute in sensitive_attributes {
+                let mut path = data_block.identifiers.clone();
+                path.push(attribute);
+                match _self.tree.node(&path).and_then(|node| node.value) {
+                    Some(index) => _self.addressables[index].sensitive = true,
+                    None => e.log(Issue::SensitiveAttributeInvalid(data_block.block_index)),
                 }
             }
 
@@ -142,7 +1008,8 @@ impl CcoDocument {
                 Default::default();
             for (ident, child_node) in &node.children {
                 if let Some(addressable) = child_node.value {
-                    let addr = &_self.addressables[addressable];
+                    let addr = &mut _self.addressables[addressable];
+                    addr.sensitive |= block_sensitive;
                     data_block_expression.insert(
                         ident.clone().into(),
                         hcl::Expression::Variable(addr.subst.clone().into()),
@@ -150,37 +1017,125 @@ impl CcoDocument {
                 }
             }
 
-            assert!(
-                _self
-                    .insert(
-                        Kind::Block,
-                        data_block.identifiers.clone(),
-                        hcl::Expression::Object(data_block_expression),
+            let index = _self
+                .insert(
+                    Kind::Block,
+                    data_block.identifiers.clone(),
+                    hcl::Expression::Object(data_block_expression),
+                    Origin::new(
+                        data_block_hcl.1.clone(),
+                        hcl_documents.block_span(data_block_hcl.0, data_block_hcl.2.span()),
+                    ),
+                )
+                .unwrap_or_else(|_| {
+                    panic!("data block object collision {:?}", data_block.identifiers)
+                });
+            _self.addressables[index].deprecation = block_deprecation;
+            _self.addressables[index].sensitive = block_sensitive;
+            _self.addressables[index].doc = util::doc_comment(data_block_hcl.2.decor());
+        }
+
+        for index in data_sources {
+            let (_, source, block) = hcl_documents.get_block(index);
+            let kind = block.ident.value().as_str();
+            let mut path = vec![hcl::Identifier::sanitized(kind)];
+            path.extend(block.labels.iter().map(hcl::Identifier::sanitized));
+            if _self.tree.node(&path).is_some() {
+                e.log(Issue::DataSourceCollision(index));
+                continue;
+            }
+
+            let labels = block
+                .labels
+                .iter()
+                .map(|label| hcl::Expression::String(label.as_str().to_string()))
+                .collect();
+            let body = block
+                .body
+                .attributes()
+                .map(|attribute| {
+                    (
+                        hcl::ObjectKey::from(hcl::Identifier::sanitized(attribute.key.value())),
+                        hcl::Expression::from(attribute.value.clone()),
                     )
-                    .is_ok(),
-                "data block object collision {:?}",
-                data_block.identifiers
-            );
+                })
+                .collect();
+            let file = match source {
+                Some(path) => hcl::Expression::String(path.to_string_lossy().into_owned()),
+                None => hcl::Expression::Null,
+            };
+            let call = hcl::FuncCall::builder(data_source_function(kind))
+                .arg(hcl::Expression::Array(labels))
+                .arg(hcl::Expression::Object(body))
+                .arg(file)
+                .build();
+
+            let index = _self
+                .insert(
+                    Kind::DataSource,
+                    path,
+                    call.into(),
+                    Origin::new(
+                        source.clone(),
+                        hcl_documents.block_span(index, block.span()),
+                    ),
+                )
+                .expect("checked above");
+            _self.addressables[index].doc = util::doc_comment(block.decor());
         }
 
-        let mut root_groups = vec![];
-        for (ident, group) in _self.tree.root.iter() {
-            if group.value.is_none() {
-                let children: hcl::Object<hcl::ObjectKey, hcl::Expression> = group
-                    .children
-                    .iter()
-                    .flat_map(|(key, value)| {
-                        value.value.map(|index| {
-                            (
-                                key.clone().into(),
-                                hcl::Expression::Variable(
-                                    _self.addressables[index].subst.clone().into(),
-                                ),
-                            )
-                        })
-                    })
-                    .collect();
+        for alias in aliases {
+            // an alias must neither replace nor shadow anything
+            if _self.tree.node(&alias.identifiers).is_some() {
+                e.log(Issue::AliasCollision(alias.block_index));
+                continue;
+            }
+
+            let alias_hcl = hcl_documents.get_block(alias.block_index);
+            let target = scoped(alias.target, &alias.identifiers, alias.namespace);
+            let index = _self
+                .insert(
+                    Kind::Alias,
+                    alias.identifiers,
+                    target,
+                    Origin::new(
+                        alias_hcl.1.clone(),
+                        hcl_documents.block_span(alias.block_index, alias_hcl.2.span()),
+                    ),
+                )
+                .expect("checked above");
+            _self.addressables[index].deprecation = alias.deprecation;
+            _self.addressables[index].doc = util::doc_comment(alias_hcl.2.decor());
+        }
+
+        if e.fails(_self.options.deny_warnings) {
+            return Err(e.into_failure(_self.options.deny_warnings, hcl_documents));
+        };
+
+        // namespaced groups and the namespaces, innermost first to be part of the outer ones
+        let mut nested: Vec<Vec<hcl::Identifier>> = data_groups
+            .keys()
+            .flat_map(|group| (2..=group.len()).map(|len| group[..len].to_vec()))
+            .collect();
+        nested.sort_by_cached_key(|path| {
+            let names: Vec<String> = path.iter().map(|i| i.to_string()).collect();
+            (std::cmp::Reverse(path.len()), names)
+        });
+        nested.dedup();
+        for path in nested {
+            if let Some(children) = _self.tree.node(&path).and_then(|node| _self.group(node)) {
+                let _ = _self.insert(
+                    Kind::Virtual,
+                    path,
+                    hcl::Expression::Object(children),
+                    Origin::default(),
+                );
+            }
+        }
 
+        let mut root_groups = vec![];
+        for (ident, group) in _self.tree.root.iter() {
+            if let Some(children) = _self.group(group) {
                 root_groups.push((ident.clone(), children));
             }
         }
@@ -190,12 +1145,45 @@ impl CcoDocument {
                 Kind::Virtual,
                 vec![ident],
                 hcl::Expression::Object(children),
+                Origin::default(),
             );
         }
 
+        e.describe(hcl_documents);
+        _self.warnings = e;
         Ok(_self)
     }
 
+    /// Object of the children of a node without value, `None` if it has one or only aliases
+    fn group(&self, node: &Node) -> Option<hcl::Object<hcl::ObjectKey, hcl::Expression>> {
+        let is_alias = |node: &Node| {
+            node.value
+                .is_some_and(|index| self.addressables[index].kind == Kind::Alias)
+        };
+        if node.value.is_some() || node.children.values().all(is_alias) {
+            return None;
+        }
+
+        Some(
+            node.children
+                .iter()
+                .flat_map(|(key, value)| {
+                    value
+                        .value
+                        .filter(|index| self.addressables[*index].kind != Kind::Alias)
+                        .map(|index| {
+                            (
+                                key.clone().into(),
+                                hcl::Expression::Variable(
+                                    self.addressables[index].subst.clone().into(),
+                                ),
+                            )
+                        })
+                })
+                .collect(),
+        )
+    }
+
     /// Insert a new addressable
     ///
     /// Returns new index when added or existing index when failed
@@ -204,6 +1192,7 @@ impl CcoDocument {
         kind: Kind,
         path: Vec<hcl::Identifier>,
         expression: hcl::Expression,
+        origin: Origin,
     ) -> Result<usize, usize> {
         let node = self.tree.get_or_insert(&path);
         if let Some(existing) = node.value {
@@ -214,14 +1203,75 @@ impl CcoDocument {
         let index = self.addressables.len();
         node.value = Some(index);
 
-        self.addressables
-            .push(Addressable::new(path, kind, expression));
+        let mut addressable = Addressable::new(path, kind, expression, origin);
+        // different paths may still hash the same, the index keeps them apart
+        if self.substs.contains_key(&addressable.subst) {
+            addressable.subst = format!("{}_{index}", addressable.subst).into();
+        }
+        self.substs.insert(addressable.subst.clone(), index);
+        self.addressables.push(addressable);
 
         Ok(index)
     }
 
+    /// Report references to deprecated elements
+    ///
+    /// An element is deprecated if it or one of its ancestors is. References from within the
+    /// deprecated element and blocks containing it are not reported.
+    fn report_deprecated(&self, subst: &hcl::Identifier, referenced_by: &hcl::Identifier) {
+        let Some(addressable) = self.get_by_subst(subst) else {
+            return;
+        };
+        let Some((path, deprecation)) = (1..=addressable.path.len()).rev().find_map(|len| {
+            let path = &addressable.path[..len];
+            let index = self.tree.node(path)?.value?;
+            Some((path, self.addressables[index].deprecation.as_deref()?))
+        }) else {
+            return;
+        };
+
+        let referencing = self.get_by_subst(referenced_by);
+        if let Some(referencing) = referencing {
+            let contains = matches!(referencing.kind, Kind::Block | Kind::Virtual)
+                && addressable.path.starts_with(&referencing.path);
+            if contains || referencing.path.starts_with(path) {
+                return;
+            }
+        }
+
+        // observers report it, e.g. as warning
+        tracing::debug!(
+            phase = "evaluate",
+            path = path.join("."),
+            referenced_by = self.describe_subst(referenced_by),
+            file = referencing
+                .and_then(|r| r.origin.source.as_deref())
+                .map(|file| file.display().to_string()),
+            span = ?referencing.and_then(|r| r.origin.span.clone()),
+            "deprecated: {deprecation}"
+        );
+
+        if let Some(observer) = self.observer() {
+            observer.on_deprecated(path, deprecation, referencing.map(|r| r.path.as_slice()));
+        }
+    }
+
+    /// Human readable path of an addressable's substitution identifier
+    fn describe_subst(&self, subst: &hcl::Identifier) -> String {
+        match self.get_by_subst(subst) {
+            Some(addressable) => addressable.path.join("."),
+            None => "<expression>".to_string(),
+        }
+    }
+
+    pub(crate) fn observer(&self) -> Option<&dyn EvalObserver> {
+        self.options.observer.as_ref().map(|observer| &*observer.0)
+    }
+
     pub fn get_by_subst(&self, subst: &hcl::Identifier) -> Option<&Addressable> {
-        self.addressables.iter().find(|addr| &addr.subst == subst)
+        self.substs
+            .get(subst)
+            .map(|index| &self.addressables[*index])
     }
 
     pub fn get_most_specific_node(
@@ -233,6 +1283,70 @@ impl CcoDocument {
             .map(|(idx, ident)| (&self.addressables[idx].subst, path.len() - ident.len()))
     }
 
+    /// Paths of all elements, [Node::value]s index [CcoDocument::addressable_at]
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// Addressable of a [Node::value]
+    pub fn addressable_at(&self, index: usize) -> Option<&Addressable> {
+        self.addressables.get(index)
+    }
+
+    /// All addressable elements
+    pub fn addressables(&self) -> impl Iterator<Item = AddressableInfo<'_>> {
+        self.addressables.iter().map(Addressable::info)
+    }
+
+    /// Paths of all addressable elements
+    pub fn paths(&self) -> impl Iterator<Item = &[hcl::Identifier]> {
+        self.addressables.iter().map(|addr| addr.path.as_slice())
+    }
+
+    /// Issues with [Severity::Warning] that did not fail the build
+    pub fn warnings(&self) -> &CcoParseErrors {
+        &self.warnings
+    }
+
+    /// Addressable element at exactly `path`
+    pub fn get(&self, path: &[hcl::Identifier]) -> Option<AddressableInfo<'_>> {
+        self.tree
+            .node(path)
+            .and_then(|node| node.value)
+            .map(|index| self.addressables[index].info())
+    }
+
+    /// Every definition of the attribute at `path`, the one in effect first
+    ///
+    /// An attribute of a data block may be defined by the block, blocks merged into it with
+    /// [MergePolicy::Override], its partials and the `type` block of the group. Empty for other
+    /// elements, `None` if there is nothing at `path`.
+    pub fn definitions(&self, path: &[hcl::Identifier]) -> Option<&[Definition]> {
+        let index = self.tree.node(path)?.value?;
+        Some(&self.addressables[index].definitions)
+    }
+
+    /// Direct children of `path`, in definition order
+    ///
+    /// An empty path yields the root elements. Returns `None` if there is nothing at `path`.
+    pub fn children_of(
+        &self,
+        path: &[hcl::Identifier],
+    ) -> Option<impl Iterator<Item = AddressableInfo<'_>>> {
+        let children = if path.is_empty() {
+            &self.tree.root
+        } else {
+            &self.tree.node(path)?.children
+        };
+
+        Some(
+            children
+                .values()
+                .filter_map(|node| node.value)
+                .map(|index| self.addressables[index].info()),
+        )
+    }
+
     fn get_by_subst_and_rewrite(&self, ident: &hcl::Identifier) -> Option<hcl::Expression> {
         self.get_by_subst(ident).map(|addressable| {
             let mut expr = addressable.expression.clone();
@@ -243,306 +1357,2999 @@ impl CcoDocument {
 
             let mut dependency_writer = AttributeReferenceRewriter::new(self);
             expr.visit_traversals_mut(&mut dependency_writer);
+            self.options.externals.rewrite(&mut expr);
 
             expr
         })
     }
 
     pub fn evaluate_in_context(
+        &self,
+        expression: hcl::Expression,
+    ) -> anyhow::Result<crate::value::Value> {
+        self.evaluate_memoized(expression, None)
+    }
+
+    /// Evaluate, reusing and recording the values of addressables in `memo`
+    pub(crate) fn evaluate_memoized(
         &self,
         mut expression: hcl::Expression,
+        memo: Option<&Memo>,
     ) -> anyhow::Result<crate::value::Value> {
         let mut dependency_writer = AttributeReferenceRewriter::new(self);
         expression.visit_traversals_mut(&mut dependency_writer);
+        self.options.externals.rewrite(&mut expression);
 
-        let mut context = hcl::eval::Context::new();
-        let mut stack = vec![(hcl::Identifier::unchecked("output"), expression)];
+        let _externals = self.options.externals.enter();
+        let mut evaluation = Evaluation {
+            context: self.options.context(),
+            stack: vec![(hcl::Identifier::unchecked("output"), expression, self.now())],
+            resolved_variables: Default::default(),
+            steps: 0,
+            deadline: self.deadline()?,
+            fallible: Default::default(),
+            failed: Default::default(),
+        };
+
+        loop {
+            let (current, expression, started) = evaluation
+                .stack
+                .pop()
+                .expect("the output stays until it is evaluated");
+            let err = match self.evaluate_step(
+                &mut evaluation,
+                memo,
+                current.clone(),
+                expression,
+                started,
+            ) {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => continue,
+                Err(err) => err,
+            };
+            if err.downcast_ref::<LimitExceeded>().is_some() {
+                return Err(err);
+            }
+
+            // an addressable only `try` or `can` wait for fails for them, and so does every
+            // addressable on the way to it
+            let stack = &mut evaluation.stack;
+            if stack.last().is_some_and(|(ident, _, _)| *ident == current) {
+                stack.pop();
+            }
+            let caught = if evaluation.fallible.contains(&current) {
+                Some(stack.len())
+            } else {
+                stack
+                    .iter()
+                    .rposition(|(ident, _, _)| evaluation.fallible.contains(ident))
+            };
+            let Some(caught) = caught else {
+                return Err(err);
+            };
+            let message = format!("{err:#}");
+            let unwound = stack
+                .split_off(caught)
+                .into_iter()
+                .map(|(ident, _, _)| ident);
+            for ident in std::iter::once(current).chain(unwound) {
+                evaluation.failed.insert(ident, message.clone());
+            }
+        }
+    }
+
+    /// Timeout and the instant an evaluation starting now exceeds it, see
+    /// [CcoDocumentBuilder::timeout]
+    fn deadline(&self) -> anyhow::Result<Option<(std::time::Duration, std::time::Instant)>> {
+        let Some(timeout) = self.options.timeout else {
+            return Ok(None);
+        };
+        // `Instant::now` panics on targets without a clock
+        if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+            anyhow::bail!("timeouts are not supported on wasm32-unknown-unknown");
+        }
+        Ok(Some((timeout, std::time::Instant::now() + timeout)))
+    }
+
+    /// Start time of an addressable, only taken when observed: `Instant` is not available on
+    /// every target
+    fn now(&self) -> Option<std::time::Instant> {
+        self.observer().map(|_| std::time::Instant::now())
+    }
+
+    /// Evaluate `expression` of `current` a step further, the value once the output is done
+    fn evaluate_step(
+        &self,
+        evaluation: &mut Evaluation,
+        memo: Option<&Memo>,
+        current: hcl::Identifier,
+        mut expression: hcl::Expression,
+        started: Option<std::time::Instant>,
+    ) -> anyhow::Result<Option<crate::value::Value>> {
+        let Evaluation {
+            context,
+            stack,
+            resolved_variables,
+            steps,
+            deadline,
+            fallible,
+            failed,
+        } = evaluation;
+
+        *steps += 1;
+        if let Some(limit) = self.options.max_steps.filter(|limit| *steps > *limit) {
+            let path = self.describe_subst(&current);
+            return Err(LimitExceeded::Steps { limit, path }.into());
+        }
+
+        if let Some((limit, _)) = (*deadline).filter(|(_, at)| std::time::Instant::now() >= *at) {
+            let path = self.describe_subst(&current);
+            return Err(LimitExceeded::Timeout { limit, path }.into());
+        }
+
+        // paths of files are relative to the document of the addressable
+        #[cfg(feature = "fs")]
+        let _file_dir = crate::functions::enter_file_dir(
+            self.get_by_subst(&current)
+                .and_then(|addressable| addressable.origin.source.as_deref())
+                .and_then(std::path::Path::parent),
+        );
+
+        crate::arithmetic::check_overflow(&mut expression, context, self.options.overflow)
+            .map_err(|operation| ArithmeticError::Overflow {
+                operation,
+                path: self.describe_subst(&current),
+            })?;
+        crate::indexing::resolve_indices(&mut expression, context)
+            .map_err(|err| anyhow::anyhow!("{err} (in {})", self.describe_subst(&current)))?;
+
+        // `try` and `can` first, their arguments may fail. The resolver gets a chance with each
+        // unknown variable before an argument counts as failed.
+        let resolvable = |var: &hcl::Identifier| {
+            self.options.resolver.is_some()
+                && !resolved_variables.contains(&(current.to_string(), var.to_string()))
+        };
+        let missing =
+            crate::functions::resolve_fallbacks(&mut expression, context, &resolvable, failed)
+                .map_err(|err| anyhow::anyhow!("{err} (in {})", self.describe_subst(&current)))?;
 
-        while let Some((current, mut expression)) = stack.pop() {
-            let Err(eval_errors) = expression.evaluate_in_place(&context) else {
+        let var = if let Some(var) = missing {
+            stack.push((current, expression, started));
+            if !var.starts_with(SUBST_PREFIX) {
+                let (frame, expression, _) = stack.last_mut().expect("was just pushed");
+                self.resolve_variable(frame, expression, &var, resolved_variables);
+                return Ok(None);
+            }
+            fallible.insert(var.clone());
+            var
+        } else {
+            let Err(eval_errors) = expression.evaluate_in_place(context) else {
                 if stack.is_empty() {
-                    return Ok(expression.into());
+                    crate::arithmetic::check_range(&mut expression, self.options.overflow)
+                        .map_err(|value| ArithmeticError::OutOfRange {
+                            value,
+                            path: self.describe_subst(&current),
+                        })?;
+                    return Ok(Some(expression.into()));
+                }
+
+                if let (Some(observer), Some(started)) = (self.observer(), started) {
+                    if let Some(addressable) = self.get_by_subst(&current) {
+                        observer.on_resolve(&addressable.path, addressable.kind, started.elapsed());
+                    }
+                }
+
+                if let Some(memo) = memo {
+                    memo.insert(current.clone(), expression.clone());
                 }
 
                 context.declare_var(current, expression);
-                continue;
+                return Ok(None);
             };
 
             // we did not succeed
-            stack.push((current, expression));
+            stack.push((current, expression, started));
 
-            if let Some(err) = eval_errors.iter().next() {
-                let ErrorKind::UndefinedVar(var) = err.kind() else {
-                    // some other error
-                    return Err(eval_errors.into());
-                };
+            let Some(err) = eval_errors.iter().next() else {
+                panic!("evaluation errored but no error was returned");
+            };
+            let ErrorKind::UndefinedVar(var) = err.kind() else {
+                // some other error
+                return Err(eval_errors.into());
+            };
 
-                if !var.starts_with("cco__") {
-                    // unknown identifier
-                    return Err(eval_errors.into());
+            if !var.starts_with(SUBST_PREFIX) {
+                let (frame, expression, _) = stack.last_mut().expect("was just pushed");
+                if self.resolve_variable(frame, expression, var, resolved_variables) {
+                    return Ok(None);
                 }
 
-                if stack
-                    .iter()
-                    .any(|(ident, _)| ident.as_str() == var.as_str())
-                {
-                    // loop detected
-                    dbg!(stack);
-                    if let Some(resolved_addressable) = self.get_by_subst(var) {
-                        anyhow::bail!("Loop detected at {:?} ({var})", resolved_addressable.path);
-                    } else {
-                        anyhow::bail!("Loop detected {var}");
-                    }
-                }
+                // unknown identifier
+                return Err(eval_errors.into());
+            }
 
-                let Some(expr) = self.get_by_subst_and_rewrite(&var) else {
-                    anyhow::bail!("Missing internal dependency {var}");
-                };
+            // failed before, for a `try` or `can`
+            if let Some(message) = failed.get(var) {
+                anyhow::bail!("{message}");
+            }
+
+            var.clone()
+        };
 
-                stack.push((var.clone(), expr));
+        if stack
+            .iter()
+            .any(|(ident, _, _)| ident.as_str() == var.as_str())
+        {
+            // loop detected, only the chain of references: expressions may be sensitive
+            tracing::debug!(
+                stack = ?stack.iter().map(|(ident, _, _)| ident.as_str()).collect::<Vec<_>>(),
+                "loop detected"
+            );
+            if let Some(resolved_addressable) = self.get_by_subst(&var) {
+                anyhow::bail!("Loop detected at {:?} ({var})", resolved_addressable.path);
             } else {
-                panic!("evaluation errored but no error was returned");
+                anyhow::bail!("Loop detected {var}");
             }
         }
 
-        unreachable!();
-    }
-}
+        let memoized = memo.and_then(|memo| memo.get(&var));
+        if let Some(value) = memoized {
+            context.declare_var(var.clone(), value);
+            return Ok(None);
+        }
 
-#[derive(derive_new::new, Debug)]
-pub struct DataGroup {
-    #[new(default)]
-    pub data_blocks: Vec<DataBlock>,
-}
+        let (referenced_by, _, _) = stack.last().expect("was just pushed");
+        self.report_deprecated(&var, referenced_by);
 
-#[derive(Debug)]
-pub struct DataBlock {
-    pub identifiers: Vec<hcl::Identifier>,
-    pub block_index: usize,
-}
+        let Some(expr) = self.get_by_subst_and_rewrite(&var) else {
+            anyhow::bail!("Missing internal dependency {var}");
+        };
 
-// FIXME: Revisit if this is a good idea. A DataBlock must be unique in its labels, so this should be ok.
-impl PartialEq for DataBlock {
-    fn eq(&self, other: &Self) -> bool {
-        self.identifiers.eq(&other.identifiers)
+        if let Some(limit) = self.options.max_depth.filter(|limit| stack.len() > *limit) {
+            let path = self.describe_subst(&var);
+            return Err(LimitExceeded::Depth { limit, path }.into());
+        }
+
+        stack.push((var, expr, self.now()));
+        Ok(None)
     }
-}
 
-impl DataBlock {
-    pub fn new(block_index: usize, block: &hcl_edit::structure::Block) -> Self {
-        let identifiers: Vec<_> = block
-            .labels
-            .iter()
-            .map(hcl::Identifier::sanitized)
-            .collect();
+    /// Replace `var` in `expression` of `frame` with what the resolver returns for it, asks the
+    /// resolver only once per frame and variable. Whether anything was replaced.
+    fn resolve_variable(
+        &self,
+        frame: &hcl::Identifier,
+        expression: &mut hcl::Expression,
+        var: &hcl::Identifier,
+        resolved_variables: &mut std::collections::HashSet<(String, String)>,
+    ) -> bool {
+        let Some(resolver) = &self.options.resolver else {
+            return false;
+        };
+        if !resolved_variables.insert((frame.to_string(), var.to_string())) {
+            return false;
+        }
+        let mut resolver_rewriter = ResolverRewriter::new(var, &*resolver.0);
+        expression.visit_traversals_mut(&mut resolver_rewriter);
+        if resolver_rewriter.substitutions == 0 {
+            return false;
+        }
+        let mut dependency_writer = AttributeReferenceRewriter::new(self);
+        expression.visit_traversals_mut(&mut dependency_writer);
+        self.options.externals.rewrite(expression);
+        true
+    }
 
-        assert!(
-            !identifiers.is_empty(),
-            "data block labels must not be empty"
+    /// Evaluate the value at a path
+    ///
+    /// Shorthand for evaluating a traversal like `a.b.c`. Segments are sanitized the same way
+    /// block labels are.
+    pub fn evaluate_path<S: AsRef<str>>(&self, path: &[S]) -> anyhow::Result<crate::value::Value> {
+        let Some((first, rest)) = path.split_first() else {
+            anyhow::bail!("path must not be empty");
+        };
+
+        let traversal = rest.iter().fold(
+            hcl::Traversal::builder(hcl::Variable::sanitized(first.as_ref())),
+            |traversal, segment| traversal.attr(hcl::Identifier::sanitized(segment.as_ref())),
         );
 
-        Self {
-            block_index,
-            identifiers,
-        }
+        self.evaluate_in_context(traversal.build().into())
     }
-}
 
-#[derive(derive_new::new, Debug)]
-pub struct CcoParseErrors {
-    #[new(default)]
-    issues: Vec<Issue>,
-}
+    /// Evaluate lazily while serializing
+    ///
+    /// [CcoDocument::evaluate_in_context] builds the whole [crate::value::Value] before it can be
+    /// serialized. The returned [StreamingValue] instead expands objects, arrays and references to
+    /// addressables one element at a time while they are written, so only a single element needs
+    /// to be held in memory at any point.
+    ///
+    /// Trade-off: intermediate results are not shared between elements. Expressions that are
+    /// referenced from multiple elements are evaluated multiple times.
+    pub fn evaluate_streaming(&self, mut expression: hcl::Expression) -> StreamingValue<'_> {
+        let mut dependency_writer = AttributeReferenceRewriter::new(self);
+        expression.visit_traversals_mut(&mut dependency_writer);
 
-impl CcoParseErrors {
-    pub fn log(&mut self, issue: Issue) {
-        tracing::trace!(?issue, "issue found");
-        self.issues.push(issue);
+        StreamingValue {
+            document: self,
+            expression,
+            stack: vec![],
+        }
     }
-}
 
-impl std::error::Error for CcoParseErrors {}
+    /// Validate an expression without evaluating it
+    ///
+    /// Parses `expression`, rewrites references to addressable elements and reports every
+    /// referenced path together with what it resolves to. Variables bound by for-expressions are
+    /// local and not reported.
+    ///
+    /// The [CcoDocumentBuilder::resolver] is consulted for paths that are neither addressable
+    /// nor declared variables, so it should be cheap and free of side effects.
+    pub fn check_expression(
+        &self,
+        expression: &str,
+    ) -> Result<ExpressionInfo<'_>, ParseOrRefError> {
+        let mut expression = crate::hcl_documents::parse_expression(expression)?;
 
-impl std::fmt::Display for CcoParseErrors {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use std::fmt::Debug;
-        self.issues.first().unwrap().fmt(f)
-    }
-}
+        let references = referenced_paths(&mut expression)
+            .into_iter()
+            .map(|path| {
+                let target = self.reference_target(&path);
+                Reference { path, target }
+            })
+            .collect();
 
-#[derive(Debug, PartialEq)]
-pub enum Issue {
-    RootAttribute(usize),
-    UnknownBlockType(usize),
-    DataBlockLabelMissing(usize),
-    DataBlockLabelCollision { existing: usize, new: usize },
-    DataBlockLabelMismatch { existing: usize, new: usize },
-    TypeBlockLabelMissing(usize),
-    TypeBlockTooManyLabels(usize),
-    TypeBlockLabelCollision { existing: usize, new: usize },
-}
+        let mut dependency_writer = AttributeReferenceRewriter::new(self);
+        expression.visit_traversals_mut(&mut dependency_writer);
 
-#[derive(Debug, Default)]
-pub struct Tree {
-    pub root: indexmap::IndexMap<hcl::Identifier, Node>,
-}
+        Ok(ExpressionInfo {
+            expression,
+            references,
+        })
+    }
 
-impl Tree {
-    fn get<'a>(
-        &'a self,
-        key_path: &'a [hcl::Identifier],
-    ) -> Option<(usize, &'a [hcl::Identifier])> {
-        if key_path.is_empty() {
-            return None;
+    /// Paths referenced by the element at `path`, in order of first occurrence
+    ///
+    /// `self` references are resolved. Blocks and data groups are made of their children and
+    /// reference nothing themselves. Returns `None` if there is no element at `path`.
+    pub fn references(&self, path: &[hcl::Identifier]) -> Option<Vec<Vec<hcl::Identifier>>> {
+        let index = self.tree.node(path)?.value?;
+        Some(self.references_of(&self.addressables[index]))
+    }
+
+    fn references_of(&self, addressable: &Addressable) -> Vec<Vec<hcl::Identifier>> {
+        if matches!(addressable.kind, Kind::Block | Kind::Virtual) {
+            return vec![];
         }
 
-        self.root
-            .get(&key_path[0])
-            .and_then(|child| child.get(&key_path[1..]))
+        let mut expression = addressable.expression.clone();
+        let block_path = &addressable.path[0..(addressable.path.len() - 1)];
+        expression.visit_traversals_mut(&mut SelfRewriter::new(block_path));
+        referenced_paths(&mut expression)
     }
 
-    fn get_or_insert(&mut self, key_path: &[hcl::Identifier]) -> &mut Node {
-        let key = &key_path[0];
+    /// Elements whose value depends on `path`, directly or through other elements
+    ///
+    /// A reference matches if it or `path` is a prefix of the other: both `service.api` and
+    /// `service.api.port.number` depend on `service.api.port`. Direct dependents come first.
+    pub fn dependents(&self, path: &[hcl::Identifier]) -> Vec<Dependent<'_>> {
+        let references: Vec<_> = self
+            .addressables
+            .iter()
+            .map(|addressable| self.references_of(addressable))
+            .collect();
 
-        let child = if self.root.contains_key(key) {
-            self.root.get_mut(key).unwrap()
-        } else {
-            self.root.insert(key.clone(), Default::default());
-            self.root.last_mut().map(|(_key, value)| value).unwrap()
-        };
+        let mut seen: std::collections::HashSet<usize> = self
+            .tree
+            .node(path)
+            .and_then(|node| node.value)
+            .into_iter()
+            .collect();
+        let mut dependents: Vec<Dependent<'_>> = vec![];
+        let mut targets = vec![(path, None)];
+        while !targets.is_empty() {
+            let mut next = vec![];
+            for (target, via) in targets {
+                for (index, paths) in references.iter().enumerate() {
+                    if seen.contains(&index) {
+                        continue;
+                    }
+                    let overlaps = |reference: &&Vec<hcl::Identifier>| {
+                        reference.starts_with(target) || target.starts_with(reference)
+                    };
+                    let Some(reference) = paths.iter().find(overlaps) else {
+                        continue;
+                    };
 
-        child.get_or_insert(&key_path[1..])
+                    seen.insert(index);
+                    let addressable = &self.addressables[index];
+                    next.push((
+                        addressable.path.as_slice(),
+                        Some(addressable.path.as_slice()),
+                    ));
+                    dependents.push(Dependent {
+                        info: addressable.info(),
+                        reference: reference.clone(),
+                        via,
+                    });
+                }
+            }
+            targets = next;
+        }
+        dependents
     }
-}
 
-#[derive(Debug, derive_new::new)]
-pub struct Node {
-    pub value: Option<usize>,
-    #[new(default)]
-    pub children: indexmap::IndexMap<hcl::Identifier, Node>,
-}
+    /// Elements nothing references, ordered by source and position
+    ///
+    /// An element is used if an element outside of it or one of the `entry_points` references
+    /// it, a prefix or a part of it. Only the outermost unused elements are returned, data groups
+    /// are represented by their blocks.
+    pub fn unused(&self, entry_points: &[Vec<hcl::Identifier>]) -> Vec<AddressableInfo<'_>> {
+        let mut references: Vec<(Option<&[hcl::Identifier]>, Vec<hcl::Identifier>)> = entry_points
+            .iter()
+            .map(|path| (None, path.clone()))
+            .collect();
+        for addressable in &self.addressables {
+            for path in self.references_of(addressable) {
+                references.push((Some(addressable.path.as_slice()), path));
+            }
+        }
+
+        let is_unused = |addressable: &Addressable| {
+            addressable.kind != Kind::Virtual
+                && !references.iter().any(|(referencing, path)| {
+                    let outside = referencing
+                        .is_none_or(|referencing| !referencing.starts_with(&addressable.path));
+                    outside
+                        && (path.starts_with(&addressable.path)
+                            || addressable.path.starts_with(path))
+                })
+        };
+        let unused: std::collections::HashSet<_> = self
+            .addressables
+            .iter()
+            .filter(|addressable| is_unused(addressable))
+            .map(|addressable| addressable.path.as_slice())
+            .collect();
+
+        let mut outermost: Vec<_> = self
+            .addressables
+            .iter()
+            .filter(|addressable| {
+                unused.contains(addressable.path.as_slice())
+                    && !(1..addressable.path.len())
+                        .any(|len| unused.contains(&addressable.path[..len]))
+            })
+            .map(Addressable::info)
+            .collect();
+        outermost.sort_by_key(|info| (info.source, info.span.as_ref().map(|span| span.start)));
+        outermost
+    }
+
+    /// References nothing provides a value for, in definition order
+    ///
+    /// Like [CcoDocument::check_expression] for the expressions of all elements, without
+    /// evaluating them: typos are found even in elements nothing uses. Blocks and data groups are
+    /// objects of exactly their children, paths below them no child provides are unresolved too.
+    pub fn unresolved(&self) -> Vec<Unresolved<'_>> {
+        let is_unresolved = |path: &Vec<hcl::Identifier>| match self.tree.get(path) {
+            Some((index, rest)) => {
+                !rest.is_empty()
+                    && matches!(self.addressables[index].kind, Kind::Block | Kind::Virtual)
+            }
+            None => self.reference_target(path).is_none(),
+        };
+
+        self.addressables
+            .iter()
+            .flat_map(|addressable| {
+                self.references_of(addressable)
+                    .into_iter()
+                    .filter(&is_unresolved)
+                    .map(|reference| Unresolved {
+                        info: addressable.info(),
+                        reference,
+                    })
+            })
+            .collect()
+    }
+
+    fn reference_target(&self, path: &[hcl::Identifier]) -> Option<ReferenceTarget<'_>> {
+        if let Some((index, _)) = self.tree.get(path) {
+            return Some(ReferenceTarget::Addressable(
+                self.addressables[index].info(),
+            ));
+        }
+
+        if self.options.variables.contains_key(&path[0]) {
+            return Some(ReferenceTarget::Variable);
+        }
+
+        let resolver = self.options.resolver.as_ref()?;
+        (1..=path.len())
+            .rev()
+            .any(|len| (resolver.0)(&path[..len]).is_some())
+            .then_some(ReferenceTarget::Resolver)
+    }
+}
+
+/// Paths referenced by `expression`, without variables of `for` expressions
+fn referenced_paths(expression: &mut hcl::Expression) -> Vec<Vec<hcl::Identifier>> {
+    let mut locals = std::collections::HashSet::new();
+    crate::util::local_variables(expression, &mut locals);
+
+    let mut paths: Vec<Vec<hcl::Identifier>> = vec![];
+    expression.visit_traversals_mut(&mut |traversal: &mut hcl::Traversal| {
+        let path = traversal.get_longest_path();
+        if !path.is_empty() && !locals.contains(path[0].as_str()) && !paths.contains(&path) {
+            paths.push(path);
+        }
+    });
+    paths
+}
+
+/// An element returned by [CcoDocument::dependents]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependent<'d> {
+    pub info: AddressableInfo<'d>,
+    /// The referenced path as written
+    pub reference: Vec<hcl::Identifier>,
+    /// The dependent element this one references, `None` for direct dependents
+    pub via: Option<&'d [hcl::Identifier]>,
+}
+
+/// A reference returned by [CcoDocument::unresolved]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unresolved<'d> {
+    /// The element referencing the path
+    pub info: AddressableInfo<'d>,
+    /// The referenced path as written, `self` references resolved
+    pub reference: Vec<hcl::Identifier>,
+}
+
+/// Result of [CcoDocument::check_expression]
+#[derive(Debug, Clone)]
+pub struct ExpressionInfo<'d> {
+    /// The parsed expression with references to addressable elements rewritten
+    pub expression: hcl::Expression,
+    /// Every referenced path, in order of first occurrence
+    pub references: Vec<Reference<'d>>,
+}
+
+impl<'d> ExpressionInfo<'d> {
+    pub fn resolved(&self) -> impl Iterator<Item = &Reference<'d>> {
+        self.references.iter().filter(|r| r.target.is_some())
+    }
+
+    pub fn unresolved(&self) -> impl Iterator<Item = &Reference<'d>> {
+        self.references.iter().filter(|r| r.target.is_none())
+    }
+
+    /// Turn unresolved references into an error
+    pub fn ensure_resolved(self) -> Result<Self, ParseOrRefError> {
+        let unresolved: Vec<_> = self.unresolved().map(|r| r.path.join(".")).collect();
+        if unresolved.is_empty() {
+            Ok(self)
+        } else {
+            Err(ParseOrRefError::Unresolved(unresolved))
+        }
+    }
+}
+
+/// A path referenced by an expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference<'d> {
+    /// The path as written, up to the first non-attribute operator
+    pub path: Vec<hcl::Identifier>,
+    /// `None` if nothing provides a value for this path
+    pub target: Option<ReferenceTarget<'d>>,
+}
+
+/// What a [Reference] resolves to
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReferenceTarget<'d> {
+    /// The most specific addressable element containing the path
+    Addressable(AddressableInfo<'d>),
+    /// A variable declared with [CcoDocumentBuilder::variable]
+    Variable,
+    /// Provided by the [CcoDocumentBuilder::resolver]
+    Resolver,
+}
+
+/// An evaluation limit of [CcoDocumentBuilder] was hit
+///
+/// Returned (wrapped in [anyhow::Error]) by the evaluation methods of [CcoDocument].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LimitExceeded {
+    #[error("evaluation depth limit of {limit} exceeded at {path}")]
+    Depth { limit: usize, path: String },
+    #[error("evaluation step limit of {limit} exceeded at {path}")]
+    Steps { limit: usize, path: String },
+    #[error("evaluation timed out after {limit:?} at {path}")]
+    Timeout {
+        limit: std::time::Duration,
+        path: String,
+    },
+}
+
+/// Behavior of integer arithmetic leaving the `i64` range, see [CcoDocumentBuilder::overflow]
+///
+/// There is no promotion to decimals: HCL numbers store whole decimals as integers, a result like
+/// `2 * 9223372036854775807` has no representation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Fail with [ArithmeticError]
+    #[default]
+    Error,
+    /// Continue with the closest integer, `i64::MAX` or `i64::MIN`
+    Saturate,
+}
+
+/// Handling of data blocks with the same labels, see [CcoDocumentBuilder::merge_policy]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Report them as [Issue::DataBlockLabelCollision]
+    #[default]
+    Reject,
+    /// Merge their attributes, attributes of blocks loaded later override earlier ones
+    ///
+    /// [CcoDocument::definitions] lists the overridden definitions.
+    Override,
+}
+
+/// Integer arithmetic left the `i64` range with [OverflowPolicy::Error]
+///
+/// Returned (wrapped in [anyhow::Error]) by the evaluation methods of [CcoDocument].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ArithmeticError {
+    #[error("integer overflow in `{operation}` at {path}")]
+    Overflow { operation: String, path: String },
+    #[error("integer {value} out of range at {path}")]
+    OutOfRange { value: String, path: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseOrRefError {
+    #[error("failed to parse expression: {0}")]
+    Parse(#[from] hcl_edit::parser::Error),
+    #[error("unresolved references: {}", .0.join(", "))]
+    Unresolved(Vec<String>),
+}
+
+/// Name of the external function evaluating data source blocks of `kind`
+fn data_source_function(kind: &str) -> hcl::Identifier {
+    hcl::Identifier::sanitized(format!("{SUBST_PREFIX}source_{kind}"))
+}
+
+/// State of [CcoDocument::evaluate_memoized]
+struct Evaluation {
+    context: hcl::eval::Context<'static>,
+    /// Expressions waiting for the addressables above them, the top one is evaluated next
+    stack: Vec<(hcl::Identifier, hcl::Expression, Option<std::time::Instant>)>,
+    /// Per addressable: the resolver is asked once for each of its variables
+    resolved_variables: std::collections::HashSet<(String, String)>,
+    steps: usize,
+    deadline: Option<(std::time::Duration, std::time::Instant)>,
+    /// Addressables `try` or `can` wait for, their errors are caught
+    fallible: std::collections::HashSet<hcl::Identifier>,
+    /// Errors of the addressables that failed for a `try` or `can`
+    failed: std::collections::HashMap<hcl::Identifier, String>,
+}
+
+/// Evaluated addressables by substitution identifier
+#[derive(Debug, Default)]
+pub(crate) struct Memo {
+    values: std::sync::RwLock<std::collections::HashMap<hcl::Identifier, hcl::Expression>>,
+    /// lookups answered from `values`
+    pub(crate) hits: std::sync::atomic::AtomicU64,
+    /// lookups of addressables that had to be evaluated
+    pub(crate) misses: std::sync::atomic::AtomicU64,
+}
+
+impl Memo {
+    fn get(&self, subst: &hcl::Identifier) -> Option<hcl::Expression> {
+        use std::sync::atomic::Ordering::Relaxed;
+        let value = self
+            .values
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(subst)
+            .cloned();
+        match value {
+            Some(_) => self.hits.fetch_add(1, Relaxed),
+            None => self.misses.fetch_add(1, Relaxed),
+        };
+        value
+    }
+
+    fn insert(&self, subst: hcl::Identifier, value: hcl::Expression) {
+        self.values
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(subst, value);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.values
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .len()
+    }
+}
+
+/// Lazily evaluated expression, see [CcoDocument::evaluate_streaming]
+///
+/// Evaluation errors are reported as serialization errors.
+pub struct StreamingValue<'d> {
+    document: &'d CcoDocument,
+    expression: hcl::Expression,
+    /// addressables currently being expanded (used to stop at reference loops)
+    stack: Vec<hcl::Identifier>,
+}
+
+impl<'d> StreamingValue<'d> {
+    fn child(&self, expression: hcl::Expression) -> Self {
+        Self {
+            document: self.document,
+            expression,
+            stack: self.stack.clone(),
+        }
+    }
+}
+
+impl<'d> serde::Serialize for StreamingValue<'d> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::{Error, SerializeMap, SerializeSeq};
+
+        match &self.expression {
+            hcl::Expression::Variable(var)
+                if var.starts_with(SUBST_PREFIX) && !self.stack.contains(var) =>
+            {
+                if let Some(expression) = self.document.get_by_subst_and_rewrite(var) {
+                    let mut child = self.child(expression);
+                    child.stack.push((**var).clone());
+                    return child.serialize(serializer);
+                }
+            }
+            hcl::Expression::Array(array) => {
+                let mut ser = serializer.serialize_seq(Some(array.len()))?;
+                for element in array {
+                    ser.serialize_element(&self.child(element.clone()))?;
+                }
+                return ser.end();
+            }
+            hcl::Expression::Object(object) if object.keys().all(is_literal_key) => {
+                let mut ser = serializer.serialize_map(Some(object.len()))?;
+                for (key, value) in object {
+                    let key = match key {
+                        hcl::ObjectKey::Expression(hcl::Expression::String(key)) => key.clone(),
+                        key => key.to_string(),
+                    };
+                    ser.serialize_entry(&key, &self.child(value.clone()))?;
+                }
+                return ser.end();
+            }
+            _ => {}
+        }
+
+        self.document
+            .evaluate_in_context(self.expression.clone())
+            .map_err(|err| S::Error::custom(format!("{err:#}")))?
+            .serialize(serializer)
+    }
+}
+
+fn is_literal_key(key: &hcl::ObjectKey) -> bool {
+    matches!(
+        key,
+        hcl::ObjectKey::Identifier(_) | hcl::ObjectKey::Expression(hcl::Expression::String(_))
+    )
+}
+
+#[derive(derive_new::new, Debug)]
+pub struct DataGroup {
+    #[new(default)]
+    pub data_blocks: Vec<DataBlock>,
+}
+
+#[derive(Debug)]
+pub struct DataBlock {
+    /// Namespace followed by the sanitized labels
+    pub identifiers: Vec<hcl::Identifier>,
+    pub block_index: usize,
+    /// Number of leading namespace identifiers, see [HclDocuments::nest]
+    pub namespace: usize,
+    /// Later blocks with the same labels, merged with [MergePolicy::Override]
+    pub overrides: Vec<usize>,
+}
+
+// FIXME: Revisit if this is a good idea. A DataBlock must be unique in its labels, so this should be ok.
+impl PartialEq for DataBlock {
+    fn eq(&self, other: &Self) -> bool {
+        self.identifiers.eq(&other.identifiers)
+    }
+}
+
+impl DataBlock {
+    pub fn new(
+        block_index: usize,
+        block: &hcl_edit::structure::Block,
+        namespace: &[hcl::Identifier],
+    ) -> Self {
+        assert!(
+            !block.labels.is_empty(),
+            "data block labels must not be empty"
+        );
+
+        let identifiers: Vec<_> = namespace
+            .iter()
+            .cloned()
+            .chain(block.labels.iter().map(hcl::Identifier::sanitized))
+            .collect();
+
+        Self {
+            block_index,
+            identifiers,
+            namespace: namespace.len(),
+            overrides: vec![],
+        }
+    }
+
+    /// Path of the data group: namespace and first label
+    pub fn group(&self) -> &[hcl::Identifier] {
+        &self.identifiers[..=self.namespace]
+    }
+
+    /// The block and its [DataBlock::overrides], in load order
+    pub fn block_indices(&self) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        std::iter::once(self.block_index).chain(self.overrides.iter().copied())
+    }
+}
+
+/// Parsed [DEPRECATED_ATTRIBUTE]
+enum Deprecations {
+    Block(String),
+    Attributes(Vec<(hcl::Identifier, String)>),
+}
+
+fn parse_deprecations(expression: hcl::Expression) -> Option<Deprecations> {
+    match expression {
+        hcl::Expression::String(message) => Some(Deprecations::Block(message)),
+        hcl::Expression::Object(object) => object
+            .into_iter()
+            .map(|(key, value)| {
+                let key = match key {
+                    hcl::ObjectKey::Identifier(ident) => ident,
+                    hcl::ObjectKey::Expression(hcl::Expression::String(key)) => {
+                        hcl::Identifier::sanitized(key)
+                    }
+                    _ => return None,
+                };
+                let hcl::Expression::String(message) = value else {
+                    return None;
+                };
+                Some((key, message))
+            })
+            .collect::<Option<_>>()
+            .map(Deprecations::Attributes),
+        _ => None,
+    }
+}
+
+/// Parsed [SENSITIVE_ATTRIBUTE]
+enum Sensitive {
+    Block(bool),
+    Attributes(Vec<hcl::Identifier>),
+}
+
+fn parse_sensitive(expression: hcl::Expression) -> Option<Sensitive> {
+    match expression {
+        hcl::Expression::Bool(sensitive) => Some(Sensitive::Block(sensitive)),
+        hcl::Expression::Array(names) => names
+            .into_iter()
+            .map(|name| match name {
+                hcl::Expression::String(name) => Some(hcl::Identifier::sanitized(name)),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+            .map(Sensitive::Attributes),
+        _ => None,
+    }
+}
+
+/// Block indices of the partials named by [INCLUDE_PARTIALS_ATTRIBUTE], `None` if it is not a list
+/// of known partial names
+/// Partials of the namespace take precedence over root partials of the same name
+fn parse_include_partials(
+    expression: hcl::Expression,
+    namespace: &[hcl::Identifier],
+    partials: &std::collections::HashMap<String, usize>,
+) -> Option<Vec<usize>> {
+    let hcl::Expression::Array(names) = expression else {
+        return None;
+    };
+    names
+        .into_iter()
+        .map(|name| match name {
+            hcl::Expression::String(name) => partials
+                .get(&partial_name(namespace, &name))
+                .or_else(|| partials.get(&name))
+                .copied(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Key of a partial block, its label prefixed by the namespace
+fn partial_name(namespace: &[hcl::Identifier], label: &str) -> String {
+    namespace
+        .iter()
+        .map(|identifier| identifier.as_str())
+        .chain(std::iter::once(label))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// `alias <labels...> { target = <traversal> }`
+///
+/// References to the labels' path resolve to `target` instead. With `deprecated = true` (or a
+/// message) every use is reported.
+struct AliasBlock {
+    block_index: usize,
+    /// Namespace followed by the sanitized labels
+    identifiers: Vec<hcl::Identifier>,
+    /// Number of leading namespace identifiers
+    namespace: usize,
+    target: hcl::Expression,
+    deprecation: Option<String>,
+}
+
+impl AliasBlock {
+    fn new(
+        block_index: usize,
+        block: &hcl_edit::structure::Block,
+        namespace: &[hcl::Identifier],
+    ) -> Option<Self> {
+        let mut target = None;
+        let mut deprecated = hcl::Expression::Bool(false);
+        for attribute in block.body.attributes() {
+            let value = hcl::Expression::from(attribute.value.clone());
+            match attribute.key.value().as_str() {
+                "target" => target = Some(value),
+                "deprecated" => deprecated = value,
+                _ => return None,
+            }
+        }
+
+        let target = target?;
+        if !matches!(
+            target,
+            hcl::Expression::Traversal(_) | hcl::Expression::Variable(_)
+        ) {
+            return None;
+        }
+
+        let deprecation = match deprecated {
+            hcl::Expression::Bool(false) => None,
+            hcl::Expression::Bool(true) => Some(format!(
+                "use {} instead",
+                hcl::format::to_string(&target).ok()?
+            )),
+            hcl::Expression::String(message) => Some(message),
+            _ => return None,
+        };
+
+        Some(Self {
+            block_index,
+            identifiers: namespace
+                .iter()
+                .cloned()
+                .chain(block.labels.iter().map(hcl::Identifier::sanitized))
+                .collect(),
+            namespace: namespace.len(),
+            target,
+            deprecation,
+        })
+    }
+}
+
+#[derive(derive_new::new, Debug)]
+pub struct CcoParseErrors {
+    #[new(default)]
+    issues: Vec<Issue>,
+    /// One message per issue, see [CcoParseErrors::describe]
+    #[new(default)]
+    messages: Vec<String>,
+}
+
+impl CcoParseErrors {
+    pub fn log(&mut self, issue: Issue) {
+        tracing::trace!(?issue, "issue found");
+        self.issues.push(issue);
+    }
+
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    /// Rendered [CcoParseErrors::issues], in the same order
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+
+    /// Whether building has to stop
+    fn fails(&self, deny_warnings: bool) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| deny_warnings || issue.severity() == Severity::Error)
+    }
+
+    /// Described errors, warnings are dropped unless they are denied
+    fn into_failure(mut self, deny_warnings: bool, hcl_documents: &HclDocuments) -> Self {
+        if !deny_warnings {
+            self.issues
+                .retain(|issue| issue.severity() == Severity::Error);
+        }
+        self.describe(hcl_documents);
+        self
+    }
+
+    /// Renders the issues with the locations of the blocks involved
+    fn describe(&mut self, hcl_documents: &HclDocuments) {
+        self.messages = self
+            .issues
+            .iter()
+            .map(|issue| issue.describe(hcl_documents))
+            .collect();
+    }
+}
+
+impl std::error::Error for CcoParseErrors {}
+
+impl std::fmt::Display for CcoParseErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.messages.is_empty() {
+            return write!(f, "{:?}", self.issues);
+        }
+        for (i, message) in self.messages.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            f.write_str(message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Labels seen so far, by their lowercase form
+///
+/// Every prefix of a block's labels is tracked so that differences on any level are found.
+#[derive(Default)]
+struct LabelCases(std::collections::HashMap<Vec<String>, (Vec<hcl::Identifier>, usize)>);
+
+impl LabelCases {
+    fn check(&mut self, identifiers: &[hcl::Identifier], index: usize, e: &mut CcoParseErrors) {
+        for len in 1..=identifiers.len() {
+            let prefix = &identifiers[..len];
+            let key = prefix.iter().map(|i| i.to_lowercase()).collect();
+            match self.0.get(&key) {
+                Some((existing_prefix, existing)) if existing_prefix.as_slice() != prefix => {
+                    e.log(Issue::LabelCaseCollision {
+                        existing: *existing,
+                        new: index,
+                    });
+                    return;
+                }
+                Some(_) => {}
+                None => {
+                    self.0.insert(key, (prefix.to_vec(), index));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Issue {
+    RootAttribute(usize),
+    UnknownBlockType(usize),
+    BlockTypeNotAllowed(usize),
+    DataBlockLabelMissing(usize),
+    DataBlockLabelCollision {
+        existing: usize,
+        new: usize,
+    },
+    DataBlockLabelMismatch {
+        existing: usize,
+        new: usize,
+    },
+    TypeBlockLabelMissing(usize),
+    TypeBlockTooManyLabels(usize),
+    TypeBlockLabelCollision {
+        existing: usize,
+        new: usize,
+    },
+    /// Root attributes of the same name, see [CcoDocumentBuilder::globals]
+    RootAttributeCollision {
+        existing: usize,
+        new: usize,
+    },
+    /// Labels that only differ in case, see [CcoDocumentBuilder::label_case_collisions]
+    LabelCaseCollision {
+        existing: usize,
+        new: usize,
+    },
+    /// Root identifier starting with [SUBST_PREFIX]
+    ReservedIdentifier(usize),
+    AliasBlockLabelMissing(usize),
+    /// `target` is missing or not a traversal, `deprecated` is neither bool nor string, or
+    /// an unknown attribute is present
+    AliasBlockInvalid(usize),
+    /// The alias path is already in use
+    AliasCollision(usize),
+    /// [DEPRECATED_ATTRIBUTE] is neither a string nor an object of strings naming attributes
+    DeprecatedAttributeInvalid(usize),
+    /// [SENSITIVE_ATTRIBUTE] is neither a bool nor a list of strings naming attributes
+    SensitiveAttributeInvalid(usize),
+    /// A [CcoDocumentBuilder::data_source] block without labels
+    DataSourceLabelMissing(usize),
+    /// A [CcoDocumentBuilder::data_source] block with nested blocks
+    DataSourceInvalid(usize),
+    /// The data source path is already in use
+    DataSourceCollision(usize),
+    /// A `partial` block without exactly one label or with nested blocks
+    PartialBlockInvalid(usize),
+    PartialBlockLabelCollision {
+        existing: usize,
+        new: usize,
+    },
+    /// [INCLUDE_PARTIALS_ATTRIBUTE] is not a list of names of `partial` blocks
+    IncludePartialsInvalid(usize),
+    /// A data or type block label that is not a valid identifier, see [hcl::Identifier::sanitized]
+    SanitizedLabel(usize),
+    /// A type block without data blocks of that type
+    UnusedTypeBlock(usize),
+    /// A data group at the path of a namespace or one of its parents, see [HclDocuments::nest]
+    NamespaceCollision {
+        group: usize,
+        namespace: usize,
+    },
+}
+
+/// Whether an [Issue] fails building a [CcoDocument], see [CcoDocumentBuilder::deny_warnings]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Issue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Issue::SanitizedLabel(_) | Issue::UnusedTypeBlock(_) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// Human readable message, collisions name both definitions
+    fn describe(&self, hcl_documents: &HclDocuments) -> String {
+        let block = |index: usize| hcl_documents.get_block(index).2;
+        let at = |index: usize| hcl_documents.block_location(index);
+        // including the namespace of namespaced blocks, see [HclDocuments::nest]
+        let labels = |index: usize| {
+            let namespace = match block(index).ident.value().as_str() {
+                "data" | "type" | "partial" | "alias" => hcl_documents.block_namespace(index),
+                _ => &[],
+            };
+            let labels: Vec<_> = namespace
+                .iter()
+                .map(|identifier| identifier.as_str())
+                .chain(block(index).labels.iter().map(|l| l.as_str()))
+                .collect();
+            labels.join(".")
+        };
+        let kind = |index: usize| block(index).ident.value().to_string();
+
+        match *self {
+            Issue::RootAttribute(index) => format!(
+                "{}: root attribute `{}` is not allowed",
+                hcl_documents.attribute_location(index),
+                hcl_documents.get_attribute(index).2.key.value()
+            ),
+            Issue::UnknownBlockType(index) => {
+                format!("{}: unknown block type `{}`", at(index), kind(index))
+            }
+            Issue::BlockTypeNotAllowed(index) => {
+                format!("{}: block type `{}` is not allowed", at(index), kind(index))
+            }
+            Issue::DataBlockLabelMissing(index) => {
+                format!("{}: data block without labels", at(index))
+            }
+            Issue::DataBlockLabelCollision { existing, new } => format!(
+                "data block `{}` is defined twice: {} and {}{}",
+                labels(new),
+                at(existing),
+                at(new),
+                group_files(hcl_documents, new)
+            ),
+            Issue::DataBlockLabelMismatch { existing, new } => format!(
+                "data block `{}` has {} labels, `{}` has {}: {} and {}{}",
+                labels(new),
+                block(new).labels.len(),
+                labels(existing),
+                block(existing).labels.len(),
+                at(existing),
+                at(new),
+                group_files(hcl_documents, new)
+            ),
+            Issue::TypeBlockLabelMissing(index) => {
+                format!("{}: type block without label", at(index))
+            }
+            Issue::TypeBlockTooManyLabels(index) => {
+                format!("{}: type block with more than one label", at(index))
+            }
+            Issue::RootAttributeCollision { existing, new } => format!(
+                "global `{}` is defined twice: {} and {}",
+                hcl_documents.get_attribute(new).2.key.value(),
+                hcl_documents.attribute_location(existing),
+                hcl_documents.attribute_location(new)
+            ),
+            Issue::TypeBlockLabelCollision { existing, new } => format!(
+                "type `{}` is defined twice: {} and {}",
+                labels(new),
+                at(existing),
+                at(new)
+            ),
+            Issue::LabelCaseCollision { existing, new } => format!(
+                "`{}` and `{}` only differ in case: {} and {}",
+                labels(existing),
+                labels(new),
+                at(existing),
+                at(new)
+            ),
+            Issue::ReservedIdentifier(index) => format!(
+                "{}: identifiers starting with `{SUBST_PREFIX}` are reserved",
+                at(index)
+            ),
+            Issue::AliasBlockLabelMissing(index) => {
+                format!("{}: alias block without labels", at(index))
+            }
+            Issue::AliasBlockInvalid(index) => {
+                format!("{}: invalid alias block `{}`", at(index), labels(index))
+            }
+            Issue::AliasCollision(index) => {
+                format!("{}: alias `{}` is already in use", at(index), labels(index))
+            }
+            Issue::DeprecatedAttributeInvalid(index) => format!(
+                "{}: `{DEPRECATED_ATTRIBUTE}` must be a string or an object of strings",
+                at(index)
+            ),
+            Issue::SensitiveAttributeInvalid(index) => format!(
+                "{}: `{SENSITIVE_ATTRIBUTE}` must be a bool or a list of attribute names",
+                at(index)
+            ),
+            Issue::DataSourceLabelMissing(index) => {
+                format!("{}: `{}` block without labels", at(index), kind(index))
+            }
+            Issue::DataSourceInvalid(index) => format!(
+                "{}: `{}` blocks must not contain blocks",
+                at(index),
+                kind(index)
+            ),
+            Issue::DataSourceCollision(index) => format!(
+                "{}: `{}.{}` is already in use",
+                at(index),
+                kind(index),
+                labels(index)
+            ),
+            Issue::PartialBlockInvalid(index) => format!(
+                "{}: partial blocks need exactly one label and no nested blocks",
+                at(index)
+            ),
+            Issue::PartialBlockLabelCollision { existing, new } => format!(
+                "partial `{}` is defined twice: {} and {}",
+                labels(new),
+                at(existing),
+                at(new)
+            ),
+            Issue::IncludePartialsInvalid(index) => format!(
+                "{}: `{INCLUDE_PARTIALS_ATTRIBUTE}` must be a list of partial block names",
+                at(index)
+            ),
+            Issue::SanitizedLabel(index) => {
+                let sanitized: Vec<_> = block(index)
+                    .labels
+                    .iter()
+                    .map(hcl::Identifier::sanitized)
+                    .map(|identifier| identifier.to_string())
+                    .collect();
+                format!(
+                    "{}: `{}` is not a valid identifier, it is addressed as `{}`",
+                    at(index),
+                    labels(index),
+                    sanitized.join(".")
+                )
+            }
+            Issue::UnusedTypeBlock(index) => {
+                format!("{}: type `{}` has no data blocks", at(index), labels(index))
+            }
+            Issue::NamespaceCollision { group, namespace } => {
+                let mut path = hcl_documents.block_namespace(group).to_vec();
+                path.extend(block(group).labels.first().map(hcl::Identifier::sanitized));
+                let path: Vec<_> = path.iter().map(|identifier| identifier.as_str()).collect();
+                format!(
+                    "data block `{}` uses the path of namespace `{}`: {} and {}",
+                    labels(group),
+                    path.join("."),
+                    at(group),
+                    at(namespace)
+                )
+            }
+        }
+    }
+}
+
+/// Files defining data blocks of the same group as the block at `index`, if there are several
+fn group_files(hcl_documents: &HclDocuments, index: usize) -> String {
+    let group = |index: usize| {
+        let block = hcl_documents.get_block(index).2;
+        (block.ident.value().as_str() == "data")
+            .then(|| block.labels.first())
+            .flatten()
+            .map(|label| {
+                let mut group = hcl_documents.block_namespace(index).to_vec();
+                group.push(hcl::Identifier::sanitized(label.as_str()));
+                group
+            })
+    };
+    let Some(group_path) = group(index) else {
+        return String::new();
+    };
+
+    let mut files: Vec<&std::path::Path> = vec![];
+    for (index, source, _) in hcl_documents.blocks() {
+        if let Some(path) = source
+            .as_deref()
+            .filter(|_| group(index).as_ref() == Some(&group_path))
+        {
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+    if files.len() < 2 {
+        return String::new();
+    }
+
+    let files: Vec<_> = files
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    let name: Vec<_> = group_path.iter().map(|i| i.as_str()).collect();
+    format!(
+        " (group `{}` is merged from {})",
+        name.join("."),
+        files.join(", ")
+    )
+}
+
+#[derive(Debug, Default)]
+pub struct Tree {
+    pub root: indexmap::IndexMap<hcl::Identifier, Node>,
+}
+
+impl Tree {
+    fn get<'a>(
+        &'a self,
+        key_path: &'a [hcl::Identifier],
+    ) -> Option<(usize, &'a [hcl::Identifier])> {
+        if key_path.is_empty() {
+            return None;
+        }
+
+        self.root
+            .get(&key_path[0])
+            .and_then(|child| child.get(&key_path[1..]))
+    }
+
+    /// Node at exactly `key_path`
+    fn node(&self, key_path: &[hcl::Identifier]) -> Option<&Node> {
+        let (first, rest) = key_path.split_first()?;
+        rest.iter()
+            .try_fold(self.root.get(first)?, |node, key| node.children.get(key))
+    }
+
+    fn get_or_insert(&mut self, key_path: &[hcl::Identifier]) -> &mut Node {
+        let key = &key_path[0];
+
+        let child = if self.root.contains_key(key) {
+            self.root.get_mut(key).unwrap()
+        } else {
+            self.root.insert(key.clone(), Default::default());
+            self.root.last_mut().map(|(_key, value)| value).unwrap()
+        };
+
+        child.get_or_insert(&key_path[1..])
+    }
+}
+
+#[derive(Debug, Default, derive_new::new)]
+pub struct Node {
+    pub value: Option<usize>,
+    #[new(default)]
+    pub children: indexmap::IndexMap<hcl::Identifier, Node>,
+}
+
+impl Node {
+    fn get<'a>(
+        &'a self,
+        key_path: &'a [hcl::Identifier],
+    ) -> Option<(usize, &'a [hcl::Identifier])> {
+        if key_path.is_empty() {
+            return self.value.as_ref().map(|r| (*r, [].as_slice()));
+        }
+
+        if let Some(result) = self.children.get(&key_path[0]) {
+            if let Some(result) = result.get(&key_path[1..]) {
+                return Some(result);
+            }
+        }
+
+        self.value.as_ref().map(|r| (*r, key_path))
+    }
+
+    fn get_or_insert(&mut self, key_path: &[hcl::Identifier]) -> &mut Node {
+        if key_path.is_empty() {
+            return self;
+        }
+
+        let key = &key_path[0];
+        let next = if self.children.contains_key(key) {
+            self.children.get_mut(key).unwrap()
+        } else {
+            self.children.insert(key.clone(), Default::default());
+            self.children.last_mut().map(|(_key, value)| value).unwrap()
+        };
+
+        next.get_or_insert(&key_path[1..])
+    }
+}
+
+pub struct Addressable {
+    pub path: Vec<hcl::Identifier>,
+    pub kind: Kind,
+    pub expression: hcl::expr::Expression,
+    pub subst: hcl::Identifier,
+    pub origin: Origin,
+    /// Deprecation message, references to this element are reported
+    pub deprecation: Option<String>,
+    /// Marked with [SENSITIVE_ATTRIBUTE], `Debug` elides the expression
+    pub sensitive: bool,
+    /// Definitions of an attribute of a data block by precedence, the first one is in effect
+    pub definitions: Vec<Definition>,
+    /// Comment directly above the definition
+    pub doc: Option<String>,
+}
+
+impl std::fmt::Debug for Addressable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("Addressable");
+        debug.field("path", &self.path).field("kind", &self.kind);
+        if self.sensitive && !sensitive_revealed() {
+            debug.field("expression", &format_args!("<sensitive>"));
+        } else {
+            debug.field("expression", &self.expression);
+        }
+        debug
+            .field("subst", &self.subst)
+            .field("origin", &self.origin)
+            .field("deprecation", &self.deprecation)
+            .field("sensitive", &self.sensitive)
+            .field("definitions", &self.definitions)
+            .field("doc", &self.doc)
+            .finish()
+    }
+}
+
+impl Addressable {
+    fn new(
+        path: Vec<hcl::Identifier>,
+        kind: Kind,
+        expression: hcl::expr::Expression,
+        origin: Origin,
+    ) -> Self {
+        let subst = format!("{SUBST_PREFIX}{kind}_{:016x}", path_hash(&path)).into();
+        Self {
+            path,
+            kind,
+            expression,
+            subst,
+            origin,
+            deprecation: None,
+            sensitive: false,
+            definitions: vec![],
+            doc: None,
+        }
+    }
+
+    fn info(&self) -> AddressableInfo<'_> {
+        AddressableInfo {
+            path: &self.path,
+            kind: self.kind,
+            source: self.origin.source.as_deref(),
+            span: self.origin.span.clone(),
+            doc: self.doc.as_deref(),
+        }
+    }
+}
+
+/// Stable hash of a path
+///
+/// Segments are length prefixed, `["a__b"]` and `["a", "b"]` hash differently.
+fn path_hash(path: &[hcl::Identifier]) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    #[cfg(test)]
+    if test::COLLIDING_HASHES.with(std::cell::Cell::get) {
+        return 0;
+    }
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for segment in path {
+        segment.len().hash(&mut hasher);
+        segment.as_str().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Where an [Addressable] was defined
+#[derive(Debug, Clone, Default, derive_new::new)]
+pub struct Origin {
+    /// Path of the source document (if any)
+    pub source: Source,
+    /// Byte range in the source document
+    pub span: Option<std::ops::Range<usize>>,
+}
+
+/// A definition of an attribute, see [CcoDocument::definitions]
+#[derive(Debug, Clone, derive_new::new)]
+pub struct Definition {
+    pub layer: Layer,
+    pub origin: Origin,
+}
+
+/// Where a [Definition] of an attribute of a data block comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layer {
+    /// The data block itself, or one merged into it with [MergePolicy::Override]
+    Data,
+    /// A `partial` block named by [INCLUDE_PARTIALS_ATTRIBUTE], later ones take precedence
+    Partial(String),
+    /// The `type` block of the group
+    Type,
+}
+
+impl std::fmt::Display for Layer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Layer::Data => f.write_str("data block"),
+            Layer::Partial(name) => write!(f, "partial {name}"),
+            Layer::Type => f.write_str("type block"),
+        }
+    }
+}
+
+/// Read-only view of an addressable element
+///
+/// Returned by the public query methods of [CcoDocument].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressableInfo<'d> {
+    pub path: &'d [hcl::Identifier],
+    pub kind: Kind,
+    /// Path of the document defining this element. [Kind::Virtual] elements have no source.
+    pub source: Option<&'d std::path::Path>,
+    /// Byte range of the definition in `source`
+    pub span: Option<std::ops::Range<usize>>,
+    /// Comment directly above the definition (`#`, `//` or `/* */`, without the markers)
+    pub doc: Option<&'d str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A "proper" attribute
+    Attribute,
+    /// A attribute with a default expression
+    DefaultAttribute,
+    /// A block
+    Block,
+    /// A node that refers to all its children
+    Virtual,
+    /// Another path, see `alias` blocks
+    Alias,
+    /// A block handled by a [CcoDocumentBuilder::data_source]
+    DataSource,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Attribute => f.write_str("attribute"),
+            Kind::DefaultAttribute => f.write_str("defaultattribute"),
+            Kind::Block => f.write_str("block"),
+            Kind::Virtual => f.write_str("virtual"),
+            Kind::Alias => f.write_str("alias"),
+            Kind::DataSource => f.write_str("datasource"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hcl_documents;
+
+    thread_local! {
+        /// Makes [path_hash] return the same hash for every path
+        pub(super) static COLLIDING_HASHES: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+    }
+
+    fn cco_parse_errors_for(doc: HclDocuments) -> CcoParseErrors {
+        CcoDocument::new(&doc).expect_err("must error")
+    }
+
+    #[test]
+    fn root_attribute_errors() {
+        let errors = cco_parse_errors_for(hcl_documents! {"root_attr = 1"});
+        assert_eq!(errors.issues.as_slice(), &[Issue::RootAttribute(0)]);
+    }
+
+    #[test]
+    fn unknown_block_type_errors() {
+        let errors = cco_parse_errors_for(hcl_documents! {"unknown_block_type {}"});
+        assert!(errors.issues.contains(&Issue::UnknownBlockType(0)));
+    }
+
+    #[test]
+    fn data_label_missing() {
+        let errors = cco_parse_errors_for(hcl_documents! {"data {}"});
+        assert!(errors.issues.contains(&Issue::DataBlockLabelMissing(0)));
+    }
+
+    #[test]
+    fn data_label_collision() {
+        let errors = cco_parse_errors_for(hcl_documents! {"data one two {}\ndata one two {}"});
+        assert!(errors.issues.contains(&Issue::DataBlockLabelCollision {
+            existing: 0,
+            new: 1
+        }));
+    }
+
+    #[test]
+    fn collision_messages() {
+        let errors = cco_parse_errors_for(hcl_documents! {
+            "a.cco.hcl" => "data service app {}\ntype port {}",
+            "b.cco.hcl" => "data service db {}",
+            "c.cco.hcl" => "\ndata service app {}\n\ntype port {}"
+        });
+        assert_eq!(
+            errors.to_string(),
+            "data block `service.app` is defined twice: a.cco.hcl:1:1 and c.cco.hcl:2:1 \
+             (group `service` is merged from a.cco.hcl, b.cco.hcl, c.cco.hcl)\n\
+             type `port` is defined twice: a.cco.hcl:2:1 and c.cco.hcl:4:1"
+        );
+
+        let errors = cco_parse_errors_for(hcl_documents! {"data one two {}\ndata one {}"});
+        assert_eq!(
+            errors.to_string(),
+            "data block `one` has 1 labels, `one.two` has 2: <input> and <input>"
+        );
+    }
+
+    #[test]
+    fn streaming_matches_evaluation() {
+        let documents = hcl_documents! {r#"
+        data service app {
+          port  = 1
+          hosts = ["a", service.db.host]
+        }
+        data service db {
+          host = "db"
+          url  = "${self.host}:${service.app.port}"
+        }
+        "#};
+        let document = CcoDocument::new(&documents).unwrap();
+        let expression = hcl::Expression::from(hcl::Variable::unchecked("service"));
+
+        let evaluated = document.evaluate_in_context(expression.clone()).unwrap();
+        let streamed = document.evaluate_streaming(expression);
+
+        assert_eq!(
+            serde_json::to_string(&streamed).unwrap(),
+            serde_json::to_string(&evaluated).unwrap()
+        );
+    }
+
+    #[test]
+    fn for_expressions_keep_source_order() {
+        let documents = hcl_documents! {r#"
+        data service zeta { port = 1 }
+        data service alpha { port = 2 }
+        data service mid { port = 3 }
+        data config x {
+          map = { z = 1, a = 2, m = 3 }
+        }
+        "#};
+        let document = CcoDocument::new(&documents).unwrap();
+        let evaluate = |expression: &str| {
+            let expression: hcl_edit::expr::Expression = expression.parse().unwrap();
+            document.evaluate_in_context(expression.into()).unwrap()
+        };
+        let keys = |expression: &str| {
+            let crate::value::Value::Object(object) = evaluate(expression) else {
+                panic!("not an object: {expression}");
+            };
+            object.keys().cloned().collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys("service"), ["zeta", "alpha", "mid"]);
+        assert_eq!(
+            keys("{for k, s in service : k => s.port}"),
+            ["zeta", "alpha", "mid"]
+        );
+        assert_eq!(
+            keys("{for k, s in service : k => s if s.port > 1}"),
+            ["alpha", "mid"]
+        );
+        assert_eq!(keys("{for k, v in config.x.map : k => v}"), ["z", "a", "m"]);
+        assert_eq!(
+            keys("{for k, s in service : s.port > 1 => k...}"),
+            ["false", "true"]
+        );
+
+        assert_eq!(
+            serde_json::to_value(evaluate("[for k, s in service : k]")).unwrap(),
+            serde_json::json!(["zeta", "alpha", "mid"])
+        );
+    }
+
+    #[test]
+    fn doc_comments() {
+        let documents = hcl_documents! {r#"
+        # The application
+        data service app {
+          # port to listen on
+          port = 80
+          host = "x"
+        }
+
+        type service {
+          // protocol, `http` unless set
+          protocol = "http"
+        }
+        "#};
+        let document = CcoDocument::new(&documents).unwrap();
+        let doc = |path: &[&str]| {
+            let path: Vec<_> = path
+                .iter()
+                .map(|s| hcl::Identifier::unchecked(*s))
+                .collect();
+            document.get(&path).unwrap().doc.map(str::to_string)
+        };
+
+        assert_eq!(doc(&["service", "app"]).as_deref(), Some("The application"));
+        assert_eq!(
+            doc(&["service", "app", "port"]).as_deref(),
+            Some("port to listen on")
+        );
+        assert_eq!(doc(&["service", "app", "host"]), None);
+        assert_eq!(
+            doc(&["service", "app", "protocol"]).as_deref(),
+            Some("protocol, `http` unless set")
+        );
+        assert_eq!(doc(&["service"]), None);
+    }
+
+    #[test]
+    fn streaming_reports_loops() {
+        let documents = hcl_documents! {"data example {\n a = example.a\n}"};
+        let document = CcoDocument::new(&documents).unwrap();
+        let streamed = document.evaluate_streaming(hcl::Variable::unchecked("example").into());
+
+        assert!(serde_json::to_string(&streamed).is_err());
+    }
+
+    #[test]
+    fn builder_strictness() {
+        let documents = hcl_documents! {"root_attr = 1\nunknown {}\ndata a {\n b = 1\n}"};
+        assert!(CcoDocument::new(&documents).is_err());
+
+        let document = CcoDocument::builder()
+            .strict(false)
+            .build(&documents)
+            .unwrap();
+        assert_eq!(
+            document.evaluate_path(&["a", "b"]).unwrap().as_i64(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn warnings() {
+        let documents = hcl_documents! {"type unused {}\ntype app {}\ndata app \"my app\" {}"};
+        let document = CcoDocument::new(&documents).unwrap();
+        assert_eq!(
+            document.warnings().issues(),
+            &[Issue::SanitizedLabel(2), Issue::UnusedTypeBlock(0)]
+        );
+        assert_eq!(
+            document.warnings().messages()[1],
+            "<input>: type `unused` has no data blocks"
+        );
+
+        let errors = CcoDocument::builder()
+            .deny_warnings(true)
+            .build(&documents)
+            .expect_err("must error");
+        assert_eq!(errors.issues().len(), 2);
+
+        // warnings are not repeated along with errors
+        let errors = cco_parse_errors_for(hcl_documents! {"type unused {}\nroot = 1"});
+        assert_eq!(errors.issues(), &[Issue::RootAttribute(0)]);
+    }
+
+    #[test]
+    fn globals() {
+        let documents = hcl_documents! {
+            "a.cco.hcl" => "region = \"eu\"\nname = \"${global.region}-app\"",
+            "b.cco.hcl" => "data app {\n name = global.name\n}"
+        };
+        assert_eq!(
+            cco_parse_errors_for(hcl_documents! {"region = 1"}).issues(),
+            &[Issue::RootAttribute(0)]
+        );
+
+        let document = CcoDocument::builder()
+            .globals(true)
+            .build(&documents)
+            .unwrap();
+        assert_eq!(
+            document.evaluate_path(&["app", "name"]).unwrap().as_str(),
+            Some("eu-app")
+        );
+        assert_eq!(
+            serde_json::to_value(document.evaluate_path(&["global"]).unwrap()).unwrap(),
+            serde_json::json!({ "region": "eu", "name": "eu-app" })
+        );
+
+        let errors = CcoDocument::builder()
+            .globals(true)
+            .build(&hcl_documents! {
+                "a.cco.hcl" => "a = 1\ndata global x {}",
+                "b.cco.hcl" => "a = 2"
+            })
+            .expect_err("must error");
+        assert_eq!(
+            errors.issues(),
+            &[
+                Issue::ReservedIdentifier(0),
+                Issue::RootAttributeCollision {
+                    existing: 0,
+                    new: 1
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn partials() {
+        let documents = hcl_documents! {r#"
+        partial limits {
+          cpu    = 2
+          memory = "4Gi"
+        }
+        partial large {
+          memory = "16Gi"
+          name   = "large-${self.id}"
+        }
+        type service {
+          cpu  = 1
+          port = 80
+        }
+        data service app {
+          id               = "app"
+          cpu              = 4
+          include_partials = ["limits", "large"]
+        }
+        "#};
+        let document = CcoDocument::new(&documents).unwrap();
+        assert_eq!(
+            serde_json::to_value(document.evaluate_path(&["service", "app"]).unwrap()).unwrap(),
+            serde_json::json!({
+                "id": "app",
+                "cpu": 4,
+                "memory": "16Gi",
+                "name": "large-app",
+                "port": 80
+            })
+        );
+
+        let layers = |path: &[&str]| {
+            let path: Vec<_> = path.iter().map(|s| hcl::Identifier::from(*s)).collect();
+            document
+                .definitions(&path)
+                .unwrap()
+                .iter()
+                .map(|definition| definition.layer.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            layers(&["service", "app", "cpu"]),
+            ["data block", "partial limits", "type block"]
+        );
+        assert_eq!(
+            layers(&["service", "app", "memory"]),
+            ["partial large", "partial limits"]
+        );
+        assert_eq!(layers(&["service", "app", "port"]), ["type block"]);
+        assert!(layers(&["service", "app"]).is_empty());
+
+        let errors = cco_parse_errors_for(hcl_documents! {r#"
+        partial a {}
+        partial a {}
+        partial {}
+        "#});
+        assert_eq!(
+            errors.issues(),
+            &[
+                Issue::PartialBlockLabelCollision {
+                    existing: 0,
+                    new: 1
+                },
+                Issue::PartialBlockInvalid(2)
+            ]
+        );
+
+        let errors = cco_parse_errors_for(hcl_documents! {r#"
+        partial a {}
+        data x {
+          include_partials = ["a", "unknown"]
+        }
+        "#});
+        assert_eq!(errors.issues(), &[Issue::IncludePartialsInvalid(1)]);
+    }
+
+    #[test]
+    fn dependents() {
+        let document = CcoDocument::new(&hcl_documents!(
+            r#"
+            data service api {
+              port = 80
+              url  = "http://localhost:${self.port}"
+            }
+            data service web {
+              backend = service.api.url
+              all     = service
+              names   = [for name, _ in service: name]
+            }
+            "#
+        ))
+        .unwrap();
+        let path = |path: &str| {
+            path.split('.')
+                .map(hcl::Identifier::from)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            document.references(&path("service.api.url")),
+            Some(vec![path("service.api.port")])
+        );
+        assert_eq!(document.references(&path("service.api")), Some(vec![]));
+
+        let dependents: Vec<_> = document
+            .dependents(&path("service.api.port"))
+            .into_iter()
+            .map(|dependent| {
+                (
+                    dependent.info.path.join("."),
+                    dependent.via.map(|via| via.join(".")),
+                )
+            })
+            .collect();
+        assert_eq!(
+            dependents,
+            [
+                ("service.api.url".to_string(), None),
+                ("service.web.all".to_string(), None),
+                ("service.web.names".to_string(), None),
+                (
+                    "service.web.backend".to_string(),
+                    Some("service.api.url".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unused() {
+        let document = CcoDocument::new(&hcl_documents!(
+            r#"
+            data service api {
+              port = 80
+              url  = "http://localhost:${self.port}"
+              old  = 1
+            }
+            data service web {
+              backend = service.api.url
+            }
+            data stale x {
+              y = 1
+            }
+            "#
+        ))
+        .unwrap();
+        let unused = |entry_points: &[&str]| {
+            let entry_points: Vec<_> = entry_points
+                .iter()
+                .map(|path| path.split('.').map(hcl::Identifier::from).collect())
+                .collect();
+            document
+                .unused(&entry_points)
+                .into_iter()
+                .map(|info| info.path.join("."))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(unused(&[]), ["service.api.old", "service.web", "stale.x"]);
+        assert_eq!(unused(&["service.web"]), ["service.api.old", "stale.x"]);
+    }
+
+    #[test]
+    fn unresolved() {
+        let document = CcoDocument::builder()
+            .variable("environment", "prod")
+            .build(&hcl_documents!(
+                r#"
+                data service api {
+                  port = 80
+                  url  = "http://${self.host}:${self.port}/${environment}"
+                  tags = [for tag in ["a"] : tag]
+                }
+                data service web {
+                  backend = servce.api.url
+                  port    = service.api.port.number
+                }
+                "#
+            ))
+            .unwrap();
+
+        let unresolved: Vec<_> = document
+            .unresolved()
+            .into_iter()
+            .map(|u| format!("{} -> {}", u.info.path.join("."), u.reference.join(".")))
+            .collect();
+        assert_eq!(
+            unresolved,
+            [
+                "service.api.url -> service.api.host",
+                "service.web.backend -> servce.api.url",
+            ]
+        );
+    }
+
+    #[test]
+    fn builder_block_kinds() {
+        let documents = hcl_documents! {"type a {}\ndata a {}"};
+        let errors = CcoDocument::builder()
+            .block_kinds(["data"])
+            .build(&documents)
+            .expect_err("must error");
+        assert_eq!(errors.issues.as_slice(), &[Issue::BlockTypeNotAllowed(0)]);
+    }
+
+    #[test]
+    fn builder_functions() {
+        fn double(args: hcl::eval::FuncArgs) -> Result<hcl::Value, String> {
+            let number = args[0].as_i64().ok_or("expected integer")?;
+            Ok(hcl::Value::from(number * 2))
+        }
+
+        let documents = hcl_documents! {"data a {\n b = 2\n c = double(a.b)\n}"};
+        let document = CcoDocument::builder()
+            .function(
+                "double",
+                hcl::eval::FuncDef::new(double, [hcl::eval::ParamType::Number]),
+            )
+            .build(&documents)
+            .unwrap();
+        assert_eq!(
+            document.evaluate_path(&["a", "c"]).unwrap().as_i64(),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn builder_external_functions() {
+        let offset = 10;
+        let documents = hcl_documents! {r#"
+        data a {
+          b = add(a.c)
+          c = 1
+          list = [for x in [1, 2] : add(x)]
+          text = "n${add(add(0))}"
+          keyed = { (tostring(add(1))) = add(2) }
+        }
+        "#};
+        let document = CcoDocument::builder()
+            .external_function("add", move |args| {
+                let number = args[0].as_i64().ok_or("expected integer")?;
+                Ok(hcl::Value::from(number + offset))
+            })
+            .build(&documents)
+            .unwrap();
+
+        assert_eq!(
+            document.evaluate_path(&["a", "b"]).unwrap().as_i64(),
+            Some(11)
+        );
+        assert_eq!(
+            serde_json::to_value(document.evaluate_path(&["a", "list"]).unwrap()).unwrap(),
+            serde_json::json!([11, 12])
+        );
+        assert_eq!(
+            document.evaluate_path(&["a", "text"]).unwrap().as_str(),
+            Some("n20")
+        );
+        assert_eq!(
+            serde_json::to_value(document.evaluate_path(&["a", "keyed"]).unwrap()).unwrap(),
+            serde_json::json!({ "11": 12 })
+        );
+
+        let err = document
+            .evaluate_in_context(hcl::FuncCall::builder("add").arg("x").build().into())
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("add: expected integer"));
+    }
+
+    #[test]
+    fn builder_data_source() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let documents = hcl_documents! {r#"
+        data app {
+          greeting = upper.hello.text
+        }
+        data name {
+          value = "world"
+        }
+        upper hello {
+          text = "hello ${name.value}"
+        }
+        upper unused {
+          text = "unused"
+        }
+        "#};
+        let document = CcoDocument::builder()
+            .data_source("upper", {
+                let calls = calls.clone();
+                move |labels, body| {
+                    calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let text = body.as_object().and_then(|body| body.get("text"));
+                    let text = text.and_then(hcl::Value::as_str).ok_or("missing text")?;
+                    Ok(hcl::value!({
+                        label = (labels.join("."))
+                        text = (text.to_uppercase())
+                    }))
+                }
+            })
+            .build(&documents)
+            .unwrap();
+
+        assert_eq!(
+            document
+                .evaluate_path(&["app", "greeting"])
+                .unwrap()
+                .as_str(),
+            Some("HELLO WORLD")
+        );
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 1);
+        assert_eq!(
+            document
+                .evaluate_path(&["upper", "unused", "label"])
+                .unwrap()
+                .as_str(),
+            Some("unused")
+        );
+
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str("file here {\n}", std::path::PathBuf::from("/etc/a.cco.hcl"))
+            .unwrap();
+        documents.insert_str("file stdin {\n}", None).unwrap();
+        let document = CcoDocument::builder()
+            .data_source_with_origin("file", |_, file, _| {
+                Ok(file.map_or("<input>".into(), |file| file.display().to_string().into()))
+            })
+            .build(&documents)
+            .unwrap();
+        assert_eq!(
+            document.evaluate_path(&["file", "here"]).unwrap().as_str(),
+            Some("/etc/a.cco.hcl")
+        );
+        assert_eq!(
+            document.evaluate_path(&["file", "stdin"]).unwrap().as_str(),
+            Some("<input>")
+        );
 
-impl Node {
-    fn get<'a>(
-        &'a self,
-        key_path: &'a [hcl::Identifier],
-    ) -> Option<(usize, &'a [hcl::Identifier])> {
-        if key_path.is_empty() {
-            return self.value.as_ref().map(|r| (*r, [].as_slice()));
+        let builder = CcoDocument::builder().data_source("upper", |_, body| Ok(body));
+        let issues = |documents| builder.clone().build(&documents).unwrap_err().issues;
+        assert_eq!(
+            issues(hcl_documents! {"upper {\n}"}),
+            &[Issue::DataSourceLabelMissing(0)]
+        );
+        assert_eq!(
+            issues(hcl_documents! {"upper a {\n}\nupper a {\n}"}),
+            &[Issue::DataSourceCollision(1)]
+        );
+    }
+
+    #[test]
+    fn builder_resolver() {
+        let documents = hcl_documents! {"data a {\n port = runtime.app.port + 1\n name = runtime_name\n}\n\
+        data b {\n name = runtime_name\n port = runtime.app.port\n}"};
+        let document = CcoDocument::builder()
+            .resolver(|path| match path {
+                [runtime, app] if runtime.as_str() == "runtime" && app.as_str() == "app" => {
+                    Some(hcl::expression!({ port = 8080 }))
+                }
+                [name] if name.as_str() == "runtime_name" => Some("a".into()),
+                _ => None,
+            })
+            .build(&documents)
+            .unwrap();
+
+        assert_eq!(
+            document.evaluate_path(&["a", "port"]).unwrap().as_i64(),
+            Some(8081)
+        );
+        assert_eq!(
+            document.evaluate_path(&["a", "name"]).unwrap().as_str(),
+            Some("a")
+        );
+
+        // the resolver is asked before `try` moves on
+        let fallback = |expression: &str| {
+            let expression: hcl::Expression = expression
+                .parse::<hcl_edit::expr::Expression>()
+                .unwrap()
+                .into();
+            serde_json::to_value(document.evaluate_in_context(expression).unwrap()).unwrap()
+        };
+        assert_eq!(
+            fallback(r#"[try(runtime_name, "x"), try(unknown, "x"), can(runtime.app.port)]"#),
+            serde_json::json!(["a", "x", true])
+        );
+
+        // several addressables referencing the same variables in one evaluation
+        let both: hcl::Expression = "[a, b]"
+            .parse::<hcl_edit::expr::Expression>()
+            .unwrap()
+            .into();
+        assert_eq!(
+            serde_json::to_value(document.evaluate_in_context(both).unwrap()).unwrap(),
+            serde_json::json!([
+                { "port": 8081, "name": "a" },
+                { "name": "a", "port": 8080 }
+            ])
+        );
+
+        let missing = hcl::Variable::unchecked("unknown").into();
+        assert!(document.evaluate_in_context(missing).is_err());
+    }
+
+    #[test]
+    fn check_expression() {
+        let documents = hcl_documents! {"data a {\n b = 1\n}"};
+        let document = CcoDocument::builder()
+            .variable("env", "prod")
+            .build(&documents)
+            .unwrap();
+
+        let info = document
+            .check_expression("[a.b, a.b.c, env, missing.x, [for k, v in a: v]]")
+            .unwrap();
+
+        let paths =
+            |refs: Vec<&Reference>| refs.iter().map(|r| r.path.join(".")).collect::<Vec<_>>();
+        assert_eq!(
+            paths(info.resolved().collect()),
+            vec!["a.b", "a.b.c", "env", "a"]
+        );
+        assert_eq!(paths(info.unresolved().collect()), vec!["missing.x"]);
+
+        let Some(ReferenceTarget::Addressable(target)) = &info.references[1].target else {
+            panic!("must resolve to an addressable");
+        };
+        assert_eq!(target.path, ["a".into(), "b".into()]);
+
+        let err = info.ensure_resolved().unwrap_err();
+        assert_eq!(err.to_string(), "unresolved references: missing.x");
+
+        assert!(matches!(
+            document.check_expression("a +"),
+            Err(ParseOrRefError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn builder_observer() {
+        #[derive(Default)]
+        struct Recorder {
+            resolved: std::sync::Mutex<Vec<String>>,
+            rewritten: std::sync::Mutex<Vec<String>>,
         }
 
-        if let Some(result) = self.children.get(&key_path[0]) {
-            if let Some(result) = result.get(&key_path[1..]) {
-                return Some(result);
+        impl EvalObserver for Recorder {
+            fn on_resolve(&self, path: &[hcl::Identifier], kind: Kind, _: std::time::Duration) {
+                let entry = format!("{} ({kind})", path.join("."));
+                self.resolved.lock().unwrap().push(entry);
+            }
+
+            fn on_rewrite(&self, traversal: &hcl::Traversal, subst: &hcl::Identifier) {
+                let entry = format!("{} -> {subst}", hcl::format::to_string(traversal).unwrap());
+                self.rewritten.lock().unwrap().push(entry);
             }
         }
 
-        self.value.as_ref().map(|r| (*r, key_path))
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let documents = hcl_documents! {"data a {\n b = 1\n c = self.b + 1\n}"};
+        let document = CcoDocument::builder()
+            .observer(recorder.clone())
+            .build(&documents)
+            .unwrap();
+
+        document.evaluate_path(&["a", "c"]).unwrap();
+
+        assert_eq!(
+            *recorder.resolved.lock().unwrap(),
+            ["a.b (attribute)", "a.c (attribute)"]
+        );
+        let subst = |path: &[&str]| {
+            let path: Vec<hcl::Identifier> = path
+                .iter()
+                .map(|p| hcl::Identifier::unchecked(*p))
+                .collect();
+            document
+                .get_most_specific_node(&path)
+                .unwrap()
+                .0
+                .to_string()
+        };
+        assert_eq!(
+            *recorder.rewritten.lock().unwrap(),
+            [
+                format!("a.c -> {}", subst(&["a", "c"])),
+                format!("a.b -> {}", subst(&["a", "b"]))
+            ]
+        );
     }
 
-    fn get_or_insert(&mut self, key_path: &[hcl::Identifier]) -> &mut Node {
-        if key_path.is_empty() {
-            return self;
+    #[test]
+    fn builder_limits() {
+        let documents = hcl_documents! {"data a {\n b = 1\n c = self.b\n d = self.c\n}"};
+
+        let document = CcoDocument::builder()
+            .max_depth(2)
+            .build(&documents)
+            .unwrap();
+        assert!(document.evaluate_path(&["a", "c"]).is_ok());
+        let err = document.evaluate_path(&["a", "d"]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LimitExceeded>(),
+            Some(&LimitExceeded::Depth {
+                limit: 2,
+                path: "a.b".to_string()
+            })
+        );
+
+        let document = CcoDocument::builder()
+            .max_depth(None)
+            .max_steps(3)
+            .build(&documents)
+            .unwrap();
+        assert!(document.evaluate_path(&["a", "b"]).is_ok());
+        let err = document.evaluate_path(&["a", "d"]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<LimitExceeded>(),
+            Some(LimitExceeded::Steps { limit: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn builder_timeout() {
+        let documents = hcl_documents! {"data a {\n b = 1\n c = self.b\n}"};
+
+        let document = CcoDocument::builder()
+            .timeout(std::time::Duration::ZERO)
+            .build(&documents)
+            .unwrap();
+        let err = document.evaluate_path(&["a", "c"]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<LimitExceeded>(),
+            Some(LimitExceeded::Timeout { .. })
+        ));
+
+        let document = CcoDocument::builder()
+            .timeout(std::time::Duration::from_secs(60))
+            .build(&documents)
+            .unwrap();
+        assert!(document.evaluate_path(&["a", "c"]).is_ok());
+    }
+
+    #[test]
+    fn builder_overflow() {
+        let documents = hcl_documents! {"data a {\n b = 9223372036854775807\n c = self.b + 1\n d = -(self.c)\n\
+        e = 18446744073709551615\n f = [for x in [1, 2]: x * self.b]\n g = \"${self.b + 1}\"\n\
+        h = 2 * 3 - 1 - 1\n i = 18446744073709551615 % 1000\n}"};
+
+        let document = CcoDocument::new(&documents).unwrap();
+        let err = document.evaluate_path(&["a", "c"]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ArithmeticError>(),
+            Some(&ArithmeticError::Overflow {
+                operation: "9223372036854775807 + 1".to_string(),
+                path: "a.c".to_string()
+            })
+        );
+
+        let err = document.evaluate_path(&["a", "e"]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ArithmeticError>(),
+            Some(&ArithmeticError::OutOfRange {
+                value: "18446744073709551615".to_string(),
+                path: "<expression>".to_string()
+            })
+        );
+        for path in ["f", "g"] {
+            let err = document.evaluate_path(&["a", path]).unwrap_err();
+            assert!(
+                format!("{err:#}").contains("integer overflow in `"),
+                "{err:#}"
+            );
         }
+        let value = |path| document.evaluate_path(&["a", path]).unwrap().as_i64();
+        assert_eq!(value("h"), Some(4));
+        assert_eq!(value("i"), Some(615));
 
-        let key = &key_path[0];
-        let next = if self.children.contains_key(key) {
-            self.children.get_mut(key).unwrap()
-        } else {
-            self.children.insert(key.clone(), Default::default());
-            self.children.last_mut().map(|(_key, value)| value).unwrap()
+        let document = CcoDocument::builder()
+            .overflow(OverflowPolicy::Saturate)
+            .build(&documents)
+            .unwrap();
+        let value = document.evaluate_path(&["a", "d"]).unwrap();
+        assert_eq!(value.as_i64(), Some(-i64::MAX));
+        let value = document.evaluate_path(&["a", "e"]).unwrap();
+        assert_eq!(value.as_i64(), Some(i64::MAX));
+        let value = document.evaluate_path(&["a", "f"]).unwrap();
+        assert_eq!(
+            serde_json::to_value(value).unwrap(),
+            serde_json::json!([i64::MAX, i64::MAX])
+        );
+        let value = document.evaluate_path(&["a", "g"]).unwrap();
+        assert_eq!(value.as_i64(), Some(i64::MAX));
+    }
+
+    #[test]
+    fn builder_merge_policy() {
+        let documents = hcl_documents! {
+            "a.cco.hcl" => "type service {\n cpu = 1\n}\n\
+                data service api {\n cpu = 2\n port = 80\n cco_sensitive = [\"port\"]\n}",
+            "b.cco.hcl" => "data service api {\n cpu = 4\n url = \"http://api:${self.port}\"\n}",
+            "c.cco.hcl" => "data service api {\n cpu = 8\n}"
+        };
+        let errors = CcoDocument::new(&documents).unwrap_err();
+        assert_eq!(
+            errors.issues(),
+            &[
+                Issue::DataBlockLabelCollision {
+                    existing: 1,
+                    new: 2
+                },
+                Issue::DataBlockLabelCollision {
+                    existing: 1,
+                    new: 3
+                }
+            ]
+        );
+
+        let document = CcoDocument::builder()
+            .merge_policy(MergePolicy::Override)
+            .build(&documents)
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(document.evaluate_path(&["service", "api"]).unwrap()).unwrap(),
+            serde_json::json!({ "cpu": 8, "port": 80, "url": "http://api:80" })
+        );
+        let path = |path: &[&str]| -> Vec<hcl::Identifier> {
+            path.iter()
+                .map(|i| hcl::Identifier::unchecked(*i))
+                .collect()
         };
 
-        next.get_or_insert(&key_path[1..])
+        let files: Vec<_> = document
+            .definitions(&path(&["service", "api", "cpu"]))
+            .unwrap()
+            .iter()
+            .map(|definition| {
+                let file = definition.origin.source.as_deref().unwrap();
+                format!("{} {}", definition.layer, file.display())
+            })
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "data block c.cco.hcl",
+                "data block b.cco.hcl",
+                "data block a.cco.hcl",
+                "type block a.cco.hcl"
+            ]
+        );
     }
-}
 
-impl Default for Node {
-    fn default() -> Self {
-        Self {
-            children: Default::default(),
-            value: Default::default(),
+    #[test]
+    fn builder_secret_providers() {
+        let documents = hcl_documents! {r#"
+        data db {
+          password = secret("env", "DB_PASSWORD")
+          missing  = secret("env", "OTHER")
+          unknown  = secret("vault", "db")
+          fallback = try(secret("env", "OTHER"), "default")
         }
-    }
-}
+        "#};
+        let document = CcoDocument::builder()
+            .secret_provider("env", |key| match key {
+                "DB_PASSWORD" => Ok("hunter2".to_string()),
+                _ => Err("not set".to_string()),
+            })
+            .build(&documents)
+            .unwrap();
 
-#[derive(Debug)]
-pub struct Addressable {
-    pub path: Vec<hcl::Identifier>,
-    pub kind: Kind,
-    pub expression: hcl::expr::Expression,
-    pub subst: hcl::Identifier,
-}
+        let value = |name: &str| document.evaluate_path(&["db", name]);
+        assert_eq!(value("password").unwrap().as_str(), Some("hunter2"));
+        assert!(format!("{:#}", value("missing").unwrap_err()).contains("env OTHER: not set"));
+        assert!(format!("{:#}", value("unknown").unwrap_err())
+            .contains("unknown secret provider 'vault', expected one of env"));
+        assert_eq!(value("fallback").unwrap().as_str(), Some("default"));
 
-impl Addressable {
-    fn new(path: Vec<hcl::Identifier>, kind: Kind, expression: hcl::expr::Expression) -> Self {
-        let subst = format!("cco__{}_{}", kind, path.join("__")).into();
-        Self {
-            path,
-            kind,
-            expression,
-            subst,
+        // without providers `secret` is an unknown function
+        assert!(CcoDocument::new(&documents)
+            .unwrap()
+            .evaluate_path(&["db", "password"])
+            .is_err());
+    }
+
+    #[test]
+    fn evaluate_path_into_struct() {
+        #[derive(serde::Deserialize)]
+        struct App {
+            port: u16,
         }
+
+        let documents = hcl_documents! {"data service app {\n port = 8080\n}"};
+        let document = CcoDocument::new(&documents).unwrap();
+        let app: App = document
+            .evaluate_path(&["service", "app"])
+            .unwrap()
+            .deserialize_into()
+            .unwrap();
+
+        assert_eq!(app.port, 8080);
     }
-}
 
-#[derive(Debug)]
-pub enum Kind {
-    /// A "proper" attribute
-    Attribute,
-    /// A attribute with a default expression
-    DefaultAttribute,
-    /// A block
-    Block,
-    /// A node that refers to all its children
-    Virtual,
+    #[test]
+    fn query_api() {
+        let documents = hcl_documents! {"data service app {\n port = 1\n}"};
+        let document = CcoDocument::new(&documents).unwrap();
+        let path = |p: &[&str]| -> Vec<hcl::Identifier> {
+            p.iter().map(|i| hcl::Identifier::unchecked(*i)).collect()
+        };
+
+        assert_eq!(document.paths().count(), 3);
+        assert_eq!(
+            document.get(&path(&["service", "app"])).unwrap().kind,
+            Kind::Block
+        );
+        assert!(document.get(&path(&["service", "nope"])).is_none());
+
+        let port = document.get(&path(&["service", "app", "port"])).unwrap();
+        assert_eq!(port.kind, Kind::Attribute);
+        assert_eq!(
+            port.span
+                .map(|span| &"data service app {\n port = 1\n}"[span]),
+            Some("port = 1")
+        );
+
+        let children: Vec<_> = document
+            .children_of(&path(&["service"]))
+            .unwrap()
+            .map(|child| child.path.to_vec())
+            .collect();
+        assert_eq!(children, vec![path(&["service", "app"])]);
+
+        assert_eq!(document.children_of(&[]).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn label_case_collisions() {
+        let documents =
+            hcl_documents! {"data service Prod {}\ndata service prod {}\ntype Service {}"};
+        assert!(CcoDocument::new(&documents).is_ok());
+
+        let errors = CcoDocument::builder()
+            .label_case_collisions(true)
+            .build(&documents)
+            .expect_err("must error");
+        assert_eq!(
+            errors.issues.as_slice(),
+            &[
+                Issue::LabelCaseCollision {
+                    existing: 0,
+                    new: 1
+                },
+                Issue::LabelCaseCollision {
+                    existing: 0,
+                    new: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reserved_root_identifiers() {
+        let errors = cco_parse_errors_for(hcl_documents! {"data cco__a {}\ntype cco__b {}"});
+        assert_eq!(
+            errors.issues.as_slice(),
+            &[Issue::ReservedIdentifier(0), Issue::ReservedIdentifier(1)]
+        );
+
+        // only root identifiers are reserved
+        let documents = hcl_documents! {"data a cco__b {\n cco__c = 1\n}"};
+        let document = CcoDocument::new(&documents).unwrap();
+        assert_eq!(
+            document
+                .evaluate_path(&["a", "cco__b", "cco__c"])
+                .unwrap()
+                .as_i64(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn negative_and_slice_indices() {
+        let mut documents = HclDocuments::default();
+        documents
+            .insert_str(
+                r#"
+data cluster {
+  servers = [for i in [1, 2, 3, 4] : "server-${i}"]
 }
+data x {
+  last   = cluster.servers[-1]
+  window = cluster.servers[1:3]
+  tail   = "${cluster.servers[2:][0]}"
+}
+"#,
+                None,
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        let value = document.evaluate_path(&["x"]).unwrap();
+        assert_eq!(
+            serde_json::to_value(value).unwrap(),
+            serde_json::json!({
+                "last": "server-4",
+                "window": ["server-2", "server-3"],
+                "tail": "server-3",
+            })
+        );
 
-impl std::fmt::Display for Kind {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Kind::Attribute => f.write_str("attribute"),
-            Kind::DefaultAttribute => f.write_str("defaultattribute"),
-            Kind::Block => f.write_str("block"),
-            Kind::Virtual => f.write_str("virtual"),
-        }
+        let value = document
+            .evaluate_in_context(
+                crate::hcl_documents::parse_expression("cluster.servers[:-3]").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(value).unwrap(),
+            serde_json::json!(["server-1"])
+        );
     }
+
+    #[test]
+    fn string_index_addresses_sanitized_labels() {
+        let documents = hcl_documents! {r#"
+data service "my-weird name" {
+  port = 8080
+  tags = { "app.kubernetes.io/name" = "weird" }
+}
+data service api {
+  port = 80
 }
+data x y {
+  port = service["my-weird name"].port
+  api  = service["api"]["port"]
+  tag  = service["my-weird name"].tags["app.kubernetes.io/name"]
+}
+"#};
+        let document = CcoDocument::new(&documents).unwrap();
+        let value = document.evaluate_path(&["x", "y"]).unwrap();
+        assert_eq!(
+            value.get("port").and_then(crate::value::Value::as_i64),
+            Some(8080)
+        );
+        assert_eq!(
+            value.get("api").and_then(crate::value::Value::as_i64),
+            Some(80)
+        );
+        assert_eq!(
+            value.get("tag").and_then(crate::value::Value::as_str),
+            Some("weird")
+        );
+    }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::hcl_documents;
+    #[test]
+    fn subst_ids_do_not_collide() {
+        let documents = hcl_documents! {"data a b {\n c = 1\n}\ndata a__b {\n c = 2\n}"};
+        let document = CcoDocument::new(&documents).unwrap();
+        assert_eq!(
+            document.evaluate_path(&["a", "b", "c"]).unwrap().as_i64(),
+            Some(1)
+        );
+        assert_eq!(
+            document.evaluate_path(&["a__b", "c"]).unwrap().as_i64(),
+            Some(2)
+        );
 
-    fn cco_parse_errors_for(doc: HclDocuments) -> CcoParseErrors {
-        CcoDocument::new(&doc).expect_err("must error")
+        // joining segments with `_` would make both `a_b_c`
+        let documents = hcl_documents! {"data a b_c {\n d = 1\n}\ndata a_b c {\n d = 2\n}"};
+        let document = CcoDocument::new(&documents).unwrap();
+        let subst = |path: &[&str]| {
+            let path: Vec<_> = path
+                .iter()
+                .map(|s| hcl::Identifier::new(*s).unwrap())
+                .collect();
+            document.get_most_specific_node(&path).unwrap().0.clone()
+        };
+        assert_ne!(subst(&["a", "b_c"]), subst(&["a_b", "c"]));
+        assert_eq!(
+            document.evaluate_path(&["a", "b_c", "d"]).unwrap().as_i64(),
+            Some(1)
+        );
+        assert_eq!(
+            document.evaluate_path(&["a_b", "c", "d"]).unwrap().as_i64(),
+            Some(2)
+        );
+
+        let substs: std::collections::HashSet<_> =
+            document.addressables.iter().map(|a| &a.subst).collect();
+        assert_eq!(substs.len(), document.addressables.len());
+        for addressable in &document.addressables {
+            let found = document.get_by_subst(&addressable.subst).unwrap();
+            assert_eq!(found.path, addressable.path);
+        }
+
+        // every path hashes the same, only the `_{index}` suffix keeps them apart
+        COLLIDING_HASHES.with(|colliding| colliding.set(true));
+        let documents = hcl_documents! {"data a b {\n d = 1\n}\ndata c d {\n e = a.b.d + 1\n}"};
+        let document = CcoDocument::new(&documents);
+        COLLIDING_HASHES.with(|colliding| colliding.set(false));
+        let document = document.unwrap();
+
+        let substs: std::collections::HashSet<_> =
+            document.addressables.iter().map(|a| &a.subst).collect();
+        assert_eq!(substs.len(), document.addressables.len());
+        assert!(document
+            .addressables
+            .iter()
+            .any(|addressable| addressable.subst.as_str().contains("_0000000000000000_")));
+        assert_eq!(
+            document.evaluate_path(&["a", "b", "d"]).unwrap().as_i64(),
+            Some(1)
+        );
+        assert_eq!(
+            document.evaluate_path(&["c", "d", "e"]).unwrap().as_i64(),
+            Some(2)
+        );
     }
 
     #[test]
-    fn root_attribute_errors() {
-        let errors = cco_parse_errors_for(hcl_documents! {"root_attr = 1"});
-        assert_eq!(errors.issues.as_slice(), &[Issue::RootAttribute(0)]);
+    fn alias_blocks() {
+        let documents = hcl_documents! {r#"
+            data service api {
+                port = 80
+            }
+            alias service old_api {
+                target = service.api
+                deprecated = true
+            }
+            alias legacy_port {
+                target = service.old_api.port
+            }
+        "#};
+        let document = CcoDocument::new(&documents).unwrap();
+
+        assert_eq!(
+            document
+                .evaluate_path(&["service", "old_api", "port"])
+                .unwrap()
+                .as_i64(),
+            Some(80)
+        );
+        assert_eq!(
+            document.evaluate_path(&["legacy_port"]).unwrap().as_i64(),
+            Some(80)
+        );
+
+        // aliases are not part of the output
+        let service = document.evaluate_path(&["service"]).unwrap();
+        assert!(service.get("old_api").is_none());
+
+        let alias = document.get(&["service".into(), "old_api".into()]).unwrap();
+        assert_eq!(alias.kind, Kind::Alias);
+        let subst = document
+            .get_most_specific_node(&["service".into(), "old_api".into()])
+            .unwrap()
+            .0;
+        assert_eq!(
+            document.get_by_subst(subst).unwrap().deprecation.as_deref(),
+            Some("use service.api instead")
+        );
     }
 
     #[test]
-    fn unknown_block_type_errors() {
-        let errors = cco_parse_errors_for(hcl_documents! {"unknown_block_type {}"});
-        assert!(errors.issues.contains(&Issue::UnknownBlockType(0)));
+    fn alias_block_issues() {
+        let errors = cco_parse_errors_for(hcl_documents! {r#"
+            data a b {}
+            alias {
+                target = a
+            }
+            alias c {
+                target = "a"
+            }
+        "#});
+        assert_eq!(
+            errors.issues.as_slice(),
+            &[
+                Issue::AliasBlockLabelMissing(1),
+                Issue::AliasBlockInvalid(2)
+            ]
+        );
+
+        let errors = cco_parse_errors_for(hcl_documents! {r#"
+            data a b {}
+            alias a b {
+                target = a
+            }
+            alias a {
+                target = a.b
+            }
+        "#});
+        assert_eq!(
+            errors.issues.as_slice(),
+            &[Issue::AliasCollision(1), Issue::AliasCollision(2)]
+        );
     }
 
     #[test]
-    fn data_label_missing() {
-        let errors = cco_parse_errors_for(hcl_documents! {"data {}"});
-        assert!(errors.issues.contains(&Issue::DataBlockLabelMissing(0)));
+    fn deprecations() {
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<String>>);
+
+        impl EvalObserver for Recorder {
+            fn on_deprecated(
+                &self,
+                path: &[hcl::Identifier],
+                message: &str,
+                referenced_by: Option<&[hcl::Identifier]>,
+            ) {
+                let referenced_by = referenced_by.map(|path| path.join("."));
+                let entry = format!("{} ({message}) <- {referenced_by:?}", path.join("."));
+                self.0.lock().unwrap().push(entry);
+            }
+        }
+
+        let documents = hcl_documents! {r#"
+            data old {
+                cco_deprecated = "use new"
+                value = 1
+                copy = self.value
+            }
+            data new {
+                url = "a"
+                old_url = "b"
+                cco_deprecated = { old_url = "use url" }
+            }
+            data consumer {
+                value = old.value
+                url = new.old_url
+            }
+        "#};
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let document = CcoDocument::builder()
+            .observer(recorder.clone())
+            .build(&documents)
+            .unwrap();
+
+        // the marker is not an attribute
+        assert!(document.evaluate_path(&["old", "cco_deprecated"]).is_err());
+        recorder.0.lock().unwrap().clear();
+
+        // blocks and references from within the deprecated block do not count
+        document.evaluate_path(&["old", "copy"]).unwrap();
+        document.evaluate_path(&["new"]).unwrap();
+        document.evaluate_path(&["consumer"]).unwrap();
+        document.evaluate_path(&["new", "old_url"]).unwrap();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "old (use new) <- None",
+                "old (use new) <- Some(\"consumer.value\")",
+                "new.old_url (use url) <- Some(\"consumer.url\")",
+                "new.old_url (use url) <- None",
+            ]
+        );
+
+        let errors = cco_parse_errors_for(
+            hcl_documents! {"data a {\n cco_deprecated = { missing = \"x\" }\n}"},
+        );
+        assert_eq!(
+            errors.issues.as_slice(),
+            &[Issue::DeprecatedAttributeInvalid(0)]
+        );
     }
 
     #[test]
-    fn data_label_collision() {
-        let errors = cco_parse_errors_for(hcl_documents! {"data one two {}\ndata one two {}"});
-        assert!(errors.issues.contains(&Issue::DataBlockLabelCollision {
-            existing: 0,
-            new: 1
-        }));
+    fn sensitive_debug() {
+        let documents = hcl_documents! {r#"
+            type db {
+                password = "default-secret"
+            }
+            data db main {
+                cco_sensitive = true
+            }
+            data service api {
+                cco_sensitive = ["token"]
+                token = "api-secret"
+                host = "api.internal"
+                port = 8080
+            }
+        "#};
+        let document = CcoDocument::new(&documents).unwrap();
+
+        let debug = format!("{document:?}");
+        assert!(!debug.contains("default-secret"));
+        assert!(!debug.contains("api-secret"));
+        assert!(debug.contains("api.internal") && debug.contains("8080"));
+
+        let revealed = reveal_sensitive(|| format!("{document:#?}"));
+        assert!(revealed.contains("default-secret"));
+        assert!(revealed.contains("api-secret"));
+
+        // the marker is not an attribute, values are unaffected
+        assert!(document
+            .evaluate_path(&["db", "main", "cco_sensitive"])
+            .is_err());
+        let token = document
+            .evaluate_path(&["service", "api", "token"])
+            .unwrap();
+        assert_eq!(token.as_str(), Some("api-secret"));
+
+        let errors =
+            cco_parse_errors_for(hcl_documents! {"data a {\n cco_sensitive = [\"missing\"]\n}"});
+        assert_eq!(
+            errors.issues.as_slice(),
+            &[Issue::SensitiveAttributeInvalid(0)]
+        );
     }
 
     #[test]
@@ -554,4 +4361,99 @@ mod test {
             new: 1
         }));
     }
+
+    #[test]
+    fn namespaced_groups() {
+        let env = "type service {\n replicas = 1\n}\n\
+            data service api {\n url = \"https://${self[1]}.${domain.name.value}\"\n}\n\
+            data domain name {\n value = \"example.com\"\n}";
+        let mut documents = hcl_documents! {
+            "environments/prod/main.cco.hcl" => env,
+            "environments/staging/main.cco.hcl" => env,
+            "environments/staging/domain.cco.hcl" => "data domain override {\n value = prod.domain.name.value\n}",
+            "root.cco.hcl" => "data report all {\n prod = prod.service.api.url\n}"
+        };
+        documents.nest(|path| {
+            let dir = path.parent().and_then(|dir| dir.file_name());
+            dir.map(|dir| hcl::Identifier::sanitized(dir.to_str().unwrap()))
+                .into_iter()
+                .collect()
+        });
+        let document = CcoDocument::new(&documents).unwrap();
+
+        assert_eq!(
+            document
+                .evaluate_path(&["report", "all", "prod"])
+                .unwrap()
+                .as_str(),
+            Some("https://api.example.com")
+        );
+        assert_eq!(
+            serde_json::to_value(document.evaluate_path(&["staging", "service"]).unwrap()).unwrap(),
+            serde_json::json!({ "api": { "replicas": 1, "url": "https://api.example.com" } })
+        );
+        assert_eq!(
+            document
+                .evaluate_path(&["staging", "domain", "override", "value"])
+                .unwrap()
+                .as_str(),
+            Some("example.com")
+        );
+
+        // a data group may not live where a namespace is
+        let mut documents = hcl_documents! {
+            "prod/main.cco.hcl" => "data service api {}",
+            "root.cco.hcl" => "data prod service {}"
+        };
+        documents.nest(|path| match path.parent() {
+            Some(dir) if dir.as_os_str() == "prod" => vec![hcl::Identifier::unchecked("prod")],
+            _ => vec![],
+        });
+        let errors = cco_parse_errors_for(documents);
+        assert_eq!(
+            errors.issues.as_slice(),
+            &[Issue::NamespaceCollision {
+                group: 1,
+                namespace: 0
+            }]
+        );
+        assert_eq!(
+            errors.to_string(),
+            "data block `prod.service` uses the path of namespace `prod`: \
+             root.cco.hcl:1:1 and prod/main.cco.hcl:1:1"
+        );
+    }
+
+    #[test]
+    fn namespaced_groups_in_source_order() {
+        let mut documents = hcl_documents! {
+            "environments/zeta/main.cco.hcl" => "data service api {}\ndata domain name {}",
+            "environments/alpha/main.cco.hcl" => "data domain name {}\ndata service api {}",
+            "environments/mid/main.cco.hcl" => "data queue jobs {}\ndata service api {}",
+            "root.cco.hcl" => "data report all {}"
+        };
+        documents.nest(|path| {
+            let dir = path.parent().and_then(|dir| dir.file_name());
+            dir.map(|dir| hcl::Identifier::sanitized(dir.to_str().unwrap()))
+                .into_iter()
+                .collect()
+        });
+        let document = CcoDocument::new(&documents).unwrap();
+
+        let keys = |node: &indexmap::IndexMap<hcl::Identifier, Node>| -> Vec<String> {
+            node.keys().map(|key| key.to_string()).collect()
+        };
+        assert_eq!(
+            keys(&document.tree.root),
+            ["zeta", "alpha", "mid", "report"]
+        );
+        let children = |namespace: &str| keys(&document.tree.root[namespace].children);
+        assert_eq!(children("zeta"), ["service", "domain"]);
+        assert_eq!(children("alpha"), ["domain", "service"]);
+        assert_eq!(children("mid"), ["queue", "service"]);
+        assert_eq!(
+            serde_json::to_value(document.evaluate_path(&["alpha"]).unwrap()).unwrap(),
+            serde_json::json!({ "domain": { "name": {} }, "service": { "api": {} } })
+        );
+    }
 }
@@ -0,0 +1,284 @@
+//! list and map shaping functions: `zipmap`, `transpose`, `flatten`, `distinct`, `sort`,
+//! `reverse`, `chunklist` and `setproduct`, and the sequences of `range`
+//!
+//! Lists keep the order of their input, `distinct` keeps the first occurrence of each element.
+use hcl::{Map, Value};
+
+fn list(value: Value, what: &str) -> Result<Vec<Value>, String> {
+    match value {
+        Value::Array(list) => Ok(list),
+        other => Err(format!("expected a list as {what}, got {other}")),
+    }
+}
+
+/// Object of `keys` (strings) and `values` at the same position
+pub fn zipmap(keys: Value, values: Value) -> Result<Value, String> {
+    let (keys, values) = (list(keys, "keys")?, list(values, "values")?);
+    if keys.len() != values.len() {
+        return Err(format!(
+            "expected as many keys as values, got {} and {}",
+            keys.len(),
+            values.len()
+        ));
+    }
+    let mut map = Map::new();
+    for (key, value) in keys.into_iter().zip(values) {
+        match key {
+            Value::String(key) => map.insert(key, value),
+            other => return Err(format!("keys must be strings, got {other}")),
+        };
+    }
+    Ok(Value::Object(map))
+}
+
+/// Swap keys and values of a map of string lists: `{ a = ["x"], b = ["x", "y"] }` becomes
+/// `{ x = ["a", "b"], y = ["b"] }`
+pub fn transpose(value: Value) -> Result<Value, String> {
+    let Value::Object(object) = value else {
+        return Err(format!("expected a map of lists, got {value}"));
+    };
+    let mut transposed: Map<String, Value> = Map::new();
+    for (key, values) in object {
+        for value in list(values, &format!("value of {key}"))? {
+            let Value::String(value) = value else {
+                return Err(format!("expected lists of strings, got {value} in {key}"));
+            };
+            match transposed
+                .entry(value)
+                .or_insert_with(|| Value::Array(vec![]))
+            {
+                Value::Array(keys) => keys.push(Value::String(key.clone())),
+                _ => unreachable!("only lists are inserted"),
+            }
+        }
+    }
+    Ok(Value::Object(transposed))
+}
+
+/// Elements of nested lists, recursively
+pub fn flatten(value: Value) -> Result<Value, String> {
+    fn extend(flat: &mut Vec<Value>, list: Vec<Value>) {
+        for value in list {
+            match value {
+                Value::Array(nested) => extend(flat, nested),
+                value => flat.push(value),
+            }
+        }
+    }
+    let mut flat = vec![];
+    extend(&mut flat, list(value, "argument")?);
+    Ok(Value::Array(flat))
+}
+
+pub fn distinct(value: Value) -> Result<Value, String> {
+    let mut distinct = vec![];
+    for value in list(value, "argument")? {
+        if !distinct.contains(&value) {
+            distinct.push(value);
+        }
+    }
+    Ok(Value::Array(distinct))
+}
+
+/// Strings lexicographically or numbers by value, not both
+pub fn sort(value: Value) -> Result<Value, String> {
+    let mut list = list(value, "argument")?;
+    if list.iter().all(Value::is_string) {
+        list.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+    } else if list.iter().all(Value::is_number) {
+        let number = |value: &Value| value.as_f64().unwrap_or_default();
+        list.sort_by(|a, b| number(a).total_cmp(&number(b)));
+    } else {
+        return Err("expected a list of only strings or only numbers".to_string());
+    }
+    Ok(Value::Array(list))
+}
+
+pub fn reverse(value: Value) -> Result<Value, String> {
+    let mut list = list(value, "argument")?;
+    list.reverse();
+    Ok(Value::Array(list))
+}
+
+/// Consecutive lists of `size` elements, the last one can be shorter
+pub fn chunklist(value: Value, size: Value) -> Result<Value, String> {
+    let list = list(value, "first argument")?;
+    let size = size
+        .as_u64()
+        .filter(|size| *size > 0)
+        .ok_or_else(|| format!("expected a positive chunk size, got {size}"))?;
+    let chunks = list
+        .chunks(size as usize)
+        .map(|chunk| Value::Array(chunk.to_vec()))
+        .collect();
+    Ok(Value::Array(chunks))
+}
+
+/// Most combinations `setproduct` generates, a guard against accidental combinatorial blowup
+pub const PRODUCT_LIMIT: usize = 100_000;
+
+/// Every combination of one element of each list, in order: `setproduct(["a", "b"], [1, 2])` is
+/// `[["a", 1], ["a", 2], ["b", 1], ["b", 2]]`
+pub fn setproduct(lists: Vec<Value>) -> Result<Value, String> {
+    let lists = lists
+        .into_iter()
+        .enumerate()
+        .map(|(index, values)| list(values, &format!("argument {}", index + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let size = lists
+        .iter()
+        .try_fold(1usize, |size, values| size.checked_mul(values.len()));
+    if size.is_none_or(|size| size > PRODUCT_LIMIT) {
+        return Err(format!("more than {PRODUCT_LIMIT} combinations"));
+    }
+
+    let mut product = vec![vec![]];
+    for values in lists {
+        product = product
+            .into_iter()
+            .flat_map(|prefix: Vec<Value>| {
+                values.iter().map(move |value| {
+                    let mut combination = prefix.clone();
+                    combination.push(value.clone());
+                    combination
+                })
+            })
+            .collect();
+    }
+    Ok(Value::Array(
+        product.into_iter().map(Value::Array).collect(),
+    ))
+}
+
+/// Most elements `range` generates, a guard against steps that are far too small
+pub const RANGE_LIMIT: usize = 100_000;
+
+/// `range(end)`, `range(start, end)` or `range(start, end, step)`, `end` excluded
+///
+/// The step is 1, or -1 if `end` is smaller than `start`.
+pub fn range(args: &[Value]) -> Result<Value, String> {
+    let numbers = args
+        .iter()
+        .map(|arg| {
+            arg.as_f64()
+                .ok_or_else(|| format!("expected numbers, got {arg}"))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    let (start, end, step) = match numbers[..] {
+        [end] => (0.0, end, 1.0),
+        [start, end] => (start, end, if end < start { -1.0 } else { 1.0 }),
+        [start, end, step] => (start, end, step),
+        _ => return Err("expected one to three arguments".to_string()),
+    };
+    if step == 0.0 || (end - start) * step < 0.0 {
+        return Err(format!("step {step} does not lead from {start} to {end}"));
+    }
+
+    let mut range = vec![];
+    let mut value = start;
+    while (step > 0.0 && value < end) || (step < 0.0 && value > end) {
+        if range.len() == RANGE_LIMIT {
+            return Err(format!("more than {RANGE_LIMIT} elements"));
+        }
+        range.push(if value.fract() == 0.0 {
+            Value::from(value as i64)
+        } else {
+            Value::from(value)
+        });
+        value = start + step * range.len() as f64;
+    }
+    Ok(Value::Array(range))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Value {
+        Value::Array(values.iter().map(|value| Value::from(*value)).collect())
+    }
+
+    #[test]
+    fn shapes() {
+        let mut map = Map::new();
+        map.insert("a".to_string(), strings(&["x"]));
+        map.insert("b".to_string(), strings(&["x", "y"]));
+        let mut transposed = Map::new();
+        transposed.insert("x".to_string(), strings(&["a", "b"]));
+        transposed.insert("y".to_string(), strings(&["b"]));
+        assert_eq!(transpose(Value::Object(map)), Ok(Value::Object(transposed)));
+
+        let nested = Value::Array(vec![strings(&["a"]), Value::Array(vec![strings(&["b"])])]);
+        assert_eq!(flatten(nested), Ok(strings(&["a", "b"])));
+        assert_eq!(
+            distinct(strings(&["b", "a", "b"])),
+            Ok(strings(&["b", "a"]))
+        );
+        assert_eq!(
+            sort(strings(&["b", "a", "c"])),
+            Ok(strings(&["a", "b", "c"]))
+        );
+        assert_eq!(
+            sort(Value::Array(vec![10.into(), 9.into(), 0.5.into()])),
+            Ok(Value::Array(vec![0.5.into(), 9.into(), 10.into()]))
+        );
+        assert!(sort(Value::Array(vec![1.into(), "a".into()])).is_err());
+        assert_eq!(reverse(strings(&["a", "b"])), Ok(strings(&["b", "a"])));
+        assert!(zipmap(strings(&["a"]), strings(&[])).is_err());
+    }
+
+    #[test]
+    fn chunks_and_products() {
+        assert_eq!(
+            chunklist(strings(&["a", "b", "c"]), 2.into()),
+            Ok(Value::Array(vec![strings(&["a", "b"]), strings(&["c"])]))
+        );
+        assert!(chunklist(strings(&["a"]), 0.into()).is_err());
+
+        assert_eq!(
+            setproduct(vec![strings(&["a", "b"]), strings(&["1", "2"])]),
+            Ok(Value::Array(vec![
+                strings(&["a", "1"]),
+                strings(&["a", "2"]),
+                strings(&["b", "1"]),
+                strings(&["b", "2"]),
+            ]))
+        );
+        assert_eq!(
+            setproduct(vec![strings(&["a"]), strings(&[])]),
+            Ok(Value::Array(vec![]))
+        );
+        let thousand = Value::Array((0..1000).map(Value::from).collect());
+        assert!(setproduct(vec![thousand.clone(), thousand.clone()])
+            .unwrap_err()
+            .contains("more than"));
+        // the size is checked before any combination is built
+        assert!(setproduct(vec![thousand; 8]).is_err());
+    }
+
+    #[test]
+    fn ranges() {
+        let numbers = |values: Vec<Value>| Ok(Value::Array(values));
+        assert_eq!(
+            range(&[3.into()]),
+            numbers(vec![0.into(), 1.into(), 2.into()])
+        );
+        assert_eq!(
+            range(&[3.into(), 1.into()]),
+            numbers(vec![3.into(), 2.into()])
+        );
+        assert_eq!(
+            range(&[8080.into(), 8090.into(), 5.into()]),
+            numbers(vec![8080.into(), 8085.into()])
+        );
+        assert_eq!(
+            range(&[0.into(), 1.into(), 0.25.into()]),
+            numbers(vec![0.into(), 0.25.into(), 0.5.into(), 0.75.into()])
+        );
+        assert_eq!(range(&[0.into()]), numbers(vec![]));
+        assert!(range(&[0.into(), 5.into(), (-1).into()]).is_err());
+        assert!(range(&[1_000_000.into()])
+            .unwrap_err()
+            .contains("more than"));
+    }
+}
@@ -0,0 +1,224 @@
+//! type conversions of `tobool`, `tonumber`, `tostring`, `tolist` and `tomap`
+//!
+//! Strict conversions only accept values that have an obvious counterpart (`"true"`, `"8080"`).
+//! Lenient ones accept the spellings found in environment variables and other string-only
+//! sources: `yes`/`off`, comma separated lists, `key=value` pairs. `null` stays `null` in strict
+//! mode.
+use hcl::{Map, Number, Value};
+
+/// How forgiving a conversion is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Strict,
+    Lenient,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "strict" => Ok(Mode::Strict),
+            "lenient" => Ok(Mode::Lenient),
+            other => Err(format!(
+                "invalid mode {other:?}, expected strict or lenient"
+            )),
+        }
+    }
+}
+
+fn invalid(value: &Value, target: &str) -> String {
+    format!("can not convert {value} to {target}")
+}
+
+pub fn to_bool(value: Value, mode: Mode) -> Result<Value, String> {
+    match (value, mode) {
+        (value @ (Value::Bool(_) | Value::Null), Mode::Strict) => Ok(value),
+        (Value::String(string), Mode::Strict) => match string.as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(invalid(&Value::String(string), "bool")),
+        },
+        (Value::Bool(bool), Mode::Lenient) => Ok(Value::Bool(bool)),
+        (Value::Null, Mode::Lenient) => Ok(Value::Bool(false)),
+        (Value::Number(number), Mode::Lenient) => {
+            Ok(Value::Bool(number.as_f64().is_some_and(|n| n != 0.0)))
+        }
+        (Value::String(string), Mode::Lenient) => {
+            match string.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" | "y" | "on" | "1" => Ok(Value::Bool(true)),
+                "false" | "no" | "n" | "off" | "0" | "" => Ok(Value::Bool(false)),
+                _ => Err(invalid(&Value::String(string), "bool")),
+            }
+        }
+        (value, _) => Err(invalid(&value, "bool")),
+    }
+}
+
+fn parse_number(text: &str) -> Option<Number> {
+    match text.parse::<i64>() {
+        Ok(int) => Some(Number::from(int)),
+        Err(_) => text
+            .parse::<f64>()
+            .ok()
+            .filter(|float| float.is_finite())
+            .and_then(Number::from_f64),
+    }
+}
+
+pub fn to_number(value: Value, mode: Mode) -> Result<Value, String> {
+    match (value, mode) {
+        (value @ (Value::Number(_) | Value::Null), _) => Ok(value),
+        (Value::String(string), Mode::Strict) => parse_number(&string)
+            .map(Value::Number)
+            .ok_or_else(|| invalid(&Value::String(string), "number")),
+        (Value::String(string), Mode::Lenient) => {
+            // `1_000` and ` 42\n` as read from files and environment variables
+            let text = string.trim().replace('_', "");
+            if text.is_empty() {
+                return Ok(Value::Null);
+            }
+            parse_number(&text)
+                .map(Value::Number)
+                .ok_or_else(|| invalid(&Value::String(string), "number"))
+        }
+        (Value::Bool(bool), Mode::Lenient) => Ok(Value::from(bool as i64)),
+        (value, _) => Err(invalid(&value, "number")),
+    }
+}
+
+pub fn to_string(value: Value, mode: Mode) -> Result<Value, String> {
+    match (value, mode) {
+        (value @ (Value::String(_) | Value::Null), Mode::Strict) => Ok(value),
+        (Value::String(string), Mode::Lenient) => Ok(Value::String(string)),
+        (Value::Null, Mode::Lenient) => Ok(Value::String(String::new())),
+        (Value::Number(number), _) => Ok(Value::String(number.to_string())),
+        (Value::Bool(bool), _) => Ok(Value::String(bool.to_string())),
+        (value @ (Value::Array(_) | Value::Object(_)), Mode::Lenient) => {
+            serde_json::to_string(&value)
+                .map(Value::String)
+                .map_err(|err| err.to_string())
+        }
+        (value, Mode::Strict) => Err(invalid(&value, "string")),
+    }
+}
+
+pub fn to_list(value: Value, mode: Mode) -> Result<Value, String> {
+    match (value, mode) {
+        (value @ (Value::Array(_) | Value::Null), Mode::Strict) => Ok(value),
+        (Value::Array(array), Mode::Lenient) => Ok(Value::Array(array)),
+        (Value::Null, Mode::Lenient) => Ok(Value::Array(vec![])),
+        // `"a, b,c"`
+        (Value::String(string), Mode::Lenient) => Ok(Value::Array(
+            string
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        )),
+        (Value::Object(object), Mode::Lenient) => Ok(Value::Array(object.into_values().collect())),
+        (value @ (Value::Bool(_) | Value::Number(_)), Mode::Lenient) => {
+            Ok(Value::Array(vec![value]))
+        }
+        (value, _) => Err(invalid(&value, "list")),
+    }
+}
+
+pub fn to_map(value: Value, mode: Mode) -> Result<Value, String> {
+    match (value, mode) {
+        (value @ (Value::Object(_) | Value::Null), Mode::Strict) => Ok(value),
+        (Value::Object(object), Mode::Lenient) => Ok(Value::Object(object)),
+        (Value::Null, Mode::Lenient) => Ok(Value::Object(Map::new())),
+        // `"a=1, b=2"`
+        (Value::String(string), Mode::Lenient) => {
+            let mut map = Map::new();
+            for pair in string
+                .split(',')
+                .map(str::trim)
+                .filter(|pair| !pair.is_empty())
+            {
+                let (key, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected key=value, got {pair:?}"))?;
+                map.insert(
+                    key.trim().to_string(),
+                    Value::String(value.trim().to_string()),
+                );
+            }
+            Ok(Value::Object(map))
+        }
+        // `[["a", 1], ["b", 2]]`
+        (Value::Array(pairs), Mode::Lenient) => {
+            let mut map = Map::new();
+            for pair in pairs {
+                match pair {
+                    Value::Array(pair) if pair.len() == 2 => {
+                        let mut pair = pair.into_iter();
+                        let (key, value) = (pair.next().unwrap(), pair.next().unwrap());
+                        let Value::String(key) = to_string(key, Mode::Strict)? else {
+                            return Err("keys must not be null".to_string());
+                        };
+                        map.insert(key, value);
+                    }
+                    other => return Err(format!("expected a [key, value] pair, got {other}")),
+                }
+            }
+            Ok(Value::Object(map))
+        }
+        (value, _) => Err(invalid(&value, "map")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strict() {
+        assert_eq!(to_bool("true".into(), Mode::Strict), Ok(Value::Bool(true)));
+        assert!(to_bool("yes".into(), Mode::Strict).is_err());
+        assert_eq!(
+            to_number("8080".into(), Mode::Strict),
+            Ok(Value::from(8080))
+        );
+        assert_eq!(to_number("0.5".into(), Mode::Strict), Ok(Value::from(0.5)));
+        assert!(to_number(" 1".into(), Mode::Strict).is_err());
+        assert_eq!(to_string(Value::from(1), Mode::Strict), Ok("1".into()));
+        assert!(to_string(Value::Array(vec![]), Mode::Strict).is_err());
+        assert_eq!(to_list(Value::Null, Mode::Strict), Ok(Value::Null));
+        assert!(to_map("a=1".into(), Mode::Strict).is_err());
+    }
+
+    #[test]
+    fn lenient() {
+        assert_eq!(
+            to_bool(" Off ".into(), Mode::Lenient),
+            Ok(Value::Bool(false))
+        );
+        assert_eq!(
+            to_bool(Value::from(2), Mode::Lenient),
+            Ok(Value::Bool(true))
+        );
+        assert!(to_bool("maybe".into(), Mode::Lenient).is_err());
+        assert_eq!(
+            to_number("1_000\n".into(), Mode::Lenient),
+            Ok(Value::from(1000))
+        );
+        assert_eq!(to_number("".into(), Mode::Lenient), Ok(Value::Null));
+        assert_eq!(to_string(Value::Null, Mode::Lenient), Ok("".into()));
+        assert_eq!(
+            to_list("a, b,,c".into(), Mode::Lenient),
+            Ok(Value::Array(vec!["a".into(), "b".into(), "c".into()]))
+        );
+
+        let mut map = Map::new();
+        map.insert("a".to_string(), Value::from("1"));
+        map.insert("b".to_string(), Value::from("x=y"));
+        assert_eq!(
+            to_map("a=1, b = x=y".into(), Mode::Lenient),
+            Ok(Value::Object(map))
+        );
+        assert!(to_map("a".into(), Mode::Lenient).is_err());
+    }
+}
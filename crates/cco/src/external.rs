@@ -0,0 +1,117 @@
+//! Functions implemented by closures, see [CcoDocumentBuilder::external_function]
+//!
+//! [hcl::eval::FuncDef]s are plain function pointers and can not carry state. Calls of external
+//! functions are rewritten to `cco__call("<name>", args...)`. That function looks the closure up in
+//! the functions of the document that is currently being evaluated on this thread.
+//!
+//! [CcoDocumentBuilder::external_function]: crate::cco_document::CcoDocumentBuilder::external_function
+use crate::cco_document::{ExternalFn, SUBST_PREFIX};
+use crate::visit::{VisitFuncCallsMut, VisitMut};
+use hcl::{Expression, FuncCall, Identifier};
+use std::cell::RefCell;
+use std::sync::Arc;
+
+/// Function all external calls are rewritten to
+const CALL_FUNCTION: &str = "cco__call";
+
+type Functions = indexmap::IndexMap<Identifier, Arc<ExternalFn>>;
+
+thread_local! {
+    /// Functions of the documents being evaluated on this thread, innermost last
+    static ACTIVE: RefCell<Vec<Arc<Functions>>> = const { RefCell::new(vec![]) };
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Externals {
+    functions: Arc<Functions>,
+}
+
+impl std::fmt::Debug for Externals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.functions.keys()).finish()
+    }
+}
+
+impl Externals {
+    pub fn insert(&mut self, name: Identifier, func: Arc<ExternalFn>) {
+        Arc::make_mut(&mut self.functions).insert(name, func);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// Declare `cco__call` in `context`
+    pub fn declare(&self, context: &mut hcl::eval::Context) {
+        if !self.is_empty() {
+            let func = hcl::eval::FuncDef::builder()
+                .variadic_param(hcl::eval::ParamType::Any)
+                .build(call);
+            context.declare_func(CALL_FUNCTION, func);
+        }
+    }
+
+    /// Rewrite calls of external functions in `expression` to `cco__call`
+    pub fn rewrite(&self, expression: &mut Expression) {
+        if self.is_empty() {
+            return;
+        }
+
+        let mut rewriter = |func_call: &mut FuncCall| {
+            if func_call.name.is_namespaced() || !self.functions.contains_key(&func_call.name.name)
+            {
+                return;
+            }
+            let name = std::mem::replace(
+                &mut func_call.name,
+                Identifier::unchecked(CALL_FUNCTION).into(),
+            );
+            func_call
+                .args
+                .insert(0, Expression::String(name.to_string()));
+        };
+        expression.visit_func_calls_mut(&mut rewriter as &mut dyn VisitMut<FuncCall>);
+    }
+
+    /// Make the functions available to `cco__call` until the guard is dropped
+    pub fn enter(&self) -> Option<Scope> {
+        if self.is_empty() {
+            return None;
+        }
+        ACTIVE.with(|active| active.borrow_mut().push(self.functions.clone()));
+        Some(Scope(()))
+    }
+}
+
+/// Active while a document with external functions is evaluated, see [Externals::enter]
+pub(crate) struct Scope(());
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| active.borrow_mut().pop());
+    }
+}
+
+fn call(args: hcl::eval::FuncArgs) -> Result<hcl::Value, String> {
+    let mut args = args.into_values();
+    let name = match (!args.is_empty()).then(|| args.remove(0)) {
+        Some(hcl::Value::String(name)) => name,
+        _ => return Err("missing function name".to_string()),
+    };
+
+    // no borrow while calling, the function may evaluate another document
+    let func = ACTIVE.with(|active| {
+        active
+            .borrow()
+            .last()
+            .and_then(|functions| functions.get(name.as_str()).cloned())
+    });
+    let Some(func) = func else {
+        return Err(format!("unknown external function '{name}'"));
+    };
+    // hcl reports the error for `cco__call`, name the actual function
+    func(&args).map_err(|err| match name.starts_with(SUBST_PREFIX) {
+        true => err,
+        false => format!("{name}: {err}"),
+    })
+}
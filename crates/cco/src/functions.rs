@@ -0,0 +1,983 @@
+//! Functions available to all expressions
+//!
+//! They are declared before [CcoDocumentBuilder::function]s, which can replace them.
+//!
+//! `try` and `can` need their arguments unevaluated, see [resolve_fallbacks].
+//!
+//! [CcoDocumentBuilder::function]: crate::cco_document::CcoDocumentBuilder::function
+use crate::cco_document::{OverflowPolicy, SUBST_PREFIX};
+use crate::collections;
+use crate::convert;
+use crate::printf;
+use crate::quantity;
+use crate::seeded;
+use crate::semver;
+use crate::url;
+use crate::visit::{VisitFuncCallsMut, VisitMut};
+use hcl::eval::{Context, ErrorKind, Evaluate, FuncArgs, FuncDef, ParamType};
+use hcl::{Expression, FuncCall, Identifier, Value};
+
+/// Declare all functions in `context`
+pub(crate) fn declare(context: &mut Context) {
+    context.declare_func(
+        "deepmerge",
+        FuncDef::builder()
+            .params([ParamType::Any, ParamType::Any])
+            .variadic_param(ParamType::Any)
+            .build(deepmerge),
+    );
+    context.declare_func(
+        "coalesce",
+        FuncDef::builder()
+            .variadic_param(ParamType::Any)
+            .build(coalesce),
+    );
+    context.declare_func(
+        crate::arithmetic::OVERFLOW_FUNCTION,
+        FuncDef::builder().param(ParamType::String).build(|args| {
+            let operation = args[0].as_str().unwrap_or_default();
+            Err(format!("integer overflow in `{operation}`"))
+        }),
+    );
+    context.declare_func(
+        crate::arithmetic::CHECKED_FUNCTION,
+        FuncDef::builder()
+            .param(ParamType::String)
+            .variadic_param(ParamType::Any)
+            .build(|args| crate::arithmetic::deferred(&args, OverflowPolicy::Error)),
+    );
+    context.declare_func(
+        crate::arithmetic::SATURATING_FUNCTION,
+        FuncDef::builder()
+            .param(ParamType::String)
+            .variadic_param(ParamType::Any)
+            .build(|args| crate::arithmetic::deferred(&args, OverflowPolicy::Saturate)),
+    );
+    // only reached with arguments that evaluated fine, see [resolve_fallbacks]
+    context.declare_func(
+        "try",
+        FuncDef::builder()
+            .variadic_param(ParamType::Any)
+            .build(|args| Ok(args.into_values().into_iter().next().unwrap_or_default())),
+    );
+    context.declare_func(
+        "can",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .build(|_| Ok(Value::Bool(true))),
+    );
+    context.declare_func(
+        "base64encode",
+        FuncDef::builder().param(ParamType::Any).build(base64encode),
+    );
+    context.declare_func(
+        "base64decode",
+        FuncDef::builder()
+            .param(ParamType::String)
+            .build(|args| base64decode(args[0].as_str().unwrap_or_default())),
+    );
+    context.declare_func(
+        "parse_duration",
+        FuncDef::builder().param(ParamType::String).build(|args| {
+            let seconds = quantity::parse_duration(args[0].as_str().unwrap_or_default())?;
+            Ok(Value::from(seconds))
+        }),
+    );
+    context.declare_func(
+        "format_duration",
+        FuncDef::builder()
+            .param(ParamType::Number)
+            .variadic_param(ParamType::String)
+            .build(|args| {
+                let seconds = args[0].as_f64().unwrap_or_default();
+                Ok(quantity::format_duration(seconds, unit(&args)?)?.into())
+            }),
+    );
+    context.declare_func(
+        "parse_bytes",
+        FuncDef::builder().param(ParamType::String).build(|args| {
+            let bytes = quantity::parse_bytes(args[0].as_str().unwrap_or_default())?;
+            Ok(Value::from(bytes))
+        }),
+    );
+    context.declare_func(
+        "format_bytes",
+        FuncDef::builder()
+            .param(ParamType::Number)
+            .variadic_param(ParamType::String)
+            .build(|args| {
+                let bytes = args[0]
+                    .as_i64()
+                    .ok_or_else(|| format!("expected a whole number of bytes, got {}", args[0]))?;
+                Ok(quantity::format_bytes(bytes, unit(&args)?)?.into())
+            }),
+    );
+    context.declare_func(
+        "tobool",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .variadic_param(ParamType::String)
+            .build(|args| convert::to_bool(args[0].clone(), mode(&args)?)),
+    );
+    context.declare_func(
+        "tonumber",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .variadic_param(ParamType::String)
+            .build(|args| convert::to_number(args[0].clone(), mode(&args)?)),
+    );
+    context.declare_func(
+        "tostring",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .variadic_param(ParamType::String)
+            .build(|args| convert::to_string(args[0].clone(), mode(&args)?)),
+    );
+    context.declare_func(
+        "tolist",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .variadic_param(ParamType::String)
+            .build(|args| convert::to_list(args[0].clone(), mode(&args)?)),
+    );
+    context.declare_func(
+        "tomap",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .variadic_param(ParamType::String)
+            .build(|args| convert::to_map(args[0].clone(), mode(&args)?)),
+    );
+    context.declare_func(
+        "format",
+        FuncDef::builder()
+            .param(ParamType::String)
+            .variadic_param(ParamType::Any)
+            .build(|args| {
+                let template = args[0].as_str().unwrap_or_default();
+                Ok(printf::format(template, &args[1..])?.into())
+            }),
+    );
+    context.declare_func(
+        "formatlist",
+        FuncDef::builder()
+            .param(ParamType::String)
+            .variadic_param(ParamType::Any)
+            .build(|args| {
+                let template = args[0].as_str().unwrap_or_default();
+                let list = printf::format_list(template, &args[1..])?;
+                Ok(Value::Array(list.into_iter().map(Value::String).collect()))
+            }),
+    );
+    context.declare_func(
+        "zipmap",
+        FuncDef::builder()
+            .params([ParamType::Any, ParamType::Any])
+            .build(|args| collections::zipmap(args[0].clone(), args[1].clone())),
+    );
+    context.declare_func(
+        "transpose",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .build(|args| collections::transpose(args[0].clone())),
+    );
+    context.declare_func(
+        "flatten",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .build(|args| collections::flatten(args[0].clone())),
+    );
+    context.declare_func(
+        "distinct",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .build(|args| collections::distinct(args[0].clone())),
+    );
+    context.declare_func(
+        "sort",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .build(|args| collections::sort(args[0].clone())),
+    );
+    context.declare_func(
+        "reverse",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .build(|args| collections::reverse(args[0].clone())),
+    );
+    context.declare_func(
+        "chunklist",
+        FuncDef::builder()
+            .params([ParamType::Any, ParamType::Any])
+            .build(|args| collections::chunklist(args[0].clone(), args[1].clone())),
+    );
+    context.declare_func(
+        "range",
+        FuncDef::builder()
+            .param(ParamType::Number)
+            .variadic_param(ParamType::Number)
+            .build(|args| collections::range(&args)),
+    );
+    context.declare_func(
+        "setproduct",
+        FuncDef::builder()
+            .param(ParamType::Any)
+            .variadic_param(ParamType::Any)
+            .build(|args| collections::setproduct(args.into_values())),
+    );
+    context.declare_func(
+        "uuidv5",
+        FuncDef::builder()
+            .params([ParamType::String, ParamType::String])
+            .build(|args| {
+                let (namespace, name) = (args[0].as_str(), args[1].as_str());
+                Ok(seeded::uuidv5(namespace.unwrap_or_default(), name.unwrap_or_default())?.into())
+            }),
+    );
+    context.declare_func(
+        "random",
+        FuncDef::builder()
+            .params([ParamType::Any, ParamType::Number])
+            .build(|args| {
+                let max = args[1]
+                    .as_u64()
+                    .ok_or_else(|| format!("expected a positive whole number, got {}", args[1]))?;
+                Ok(seeded::random(&seed(&args[0])?, max)?.into())
+            }),
+    );
+    context.declare_func(
+        "random_string",
+        FuncDef::builder()
+            .params([ParamType::Any, ParamType::Number])
+            .build(|args| {
+                let length = args[1]
+                    .as_u64()
+                    .ok_or_else(|| format!("expected a whole number length, got {}", args[1]))?;
+                let length = usize::try_from(length).unwrap_or(usize::MAX);
+                Ok(seeded::random_string(&seed(&args[0])?, length)?.into())
+            }),
+    );
+    context.declare_func(
+        "urlencode",
+        FuncDef::builder()
+            .param(ParamType::String)
+            .build(|args| Ok(url::encode(args[0].as_str().unwrap_or_default()).into())),
+    );
+    context.declare_func(
+        "urldecode",
+        FuncDef::builder()
+            .param(ParamType::String)
+            .build(|args| Ok(url::decode(args[0].as_str().unwrap_or_default())?.into())),
+    );
+    context.declare_func(
+        "urljoin",
+        FuncDef::builder()
+            .param(ParamType::String)
+            .variadic_param(ParamType::String)
+            .build(|args| {
+                let mut joined = args[0].as_str().unwrap_or_default().to_string();
+                for reference in &args[1..] {
+                    joined = url::join(&joined, reference.as_str().unwrap_or_default())?;
+                }
+                Ok(joined.into())
+            }),
+    );
+    context.declare_func(
+        "parseurl",
+        FuncDef::builder()
+            .param(ParamType::String)
+            .build(|args| url::parse(args[0].as_str().unwrap_or_default())),
+    );
+    context.declare_func(
+        "semver_satisfies",
+        FuncDef::builder()
+            .params([ParamType::String, ParamType::String])
+            .build(|args| {
+                let (version, constraint) = (args[0].as_str(), args[1].as_str());
+                let satisfies =
+                    semver::satisfies(version.unwrap_or_default(), constraint.unwrap_or_default())?;
+                Ok(Value::Bool(satisfies))
+            }),
+    );
+    context.declare_func(
+        "semver_compare",
+        FuncDef::builder()
+            .params([ParamType::String, ParamType::String])
+            .build(|args| {
+                let a = semver::parse(args[0].as_str().unwrap_or_default())?;
+                let b = semver::parse(args[1].as_str().unwrap_or_default())?;
+                Ok(Value::from(a.cmp(&b) as i64))
+            }),
+    );
+    context.declare_func(
+        "semver_parse",
+        FuncDef::builder().param(ParamType::String).build(|args| {
+            let version = semver::parse(args[0].as_str().unwrap_or_default())?;
+            let mut object = hcl::Map::new();
+            object.insert("major".to_string(), Value::from(version.major));
+            object.insert("minor".to_string(), Value::from(version.minor));
+            object.insert("patch".to_string(), Value::from(version.patch));
+            if !version.pre.is_empty() {
+                object.insert("prerelease".to_string(), Value::from(version.pre.join(".")));
+            }
+            if let Some(build) = version.build {
+                object.insert("build".to_string(), Value::from(build));
+            }
+            Ok(Value::Object(object))
+        }),
+    );
+    #[cfg(feature = "fs")]
+    context.declare_func(
+        FILE_FUNCTION,
+        FuncDef::builder()
+            .param(ParamType::String)
+            .build(filebase64decode),
+    );
+}
+
+/// Function reading files, paths are relative to the document calling it
+#[cfg(feature = "fs")]
+const FILE_FUNCTION: &str = "filebase64decode";
+
+/// Replace [FILE_FUNCTION] with a function that fails, for documents evaluating untrusted
+/// expressions
+#[cfg(feature = "fs")]
+pub(crate) fn disable_files(context: &mut Context) {
+    context.declare_func(
+        FILE_FUNCTION,
+        FuncDef::builder()
+            .variadic_param(ParamType::Any)
+            .build(|_| Err(format!("{FILE_FUNCTION} is disabled"))),
+    );
+}
+
+#[cfg(feature = "fs")]
+thread_local! {
+    /// Directory of the document whose expression is being evaluated on this thread
+    static FILE_DIR: std::cell::RefCell<Option<std::path::PathBuf>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Resolve relative paths of [FILE_FUNCTION] against `dir` until the guard is dropped
+///
+/// The directory is not an argument of the function, documents can not pass another one.
+#[cfg(feature = "fs")]
+pub(crate) fn enter_file_dir(dir: Option<&std::path::Path>) -> FileDir {
+    let previous = FILE_DIR.with(|active| active.replace(dir.map(std::path::Path::to_path_buf)));
+    FileDir(previous)
+}
+
+/// Active while an expression of a document is evaluated, see [enter_file_dir]
+#[cfg(feature = "fs")]
+pub(crate) struct FileDir(Option<std::path::PathBuf>);
+
+#[cfg(feature = "fs")]
+impl Drop for FileDir {
+    fn drop(&mut self) {
+        FILE_DIR.with(|active| *active.borrow_mut() = self.0.take());
+    }
+}
+
+/// Replace `try(...)` and `can(...)` calls with their result
+///
+/// Arguments are evaluated one after the other, errors make `try` move on to the next one and
+/// `can` return `false`. Returns the substitution identifier of an addressable that has to be
+/// evaluated first, or an unknown variable that is `resolvable` by the resolver: arguments are
+/// only judged once all their references are known. References to addressables that `failed`
+/// fail the argument.
+///
+/// Arguments referencing variables of `for` expressions can not be evaluated on their own. Those
+/// calls are left to the plain functions declared by [declare], errors are not caught there.
+pub(crate) fn resolve_fallbacks(
+    expression: &mut Expression,
+    context: &Context,
+    resolvable: &dyn Fn(&Identifier) -> bool,
+    failed: &std::collections::HashMap<Identifier, String>,
+) -> Result<Option<Identifier>, String> {
+    let mut locals = std::collections::HashSet::new();
+    crate::util::local_variables(expression, &mut locals);
+
+    let mut outcome = Ok(None);
+    let mut resolve = |func_call: &mut FuncCall| {
+        let can = match func_call.name.name.as_str() {
+            "try" => false,
+            "can" => true,
+            _ => return,
+        };
+        if !matches!(outcome, Ok(None)) || func_call.args.is_empty() {
+            return;
+        }
+
+        let mut last_error = String::new();
+        for arg in &func_call.args {
+            match arg.evaluate(context) {
+                Ok(value) => {
+                    let value = if can { Value::Bool(true) } else { value };
+                    *func_call = FuncCall::builder("try").arg(value).build();
+                    return;
+                }
+                Err(err) => {
+                    if let ErrorKind::UndefinedVar(var) = err.kind() {
+                        if let Some(message) = failed.get(var) {
+                            last_error = message.clone();
+                            continue;
+                        }
+                        if locals.contains(var.as_str()) {
+                            return;
+                        }
+                        if var.starts_with(SUBST_PREFIX) || resolvable(var) {
+                            outcome = Ok(Some(var.clone()));
+                            return;
+                        }
+                    }
+                    last_error = err.to_string();
+                }
+            }
+        }
+
+        if can {
+            *func_call = FuncCall::builder("try").arg(false).build();
+        } else {
+            outcome = Err(format!("all arguments of try failed, last: {last_error}"));
+        }
+    };
+    expression.visit_func_calls_mut(&mut resolve as &mut dyn VisitMut<FuncCall>);
+    outcome
+}
+
+/// First argument that is neither `null` nor an empty string
+fn coalesce(args: FuncArgs) -> Result<Value, String> {
+    args.into_values()
+        .into_iter()
+        .find(|value| !value.is_null() && value.as_str() != Some(""))
+        .ok_or_else(|| "no non-null, non-empty argument".to_string())
+}
+
+/// Optional unit argument of `format_duration` and `format_bytes`
+fn unit(args: &FuncArgs) -> Result<Option<&str>, String> {
+    match &args[1..] {
+        [] => Ok(None),
+        [unit] => Ok(unit.as_str()),
+        _ => Err("expected at most one unit".to_string()),
+    }
+}
+
+/// Optional mode argument of the conversion functions, `strict` by default
+fn mode(args: &FuncArgs) -> Result<convert::Mode, String> {
+    match &args[1..] {
+        [] => Ok(convert::Mode::Strict),
+        [mode] => mode.as_str().unwrap_or_default().parse(),
+        _ => Err("expected at most one mode".to_string()),
+    }
+}
+
+/// Seed of `random` and `random_string`: a string, number or bool
+fn seed(value: &Value) -> Result<String, String> {
+    match convert::to_string(value.clone(), convert::Mode::Strict) {
+        Ok(Value::String(seed)) => Ok(seed),
+        _ => Err(format!(
+            "expected a string, number or bool as seed, got {value}"
+        )),
+    }
+}
+
+/// Base64 of a string (UTF-8) or of bytes
+fn base64encode(args: FuncArgs) -> Result<Value, String> {
+    match crate::value::Value::from(args[0].clone()) {
+        crate::value::Value::String(string) => Ok(crate::base64::encode(string.as_bytes()).into()),
+        crate::value::Value::Bytes(bytes) => Ok(crate::base64::encode(&bytes).into()),
+        other => Err(format!(
+            "expected a string or bytes, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+/// Bytes of base64 text, see [crate::value::bytes_key]
+fn base64decode(encoded: &str) -> Result<Value, String> {
+    let bytes = crate::base64::decode(encoded).map_err(|err| err.to_string())?;
+    Ok(crate::value::Value::Bytes(bytes).into())
+}
+
+/// Bytes of a file containing base64 text (e.g. a PEM body)
+///
+/// Relative to the directory of the document, see [enter_file_dir], the working directory for
+/// expressions outside of documents.
+#[cfg(feature = "fs")]
+fn filebase64decode(args: FuncArgs) -> Result<Value, String> {
+    let path = args[0].as_str().unwrap_or_default();
+    let full_path = FILE_DIR.with(|dir| match &*dir.borrow() {
+        Some(dir) => dir.join(path),
+        None => std::path::PathBuf::from(path),
+    });
+    let encoded = std::fs::read_to_string(full_path)
+        .map_err(|err| format!("failed to read {path}: {err}"))?;
+    base64decode(&encoded).map_err(|err| format!("{path}: {err}"))
+}
+
+/// How [deepmerge] combines two lists
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lists {
+    /// Like any other value, see [Conflicts]
+    Replace,
+    /// Elements of the second list after the ones of the first
+    Append,
+    /// Like [Lists::Append], without elements the first list already contains
+    Union,
+}
+
+/// How [deepmerge] resolves two different values that are not both objects
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Conflicts {
+    /// The second value wins
+    Override,
+    /// The first value wins
+    Keep,
+    Error,
+}
+
+/// `deepmerge(a, b, {lists = "append", conflicts = "error"})`
+///
+/// Objects are merged recursively. The options are optional:
+/// - `lists`: `replace` (default), `append` or `union`
+/// - `conflicts`: `override` (default), `keep` or `error`
+fn deepmerge(args: FuncArgs) -> Result<Value, String> {
+    let mut args = args.into_values().into_iter();
+    let (a, b) = (
+        args.next().unwrap_or_default(),
+        args.next().unwrap_or_default(),
+    );
+
+    let (mut lists, mut conflicts) = (Lists::Replace, Conflicts::Override);
+    match (args.next(), args.next()) {
+        (None, _) => {}
+        (Some(Value::Object(options)), None) => {
+            for (key, value) in options {
+                match (key.as_str(), value.as_str()) {
+                    ("lists", Some("replace")) => lists = Lists::Replace,
+                    ("lists", Some("append")) => lists = Lists::Append,
+                    ("lists", Some("union")) => lists = Lists::Union,
+                    ("conflicts", Some("override")) => conflicts = Conflicts::Override,
+                    ("conflicts", Some("keep")) => conflicts = Conflicts::Keep,
+                    ("conflicts", Some("error")) => conflicts = Conflicts::Error,
+                    _ => return Err(format!("invalid option {key} = {value}")),
+                }
+            }
+        }
+        _ => return Err("expected an object of options as third argument".to_string()),
+    }
+
+    merge(a, b, lists, conflicts, &mut vec![])
+}
+
+fn merge(
+    a: Value,
+    b: Value,
+    lists: Lists,
+    conflicts: Conflicts,
+    path: &mut Vec<String>,
+) -> Result<Value, String> {
+    match (a, b) {
+        (Value::Object(mut a), Value::Object(b)) => {
+            // keys keep their position in `a`, new ones follow in the order of `b`
+            for (key, b_value) in b {
+                match a.get_mut(&key) {
+                    Some(slot) => {
+                        path.push(key);
+                        *slot = merge(std::mem::take(slot), b_value, lists, conflicts, path)?;
+                        path.pop();
+                    }
+                    None => {
+                        a.insert(key, b_value);
+                    }
+                }
+            }
+            Ok(Value::Object(a))
+        }
+        (Value::Array(mut a), Value::Array(b)) if lists == Lists::Append => {
+            a.extend(b);
+            Ok(Value::Array(a))
+        }
+        (Value::Array(mut a), Value::Array(b)) if lists == Lists::Union => {
+            for value in b {
+                if !a.contains(&value) {
+                    a.push(value);
+                }
+            }
+            Ok(Value::Array(a))
+        }
+        (a, b) if a == b => Ok(a),
+        (a, b) => match conflicts {
+            Conflicts::Override => Ok(b),
+            Conflicts::Keep => Ok(a),
+            Conflicts::Error => Err(format!(
+                "conflicting values at `{}`: {a} and {b}",
+                path.join(".")
+            )),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cco_document::CcoDocument;
+    use crate::hcl_documents;
+
+    fn evaluate(expression: &str) -> Result<serde_json::Value, String> {
+        let document = CcoDocument::new(&hcl_documents!("data a {}")).unwrap();
+        let expression: hcl_edit::expr::Expression = expression.parse().unwrap();
+        document
+            .evaluate_in_context(expression.into())
+            .map(|value| serde_json::to_value(value).unwrap())
+            .map_err(|err| format!("{err:#}"))
+    }
+
+    #[test]
+    fn deepmerge() {
+        let a = r#"{ name = "a", tags = ["x"], limits = { cpu = 1, memory = "1Gi" } }"#;
+        let b = r#"{ tags = ["x", "y"], limits = { cpu = 2 } }"#;
+
+        assert_eq!(
+            evaluate(&format!("deepmerge({a}, {b})")),
+            Ok(serde_json::json!({
+                "name": "a",
+                "tags": ["x", "y"],
+                "limits": { "cpu": 2, "memory": "1Gi" }
+            }))
+        );
+        assert_eq!(
+            evaluate(&format!(
+                r#"deepmerge({a}, {b}, {{ lists = "append", conflicts = "keep" }})"#
+            )),
+            Ok(serde_json::json!({
+                "name": "a",
+                "tags": ["x", "x", "y"],
+                "limits": { "cpu": 1, "memory": "1Gi" }
+            }))
+        );
+        assert_eq!(
+            evaluate(&format!(r#"deepmerge({a}, {b}, {{ lists = "union" }})"#)).unwrap()["tags"],
+            serde_json::json!(["x", "y"])
+        );
+
+        let merged =
+            evaluate(r#"deepmerge({ b = 1, a = { y = 1, x = 1 } }, { c = 1, a = { x = 2 } })"#);
+        assert_eq!(
+            serde_json::to_string(&merged.unwrap()).unwrap(),
+            r#"{"b":1,"a":{"y":1,"x":2},"c":1}"#
+        );
+    }
+
+    #[test]
+    fn fallbacks() {
+        let document = CcoDocument::new(&hcl_documents!(
+            r#"
+            data service api {
+              port = 80
+              tags = ["a"]
+            }
+            data service db {
+              optional = try(service.api.missing, service.api.port + 1)
+              fails    = can(service.api.tags[3])
+              works    = can(service.api.tags[0])
+              template = "port ${try(service.api.port, 0)}"
+              nested   = try(try(unknown.path, "inner"), "fallback")
+              looped   = [for tag in service.api.tags: try(tag, "none")]
+              caught   = try(service.broken.value, "fallback")
+              chained  = can(service.broken.chained)
+            }
+            data service broken {
+              value   = 1 + "a"
+              chained = service.broken.value + 1
+            }
+            "#
+        ))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(document.evaluate_path(&["service", "db"]).unwrap()).unwrap(),
+            serde_json::json!({
+                "optional": 81,
+                "fails": false,
+                "works": true,
+                "template": "port 80",
+                "nested": "inner",
+                "looped": ["a"],
+                "caught": "fallback",
+                "chained": false
+            })
+        );
+        // the failed addressable is still an error outside of `try`
+        assert!(document
+            .evaluate_path(&["service", "broken", "chained"])
+            .is_err());
+
+        assert!(evaluate(r#"try(nothing.here, 1 + "a")"#)
+            .unwrap_err()
+            .contains("all arguments of try failed"));
+        assert_eq!(
+            evaluate(r#"coalesce(null, "", "x", "y")"#),
+            Ok(serde_json::json!("x"))
+        );
+        assert!(evaluate("coalesce(null)").is_err());
+    }
+
+    #[test]
+    fn deepmerge_errors() {
+        let conflict =
+            r#"deepmerge({ a = { b = 1 } }, { a = { b = 2 } }, { conflicts = "error" })"#;
+        assert!(evaluate(conflict)
+            .unwrap_err()
+            .contains("conflicting values at `a.b`: 1 and 2"));
+
+        let same = r#"deepmerge({ a = 1 }, { a = 1 }, { conflicts = "error" })"#;
+        assert_eq!(evaluate(same), Ok(serde_json::json!({ "a": 1 })));
+
+        let option = r#"deepmerge({}, {}, { lists = "prepend" })"#;
+        assert!(evaluate(option).unwrap_err().contains("invalid option"));
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(
+            evaluate(r#"base64decode("/wD+")"#),
+            Ok(serde_json::json!("/wD+"))
+        );
+        assert_eq!(
+            evaluate(r#"base64encode(base64decode("Zm9v"))"#),
+            Ok(serde_json::json!("Zm9v"))
+        );
+        assert_eq!(
+            evaluate(r#"base64encode("foo")"#),
+            Ok(serde_json::json!("Zm9v"))
+        );
+        assert!(evaluate(r#"base64decode("!")"#)
+            .unwrap_err()
+            .contains("invalid base64"));
+        assert!(evaluate("base64encode(1)")
+            .unwrap_err()
+            .contains("got integer"));
+
+        let document =
+            CcoDocument::new(&hcl_documents!(r#"data a { key = base64decode("AAE=") }"#)).unwrap();
+        let value = document.evaluate_path(&["a", "key"]).unwrap();
+        assert_eq!(value.as_bytes(), Some(&[0u8, 1][..]));
+    }
+
+    #[test]
+    fn quantities() {
+        assert_eq!(
+            evaluate(r#"parse_duration("1h30m") / 60"#),
+            Ok(serde_json::json!(90))
+        );
+        assert_eq!(
+            evaluate(r#"format_duration(parse_duration("1h") * 1.5)"#),
+            Ok(serde_json::json!("1h30m"))
+        );
+        assert_eq!(
+            evaluate(r#"format_duration(parse_duration("1h30m"), "m")"#),
+            Ok(serde_json::json!("90m"))
+        );
+        assert_eq!(
+            evaluate(r#"format_bytes(parse_bytes("512Mi") * 4)"#),
+            Ok(serde_json::json!("2Gi"))
+        );
+        assert_eq!(
+            evaluate(r#"format_bytes(parse_bytes("1Gi"), "Mi")"#),
+            Ok(serde_json::json!("1024Mi"))
+        );
+        assert!(evaluate("format_bytes(0.5)")
+            .unwrap_err()
+            .contains("whole number"));
+        assert!(evaluate(r#"format_bytes(1, "Mi", "Gi")"#)
+            .unwrap_err()
+            .contains("at most one unit"));
+        assert!(evaluate(r#"parse_duration("soon")"#)
+            .unwrap_err()
+            .contains("invalid duration"));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            evaluate(r#"tobool("true") && tonumber("8080") == 8080"#),
+            Ok(serde_json::json!(true))
+        );
+        assert_eq!(
+            evaluate(r#"[tobool("on", "lenient"), tolist("a,b", "lenient"), tostring(1)]"#),
+            Ok(serde_json::json!([true, ["a", "b"], "1"]))
+        );
+        assert_eq!(
+            evaluate(r#"tomap([["a", 1]], "lenient")"#),
+            Ok(serde_json::json!({ "a": 1 }))
+        );
+        assert!(evaluate(r#"tobool("on")"#)
+            .unwrap_err()
+            .contains("can not convert"));
+        assert!(evaluate(r#"tonumber("1", "loose")"#)
+            .unwrap_err()
+            .contains("invalid mode"));
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            evaluate(r#"format("db-%s-%02d", "prod", 3)"#),
+            Ok(serde_json::json!("db-prod-03"))
+        );
+        assert_eq!(
+            evaluate(r#"formatlist("%s.internal:%d", ["a", "b"], 5432)"#),
+            Ok(serde_json::json!(["a.internal:5432", "b.internal:5432"]))
+        );
+        assert!(evaluate(r#"format("%s-%s", "a")"#)
+            .unwrap_err()
+            .contains("not enough arguments"));
+    }
+
+    #[test]
+    fn collections() {
+        assert_eq!(
+            evaluate(r#"zipmap(["api", "db"], [80, 5432])"#),
+            Ok(serde_json::json!({ "api": 80, "db": 5432 }))
+        );
+        assert_eq!(
+            evaluate(r#"sort(distinct(flatten([["b", "a"], ["b"]])))"#),
+            Ok(serde_json::json!(["a", "b"]))
+        );
+        assert_eq!(
+            evaluate(
+                r#"[for p in setproduct(["api"], ["eu", "us"]): format("%s-%s", p[0], p[1])]"#
+            ),
+            Ok(serde_json::json!(["api-eu", "api-us"]))
+        );
+        assert_eq!(
+            evaluate("chunklist(reverse([1, 2, 3]), 2)"),
+            Ok(serde_json::json!([[3, 2], [1]]))
+        );
+        assert_eq!(
+            evaluate(r#"[for shard in range(3): "replica-${shard}"]"#),
+            Ok(serde_json::json!(["replica-0", "replica-1", "replica-2"]))
+        );
+        assert_eq!(
+            evaluate("range(8080, 8083)"),
+            Ok(serde_json::json!([8080, 8081, 8082]))
+        );
+        assert!(evaluate(r#"zipmap([1], ["a"])"#)
+            .unwrap_err()
+            .contains("keys must be strings"));
+    }
+
+    #[test]
+    fn seeded() {
+        assert_eq!(
+            evaluate(r#"uuidv5("dns", "python.org")"#),
+            Ok(serde_json::json!("886313e1-3b8a-5372-9b90-0c9aee199e5d"))
+        );
+        assert_eq!(
+            evaluate(r#"random("api", 8) == random("api", 8) && random("api", 8) < 8"#),
+            Ok(serde_json::json!(true))
+        );
+        let token = evaluate(r#"random_string("token", 24)"#).unwrap();
+        assert_eq!(token.as_str().map(str::len), Some(24));
+        assert!(evaluate(r#"random_string("token", 1000000000000)"#)
+            .unwrap_err()
+            .contains("larger than 4096"));
+        assert!(evaluate(r#"random([], 8)"#)
+            .unwrap_err()
+            .contains("as seed"));
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            evaluate(r#"urljoin("https://api.example/v1/", "users/", urlencode("a b"))"#),
+            Ok(serde_json::json!("https://api.example/v1/users/a%20b"))
+        );
+        assert_eq!(
+            evaluate(r#"urldecode("a%20b")"#),
+            Ok(serde_json::json!("a b"))
+        );
+        assert_eq!(
+            evaluate(r#"parseurl("postgres://app@db.internal:5432/main?sslmode=require")"#),
+            Ok(serde_json::json!({
+                "scheme": "postgres",
+                "username": "app",
+                "host": "db.internal",
+                "port": 5432,
+                "path": "/main",
+                "query": "sslmode=require",
+                "params": { "sslmode": "require" }
+            }))
+        );
+        assert!(evaluate(r#"parseurl("db.internal")"#)
+            .unwrap_err()
+            .contains("not an absolute URL"));
+    }
+
+    #[test]
+    fn semver() {
+        assert_eq!(
+            evaluate(r#"semver_satisfies("1.4.2", ">=1.2, <2")"#),
+            Ok(serde_json::json!(true))
+        );
+        assert_eq!(
+            evaluate(r#"[semver_compare("1.10.0", "1.9.0"), semver_compare("v2", "2.0.0")]"#),
+            Ok(serde_json::json!([1, 0]))
+        );
+        assert_eq!(
+            evaluate(r#"semver_parse("1.2.3-rc.1")"#),
+            Ok(serde_json::json!({
+                "major": 1,
+                "minor": 2,
+                "patch": 3,
+                "prerelease": "rc.1"
+            }))
+        );
+        assert!(evaluate(r#"semver_satisfies("latest", ">=1")"#)
+            .unwrap_err()
+            .contains("invalid version"));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn filebase64decode() {
+        let dir = std::env::temp_dir().join(format!("cco-base64-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("certs")).unwrap();
+        let path = dir.join("certs").join("ca.b64");
+        std::fs::write(&path, "AAE=\n").unwrap();
+        let value = evaluate(&format!(
+            "filebase64decode({:?})",
+            path.display().to_string()
+        ));
+        assert_eq!(value, Ok(serde_json::json!("AAE=")));
+
+        let mut documents = crate::hcl_documents::HclDocuments::default();
+        documents
+            .insert_str(
+                "data tls {\n  ca = filebase64decode(\"certs/ca.b64\")\n}\n",
+                Some(dir.join("tls.cco.hcl")),
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        let ca = document.evaluate_path(&["tls", "ca"]).unwrap();
+        assert_eq!(ca.as_bytes(), Some(&[0, 1][..]));
+
+        // the directory is not an argument documents could set
+        let mut documents = crate::hcl_documents::HclDocuments::default();
+        documents
+            .insert_str(
+                "data tls {\n  ca = filebase64decode(\"ca.b64\", \"certs\")\n}\n",
+                Some(dir.join("tls.cco.hcl")),
+            )
+            .unwrap();
+        let document = CcoDocument::new(&documents).unwrap();
+        assert!(document.evaluate_path(&["tls", "ca"]).is_err());
+
+        let document = CcoDocument::builder()
+            .file_functions(false)
+            .build(&documents)
+            .unwrap();
+        let error = document.evaluate_path(&["tls", "ca"]).unwrap_err();
+        assert!(format!("{error:#}").contains("filebase64decode is disabled"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
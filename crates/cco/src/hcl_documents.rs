@@ -4,22 +4,79 @@
 //! - the source path
 //! - the root blocks
 //! - the root attributes
+//!
 //! and defines a numeric index for each. Once added those indices are stable (removal is not possible)
 use hcl_edit::structure::{Attribute, Block, Body, Structure};
+use hcl_edit::Span;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
-#[derive(Default, Debug)]
+/// `Debug` output only shows the structure of the documents, their expressions (and so literal
+/// secrets) only within [reveal_sensitive](crate::cco_document::reveal_sensitive).
+#[derive(Default)]
 pub struct HclDocuments {
+    /// How [HclDocuments::load_directory] treats symbolic links
+    #[cfg(feature = "fs")]
+    symlinks: SymlinkPolicy,
     sources: Vec<Source>,
+    /// Byte offsets of the line starts per source, empty if the text is unknown
+    line_starts: Vec<Vec<usize>>,
+    /// Offsets in the text as written for spans of the parsed documents per source
+    span_maps: Vec<SpanMap>,
+    /// Identifiers prepended to the data groups per source, see [HclDocuments::nest]
+    namespaces: Vec<Vec<hcl::Identifier>>,
+    /// SHA-256 (hex) of the text per source, `None` if the text is unknown
+    #[cfg(feature = "fs")]
+    hashes: Vec<Option<String>>,
     root_attributes: Vec<(usize, Attribute)>,
     root_blocks: Vec<(usize, Block)>,
 }
 
+impl std::fmt::Debug for HclDocuments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("HclDocuments");
+        debug
+            .field("sources", &self.sources)
+            .field("namespaces", &self.namespaces);
+        if crate::cco_document::sensitive_revealed() {
+            return debug
+                .field("root_attributes", &self.root_attributes)
+                .field("root_blocks", &self.root_blocks)
+                .finish();
+        }
+        let attributes: Vec<_> = self
+            .root_attributes
+            .iter()
+            .map(|(source, attribute)| (source, attribute.key.as_str()))
+            .collect();
+        let blocks: Vec<_> = self
+            .root_blocks
+            .iter()
+            .map(|(source, block)| {
+                let labels = block.labels.iter().map(|label| label.as_str());
+                let header: Vec<_> = std::iter::once(block.ident.as_str())
+                    .chain(labels)
+                    .collect();
+                (source, header.join(" "))
+            })
+            .collect();
+        debug
+            .field("root_attributes", &attributes)
+            .field("root_blocks", &blocks)
+            .finish_non_exhaustive()
+    }
+}
+
 impl HclDocuments {
     /// Inserts and indexes an hcl document
     pub fn insert(&mut self, document: Body, path: impl Into<Option<std::path::PathBuf>>) {
         let source_index = self.sources.len();
         self.sources.push(path.into());
+        self.line_starts.push(vec![]);
+        self.span_maps.push(SpanMap::default());
+        self.namespaces.push(vec![]);
+        #[cfg(feature = "fs")]
+        self.hashes.push(None);
 
         for structure in document.into_iter() {
             match structure {
@@ -31,12 +88,12 @@ impl HclDocuments {
         }
     }
 
-    pub fn get_attribute(&self, index: usize) -> SourceAttribute {
+    pub fn get_attribute(&self, index: usize) -> SourceAttribute<'_> {
         let (source_index, block) = &self.root_attributes[index];
         (index, &self.sources[*source_index], block)
     }
 
-    pub fn attributes(&self) -> impl Iterator<Item = SourceAttribute> {
+    pub fn attributes(&self) -> impl Iterator<Item = SourceAttribute<'_>> {
         self.root_attributes
             .iter()
             .enumerate()
@@ -45,71 +102,554 @@ impl HclDocuments {
             })
     }
 
-    pub fn get_block(&self, index: usize) -> SourceBlock {
+    pub fn get_block(&self, index: usize) -> SourceBlock<'_> {
         let (source_index, block) = &self.root_blocks[index];
         (index, &self.sources[*source_index], block)
     }
 
-    pub fn blocks(&self) -> impl Iterator<Item = SourceBlock> {
+    pub fn blocks(&self) -> impl Iterator<Item = SourceBlock<'_>> {
         self.root_blocks
             .iter()
             .enumerate()
             .map(|(index, (source_index, block))| (index, &self.sources[*source_index], block))
     }
 
+    /// Nest the data groups of documents loaded from files under a namespace
+    ///
+    /// `namespace_of` returns the namespace for a source path, e.g. `[prod]` to turn
+    /// `data "service" "api"` into `prod.service.api`. Documents without a path stay in the root.
+    pub fn nest(&mut self, namespace_of: impl Fn(&std::path::Path) -> Vec<hcl::Identifier>) {
+        for (source, namespace) in self.sources.iter().zip(&mut self.namespaces) {
+            if let Some(path) = source {
+                *namespace = namespace_of(path);
+            }
+        }
+    }
+
+    /// Namespace of the document defining a root block, see [HclDocuments::nest]
+    pub fn block_namespace(&self, index: usize) -> &[hcl::Identifier] {
+        &self.namespaces[self.root_blocks[index].0]
+    }
+
+    /// Sources added with [HclDocuments::insert_str] and the SHA-256 (hex) of their text
+    ///
+    /// The hash covers the text as given, a render can be traced back to the exact inputs.
+    #[cfg(feature = "fs")]
+    pub fn source_hashes(&self) -> impl Iterator<Item = (&Source, &str)> {
+        self.sources
+            .iter()
+            .zip(&self.hashes)
+            .filter_map(|(source, hash)| Some((source, hash.as_deref()?)))
+    }
+
     pub fn source_count(&self) -> usize {
         self.sources.len()
     }
+
+    /// Where a root block is defined, see [HclDocuments::location]
+    pub fn block_location(&self, index: usize) -> String {
+        let (source_index, block) = &self.root_blocks[index];
+        let span = self.block_span(index, block.span());
+        self.location(*source_index, span.map(|span| span.start))
+    }
+
+    /// Where a root attribute is defined, see [HclDocuments::location]
+    pub fn attribute_location(&self, index: usize) -> String {
+        let (source_index, attribute) = &self.root_attributes[index];
+        let span = self.attribute_span(index, attribute.span());
+        self.location(*source_index, span.map(|span| span.start))
+    }
+
+    /// Span in the text as written of a span within a root block, see [SpanMap]
+    pub fn block_span(
+        &self,
+        index: usize,
+        span: Option<std::ops::Range<usize>>,
+    ) -> Option<std::ops::Range<usize>> {
+        let (source_index, _) = &self.root_blocks[index];
+        Some(self.span_maps[*source_index].span(span?))
+    }
+
+    /// Span in the text as written of a span within a root attribute, see [SpanMap]
+    pub fn attribute_span(
+        &self,
+        index: usize,
+        span: Option<std::ops::Range<usize>>,
+    ) -> Option<std::ops::Range<usize>> {
+        let (source_index, _) = &self.root_attributes[index];
+        Some(self.span_maps[*source_index].span(span?))
+    }
+
+    /// [HclDocuments::location] of the first source with this path
+    pub fn source_location(&self, path: Option<&std::path::Path>, offset: Option<usize>) -> String {
+        match self
+            .sources
+            .iter()
+            .position(|source| source.as_deref() == path)
+        {
+            Some(source_index) => self.location(source_index, offset),
+            None => display_path(path).to_string(),
+        }
+    }
+
+    /// Line (1-based) of a byte offset in the first source with this path
+    ///
+    /// Only known for documents added with [HclDocuments::insert_str].
+    pub fn source_line(&self, path: Option<&std::path::Path>, offset: usize) -> Option<usize> {
+        let source_index = self
+            .sources
+            .iter()
+            .position(|source| source.as_deref() == path)?;
+        let line_starts = &self.line_starts[source_index];
+        (!line_starts.is_empty()).then(|| line_starts.partition_point(|start| *start <= offset))
+    }
+
+    /// `path:line:column` of a byte offset in the text of a source as written
+    ///
+    /// Sources without path are shown as `<input>`. Line and column are only known for documents
+    /// added with [HclDocuments::insert_str].
+    pub fn location(&self, source_index: usize, offset: Option<usize>) -> String {
+        let path = match &self.sources[source_index] {
+            Some(path) => path.display().to_string(),
+            None => "<input>".to_string(),
+        };
+        let line_starts = &self.line_starts[source_index];
+        match offset {
+            Some(offset) if !line_starts.is_empty() => {
+                let line = line_starts.partition_point(|start| *start <= offset);
+                let column = offset - line_starts[line - 1] + 1;
+                format!("{path}:{line}:{column}")
+            }
+            _ => path,
+        }
+    }
+}
+
+/// What loading a directory does with symbolic links to `cco.hcl` files
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Skip them
+    #[default]
+    Ignore,
+    /// Load their target, unless it was loaded already
+    Follow,
+    /// Fail with [LoadError::Symlink]
+    Error,
 }
 
+#[cfg(feature = "fs")]
 impl HclDocuments {
+    /// Set how [HclDocuments::load_directory] treats symbolic links (default: ignore them)
+    pub fn set_symlink_policy(&mut self, symlinks: SymlinkPolicy) {
+        self.symlinks = symlinks;
+    }
+
+    /// Whether a document with this (canonical) path was inserted
+    fn is_loaded(&self, path: &std::path::Path) -> bool {
+        self.sources
+            .iter()
+            .any(|source| source.as_deref() == Some(path))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl HclDocuments {
+    /// Loads a file, files that were loaded already (e.g. through a symbolic link) are skipped
     pub fn load_file(&mut self, file_path: &Path) -> Result<(), LoadError> {
-        let file_path = file_path.canonicalize()?;
-        tracing::info!(path=%file_path.display(), "loading file");
+        let file_path = file_path.canonicalize().map_err(LoadError::io(file_path))?;
+        if self.is_loaded(&file_path) {
+            tracing::debug!(phase = "load", file = %file_path.display(), "skipping file loaded before");
+            return Ok(());
+        }
+        tracing::info!(phase = "load", file = %file_path.display(), "loading file");
 
-        let file_contents = std::fs::read_to_string(&file_path)?;
-        let body = hcl_edit::parser::parse_body(&file_contents)?;
+        let file_contents =
+            std::fs::read_to_string(&file_path).map_err(LoadError::io(&file_path))?;
+        self.insert_str(&file_contents, Some(file_path))
+    }
+
+    pub fn load_directory(&mut self, dir_path: &Path) -> Result<(), LoadError> {
+        for file_path in directory_files_with(dir_path, self.symlinks)? {
+            self.load_file(&file_path)?;
+        }
 
-        self.insert(body, Some(file_path));
         Ok(())
     }
+}
+
+/// Non-blocking counterparts of the loading functions for use inside a tokio runtime
+///
+/// Files of a directory are read concurrently, they are still inserted in the same order
+/// [HclDocuments::load_directory] would insert them.
+#[cfg(feature = "tokio")]
+impl HclDocuments {
+    pub async fn load_file_async(&mut self, file_path: &Path) -> Result<(), LoadError> {
+        let (file_path, file_contents) = read_file_async(file_path.to_owned()).await?;
+        if self.is_loaded(&file_path) {
+            return Ok(());
+        }
+        self.insert_str(&file_contents, Some(file_path))
+    }
 
-    pub fn load_directory(&mut self, dir_path: &Path) -> Result<(), LoadError> {
-        let mut any_files_loaded = false;
+    pub async fn load_directory_async(&mut self, dir_path: &Path) -> Result<(), LoadError> {
+        let reads: Vec<_> = directory_files_async_with(dir_path, self.symlinks)
+            .await?
+            .into_iter()
+            .map(|file_path| tokio::spawn(read_file_async(file_path)))
+            .collect();
 
-        let read_dir = std::fs::read_dir(dir_path)?;
-        for dir_entry in read_dir {
-            let dir_entry = dir_entry?;
-            if !dir_entry.file_type()?.is_file() {
-                continue;
+        for read in reads {
+            let (file_path, file_contents) = read
+                .await
+                .map_err(|error| LoadError::io(dir_path)(error.into()))??;
+            if !self.is_loaded(&file_path) {
+                self.insert_str(&file_contents, Some(file_path))?;
             }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+async fn read_file_async(
+    file_path: std::path::PathBuf,
+) -> Result<(std::path::PathBuf, String), LoadError> {
+    let file_path = tokio::fs::canonicalize(&file_path)
+        .await
+        .map_err(LoadError::io(&file_path))?;
+    tracing::info!(phase = "load", file = %file_path.display(), "loading file");
+
+    let file_contents = tokio::fs::read_to_string(&file_path)
+        .await
+        .map_err(LoadError::io(&file_path))?;
+    Ok((file_path, file_contents))
+}
+
+/// Async version of [directory_files]
+#[cfg(feature = "tokio")]
+pub async fn directory_files_async(dir_path: &Path) -> Result<Vec<std::path::PathBuf>, LoadError> {
+    directory_files_async_with(dir_path, SymlinkPolicy::default()).await
+}
+
+/// Async version of [directory_files_with]
+#[cfg(feature = "tokio")]
+pub async fn directory_files_async_with(
+    dir_path: &Path,
+    symlinks: SymlinkPolicy,
+) -> Result<Vec<std::path::PathBuf>, LoadError> {
+    let mut files = vec![];
 
-            let is_cco_hcl_file = dir_entry.file_name().to_string_lossy().ends_with("cco.hcl");
-            if !is_cco_hcl_file {
-                continue;
+    let mut read_dir = tokio::fs::read_dir(dir_path)
+        .await
+        .map_err(LoadError::io(dir_path))?;
+    while let Some(dir_entry) = read_dir
+        .next_entry()
+        .await
+        .map_err(LoadError::io(dir_path))?
+    {
+        let is_cco_hcl_file = dir_entry.file_name().to_string_lossy().ends_with("cco.hcl");
+        if !is_cco_hcl_file {
+            continue;
+        }
+
+        let path = dir_entry.path();
+        let file_type = dir_entry.file_type().await.map_err(LoadError::io(&path))?;
+        if accept(&path, file_type, symlinks)? {
+            files.push(path);
+        }
+    }
+
+    if files.is_empty() {
+        return Err(LoadError::NoFilesFound(dir_path.to_owned()));
+    }
+    sorted_unique(files)
+}
+
+impl HclDocuments {
+    /// Parses and inserts an hcl document
+    pub fn insert_str(
+        &mut self,
+        contents: &str,
+        path: impl Into<Option<std::path::PathBuf>>,
+    ) -> Result<(), LoadError> {
+        let path = path.into();
+        match parse_body(contents) {
+            Ok((body, span_map)) => {
+                self.insert_parsed(contents, body, span_map, path);
+                Ok(())
             }
+            Err(error) => Err(LoadError::HclParseFailed { path, error }),
+        }
+    }
 
-            let file_path = dir_entry.path();
-            self.load_file(&file_path)?;
-            any_files_loaded = true;
+    /// [HclDocuments::insert] with the text the document was parsed from
+    fn insert_parsed(
+        &mut self,
+        contents: &str,
+        body: Body,
+        span_map: SpanMap,
+        path: Option<std::path::PathBuf>,
+    ) {
+        self.insert(body, path);
+        let line_starts = std::iter::once(0)
+            .chain(contents.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        *self.line_starts.last_mut().expect("inserted above") = line_starts;
+        *self.span_maps.last_mut().expect("inserted above") = span_map;
+        #[cfg(feature = "fs")]
+        {
+            use sha2::Digest;
+            *self.hashes.last_mut().expect("inserted above") =
+                Some(format!("{:x}", sha2::Sha256::digest(contents.as_bytes())));
         }
+    }
+}
 
-        if !any_files_loaded {
-            return Err(LoadError::NoFilesFound);
+#[cfg(feature = "fs")]
+impl HclDocuments {
+    /// Inserts an hcl document like [HclDocuments::insert_str], parsed only if `cache` does not
+    /// have it yet
+    ///
+    /// Documents taken from the cache have no source positions (messages point to the file only)
+    /// and keep only the comments above blocks and attributes.
+    pub fn insert_str_cached(
+        &mut self,
+        contents: &str,
+        path: impl Into<Option<std::path::PathBuf>>,
+        cache: &crate::cache::Cache,
+    ) -> Result<(), LoadError> {
+        let path = path.into();
+        let key = crate::cache::CacheKey::document(contents);
+        if let Some(body) = cache.get_document(&key) {
+            self.insert(body, path);
+            *self.hashes.last_mut().expect("inserted above") =
+                Some(crate::cache::content_hash(contents.as_bytes()));
+            return Ok(());
         }
 
+        let (body, span_map) = match parse_body(contents) {
+            Ok(parsed) => parsed,
+            Err(error) => return Err(LoadError::HclParseFailed { path, error }),
+        };
+        if let Err(err) = cache.put_document(&key, &body) {
+            tracing::warn!(%err, "failed to write cached document");
+        }
+        self.insert_parsed(contents, body, span_map, path);
         Ok(())
     }
 }
 
+/// Parses a document like [HclDocuments::insert_str], slices (`servers[1:3]`) included
+///
+/// Spans of the parsed structures point into the rewritten text, [SpanMap] turns them into spans
+/// of `text`.
+pub fn parse_body(text: &str) -> Result<(Body, SpanMap), hcl_edit::parser::Error> {
+    let (rewritten, span_map) = crate::indexing::rewrite_slices_mapped(text);
+    Ok((hcl_edit::parser::parse_body(&rewritten)?, span_map))
+}
+
+/// Offsets in the text of a document as written for offsets in the text that was parsed
+///
+/// Slices are no HCL syntax, they are rewritten before parsing: `a[1:]` becomes `a[[1, null]]`
+/// and the spans after it move. Offsets within a replacement map to the replaced byte.
+#[derive(Debug, Default, Clone)]
+pub struct SpanMap {
+    /// Offset in the parsed text, offset in the text as written and length of each replacement
+    /// of a single byte, in order
+    replacements: Vec<(usize, usize, usize)>,
+}
+
+impl SpanMap {
+    pub(crate) fn new(replacements: Vec<(usize, usize, usize)>) -> Self {
+        Self { replacements }
+    }
+
+    pub fn offset(&self, offset: usize) -> usize {
+        let preceding = self
+            .replacements
+            .partition_point(|(parsed, _, _)| *parsed <= offset);
+        match preceding
+            .checked_sub(1)
+            .map(|index| self.replacements[index])
+        {
+            None => offset,
+            Some((parsed, written, _)) if offset == parsed => written,
+            Some((parsed, written, len)) if offset < parsed + len => written + 1,
+            Some((parsed, written, len)) => written + 1 + (offset - parsed - len),
+        }
+    }
+
+    pub fn span(&self, span: std::ops::Range<usize>) -> std::ops::Range<usize> {
+        self.offset(span.start)..self.offset(span.end)
+    }
+
+    /// Span in the text as written of a parsed block, attribute, expression, ...
+    pub fn of(&self, node: &impl Span) -> Option<std::ops::Range<usize>> {
+        node.span().map(|span| self.span(span))
+    }
+}
+
+/// Parses an expression such as `service.api` or `servers[1:3]`
+///
+/// Like documents expressions may slice arrays (`servers[1:3]`), which plain HCL parsers reject.
+pub fn parse_expression(expression: &str) -> Result<hcl::Expression, hcl_edit::parser::Error> {
+    let expression = crate::indexing::rewrite_slices(expression);
+    let expression: hcl_edit::expr::Expression = expression.parse()?;
+    Ok(expression.into())
+}
+
+/// Lists the files [HclDocuments::load_directory] would load from a directory, sorted by name
+///
+/// Only files with names ending in `cco.hcl` are considered, symbolic links are ignored.
+#[cfg(feature = "fs")]
+pub fn directory_files(dir_path: &Path) -> Result<Vec<std::path::PathBuf>, LoadError> {
+    directory_files_with(dir_path, SymlinkPolicy::default())
+}
+
+/// [directory_files] with a policy for symbolic links
+///
+/// With [SymlinkPolicy::Follow] every file is listed once, links to a file that is listed already
+/// are skipped.
+#[cfg(feature = "fs")]
+pub fn directory_files_with(
+    dir_path: &Path,
+    symlinks: SymlinkPolicy,
+) -> Result<Vec<std::path::PathBuf>, LoadError> {
+    let mut files = vec![];
+
+    let read_dir = std::fs::read_dir(dir_path).map_err(LoadError::io(dir_path))?;
+    for dir_entry in read_dir {
+        let dir_entry = dir_entry.map_err(LoadError::io(dir_path))?;
+        let is_cco_hcl_file = dir_entry.file_name().to_string_lossy().ends_with("cco.hcl");
+        if !is_cco_hcl_file {
+            continue;
+        }
+
+        let path = dir_entry.path();
+        let file_type = dir_entry.file_type().map_err(LoadError::io(&path))?;
+        if accept(&path, file_type, symlinks)? {
+            files.push(path);
+        }
+    }
+
+    if files.is_empty() {
+        return Err(LoadError::NoFilesFound(dir_path.to_owned()));
+    }
+    sorted_unique(files)
+}
+
+/// Identity of a file: device and inode on unix, the canonical path elsewhere
+#[cfg(all(feature = "fs", unix))]
+type FileId = (u64, u64);
+#[cfg(all(feature = "fs", not(unix)))]
+type FileId = std::path::PathBuf;
+
+/// Files listed so far
+#[cfg(feature = "fs")]
+#[derive(Default)]
+struct Visited(std::collections::HashSet<FileId>);
+
+#[cfg(feature = "fs")]
+impl Visited {
+    /// `false` if the file was visited before
+    fn insert(&mut self, path: &Path) -> std::io::Result<bool> {
+        #[cfg(unix)]
+        let id = {
+            use std::os::unix::fs::MetadataExt;
+            let metadata = std::fs::metadata(path)?;
+            (metadata.dev(), metadata.ino())
+        };
+        #[cfg(not(unix))]
+        let id = path.canonicalize()?;
+        Ok(self.0.insert(id))
+    }
+}
+
+/// `files` sorted by name, of files that are the same only the first one
+///
+/// Directory listings are unordered, sorting before skipping keeps the choice of which name of a
+/// file is listed independent of it. The load order decides the order of keys in objects.
+#[cfg(feature = "fs")]
+fn sorted_unique(mut files: Vec<std::path::PathBuf>) -> Result<Vec<std::path::PathBuf>, LoadError> {
+    files.sort();
+    let mut visited = Visited::default();
+    let mut unique = Vec::with_capacity(files.len());
+    for path in files {
+        if visited.insert(&path).map_err(LoadError::io(&path))? {
+            unique.push(path);
+        }
+    }
+    Ok(unique)
+}
+
+/// Whether a directory entry is listed according to `symlinks`
+#[cfg(feature = "fs")]
+fn accept(
+    path: &Path,
+    file_type: std::fs::FileType,
+    symlinks: SymlinkPolicy,
+) -> Result<bool, LoadError> {
+    if !file_type.is_symlink() {
+        return Ok(file_type.is_file());
+    }
+    match symlinks {
+        SymlinkPolicy::Ignore => {
+            tracing::debug!(phase = "load", file = %path.display(), "ignoring symbolic link");
+            Ok(false)
+        }
+        SymlinkPolicy::Error => Err(LoadError::Symlink(path.to_owned())),
+        // a link to a directory is not a file, dangling links fail
+        SymlinkPolicy::Follow => {
+            let metadata = std::fs::metadata(path).map_err(LoadError::io(path))?;
+            Ok(metadata.is_file())
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum LoadError {
-    #[error("No files found in directory")]
-    NoFilesFound,
-    #[error("IO error")]
-    IoError(#[from] std::io::Error),
-    #[error("Unable to parse hcl file")]
-    HclParseFailed(#[from] hcl_edit::parser::Error),
+    #[error("No files found in directory {0}")]
+    NoFilesFound(std::path::PathBuf),
+    #[error("failed to load {path}: {error}")]
+    IoError {
+        path: std::path::PathBuf,
+        error: std::io::Error,
+    },
+    /// `path` is `None` for documents inserted without one
+    #[error("failed to load {}:{}:{}\n{error}", display_path(.path.as_deref()), .error.location().line(), .error.location().column())]
+    HclParseFailed {
+        path: Option<std::path::PathBuf>,
+        error: hcl_edit::parser::Error,
+    },
+    #[cfg(feature = "fs")]
+    #[error("Symbolic link {0} not allowed")]
+    Symlink(std::path::PathBuf),
+}
+
+impl LoadError {
+    /// Wraps an io error of reading `path`
+    pub fn io(path: &std::path::Path) -> impl FnOnce(std::io::Error) -> Self + '_ {
+        move |error| Self::IoError {
+            path: path.to_owned(),
+            error,
+        }
+    }
+
+    /// File or directory that failed to load
+    pub fn path(&self) -> Option<&std::path::Path> {
+        match self {
+            LoadError::NoFilesFound(path) | LoadError::IoError { path, .. } => Some(path),
+            LoadError::HclParseFailed { path, .. } => path.as_deref(),
+            #[cfg(feature = "fs")]
+            LoadError::Symlink(path) => Some(path),
+        }
+    }
+}
+
+fn display_path(path: Option<&std::path::Path>) -> std::path::Display<'_> {
+    path.unwrap_or(std::path::Path::new("<input>")).display()
 }
 
 impl From<Body> for HclDocuments {
@@ -151,14 +691,14 @@ macro_rules! hcl_documents {
         $crate::hcl_documents::HclDocuments::from(hcl_edit::parser::parse_body($expr).expect("body must parse"))
     };
     // multi document with sources
-    { $($source:expr => $expr:expr),+ } => {
+    { $($source:expr => $expr:expr),+ } => {{
         let mut docs = $crate::hcl_documents::HclDocuments::default();
         $(
-            docs.insert(hcl_edit::parser::parse_body($expr).expect("body must parse"), Some($source.into()));
+            docs.insert_str($expr, Some($source.into())).expect("body must parse");
         )+
 
         docs
-    };
+    }};
 }
 
 pub type Source = Option<std::path::PathBuf>;
@@ -180,4 +720,193 @@ pub(crate) mod test {
         assert_eq!(hcl_documents.attributes().count(), 3);
         assert_eq!(hcl_documents.blocks().count(), 2);
     }
+
+    #[test]
+    fn redacted_debug() {
+        let hcl_documents =
+            hcl_documents! {"token = \"hunter2\"\ndata db main {\n  password = \"hunter2\"\n}"};
+        let debug = format!("{hcl_documents:?}");
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("\"token\"") && debug.contains("\"data db main\""));
+        let revealed = crate::cco_document::reveal_sensitive(|| format!("{hcl_documents:?}"));
+        assert!(revealed.contains("hunter2"));
+    }
+
+    #[test]
+    fn locations() {
+        let hcl_documents = hcl_documents! {
+            "one.hcl" => "attr = 1\ndata a {}\n  data b {}",
+            "two.hcl" => "\n\ndata c {}"
+        };
+
+        assert_eq!(hcl_documents.attribute_location(0), "one.hcl:1:1");
+        assert_eq!(hcl_documents.block_location(0), "one.hcl:2:1");
+        assert_eq!(hcl_documents.block_location(1), "one.hcl:3:3");
+        assert_eq!(hcl_documents.block_location(2), "two.hcl:3:1");
+
+        let unknown_text =
+            super::HclDocuments::from(hcl_edit::parser::parse_body("data a {}").unwrap());
+        assert_eq!(unknown_text.block_location(0), "<input>");
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn cached_documents() {
+        use hcl_edit::Decorate;
+
+        let dir = std::env::temp_dir().join(format!("cco-cached-documents-{}", std::process::id()));
+        let cache = crate::cache::Cache::new(&dir);
+        let contents = "attr = 1\n# the a block\ndata a {}\n";
+        let load = || {
+            let mut documents = super::HclDocuments::default();
+            documents
+                .insert_str_cached(contents, std::path::PathBuf::from("a.hcl"), &cache)
+                .unwrap();
+            documents
+        };
+
+        // parsed, with positions
+        let parsed = load();
+        assert_eq!(parsed.block_location(0), "a.hcl:3:1");
+        // from the cache, only the file
+        let cached = load();
+        assert_eq!(cached.block_location(0), "a.hcl");
+        for documents in [parsed, cached] {
+            let (_, _, block) = documents.get_block(0);
+            assert_eq!(
+                crate::util::doc_comment(block.decor()).as_deref(),
+                Some("the a block")
+            );
+            assert_eq!(documents.source_hashes().count(), 1);
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn source_hashes() {
+        let mut hcl_documents = hcl_documents! {
+            "a.hcl" => "data a {}\n",
+            "b.hcl" => ""
+        };
+        hcl_documents.insert(hcl_edit::parser::parse_body("data c {}").unwrap(), None);
+
+        let hashes: Vec<_> = hcl_documents
+            .source_hashes()
+            .map(|(source, hash)| (source.as_ref().unwrap().to_str().unwrap(), hash))
+            .collect();
+        assert_eq!(
+            hashes,
+            [
+                (
+                    "a.hcl",
+                    "746f37b2c4c02e266416aa9f4d53811c53a9b041096159450c4dc0e82555c19b"
+                ),
+                (
+                    "b.hcl",
+                    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn load_errors() {
+        let mut documents = super::HclDocuments::default();
+        let error = documents
+            .insert_str("data a {}\ndata b {", std::path::PathBuf::from("b.cco.hcl"))
+            .unwrap_err();
+        assert_eq!(error.path(), Some(std::path::Path::new("b.cco.hcl")));
+        assert!(
+            error.to_string().starts_with("failed to load b.cco.hcl:2:"),
+            "{error}"
+        );
+
+        let error = documents.insert_str("a = ", None).unwrap_err();
+        assert!(error.to_string().starts_with("failed to load <input>:1:"));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn load_file_errors() {
+        let missing = std::env::temp_dir().join("cco-missing-file.cco.hcl");
+        let error = super::HclDocuments::default()
+            .load_file(&missing)
+            .unwrap_err();
+        assert_eq!(error.path(), Some(missing.as_path()));
+        assert!(error
+            .to_string()
+            .starts_with(&format!("failed to load {}: ", missing.display())));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn directory_files_sorted() {
+        let dir = std::env::temp_dir().join(format!("cco-directory-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["c.cco.hcl", "a.cco.hcl", "b.cco.hcl"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let files = super::directory_files(&dir).unwrap();
+        let names: Vec<_> = files.iter().filter_map(|file| file.file_name()).collect();
+        assert_eq!(names, ["a.cco.hcl", "b.cco.hcl", "c.cco.hcl"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(all(feature = "fs", unix))]
+    #[test]
+    fn directory_files_symlinks() {
+        use super::{directory_files_with, LoadError, SymlinkPolicy};
+
+        let dir = std::env::temp_dir().join(format!("cco-symlinks-{}", std::process::id()));
+        let other = dir.join("other");
+        std::fs::create_dir_all(&other).unwrap();
+        std::fs::write(dir.join("a.cco.hcl"), "").unwrap();
+        std::fs::write(other.join("shared.hcl"), "").unwrap();
+        std::os::unix::fs::symlink(other.join("shared.hcl"), dir.join("b.cco.hcl")).unwrap();
+        std::os::unix::fs::symlink(dir.join("a.cco.hcl"), dir.join("c.cco.hcl")).unwrap();
+        std::os::unix::fs::symlink(&other, dir.join("d.cco.hcl")).unwrap();
+        std::os::unix::fs::symlink(other.join("shared.hcl"), dir.join("e.cco.hcl")).unwrap();
+
+        let names = |policy| {
+            let files = directory_files_with(&dir, policy).unwrap();
+            files
+                .iter()
+                .map(|file| file.file_name().unwrap().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(SymlinkPolicy::Ignore), ["a.cco.hcl"]);
+        // `c.cco.hcl` is the same file as `a.cco.hcl`, `e.cco.hcl` as `b.cco.hcl`: the first name
+        // is kept whatever order the directory lists them in. `d.cco.hcl` is a directory.
+        assert_eq!(names(SymlinkPolicy::Follow), ["a.cco.hcl", "b.cco.hcl"]);
+        assert!(matches!(
+            directory_files_with(&dir, SymlinkPolicy::Error),
+            Err(LoadError::Symlink(path)) if path.ends_with("b.cco.hcl")
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn load_directory_async() {
+        let dir = std::env::temp_dir().join(format!("cco-load-async-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("one.cco.hcl"), "data one {}").unwrap();
+        std::fs::write(dir.join("two.cco.hcl"), "data two {}").unwrap();
+        std::fs::write(dir.join("ignored.hcl"), "data ignored {}").unwrap();
+
+        let mut documents = super::HclDocuments::default();
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(documents.load_directory_async(&dir))
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(documents.source_count(), 2);
+        assert_eq!(documents.blocks().count(), 2);
+    }
 }
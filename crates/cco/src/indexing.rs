@@ -0,0 +1,454 @@
+//! negative (`servers[-1]`) and slice (`servers[1:3]`) indices into arrays
+//!
+//! HCL has neither. Slices are not even valid syntax, so [rewrite_slices] turns them into indices
+//! with a pair of bounds (`servers[[1, 3]]`) before a document or expression is parsed. Spans of
+//! a parsed document are mapped back to the text as written with a [SpanMap]. Before an
+//! expression is evaluated [resolve_indices] replaces negative and pair indices into arrays with
+//! the selected element or elements.
+//!
+//! Like integer overflow checks, indices inside `for` expression bodies and `%{ }` template
+//! directives are evaluated lazily by `hcl` and not supported there.
+use crate::hcl_documents::SpanMap;
+use hcl::eval::{Context, Evaluate};
+use hcl::expr::{Operation, Traversal, TraversalOperator};
+use hcl::template::{Element, Template};
+use hcl::{Expression, TemplateExpr};
+use std::borrow::Cow;
+
+/// Keywords that can precede an array literal, `for x in [1, 2] : x`
+const KEYWORDS: [&str; 4] = ["for", "in", "if", "else"];
+
+enum Frame {
+    /// `(` or `{`
+    Group,
+    /// `${ }` or `%{ }` in a template
+    Interpolation,
+    Bracket(Bracket),
+    Quoted,
+    Heredoc(String),
+}
+
+struct Bracket {
+    start: usize,
+    /// Follows an expression: `a[`, `a.b[`, `f()[` or `a[0][`
+    index: bool,
+    /// Top-level colons, a slice has exactly one
+    colons: Vec<usize>,
+    /// Contains a top-level `?`, the colon belongs to a conditional
+    conditional: bool,
+}
+
+fn is_ident(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' || !byte.is_ascii()
+}
+
+/// `a[1:3]`, `a[:2]` and `a[-2:]` as `a[[1, 3]]`, `a[[null, 2]]` and `a[[-2, null]]`
+///
+/// Strings, heredocs and comments are left alone, as are `for` expressions, conditionals and
+/// `::` of namespaced function names. Line breaks are kept, so lines of the rewritten text match
+/// the original.
+pub(crate) fn rewrite_slices(text: &str) -> Cow<'_, str> {
+    rewrite_slices_mapped(text).0
+}
+
+/// [rewrite_slices] and the offsets of `text` for offsets in the rewritten text
+pub(crate) fn rewrite_slices_mapped(text: &str) -> (Cow<'_, str>, SpanMap) {
+    let bytes = text.as_bytes();
+    let mut stack: Vec<Frame> = vec![];
+    let mut slices: Vec<(usize, usize, usize)> = vec![];
+    // last byte of code that is not whitespace
+    let mut last: Option<usize> = None;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        let next = bytes.get(i + 1).copied();
+        if matches!(stack.last(), Some(Frame::Quoted | Frame::Heredoc(_))) {
+            if let Some(Frame::Heredoc(marker)) = stack.last() {
+                if i == 0 || bytes[i - 1] == b'\n' {
+                    let end = text[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                    if text[i..end].trim() == marker {
+                        stack.pop();
+                        last = None;
+                        i = end;
+                        continue;
+                    }
+                }
+            }
+            match (byte, next) {
+                (b'\\', _) if matches!(stack.last(), Some(Frame::Quoted)) => i += 1,
+                (b'"', _) if matches!(stack.last(), Some(Frame::Quoted)) => {
+                    stack.pop();
+                    last = Some(i);
+                }
+                (b'$' | b'%', Some(b'{')) if bytes.get(i.wrapping_sub(1)) != Some(&byte) => {
+                    stack.push(Frame::Interpolation);
+                    i += 1;
+                }
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+
+        match (byte, next) {
+            (b'"', _) => stack.push(Frame::Quoted),
+            (b'#', _) | (b'/', Some(b'/')) => {
+                i = text[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                continue;
+            }
+            (b'/', Some(b'*')) => {
+                i = text[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| i + 2 + end + 2);
+                continue;
+            }
+            (b'<', Some(b'<')) => {
+                let header = &text[i + 2..];
+                let end = header.find('\n').unwrap_or(header.len());
+                let marker = header[..end].trim_end().trim_start_matches('-');
+                if !marker.is_empty() && marker.bytes().all(is_ident) && end < header.len() {
+                    stack.push(Frame::Heredoc(marker.to_string()));
+                    i += 2 + end + 1;
+                    continue;
+                }
+            }
+            (b'(' | b'{', _) => stack.push(Frame::Group),
+            (b')' | b'}', _) => {
+                if matches!(stack.last(), Some(Frame::Group | Frame::Interpolation)) {
+                    stack.pop();
+                }
+            }
+            (b'[', _) => {
+                let index = last.is_some_and(|last| match bytes[last] {
+                    b']' | b')' => true,
+                    byte if is_ident(byte) => {
+                        let start = text[..=last]
+                            .rfind(|c: char| c.is_ascii() && !is_ident(c as u8))
+                            .map_or(0, |start| start + 1);
+                        !KEYWORDS.contains(&&text[start..=last])
+                    }
+                    _ => false,
+                });
+                stack.push(Frame::Bracket(Bracket {
+                    start: i,
+                    index,
+                    colons: vec![],
+                    conditional: false,
+                }));
+            }
+            (b']', _) => {
+                if let Some(Frame::Bracket(bracket)) = stack.pop() {
+                    let content = text[bracket.start + 1..i].trim_start();
+                    let is_for = content
+                        .strip_prefix("for")
+                        .is_some_and(|rest| !rest.bytes().next().is_some_and(is_ident));
+                    if bracket.index && bracket.colons.len() == 1 && !bracket.conditional && !is_for
+                    {
+                        slices.push((bracket.start, bracket.colons[0], i));
+                    }
+                }
+            }
+            (b'?', _) => {
+                if let Some(Frame::Bracket(bracket)) = stack.last_mut() {
+                    bracket.conditional = true;
+                }
+            }
+            (b':', Some(b':')) => i += 1,
+            (b':', _) => {
+                if let Some(Frame::Bracket(bracket)) = stack.last_mut() {
+                    bracket.colons.push(i);
+                }
+            }
+            _ => {}
+        }
+        if !byte.is_ascii_whitespace() {
+            last = Some(i);
+        }
+        i += 1;
+    }
+
+    if slices.is_empty() {
+        return (Cow::Borrowed(text), SpanMap::default());
+    }
+
+    let mut edits: Vec<(usize, &str)> = vec![];
+    for (start, colon, end) in slices {
+        let lower = text[start + 1..colon].trim().is_empty();
+        let upper = text[colon + 1..end].trim().is_empty();
+        edits.push((start, if lower { "[[null" } else { "[[" }));
+        edits.push((colon, if upper { ", null" } else { "," }));
+        edits.push((end, "]]"));
+    }
+    edits.sort_by_key(|(offset, _)| *offset);
+
+    let mut rewritten = String::with_capacity(text.len() + edits.len() * 4);
+    let mut replacements = Vec::with_capacity(edits.len());
+    let mut copied = 0;
+    for (offset, replacement) in edits {
+        rewritten += &text[copied..offset];
+        replacements.push((rewritten.len(), offset, replacement.len()));
+        rewritten += replacement;
+        copied = offset + 1;
+    }
+    rewritten += &text[copied..];
+    (Cow::Owned(rewritten), SpanMap::new(replacements))
+}
+
+/// Replace negative and slice indices into arrays with the selected elements
+///
+/// Indices whose collection can not be evaluated yet (unknown references) are left to the next
+/// attempt. `Err` describes a negative index out of range, slice bounds are clamped to the array
+/// instead: `[1, 2, 3][1:10]` is `[2, 3]`.
+pub(crate) fn resolve_indices(
+    expression: &mut Expression,
+    context: &Context,
+) -> Result<(), String> {
+    match expression {
+        Expression::Operation(operation) => match operation.as_mut() {
+            Operation::Binary(binop) => {
+                resolve_indices(&mut binop.lhs_expr, context)?;
+                resolve_indices(&mut binop.rhs_expr, context)?;
+            }
+            Operation::Unary(unop) => resolve_indices(&mut unop.expr, context)?,
+        },
+        Expression::Conditional(cond) => {
+            resolve_indices(&mut cond.cond_expr, context)?;
+            // only the branch that is taken
+            if let Ok(hcl::Value::Bool(taken)) = cond.cond_expr.evaluate(context) {
+                match taken {
+                    true => resolve_indices(&mut cond.true_expr, context)?,
+                    false => resolve_indices(&mut cond.false_expr, context)?,
+                }
+            }
+        }
+        Expression::Array(array) => {
+            for expr in array {
+                resolve_indices(expr, context)?;
+            }
+        }
+        Expression::Object(object) => {
+            for value in object.values_mut() {
+                resolve_indices(value, context)?;
+            }
+        }
+        Expression::Parenthesis(expr) => resolve_indices(expr, context)?,
+        Expression::FuncCall(func_call) => {
+            for arg in func_call.args.iter_mut() {
+                resolve_indices(arg, context)?;
+            }
+        }
+        Expression::Traversal(traversal) => {
+            resolve_indices(&mut traversal.expr, context)?;
+            for operator in traversal.operators.iter_mut() {
+                if let TraversalOperator::Index(index) = operator {
+                    resolve_indices(index, context)?;
+                }
+            }
+            if let Some(resolved) = resolve_traversal(traversal, context)? {
+                *expression = resolved;
+                resolve_indices(expression, context)?;
+            }
+        }
+        Expression::TemplateExpr(template_expr) => {
+            let Ok(mut template) = Template::from_expr(template_expr) else {
+                return Ok(());
+            };
+            for element in template.elements_mut() {
+                if let Element::Interpolation(interpolation) = element {
+                    resolve_indices(&mut interpolation.expr, context)?;
+                }
+            }
+            **template_expr = TemplateExpr::QuotedString(template.to_string());
+        }
+        Expression::ForExpr(forexpr) => resolve_indices(&mut forexpr.collection_expr, context)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+enum Selection {
+    Element(i64),
+    Slice(Option<i64>, Option<i64>),
+}
+
+fn selection(index: &mut Expression, context: &Context) -> Option<Selection> {
+    match index {
+        Expression::Array(bounds) if bounds.len() == 2 => {
+            index.evaluate_in_place(context).ok()?;
+            let Expression::Array(bounds) = index else {
+                return None;
+            };
+            let bound = |bound: &Expression| match bound {
+                Expression::Null => Some(None),
+                Expression::Number(number) => number.as_i64().map(Some),
+                _ => None,
+            };
+            Some(Selection::Slice(bound(&bounds[0])?, bound(&bounds[1])?))
+        }
+        Expression::Array(_) => None,
+        _ => {
+            index.evaluate_in_place(context).ok()?;
+            match index {
+                Expression::Number(number) => number
+                    .as_i64()
+                    .filter(|index| *index < 0)
+                    .map(Selection::Element),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// The traversal up to and including its first negative or slice index, replaced by its value
+fn resolve_traversal(
+    traversal: &mut Traversal,
+    context: &Context,
+) -> Result<Option<Expression>, String> {
+    let Some((position, selection)) = traversal.operators.iter_mut().enumerate().find_map(
+        |(position, operator)| match operator {
+            TraversalOperator::Index(index) => Some((position, selection(index, context)?)),
+            _ => None,
+        },
+    ) else {
+        return Ok(None);
+    };
+
+    let collection = match position {
+        0 => traversal.expr.evaluate(context),
+        _ => Traversal::new(
+            traversal.expr.clone(),
+            traversal.operators[..position].to_vec(),
+        )
+        .evaluate(context),
+    };
+    let Ok(hcl::Value::Array(mut array)) = collection else {
+        return Ok(None);
+    };
+
+    let len = array.len() as i64;
+    let value = match selection {
+        Selection::Element(index) => {
+            let Ok(position) = usize::try_from(len + index) else {
+                return Err(format!(
+                    "index {index} out of range for a list of {len} elements"
+                ));
+            };
+            array.swap_remove(position)
+        }
+        Selection::Slice(start, end) => {
+            let clamp = |bound: i64| {
+                let bound = match bound {
+                    ..0 => (len + bound).max(0),
+                    _ => bound.min(len),
+                };
+                bound as usize
+            };
+            let start = start.map_or(0, clamp);
+            let end = end.map_or(array.len(), clamp).max(start);
+            hcl::Value::Array(array.drain(start..end).collect())
+        }
+    };
+
+    let rest = traversal.operators.split_off(position + 1);
+    Ok(Some(match rest.is_empty() {
+        true => value.into(),
+        false => Traversal::new(value, rest).into(),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slices() {
+        assert_eq!(rewrite_slices("a[1:3]"), "a[[1,3]]");
+        assert_eq!(rewrite_slices("a.b[:2]"), "a.b[[null,2]]");
+        assert_eq!(rewrite_slices("a[-2:]"), "a[[-2, null]]");
+        assert_eq!(rewrite_slices("f(x)[0:n][1:]"), "f(x)[[0,n]][[1, null]]");
+        assert_eq!(rewrite_slices("a[b[0:1][0]:2]"), "a[[b[[0,1]][0],2]]");
+        assert_eq!(
+            rewrite_slices("x = {\n  y = a[1:]\n}\n"),
+            "x = {\n  y = a[[1, null]]\n}\n"
+        );
+
+        for unchanged in [
+            "a[0]",
+            "[1, 2]",
+            "[for x in xs : x]",
+            "a[for x in xs : x]",
+            "{ for k, v in m : k => v[0] }",
+            "a[c ? 1 : 2]",
+            "for x in [1 : 2]",
+            "a[{ b : 1 }]",
+            "a[provider::f(1)]",
+            r#""a[1:2]""#,
+            r#""${a}[1:2]""#,
+            "# a[1:2]\n",
+            "/* a[1:2] */",
+            "<<EOT\na[1:2]\nEOT\n",
+        ] {
+            assert!(
+                matches!(rewrite_slices(unchanged), Cow::Borrowed(_)),
+                "{unchanged}"
+            );
+        }
+
+        assert_eq!(rewrite_slices(r#""${a[1:2]}""#), r#""${a[[1,2]]}""#);
+        assert_eq!(
+            rewrite_slices("<<EOT\n${a[1:]}\nEOT\nb = c[:1]"),
+            "<<EOT\n${a[[1, null]]}\nEOT\nb = c[[null,1]]"
+        );
+    }
+
+    #[test]
+    fn slice_spans() {
+        let text = "a = xs[1:]\nb = xs[:2] # c\n";
+        let (rewritten, spans) = rewrite_slices_mapped(text);
+        assert_eq!(rewritten, "a = xs[[1, null]]\nb = xs[[null,2]] # c\n");
+        for (original, parsed) in [
+            ("xs[1:]", "xs[[1, null]]"),
+            ("xs[:2]", "xs[[null,2]]"),
+            ("# c", "# c"),
+        ] {
+            let start = rewritten.find(parsed).unwrap();
+            let span = spans.span(start..start + parsed.len());
+            assert_eq!(&text[span], original);
+        }
+        assert_eq!(spans.offset(rewritten.len()), text.len());
+    }
+
+    fn evaluate(expression: &str) -> Result<hcl::Value, String> {
+        let mut expression = crate::hcl_documents::parse_expression(expression).unwrap();
+        let mut context = Context::new();
+        context.declare_var("xs", hcl::value!([1, 2, 3, 4]));
+        resolve_indices(&mut expression, &context)?;
+        expression.evaluate(&context).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn indices() {
+        let cases = [
+            ("xs[-1]", hcl::value!(4)),
+            ("xs[-4]", hcl::value!(1)),
+            ("xs[0]", hcl::value!(1)),
+            ("xs[1:3]", hcl::value!([2, 3])),
+            ("xs[:2]", hcl::value!([1, 2])),
+            ("xs[-2:]", hcl::value!([3, 4])),
+            ("xs[:-1]", hcl::value!([1, 2, 3])),
+            ("xs[1:10]", hcl::value!([2, 3, 4])),
+            ("xs[3:1]", hcl::value!([])),
+            ("xs[1:][-1]", hcl::value!(4)),
+            ("[xs[1 + 1:]][0][-1]", hcl::value!(4)),
+            ("true ? xs[-1] : xs[-9]", hcl::value!(4)),
+        ];
+        for (expression, expected) in cases {
+            assert_eq!(evaluate(expression).unwrap(), expected, "{expression}");
+        }
+        assert_eq!(
+            evaluate("xs[-5]").unwrap_err(),
+            "index -5 out of range for a list of 4 elements"
+        );
+    }
+}
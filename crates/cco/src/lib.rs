@@ -89,7 +89,7 @@
 //! | `block.two`           | `cco__b_t`    | `{ attribute = cco__b_t_a }`         |
 //! | `block`               | `cco__b`      | `{ one = cco__b_o, two = cco__b_t }` |
 //!
-//! _Note: Actual substitution identifiers have a different format._
+//! _Note: Actual substitution identifiers have a different format: `cco__{kind}_{hash of path}`._
 //!
 //! ### Evaluation
 //!
@@ -113,8 +113,39 @@
 //!
 //! Once the expression is evaluated we parse it as a [value::Value] which in turn gets serialized via [serde].
 //!
+//! Objects keep the order of their source: addressables in the order of their documents (as loaded, files of a directory
+//! sorted by name) and blocks/attributes within. `for` expressions producing objects keep the order of the collection
+//! they iterate. Serialized output is therefore stable as long as the sources do not change.
+//!
+//! ### Features
+//!
+//! - `fs` (default): load documents from files and directories, on-disk [cache]. Without it `cco` builds for targets
+//!   that have no filesystem, such as `wasm32-unknown-unknown`. Documents can still be added with
+//!   [hcl_documents::HclDocuments::insert_str].
+//! - `tokio`: non-blocking `load_file_async`/`load_directory_async` on [hcl_documents::HclDocuments].
+//! - `testing`: `cco::testing` to compare values of a configuration repository with expected YAML/JSON in its own
+//!   `cargo test` suite.
+//!
+mod arithmetic;
+pub mod base64;
+#[cfg(feature = "fs")]
+pub mod cache;
 pub mod cco_document;
+mod collections;
+mod convert;
+mod external;
+mod functions;
 pub mod hcl_documents;
+mod indexing;
+pub mod observer;
+mod printf;
+pub mod quantity;
+mod seeded;
+mod semver;
+pub mod session;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod url;
 mod util;
 pub mod value;
 mod visit;
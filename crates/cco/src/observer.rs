@@ -0,0 +1,81 @@
+//! Hooks into evaluation
+//!
+//! An [EvalObserver] registered with [crate::cco_document::CcoDocumentBuilder::observer] is
+//! notified while expressions are rewritten and evaluated. This is the building block for
+//! provenance views, timing reports and similar tooling that needs to know *how* a value came
+//! to be, not just what it is.
+use crate::cco_document::Kind;
+use hcl::{Identifier, Traversal};
+use std::time::Duration;
+
+/// Receives evaluation events
+///
+/// All methods default to doing nothing. Observers are shared between threads (see
+/// [crate::session::EvalSession]) and therefore need interior mutability to record anything.
+pub trait EvalObserver: Send + Sync {
+    /// An addressable element was evaluated
+    ///
+    /// `duration` includes the time spent evaluating its dependencies. Elements that were
+    /// already evaluated by an earlier call of the same session are not reported again.
+    fn on_resolve(&self, _path: &[Identifier], _kind: Kind, _duration: Duration) {}
+
+    /// A reference to an addressable element is about to be replaced by its substitution
+    /// identifier
+    ///
+    /// `traversal` is the reference as written (after `self` was expanded).
+    fn on_rewrite(&self, _traversal: &Traversal, _subst: &Identifier) {}
+
+    /// A deprecated element at `path` was referenced
+    ///
+    /// `referenced_by` is the path of the referencing element, `None` for the evaluated
+    /// expression itself. Blocks do not count as referencing their own attributes.
+    fn on_deprecated(
+        &self,
+        _path: &[Identifier],
+        _message: &str,
+        _referenced_by: Option<&[Identifier]>,
+    ) {
+    }
+}
+
+impl<T: EvalObserver + ?Sized> EvalObserver for std::sync::Arc<T> {
+    fn on_resolve(&self, path: &[Identifier], kind: Kind, duration: Duration) {
+        (**self).on_resolve(path, kind, duration)
+    }
+
+    fn on_rewrite(&self, traversal: &Traversal, subst: &Identifier) {
+        (**self).on_rewrite(traversal, subst)
+    }
+
+    fn on_deprecated(
+        &self,
+        path: &[Identifier],
+        message: &str,
+        referenced_by: Option<&[Identifier]>,
+    ) {
+        (**self).on_deprecated(path, message, referenced_by)
+    }
+}
+
+/// Notifies both observers, see [crate::cco_document::CcoDocumentBuilder::observer]
+impl<A: EvalObserver, B: EvalObserver> EvalObserver for (A, B) {
+    fn on_resolve(&self, path: &[Identifier], kind: Kind, duration: Duration) {
+        self.0.on_resolve(path, kind, duration);
+        self.1.on_resolve(path, kind, duration);
+    }
+
+    fn on_rewrite(&self, traversal: &Traversal, subst: &Identifier) {
+        self.0.on_rewrite(traversal, subst);
+        self.1.on_rewrite(traversal, subst);
+    }
+
+    fn on_deprecated(
+        &self,
+        path: &[Identifier],
+        message: &str,
+        referenced_by: Option<&[Identifier]>,
+    ) {
+        self.0.on_deprecated(path, message, referenced_by);
+        self.1.on_deprecated(path, message, referenced_by);
+    }
+}
@@ -0,0 +1,296 @@
+//! printf-style verbs of `format` and `formatlist`
+//!
+//! `%[flags][width][.precision]verb` with the flags `-` (left align), `0` (zero fill), `+` (sign)
+//! and ` ` (space for the sign). Verbs:
+//! - `%v` any value, lists and maps as JSON
+//! - `%s` string, number or bool, precision truncates
+//! - `%d` integer, `%x`/`%X` hexadecimal, `%o` octal, `%b` binary
+//! - `%f` and `%e` numbers, precision digits (default 6)
+//! - `%q` JSON quoted string, `%t` bool
+//! - `%%` a literal `%`
+use crate::convert::{self, Mode};
+use hcl::Value;
+
+/// Largest width or precision, larger ones are rather typos than intended
+const MAX_WIDTH: usize = 1024;
+
+#[derive(Debug, Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// Pad `text`, zeros go between sign and digits
+    fn pad(&self, text: String, numeric: bool) -> String {
+        let len = text.chars().count();
+        if len >= self.width {
+            return text;
+        }
+        let fill = self.width - len;
+        if self.left {
+            format!("{text}{}", " ".repeat(fill))
+        } else if self.zero && numeric {
+            let sign = if text.starts_with(['-', '+', ' ']) {
+                1
+            } else {
+                0
+            };
+            format!("{}{}{}", &text[..sign], "0".repeat(fill), &text[sign..])
+        } else {
+            format!("{}{text}", " ".repeat(fill))
+        }
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        match (negative, self.plus, self.space) {
+            (true, _, _) => "-",
+            (false, true, _) => "+",
+            (false, false, true) => " ",
+            (false, false, false) => "",
+        }
+    }
+}
+
+fn number(value: &Value, verb: char) -> Result<f64, String> {
+    match convert::to_number(value.clone(), Mode::Strict) {
+        Ok(Value::Number(number)) => number
+            .as_f64()
+            .ok_or_else(|| format!("%{verb} needs a number, got {value}")),
+        _ => Err(format!("%{verb} needs a number, got {value}")),
+    }
+}
+
+fn integer(value: &Value, verb: char) -> Result<i64, String> {
+    let number = number(value, verb)?;
+    if number.fract() != 0.0 || number.abs() >= i64::MAX as f64 {
+        return Err(format!("%{verb} needs a whole number, got {value}"));
+    }
+    Ok(number as i64)
+}
+
+fn string(value: &Value, verb: char) -> Result<String, String> {
+    match convert::to_string(value.clone(), Mode::Strict) {
+        Ok(Value::String(string)) => Ok(string),
+        _ => Err(format!(
+            "%{verb} needs a string, number or bool, got {value}"
+        )),
+    }
+}
+
+fn render(spec: &Spec, verb: char, value: &Value) -> Result<String, String> {
+    let (text, numeric) = match verb {
+        'v' => match value {
+            Value::Array(_) | Value::Object(_) => (
+                serde_json::to_string(value).map_err(|err| err.to_string())?,
+                false,
+            ),
+            Value::Null => ("null".to_string(), false),
+            value => (string(value, verb)?, false),
+        },
+        's' => {
+            let string = string(value, verb)?;
+            match spec.precision {
+                Some(precision) => (string.chars().take(precision).collect(), false),
+                None => (string, false),
+            }
+        }
+        'q' => (
+            serde_json::to_string(&string(value, verb)?).map_err(|err| err.to_string())?,
+            false,
+        ),
+        't' => match value {
+            Value::Bool(bool) => (bool.to_string(), false),
+            other => return Err(format!("%t needs a bool, got {other}")),
+        },
+        'd' | 'x' | 'X' | 'o' | 'b' => {
+            let integer = integer(value, verb)?;
+            let magnitude = integer.unsigned_abs();
+            let digits = match verb {
+                'x' => format!("{magnitude:x}"),
+                'X' => format!("{magnitude:X}"),
+                'o' => format!("{magnitude:o}"),
+                'b' => format!("{magnitude:b}"),
+                _ => magnitude.to_string(),
+            };
+            (format!("{}{digits}", spec.sign(integer < 0)), true)
+        }
+        'f' | 'e' => {
+            let number = number(value, verb)?;
+            let precision = spec.precision.unwrap_or(6);
+            let digits = match verb {
+                'f' => format!("{:.precision$}", number.abs()),
+                _ => format!("{:.precision$e}", number.abs()),
+            };
+            (format!("{}{digits}", spec.sign(number < 0.0)), true)
+        }
+        other => return Err(format!("unknown verb %{other}")),
+    };
+    Ok(spec.pad(text, numeric))
+}
+
+/// Width or precision at the start of `chars`, `0` without digits
+fn number_in(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    what: &str,
+) -> Result<usize, String> {
+    let mut number: usize = 0;
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        number = number
+            .checked_mul(10)
+            .and_then(|number| number.checked_add(digit.to_digit(10).unwrap_or_default() as usize))
+            .filter(|number| *number <= MAX_WIDTH)
+            .ok_or_else(|| format!("{what} larger than {MAX_WIDTH}"))?;
+    }
+    Ok(number)
+}
+
+/// `format` of a single set of arguments
+pub fn format(template: &str, args: &[Value]) -> Result<String, String> {
+    let mut output = String::new();
+    let mut args = args.iter();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'%').is_some() {
+            output.push('%');
+            continue;
+        }
+
+        let mut spec = Spec::default();
+        while let Some(flag) = chars.next_if(|c| matches!(*c, '-' | '0' | '+' | ' ')) {
+            match flag {
+                '-' => spec.left = true,
+                '0' => spec.zero = true,
+                '+' => spec.plus = true,
+                _ => spec.space = true,
+            }
+        }
+        spec.width = number_in(&mut chars, "width")?;
+        if chars.next_if_eq(&'.').is_some() {
+            spec.precision = Some(number_in(&mut chars, "precision")?);
+        }
+
+        let verb = chars
+            .next()
+            .ok_or_else(|| "incomplete verb at the end of the template".to_string())?;
+        let value = args
+            .next()
+            .ok_or_else(|| format!("not enough arguments for %{verb}"))?;
+        output.push_str(&render(&spec, verb, value)?);
+    }
+
+    match args.len() {
+        0 => Ok(output),
+        extra => Err(format!("{extra} more arguments than verbs")),
+    }
+}
+
+/// `format` for each element of the list arguments, other arguments are used for all of them
+pub fn format_list(template: &str, args: &[Value]) -> Result<Vec<String>, String> {
+    let mut len = None;
+    for arg in args {
+        if let Value::Array(list) = arg {
+            match len {
+                Some(len) if len != list.len() => {
+                    return Err(format!(
+                        "all lists must have the same length, got {len} and {}",
+                        list.len()
+                    ))
+                }
+                _ => len = Some(list.len()),
+            }
+        }
+    }
+
+    (0..len.unwrap_or(1))
+        .map(|index| {
+            let args: Vec<Value> = args
+                .iter()
+                .map(|arg| match arg {
+                    Value::Array(list) => list[index].clone(),
+                    other => other.clone(),
+                })
+                .collect();
+            format(template, &args)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn verbs() {
+        let args = [Value::from("prod"), Value::from(7)];
+        assert_eq!(format("db-%s-%02d", &args).as_deref(), Ok("db-prod-07"));
+        assert_eq!(
+            format("%5s|%-5s|", &[args[0].clone(), args[0].clone()]).as_deref(),
+            Ok(" prod|prod |")
+        );
+        assert_eq!(format("%.2s", &args[..1]).as_deref(), Ok("pr"));
+        assert_eq!(
+            format(
+                "%+d %x %X %o %b",
+                &[7.into(), 255.into(), 255.into(), 8.into(), 5.into()]
+            )
+            .as_deref(),
+            Ok("+7 ff FF 10 101")
+        );
+        assert_eq!(
+            format("%06.2f", &[Value::from(-1.5)]).as_deref(),
+            Ok("-01.50")
+        );
+        assert_eq!(format("%.1e", &[Value::from(1500)]).as_deref(), Ok("1.5e3"));
+        assert_eq!(
+            format("%q %t %%", &["a\"b".into(), true.into()]).as_deref(),
+            Ok(r#""a\"b" true %"#)
+        );
+        assert_eq!(format("%d", &["42".into()]).as_deref(), Ok("42"));
+        assert_eq!(
+            format("%v", &[Value::Array(vec![1.into(), "a".into()])]).as_deref(),
+            Ok(r#"[1,"a"]"#)
+        );
+
+        assert!(format("%d", &[Value::from(1.5)])
+            .unwrap_err()
+            .contains("whole number"));
+        assert!(format("%s %s", &args[..1])
+            .unwrap_err()
+            .contains("not enough"));
+        assert!(format("%s", &args).unwrap_err().contains("1 more"));
+        assert!(format("%y", &args[..1])
+            .unwrap_err()
+            .contains("unknown verb"));
+        assert!(format("%s", &[Value::Null]).is_err());
+        assert_eq!(
+            format("%1024d", &[1.into()]).map(|text| text.len()),
+            Ok(1024)
+        );
+        assert!(format("%1025d", &[1.into()])
+            .unwrap_err()
+            .contains("width larger than 1024"));
+        assert!(format("%.99999999999999999999999f", &[1.into()])
+            .unwrap_err()
+            .contains("precision larger than 1024"));
+    }
+
+    #[test]
+    fn lists() {
+        let hosts = Value::Array(vec!["a".into(), "b".into()]);
+        assert_eq!(
+            format_list("%s:%d", &[hosts.clone(), 80.into()]),
+            Ok(vec!["a:80".to_string(), "b:80".to_string()])
+        );
+        assert_eq!(format_list("%s", &["x".into()]), Ok(vec!["x".to_string()]));
+        assert!(format_list("%s%s", &[hosts, Value::Array(vec![])]).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+//! human-friendly durations (`1h30m`) and sizes (`512Mi`)
+//!
+//! Durations are numbers of seconds, sizes numbers of bytes, so configurations can calculate with
+//! them and format the result in whatever unit a consumer expects.
+
+/// Duration units and their length in nanoseconds, largest first
+const DURATION_UNITS: [(&str, i128); 7] = [
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+/// Size units and their number of bytes, binary (IEC) units first
+const SIZE_UNITS: [(&str, i128); 13] = [
+    ("Ei", 1 << 60),
+    ("Pi", 1 << 50),
+    ("Ti", 1 << 40),
+    ("Gi", 1 << 30),
+    ("Mi", 1 << 20),
+    ("Ki", 1 << 10),
+    ("E", 1_000_000_000_000_000_000),
+    ("P", 1_000_000_000_000_000),
+    ("T", 1_000_000_000_000),
+    ("G", 1_000_000_000),
+    ("M", 1_000_000),
+    ("k", 1_000),
+    ("", 1),
+];
+
+fn duration_unit(unit: &str) -> Option<i128> {
+    let unit = match unit {
+        "µs" => "us",
+        unit => unit,
+    };
+    DURATION_UNITS
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, nanos)| *nanos)
+}
+
+/// Accepts `Ki`/`KiB`, `k`/`K`/`kB`/`KB` and so on, `B` or nothing for bytes
+fn size_unit(unit: &str) -> Option<i128> {
+    let unit = unit.strip_suffix('B').unwrap_or(unit);
+    let unit = match unit {
+        "K" => "k",
+        unit => unit,
+    };
+    SIZE_UNITS
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, bytes)| *bytes)
+}
+
+/// Seconds of a duration like `1h30m`, `1.5s` or `-250ms`
+pub fn parse_duration(text: &str) -> Result<f64, String> {
+    let invalid =
+        || format!("invalid duration '{text}', e.g. 1h30m (units: d, h, m, s, ms, us, ns)");
+    let (sign, mut rest) = match text.trim().strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, text.trim()),
+    };
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut nanos = 0.0;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(invalid)?;
+        let (amount, tail) = rest.split_at(split);
+        let unit_end = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_end);
+
+        let amount: f64 = amount.parse().map_err(|_| invalid())?;
+        let unit = duration_unit(unit).ok_or_else(invalid)?;
+        nanos += amount * unit as f64;
+        rest = tail;
+    }
+    Ok(sign * nanos / 1e9)
+}
+
+/// `1h30m`, `1m0.5s` or `250ms`, or the amount of `unit` (`90m`)
+///
+/// Without a unit the largest units are used, without days which are not always 24 hours in
+/// the consumers' eyes.
+pub(crate) fn format_duration(seconds: f64, unit: Option<&str>) -> Result<String, String> {
+    if !seconds.is_finite() {
+        return Err(format!("invalid duration {seconds}"));
+    }
+    if let Some(name) = unit {
+        let nanos = duration_unit(name).ok_or_else(|| format!("unknown duration unit '{name}'"))?;
+        return Ok(format!("{}{name}", seconds * 1e9 / nanos as f64));
+    }
+
+    let nanos = (seconds * 1e9).round() as i128;
+    let sign = if nanos < 0 { "-" } else { "" };
+    let mut rest = nanos.abs();
+    if rest == 0 {
+        return Ok("0s".to_string());
+    }
+    if rest < 1_000_000_000 {
+        let (name, unit) = match rest {
+            ..1_000 => ("ns", 1),
+            1_000..1_000_000 => ("us", 1_000),
+            _ => ("ms", 1_000_000),
+        };
+        return Ok(format!("{sign}{}{name}", fixed_point(rest, unit)));
+    }
+
+    let mut text = sign.to_string();
+    for (name, unit) in &DURATION_UNITS[1..3] {
+        if rest >= *unit {
+            text += &format!("{}{name}", rest / unit);
+            rest %= unit;
+        }
+    }
+    if rest > 0 {
+        text += &format!("{}s", fixed_point(rest, 1_000_000_000));
+    }
+    Ok(text)
+}
+
+/// Bytes of a size like `512Mi`, `1.5G` or `100`
+pub(crate) fn parse_bytes(text: &str) -> Result<i64, String> {
+    let invalid =
+        || format!("invalid size '{text}', e.g. 512Mi (units: Ki, Mi, .., Ei, k, M, .., E)");
+    let trimmed = text.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (amount, unit) = trimmed.split_at(split);
+    let unit = size_unit(unit.trim_start()).ok_or_else(invalid)?;
+
+    let bytes = match amount.parse::<i128>() {
+        Ok(amount) => amount.checked_mul(unit),
+        Err(_) => {
+            let bytes = amount.parse::<f64>().map_err(|_| invalid())? * unit as f64;
+            if bytes.fract() != 0.0 {
+                return Err(format!("size '{text}' is not a whole number of bytes"));
+            }
+            Some(bytes as i128)
+        }
+    };
+    bytes
+        .and_then(|bytes| i64::try_from(bytes).ok())
+        .ok_or_else(|| format!("size '{text}' is too large"))
+}
+
+/// `512Mi`, `1k` or `1001`, or the amount of `unit` (`0.5Gi`)
+///
+/// Without a unit the largest binary unit that divides the size is used, then the largest
+/// decimal one.
+pub(crate) fn format_bytes(bytes: i64, unit: Option<&str>) -> Result<String, String> {
+    if let Some(name) = unit {
+        let unit = size_unit(name).ok_or_else(|| format!("unknown size unit '{name}'"))?;
+        return Ok(format!("{}{name}", fixed_point(bytes as i128, unit)));
+    }
+
+    let (name, unit) = SIZE_UNITS
+        .iter()
+        .find(|(_, unit)| bytes != 0 && bytes as i128 % unit == 0)
+        .copied()
+        .unwrap_or(("", 1));
+    Ok(format!("{}{name}", bytes as i128 / unit))
+}
+
+/// `value / unit` in decimal notation, at most 20 fractional digits
+fn fixed_point(value: i128, unit: i128) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    let (whole, mut remainder) = (value.abs() / unit, value.abs() % unit);
+    let mut text = format!("{sign}{whole}");
+    if remainder != 0 {
+        text.push('.');
+    }
+    for _ in 0..20 {
+        if remainder == 0 {
+            break;
+        }
+        remainder *= 10;
+        text += &(remainder / unit).to_string();
+        remainder %= unit;
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1h30m"), Ok(5400.0));
+        assert_eq!(parse_duration("1.5s"), Ok(1.5));
+        assert_eq!(parse_duration("-250ms"), Ok(-0.25));
+        assert_eq!(parse_duration("1d"), Ok(86400.0));
+        assert_eq!(parse_duration("2m3s4ms"), Ok(123.004));
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("1x").is_err());
+        assert!(parse_duration("").is_err());
+
+        assert_eq!(format_duration(5400.0, None).as_deref(), Ok("1h30m"));
+        assert_eq!(format_duration(90000.0, None).as_deref(), Ok("25h"));
+        assert_eq!(format_duration(60.5, None).as_deref(), Ok("1m0.5s"));
+        assert_eq!(format_duration(0.25, None).as_deref(), Ok("250ms"));
+        assert_eq!(format_duration(0.0000015, None).as_deref(), Ok("1.5us"));
+        assert_eq!(format_duration(-3.0, None).as_deref(), Ok("-3s"));
+        assert_eq!(format_duration(0.0, None).as_deref(), Ok("0s"));
+        assert_eq!(format_duration(5400.0, Some("m")).as_deref(), Ok("90m"));
+        assert_eq!(format_duration(5400.0, Some("h")).as_deref(), Ok("1.5h"));
+        assert!(format_duration(1.0, Some("y")).is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_bytes("512Mi"), Ok(512 << 20));
+        assert_eq!(parse_bytes("1.5Gi"), Ok(3 << 29));
+        assert_eq!(parse_bytes("2 GB"), Ok(2_000_000_000));
+        assert_eq!(parse_bytes("1K"), Ok(1000));
+        assert_eq!(parse_bytes("100"), Ok(100));
+        assert_eq!(parse_bytes("7B"), Ok(7));
+        assert!(parse_bytes("0.5").unwrap_err().contains("whole number"));
+        assert!(parse_bytes("8Ei").unwrap_err().contains("too large"));
+        assert!(parse_bytes("1Xi").is_err());
+
+        assert_eq!(format_bytes(512 << 20, None).as_deref(), Ok("512Mi"));
+        assert_eq!(format_bytes(1536, None).as_deref(), Ok("1536"));
+        assert_eq!(format_bytes(1536, Some("Ki")).as_deref(), Ok("1.5Ki"));
+        assert_eq!(format_bytes(2_000_000, None).as_deref(), Ok("2M"));
+        assert_eq!(format_bytes(1001, None).as_deref(), Ok("1001"));
+        assert_eq!(format_bytes(0, None).as_deref(), Ok("0"));
+        assert_eq!(format_bytes(512 << 20, Some("Gi")).as_deref(), Ok("0.5Gi"));
+        assert_eq!(format_bytes(1_500_000, Some("MB")).as_deref(), Ok("1.5MB"));
+        assert!(format_bytes(1, Some("x")).is_err());
+    }
+}
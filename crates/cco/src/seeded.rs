@@ -0,0 +1,165 @@
+//! name-based UUIDs (RFC 4122 version 5) and random values from a seed
+//!
+//! Both derive from SHA-1 hashes of their input, the same input always gives the same result:
+//! evaluation stays reproducible, ids and tokens stay stable between runs.
+
+/// Namespaces of RFC 4122, appendix C
+const NAMESPACES: [(&str, &str); 4] = [
+    ("dns", "6ba7b810-9dad-11d1-80b4-00c04fd430c8"),
+    ("url", "6ba7b811-9dad-11d1-80b4-00c04fd430c8"),
+    ("oid", "6ba7b812-9dad-11d1-80b4-00c04fd430c8"),
+    ("x500", "6ba7b814-9dad-11d1-80b4-00c04fd430c8"),
+];
+
+const ALPHANUMERIC: &[u8; 62] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    use sha1::Digest;
+    sha1::Sha1::digest(data).into()
+}
+
+fn parse_uuid(text: &str) -> Option<[u8; 16]> {
+    let hex: Vec<u8> = text.bytes().filter(|c| *c != b'-').collect();
+    if hex.len() != 32 || text.len() != 36 {
+        return None;
+    }
+    let mut uuid = [0u8; 16];
+    for (byte, pair) in uuid.iter_mut().zip(hex.chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(uuid)
+}
+
+fn format_uuid(uuid: &[u8]) -> String {
+    let hex: String = uuid.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// UUID version 5 of `name` in `namespace`: `dns`, `url`, `oid`, `x500` or a UUID
+pub fn uuidv5(namespace: &str, name: &str) -> Result<String, String> {
+    let namespace = NAMESPACES
+        .iter()
+        .find(|(known, _)| *known == namespace)
+        .map_or(namespace, |(_, uuid)| *uuid);
+    let namespace = parse_uuid(namespace).ok_or_else(|| {
+        format!("invalid namespace {namespace:?}, expected dns, url, oid, x500 or a UUID")
+    })?;
+
+    let mut data = namespace.to_vec();
+    data.extend_from_slice(name.as_bytes());
+    let mut uuid = sha1(&data);
+    uuid[6] = (uuid[6] & 0x0f) | 0x50;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+    Ok(format_uuid(&uuid[..16]))
+}
+
+/// Bytes derived from `seed`, as many as needed
+fn stream(seed: &str) -> impl Iterator<Item = u8> + '_ {
+    (0u64..).flat_map(move |counter| {
+        let mut data = seed.as_bytes().to_vec();
+        data.push(0);
+        data.extend_from_slice(&counter.to_be_bytes());
+        sha1(&data)
+    })
+}
+
+/// Whole number from 0 up to (excluding) `max`
+pub fn random(seed: &str, max: u64) -> Result<u64, String> {
+    if max == 0 {
+        return Err("expected a positive maximum".to_string());
+    }
+    // reject the values that would favour small results
+    let zone = u64::MAX - u64::MAX % max;
+    let mut bytes = stream(seed);
+    loop {
+        let mut number = [0u8; 8];
+        number
+            .iter_mut()
+            .for_each(|byte| *byte = bytes.next().unwrap_or_default());
+        let number = u64::from_be_bytes(number);
+        if number < zone {
+            return Ok(number % max);
+        }
+    }
+}
+
+/// Longest string `random_string` generates, a guard against lengths that are far too large
+pub const STRING_LIMIT: usize = 4096;
+
+/// `length` letters and digits, at most [STRING_LIMIT]
+pub fn random_string(seed: &str, length: usize) -> Result<String, String> {
+    if length > STRING_LIMIT {
+        return Err(format!("length {length} larger than {STRING_LIMIT}"));
+    }
+    Ok(stream(seed)
+        // 248 is the largest multiple of 62 in a byte
+        .filter(|byte| *byte < 248)
+        .take(length)
+        .map(|byte| ALPHANUMERIC[byte as usize % 62] as char)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn digests() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&sha1(&[b'a'; 1000])),
+            "291e9a6c66994949b57ba5e650361e98fc36b1ba"
+        );
+    }
+
+    #[test]
+    fn uuids() {
+        assert_eq!(
+            uuidv5("dns", "python.org").as_deref(),
+            Ok("886313e1-3b8a-5372-9b90-0c9aee199e5d")
+        );
+        assert_eq!(
+            uuidv5(
+                "6ba7b811-9dad-11d1-80b4-00c04fd430c8",
+                "https://example.com"
+            ),
+            uuidv5("url", "https://example.com")
+        );
+        assert!(uuidv5("example", "a").is_err());
+    }
+
+    #[test]
+    fn seeded() {
+        assert_eq!(random("shard-a", 16), random("shard-a", 16));
+        assert!((0..100).all(|i| random(&i.to_string(), 3).unwrap() < 3));
+        assert!(random("a", 0).is_err());
+
+        let token = random_string("token", 32).unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.bytes().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(Ok(token.clone()), random_string("token", 32));
+        assert_ne!(Ok(token), random_string("other", 32));
+        assert_eq!(
+            random_string("a", STRING_LIMIT).map(|text| text.len()),
+            Ok(STRING_LIMIT)
+        );
+        assert!(random_string("a", usize::MAX)
+            .unwrap_err()
+            .contains("larger than 4096"));
+    }
+}
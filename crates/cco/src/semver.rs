@@ -0,0 +1,258 @@
+//! semantic versions (<https://semver.org>) for `semver_compare`, `semver_satisfies` and
+//! `semver_parse`
+//!
+//! A leading `v` is accepted and missing minor or patch numbers are `0`: `v1.2` is `1.2.0`.
+//! Constraints are comparisons separated by `,` that all have to hold, alternatives are separated
+//! by `||`:
+//! - `>=1.2`, `>1.2`, `<=2`, `<2`, `!=1.3.1` and `=1.2.3` compare with the version
+//! - a version without operator (or with `=`) matches all versions it does not specify, `1.2`
+//!   matches `1.2.0` up to (excluding) `1.3.0`
+//! - `~1.2.3` allows patch updates (`>=1.2.3, <1.3`), `^1.2.3` updates that keep the first
+//!   non-zero number (`>=1.2.3, <2`, `^0.2.3` is `>=0.2.3, <0.3`)
+//! - `*` matches every version
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// Pre-release identifiers, `1.0.0-rc.1` has `["rc", "1"]`
+    pub pre: Vec<String>,
+    pub build: Option<String>,
+}
+
+impl Version {
+    fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+            pre: vec![],
+            build: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{build}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Precedence of semver, the build metadata is ignored
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => compare_pre(&self.pre, &other.pre),
+            })
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Numeric identifiers by value and before alphanumeric ones, more identifiers are greater
+fn compare_pre(a: &[String], b: &[String]) -> Ordering {
+    for (a, b) in a.iter().zip(b) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Numbers given of a version and the version with the missing ones as `0`
+fn parse_partial(text: &str) -> Result<(usize, Version), String> {
+    let invalid = || format!("invalid version {text:?}");
+    let trimmed = text.trim();
+    let trimmed = trimmed.strip_prefix('v').unwrap_or(trimmed);
+    let (rest, build) = match trimmed.split_once('+') {
+        Some((rest, build)) => (rest, Some(build.to_string())),
+        None => (trimmed, None),
+    };
+    let (numbers, pre) = match rest.split_once('-') {
+        Some((numbers, pre)) => (numbers, pre.split('.').map(str::to_string).collect()),
+        None => (rest, vec![]),
+    };
+    if pre.iter().chain(&build).any(String::is_empty) {
+        return Err(invalid());
+    }
+
+    let numbers = numbers
+        .split('.')
+        .map(|number| number.parse::<u64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (given, [major, minor, patch]) = match numbers[..] {
+        [major] => (1, [major, 0, 0]),
+        [major, minor] => (2, [major, minor, 0]),
+        [major, minor, patch] => (3, [major, minor, patch]),
+        _ => return Err(invalid()),
+    };
+    Ok((
+        given,
+        Version {
+            pre,
+            build,
+            ..Version::new(major, minor, patch)
+        },
+    ))
+}
+
+pub fn parse(text: &str) -> Result<Version, String> {
+    parse_partial(text).map(|(_, version)| version)
+}
+
+/// Smallest version above all versions that start with the `given` numbers of `version`
+fn next(version: &Version, given: usize) -> Version {
+    match given {
+        1 => Version::new(version.major + 1, 0, 0),
+        2 => Version::new(version.major, version.minor + 1, 0),
+        _ => Version::new(version.major, version.minor, version.patch + 1),
+    }
+}
+
+/// Whether a single comparison (`>=1.2`) holds for `version`
+fn matches(version: &Version, comparison: &str) -> Result<bool, String> {
+    let comparison = comparison.trim();
+    if comparison == "*" {
+        return Ok(true);
+    }
+    let operator_end = comparison
+        .find(|c: char| !matches!(c, '<' | '>' | '=' | '!' | '~' | '^'))
+        .unwrap_or(comparison.len());
+    let (operator, bound) = comparison.split_at(operator_end);
+    let (given, bound) = parse_partial(bound)?;
+    let at_least = *version >= bound;
+
+    Ok(match operator {
+        ">=" => at_least,
+        ">" => *version > bound,
+        "<=" => *version <= bound,
+        "<" => !at_least,
+        "!=" => *version != bound,
+        "" | "=" if given == 3 => *version == bound,
+        "" | "=" => at_least && *version < next(&bound, given),
+        "~" => at_least && *version < next(&bound, given.min(2)),
+        "^" => {
+            let first_non_zero = match (bound.major, bound.minor) {
+                (0, 0) if given == 3 => 3,
+                (0, _) if given >= 2 => 2,
+                _ => 1,
+            };
+            at_least && *version < next(&bound, first_non_zero)
+        }
+        other => return Err(format!("invalid operator {other:?} in {comparison:?}")),
+    })
+}
+
+/// Whether `version` matches `constraint`, see the module documentation
+pub fn satisfies(version: &str, constraint: &str) -> Result<bool, String> {
+    let version = parse(version)?;
+    for alternative in constraint.split("||") {
+        let mut all = true;
+        for comparison in alternative.split(',') {
+            if comparison.trim().is_empty() {
+                return Err(format!("empty comparison in {constraint:?}"));
+            }
+            all &= matches(&version, comparison)?;
+        }
+        if all {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ordering() {
+        let versions = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1",
+            "1.10.0",
+            "2",
+        ];
+        for pair in versions.windows(2) {
+            assert!(
+                parse(pair[0]).unwrap() < parse(pair[1]).unwrap(),
+                "{pair:?}"
+            );
+        }
+        assert_eq!(parse("v1.2+build.5").unwrap().to_string(), "1.2.0+build.5");
+        assert_eq!(parse("1.0.0+a").unwrap(), parse("1.0.0+b").unwrap());
+        assert!(parse("1.2.3.4").is_err());
+        assert!(parse("1.x").is_err());
+        assert!(parse("1.0.0-").is_err());
+    }
+
+    #[test]
+    fn constraints() {
+        let cases = [
+            ("1.5.0", ">=1.2, <2", true),
+            ("2.0.0", ">=1.2, <2", false),
+            ("2.0.0-rc.1", "<2", true),
+            ("1.2.9", "1.2", true),
+            ("1.3.0", "=1.2", false),
+            ("1.2.3", "1.2.3", true),
+            ("1.3.1", "!=1.3.1", false),
+            ("1.2.9", "~1.2.3", true),
+            ("1.3.0", "~1.2.3", false),
+            ("1.9.0", "^1.2.3", true),
+            ("2.0.0", "^1.2.3", false),
+            ("0.2.9", "^0.2.3", true),
+            ("0.3.0", "^0.2.3", false),
+            ("0.0.4", "^0.0.3", false),
+            ("3.1.0", "<2 || >=3", true),
+            ("9.9.9", "*", true),
+        ];
+        for (version, constraint, expected) in cases {
+            assert_eq!(
+                satisfies(version, constraint),
+                Ok(expected),
+                "{version} {constraint}"
+            );
+        }
+        assert!(satisfies("1.0.0", ">=1.0,").is_err());
+        assert!(satisfies("1.0.0", "=>1.0").is_err());
+    }
+}
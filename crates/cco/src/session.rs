@@ -0,0 +1,140 @@
+//! shared evaluation state
+//!
+//! [CcoDocument::evaluate_in_context] starts from scratch for every expression. Long-running
+//! embedders (servers, editors) evaluate many expressions against the same document. An
+//! [EvalSession] remembers the value of every addressable it had to evaluate and reuses it for
+//! later expressions.
+//!
+//! [CcoDocument] is immutable after construction, so the session is `Send + Sync` and can be used
+//! from multiple threads at the same time (e.g. behind an [std::sync::Arc]).
+use crate::cco_document::{CcoDocument, Memo};
+use crate::value::Value;
+
+/// Evaluate expressions against a document, sharing intermediate results
+#[derive(Debug)]
+pub struct EvalSession {
+    document: CcoDocument,
+    memo: Memo,
+}
+
+impl EvalSession {
+    pub fn new(document: CcoDocument) -> Self {
+        Self {
+            document,
+            memo: Default::default(),
+        }
+    }
+
+    pub fn document(&self) -> &CcoDocument {
+        &self.document
+    }
+
+    /// Same as [CcoDocument::evaluate_in_context], but memoized
+    pub fn evaluate(&self, expression: hcl::Expression) -> anyhow::Result<Value> {
+        self.document
+            .evaluate_memoized(expression, Some(&self.memo))
+    }
+
+    /// Number of addressables evaluated so far
+    pub fn memoized_count(&self) -> usize {
+        self.memo.len()
+    }
+
+    /// How often references were answered from the session
+    pub fn cache_stats(&self) -> CacheStats {
+        use std::sync::atomic::Ordering::Relaxed;
+        CacheStats {
+            hits: self.memo.hits.load(Relaxed),
+            misses: self.memo.misses.load(Relaxed),
+        }
+    }
+}
+
+/// Reference lookups of an [EvalSession]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// the addressable was evaluated before
+    pub hits: u64,
+    /// the addressable had to be evaluated
+    pub misses: u64,
+}
+
+impl From<CcoDocument> for EvalSession {
+    fn from(document: CcoDocument) -> Self {
+        Self::new(document)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hcl_documents;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn thread_safe() {
+        assert_send_sync::<CcoDocument>();
+        assert_send_sync::<EvalSession>();
+    }
+
+    #[test]
+    fn concurrent_evaluation() {
+        let documents = hcl_documents! {r#"
+        data service app {
+          port = 8000
+        }
+        data service db {
+          port = service.app.port + 1
+        }
+        "#};
+        let session = EvalSession::new(CcoDocument::new(&documents).unwrap());
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let value = session
+                        .evaluate(hcl::Variable::unchecked("service").into())
+                        .unwrap();
+                    assert_eq!(value.get("db.port").and_then(Value::as_i64), Some(8001));
+                });
+            }
+        });
+
+        assert!(session.memoized_count() > 0);
+
+        let port = hcl::Traversal::builder(hcl::Variable::unchecked("service"))
+            .attr("db")
+            .attr("port")
+            .build();
+        let value = session.evaluate(port.into()).unwrap();
+        assert_eq!(value.as_i64(), Some(8001));
+    }
+
+    #[test]
+    fn cache_stats() {
+        let documents = hcl_documents! {r#"
+        data service app {
+          port = 8000
+        }
+        "#};
+        let session = EvalSession::new(CcoDocument::new(&documents).unwrap());
+        let port = || {
+            hcl::Traversal::builder(hcl::Variable::unchecked("service"))
+                .attr("app")
+                .attr("port")
+                .build()
+                .into()
+        };
+
+        session.evaluate(port()).unwrap();
+        let first = session.cache_stats();
+        assert_eq!(first.hits, 0);
+        assert!(first.misses > 0);
+
+        session.evaluate(port()).unwrap();
+        let second = session.cache_stats();
+        assert_eq!(second.misses, first.misses);
+        assert!(second.hits > 0);
+    }
+}
@@ -0,0 +1,282 @@
+//! regression tests for configuration repositories
+//!
+//! Configuration repositories can test their documents with `cargo test` the way this crate
+//! snapshots its own: load the documents, evaluate expressions and compare the values with
+//! expected YAML or JSON. Enable the `testing` feature in the `[dev-dependencies]` of the test
+//! crate.
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! let document = cco::testing::load_directory("config")?;
+//! cco::testing::assert_yaml(&document, "service.api.port", "8080");
+//! cco::testing::assert_fixture(&document, "service.api", "tests/fixtures/api.yaml");
+//! // every `<expression>.yaml`/`.json` file, e.g. `service.web.yaml`
+//! cco::testing::assert_fixtures(&document, "tests/fixtures/paths");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Objects are compared without regard to key order. Set `CCO_UPDATE_FIXTURES=1` to write the
+//! actual values to fixture files that differ or do not exist yet instead of failing.
+use crate::cco_document::CcoDocument;
+use crate::hcl_documents::HclDocuments;
+use crate::value::Value;
+use std::path::Path;
+
+/// Environment variable that makes fixture assertions write the actual values
+pub const UPDATE_FIXTURES: &str = "CCO_UPDATE_FIXTURES";
+
+/// Document of all `.cco.hcl` files of `dir`
+pub fn load_directory(dir: impl AsRef<Path>) -> anyhow::Result<CcoDocument> {
+    let mut documents = HclDocuments::default();
+    documents.load_directory(dir.as_ref())?;
+    Ok(CcoDocument::new(&documents)?)
+}
+
+/// Document of the given sources, named by their path
+pub fn load_str<'a>(
+    sources: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> anyhow::Result<CcoDocument> {
+    let mut documents = HclDocuments::default();
+    for (path, source) in sources {
+        documents.insert_str(source, Some(path.into()))?;
+    }
+    Ok(CcoDocument::new(&documents)?)
+}
+
+/// Value of an HCL expression, e.g. `service.api` or `[for s in service : s.port]`
+pub fn evaluate(document: &CcoDocument, expression: &str) -> anyhow::Result<Value> {
+    let expression = crate::hcl_documents::parse_expression(expression)
+        .map_err(|err| anyhow::anyhow!("invalid expression '{expression}': {err}"))?;
+    document.evaluate_in_context(expression)
+}
+
+/// Values of named expressions, in the given order
+pub fn evaluate_all<'n>(
+    document: &CcoDocument,
+    expressions: &[(&'n str, &str)],
+) -> anyhow::Result<Vec<(&'n str, Value)>> {
+    expressions
+        .iter()
+        .map(|(name, expression)| {
+            let value =
+                evaluate(document, expression).map_err(|err| anyhow::anyhow!("{name}: {err}"))?;
+            Ok((*name, value))
+        })
+        .collect()
+}
+
+/// Panics unless `expression` evaluates to the value of the YAML document `expected`
+#[track_caller]
+pub fn assert_yaml(document: &CcoDocument, expression: &str, expected: &str) {
+    let expected: serde_json::Value = serde_yaml::from_str(expected)
+        .unwrap_or_else(|err| panic!("expected value of {expression} is invalid YAML: {err}"));
+    assert_value(document, expression, &expected);
+}
+
+/// Panics unless `expression` evaluates to the value of the JSON document `expected`
+#[track_caller]
+pub fn assert_json(document: &CcoDocument, expression: &str, expected: &str) {
+    let expected: serde_json::Value = serde_json::from_str(expected)
+        .unwrap_or_else(|err| panic!("expected value of {expression} is invalid JSON: {err}"));
+    assert_value(document, expression, &expected);
+}
+
+/// Panics unless `expression` evaluates to the contents of the fixture file
+///
+/// Files ending in `.json` are read as JSON, all others as YAML.
+#[track_caller]
+pub fn assert_fixture(document: &CcoDocument, expression: &str, fixture: impl AsRef<Path>) {
+    if let Err(message) = check_fixture(document, expression, fixture.as_ref(), update()) {
+        panic!("{message}");
+    }
+}
+
+/// [assert_fixture] for each `.yaml`, `.yml` and `.json` file in `dir`, the file name without
+/// extension is the expression
+///
+/// All fixtures are checked before panicking with the failures of all of them.
+#[track_caller]
+pub fn assert_fixtures(document: &CcoDocument, dir: impl AsRef<Path>) {
+    let dir = dir.as_ref();
+    let mut fixtures: Vec<_> = std::fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("failed to read fixtures in {}: {err}", dir.display()))
+        .map(|entry| entry.expect("directory entry").path())
+        .filter(|path| format(path).is_some())
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "no fixtures in {}", dir.display());
+
+    let failures: Vec<_> = fixtures
+        .iter()
+        .filter_map(|fixture| {
+            let expression = fixture.file_stem()?.to_str()?;
+            check_fixture(document, expression, fixture, update()).err()
+        })
+        .collect();
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} fixtures failed\n\n{}",
+            failures.len(),
+            fixtures.len(),
+            failures.join("\n\n")
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Yaml,
+    Json,
+}
+
+fn format(path: &Path) -> Option<Format> {
+    match path.extension()?.to_str()? {
+        "yaml" | "yml" => Some(Format::Yaml),
+        "json" => Some(Format::Json),
+        _ => None,
+    }
+}
+
+/// Outcome of [check_fixture]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FixtureCheck {
+    Matched,
+    /// The fixture differed or did not exist and was written
+    Updated,
+}
+
+fn update() -> bool {
+    std::env::var_os(UPDATE_FIXTURES).is_some_and(|value| value != "0")
+}
+
+/// Compare the value of `expression` with the fixture file, the error describes the difference
+///
+/// With `update` the actual value is written to fixtures that differ or do not exist.
+pub fn check_fixture(
+    document: &CcoDocument,
+    expression: &str,
+    fixture: &Path,
+    update: bool,
+) -> Result<FixtureCheck, String> {
+    let format = format(fixture).unwrap_or(Format::Yaml);
+    let actual = actual(document, expression)?;
+
+    let expected = match std::fs::read_to_string(fixture) {
+        Ok(expected) => expected,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && update => String::new(),
+        Err(err) => return Err(format!("failed to read {}: {err}", fixture.display())),
+    };
+    let expected: Option<serde_json::Value> = match format {
+        Format::Yaml => serde_yaml::from_str(&expected).ok(),
+        Format::Json => serde_json::from_str(&expected).ok(),
+    };
+    if expected.as_ref() == Some(&actual) {
+        return Ok(FixtureCheck::Matched);
+    }
+
+    if update {
+        let contents = match format {
+            Format::Yaml => serde_yaml::to_string(&actual).map_err(|err| err.to_string())?,
+            Format::Json => {
+                serde_json::to_string_pretty(&actual).map_err(|err| err.to_string())? + "\n"
+            }
+        };
+        return std::fs::write(fixture, contents)
+            .map(|_| FixtureCheck::Updated)
+            .map_err(|err| format!("failed to write {}: {err}", fixture.display()));
+    }
+    match expected {
+        Some(expected) => Err(mismatch(expression, &expected, &actual)
+            + &format!(
+                "\nfixture: {} (set {UPDATE_FIXTURES}=1 to update)",
+                fixture.display()
+            )),
+        None => Err(format!("{} is not valid {format:?}", fixture.display())),
+    }
+}
+
+#[track_caller]
+fn assert_value(document: &CcoDocument, expression: &str, expected: &serde_json::Value) {
+    let actual = actual(document, expression).unwrap_or_else(|message| panic!("{message}"));
+    if &actual != expected {
+        panic!("{}", mismatch(expression, expected, &actual));
+    }
+}
+
+fn actual(document: &CcoDocument, expression: &str) -> Result<serde_json::Value, String> {
+    let value = evaluate(document, expression)
+        .map_err(|err| format!("failed to evaluate {expression}: {err:#}"))?;
+    serde_json::to_value(&value).map_err(|err| err.to_string())
+}
+
+fn mismatch(expression: &str, expected: &serde_json::Value, actual: &serde_json::Value) -> String {
+    let yaml = |value| serde_yaml::to_string(value).unwrap_or_default();
+    format!(
+        "value of {expression} differs\n--- expected\n{}--- actual\n{}",
+        yaml(expected),
+        yaml(actual)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SOURCE: &str = r#"data service api {
+  port = 8080
+  tags = ["a", "b"]
+}
+"#;
+
+    #[test]
+    fn assertions() {
+        let document = load_str([("a.cco.hcl", SOURCE)]).unwrap();
+        assert_yaml(&document, "service.api.port", "8080");
+        assert_yaml(&document, "service.api", "tags: [a, b]\nport: 8080");
+        assert_json(&document, "service.api.tags", r#"["a", "b"]"#);
+
+        let values = evaluate_all(&document, &[("port", "service.api.port + 1")]).unwrap();
+        assert_eq!(values[0].0, "port");
+        assert_eq!(serde_json::to_string(&values[0].1).unwrap(), "8081");
+        assert!(evaluate_all(&document, &[("missing", "service.web")]).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "value of service.api.port differs")]
+    fn assert_mismatch() {
+        let document = load_str([("a.cco.hcl", SOURCE)]).unwrap();
+        assert_yaml(&document, "service.api.port", "1");
+    }
+
+    #[test]
+    fn fixtures() {
+        let dir = std::env::temp_dir().join(format!("cco-testing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("service.api.port.yaml"), "8080\n").unwrap();
+        std::fs::write(dir.join("service.api.tags.json"), r#"["a","b"]"#).unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let document = load_str([("a.cco.hcl", SOURCE)]).unwrap();
+        assert_fixtures(&document, &dir);
+        assert!(check_fixture(
+            &document,
+            "service.api.tags",
+            &dir.join("service.api.port.yaml"),
+            false
+        )
+        .unwrap_err()
+        .contains("differs"));
+        assert!(
+            check_fixture(&document, "service.api", &dir.join("missing.yaml"), false)
+                .unwrap_err()
+                .contains("failed to read")
+        );
+
+        let written = dir.join("written.json");
+        let check = |update| check_fixture(&document, "service.api", &written, update);
+        assert_eq!(check(true), Ok(FixtureCheck::Updated));
+        assert_eq!(check(false), Ok(FixtureCheck::Matched));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
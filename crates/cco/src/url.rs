@@ -0,0 +1,319 @@
+//! `urlencode`, `urldecode`, `urljoin` and `parseurl`
+//!
+//! Encoding is the percent-encoding of RFC 3986: everything but letters, digits and `-._~` is
+//! encoded, spaces as `%20`. `urljoin` resolves references like a browser does (RFC 3986,
+//! section 5.2).
+use hcl::{Map, Value};
+
+pub fn encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+pub fn decode(encoded: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut input = encoded.bytes();
+    while let Some(byte) = input.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let hex = [input.next(), input.next()];
+        let byte = match hex {
+            [Some(high), Some(low)] => std::str::from_utf8(&[high, low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        bytes.push(byte.ok_or_else(|| format!("invalid percent-encoding in {encoded:?}"))?);
+    }
+    String::from_utf8(bytes).map_err(|_| format!("{encoded:?} does not decode to UTF-8 text"))
+}
+
+/// Components of a URL, empty where the URL has none
+#[derive(Debug, Default, PartialEq)]
+struct Parts<'u> {
+    scheme: Option<&'u str>,
+    authority: Option<&'u str>,
+    path: &'u str,
+    query: Option<&'u str>,
+    fragment: Option<&'u str>,
+}
+
+fn split(url: &str) -> Parts<'_> {
+    let mut parts = Parts::default();
+    let (rest, fragment) = match url.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (url, None),
+    };
+    parts.fragment = fragment;
+    let (mut rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    parts.query = query;
+
+    if let Some((scheme, after)) = rest.split_once(':') {
+        let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+        if valid {
+            parts.scheme = Some(scheme);
+            rest = after;
+        }
+    }
+    if let Some(after) = rest.strip_prefix("//") {
+        let end = after.find('/').unwrap_or(after.len());
+        parts.authority = Some(&after[..end]);
+        rest = &after[end..];
+    }
+    parts.path = rest;
+    parts
+}
+
+fn join_parts(parts: &Parts<'_>, path: &str) -> String {
+    let mut url = String::new();
+    if let Some(scheme) = parts.scheme {
+        url.push_str(scheme);
+        url.push(':');
+    }
+    if let Some(authority) = parts.authority {
+        url.push_str("//");
+        url.push_str(authority);
+    }
+    url.push_str(path);
+    if let Some(query) = parts.query {
+        url.push('?');
+        url.push_str(query);
+    }
+    if let Some(fragment) = parts.fragment {
+        url.push('#');
+        url.push_str(fragment);
+    }
+    url
+}
+
+/// Resolve `.` and `..` segments
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = vec![];
+    let mut parts = path.split('/').peekable();
+    while let Some(segment) = parts.next() {
+        let last = parts.peek().is_none();
+        match segment {
+            "." | ".." => {
+                if segment == ".." && segments.len() > 1 {
+                    segments.pop();
+                }
+                if last {
+                    segments.push("");
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// `reference` resolved against `base`: `urljoin("https://a.example/v1/", "users")` is
+/// `https://a.example/v1/users`
+pub fn join(base: &str, reference: &str) -> Result<String, String> {
+    let base_parts = split(base);
+    if base_parts.scheme.is_none() {
+        return Err(format!("base {base:?} is not an absolute URL"));
+    }
+    let reference = split(reference);
+
+    let mut target = Parts {
+        fragment: reference.fragment,
+        query: reference.query,
+        ..Parts::default()
+    };
+    let path = if reference.scheme.is_some() {
+        target.scheme = reference.scheme;
+        target.authority = reference.authority;
+        remove_dot_segments(reference.path)
+    } else if reference.authority.is_some() {
+        target.scheme = base_parts.scheme;
+        target.authority = reference.authority;
+        remove_dot_segments(reference.path)
+    } else {
+        target.scheme = base_parts.scheme;
+        target.authority = base_parts.authority;
+        if reference.path.is_empty() {
+            target.query = reference.query.or(base_parts.query);
+            base_parts.path.to_string()
+        } else if reference.path.starts_with('/') {
+            remove_dot_segments(reference.path)
+        } else if base_parts.authority.is_some() && base_parts.path.is_empty() {
+            remove_dot_segments(&format!("/{}", reference.path))
+        } else {
+            let directory = match base_parts.path.rfind('/') {
+                Some(end) => &base_parts.path[..=end],
+                None => "",
+            };
+            remove_dot_segments(&format!("{directory}{}", reference.path))
+        }
+    };
+    Ok(join_parts(&target, &path))
+}
+
+/// Object of the components of `url`:
+/// `scheme`, `username`, `password`, `host`, `port`, `path`, `query`, `params` and `fragment`
+///
+/// `params` are the decoded query parameters, the last one wins for repeated names. Missing
+/// components are `null`, the path is `""` at least.
+pub fn parse(url: &str) -> Result<Value, String> {
+    let parts = split(url);
+    let scheme = parts
+        .scheme
+        .ok_or_else(|| format!("{url:?} is not an absolute URL"))?;
+
+    let (mut username, mut password, mut host, mut port) = (None, None, None, None);
+    if let Some(authority) = parts.authority {
+        let host_port = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => {
+                let (user, pass) = match userinfo.split_once(':') {
+                    Some((user, pass)) => (user, Some(pass)),
+                    None => (userinfo, None),
+                };
+                username = Some(decode(user)?);
+                password = pass.map(decode).transpose()?;
+                host_port
+            }
+            None => authority,
+        };
+        // `[::1]:8080`
+        let port_start = match host_port.rfind(']') {
+            Some(end) => host_port[end..].find(':').map(|colon| end + colon),
+            None => host_port.rfind(':'),
+        };
+        let host_end = port_start.unwrap_or(host_port.len());
+        host = Some(host_port[..host_end].to_string());
+        if let Some(colon) = port_start {
+            let number = &host_port[colon + 1..];
+            port = Some(
+                number
+                    .parse::<u16>()
+                    .map_err(|_| format!("invalid port {number:?} in {url:?}"))?,
+            );
+        }
+    }
+
+    let mut params = Map::new();
+    for pair in parts.query.unwrap_or_default().split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params.insert(
+            decode(&name.replace('+', " "))?,
+            Value::String(decode(&value.replace('+', " "))?),
+        );
+    }
+
+    // components the URL does not have are left out, values have no `null`
+    let components = [
+        ("scheme", Some(Value::from(scheme))),
+        ("username", username.map(Value::String)),
+        ("password", password.map(Value::String)),
+        ("host", host.map(Value::String)),
+        ("port", port.map(Value::from)),
+        ("path", Some(Value::from(parts.path))),
+        ("query", parts.query.map(Value::from)),
+        ("params", Some(Value::Object(params))),
+        ("fragment", parts.fragment.map(Value::from)),
+    ];
+    let object: Map<String, Value> = components
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value?)))
+        .collect();
+    Ok(Value::Object(object))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        assert_eq!(encode("a b/c?d=ü~"), "a%20b%2Fc%3Fd%3D%C3%BC~");
+        assert_eq!(
+            decode("a%20b%2Fc%3Fd%3D%C3%BC~").as_deref(),
+            Ok("a b/c?d=ü~")
+        );
+        assert!(decode("%4").is_err());
+        assert!(decode("%zz").is_err());
+        assert!(decode("%FF").is_err());
+    }
+
+    #[test]
+    fn joins() {
+        let base = "http://a/b/c/d;p?q";
+        // examples of RFC 3986, section 5.4
+        for (reference, expected) in [
+            ("g:h", "g:h"),
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../../g", "http://a/g"),
+            ("../../../g", "http://a/g"),
+        ] {
+            assert_eq!(
+                join(base, reference).as_deref(),
+                Ok(expected),
+                "{reference}"
+            );
+        }
+        assert_eq!(
+            join("https://api.example", "v1/users").as_deref(),
+            Ok("https://api.example/v1/users")
+        );
+        assert!(join("/relative", "g").is_err());
+    }
+
+    #[test]
+    fn components() {
+        let url = "postgres://app:s%40cret@[::1]:5432/main?sslmode=require&x=a+b#top";
+        let Value::Object(parts) = parse(url).unwrap() else {
+            panic!("expected an object");
+        };
+        assert_eq!(parts["scheme"], Value::from("postgres"));
+        assert_eq!(parts["username"], Value::from("app"));
+        assert_eq!(parts["password"], Value::from("s@cret"));
+        assert_eq!(parts["host"], Value::from("[::1]"));
+        assert_eq!(parts["port"], Value::from(5432));
+        assert_eq!(parts["path"], Value::from("/main"));
+        assert_eq!(parts["query"], Value::from("sslmode=require&x=a+b"));
+        let mut params = Map::new();
+        params.insert("sslmode".to_string(), Value::from("require"));
+        params.insert("x".to_string(), Value::from("a b"));
+        assert_eq!(parts["params"], Value::Object(params));
+        assert_eq!(parts["fragment"], Value::from("top"));
+
+        let Value::Object(parts) = parse("https://example.com").unwrap() else {
+            panic!("expected an object");
+        };
+        assert!(!parts.contains_key("port"));
+        assert_eq!(parts["path"], Value::from(""));
+        assert!(parse("example.com/path").is_err());
+        assert!(parse("http://host:port").is_err());
+    }
+}
@@ -10,7 +10,7 @@ impl<'d> visit::VisitMut<Traversal> for AttributeReferenceRewriter<'d> {
     fn visit_mut(&mut self, traversal: &mut Traversal) {
         // was already rewritten
         if let Expression::Variable(var) = &traversal.expr {
-            if var.starts_with("cco__") {
+            if var.starts_with(cco_document::SUBST_PREFIX) {
                 return;
             }
         }
@@ -20,10 +20,53 @@ impl<'d> visit::VisitMut<Traversal> for AttributeReferenceRewriter<'d> {
             return;
         };
 
+        if let Some(observer) = self.documents.observer() {
+            observer.on_rewrite(traversal, subst);
+        }
+
         traversal.apply_substitution(Expression::Variable(subst.clone().into()), len);
     }
 }
 
+/// Substitutes traversals starting at `variable` with expressions provided by a resolver
+pub(crate) struct ResolverRewriter<'a> {
+    variable: &'a str,
+    resolver: &'a dyn Fn(&[Identifier]) -> Option<Expression>,
+    pub substitutions: usize,
+}
+
+impl<'a> ResolverRewriter<'a> {
+    pub fn new(
+        variable: &'a str,
+        resolver: &'a dyn Fn(&[Identifier]) -> Option<Expression>,
+    ) -> Self {
+        Self {
+            variable,
+            resolver,
+            substitutions: 0,
+        }
+    }
+}
+
+impl<'a> visit::VisitMut<Traversal> for ResolverRewriter<'a> {
+    fn visit_mut(&mut self, traversal: &mut Traversal) {
+        let path = traversal.get_longest_path();
+        if path.first().map(|ident| ident.as_str()) != Some(self.variable) {
+            return;
+        }
+
+        // most specific path first
+        for len in (1..=path.len()).rev() {
+            if let Some(expr) = (self.resolver)(&path[..len]) {
+                tracing::debug!(path=?&path[..len], "resolved by resolver");
+                traversal.apply_substitution(expr, len);
+                self.substitutions += 1;
+                return;
+            }
+        }
+    }
+}
+
 #[derive(derive_new::new)]
 pub(crate) struct SelfRewriter<'a> {
     block_name: &'a [Identifier],
@@ -64,7 +107,40 @@ impl<'a> visit::VisitMut<Traversal> for SelfRewriter<'a> {
     }
 }
 
-trait TraversalExt {
+/// Prefixes references to the groups of a namespace with the namespace
+///
+/// `service.api` in a document nested under `prod` is `prod.service.api` if `service` is a group
+/// of that namespace, see [crate::hcl_documents::HclDocuments::nest].
+#[derive(derive_new::new)]
+pub(crate) struct NamespaceRewriter<'a> {
+    namespace: &'a [Identifier],
+    groups: &'a std::collections::HashSet<Identifier>,
+}
+
+impl<'a> visit::VisitMut<Traversal> for NamespaceRewriter<'a> {
+    fn visit_mut(&mut self, traversal: &mut Traversal) {
+        let Expression::Variable(var) = &traversal.expr else {
+            return;
+        };
+        let Some((root, namespace)) = self.namespace.split_first() else {
+            return;
+        };
+        if !self.groups.contains(var.as_str()) {
+            return;
+        }
+
+        let mut operators: Vec<_> = namespace
+            .iter()
+            .chain(std::iter::once(&Identifier::unchecked(var.as_str())))
+            .map(|identifier| TraversalOperator::GetAttr(identifier.clone()))
+            .collect();
+        operators.append(&mut traversal.operators);
+        traversal.expr = Expression::Variable(root.clone().into());
+        traversal.operators = operators;
+    }
+}
+
+pub(crate) trait TraversalExt {
     fn apply_substitution(&mut self, expr: Expression, path_len: usize);
     fn get_longest_path(&self) -> Vec<Identifier>;
     fn squash(&mut self);
@@ -92,6 +168,10 @@ impl TraversalExt for Traversal {
         tracing::trace!(traversal=?self,"after substitution");
     }
 
+    /// Attribute access and string indices, `service["my app"].port` is `service.my_app.port`
+    ///
+    /// String indices are sanitized like block labels, so labels that are not valid identifiers
+    /// can be addressed as written.
     fn get_longest_path(&self) -> Vec<Identifier> {
         let Expression::Variable(var) = &self.expr else {
             return vec![];
@@ -99,11 +179,13 @@ impl TraversalExt for Traversal {
 
         let mut path = vec![hcl::Identifier::unchecked(var.as_str())];
         for operator in &self.operators {
-            let TraversalOperator::GetAttr(ident) = operator else {
-                break;
-            };
-
-            path.push(ident.clone());
+            match operator {
+                TraversalOperator::GetAttr(ident) => path.push(ident.clone()),
+                TraversalOperator::Index(Expression::String(key)) => {
+                    path.push(Identifier::sanitized(key))
+                }
+                _ => break,
+            }
         }
 
         path
@@ -135,11 +217,174 @@ impl TraversalExt for Traversal {
     }
 }
 
+/// Names bound by for-expressions and for-directives anywhere within `expr`
+///
+/// These are local to the expression and must not be treated as references.
+pub(crate) fn local_variables(expr: &Expression, names: &mut std::collections::HashSet<String>) {
+    use hcl::template::{Directive, Element, Template};
+
+    fn template_locals(template: &Template, names: &mut std::collections::HashSet<String>) {
+        for element in template.elements() {
+            match element {
+                Element::Interpolation(interpolation) => {
+                    local_variables(&interpolation.expr, names)
+                }
+                Element::Directive(Directive::If(ifdir)) => {
+                    local_variables(&ifdir.cond_expr, names);
+                    template_locals(&ifdir.true_template, names);
+                    if let Some(false_template) = &ifdir.false_template {
+                        template_locals(false_template, names);
+                    }
+                }
+                Element::Directive(Directive::For(fordir)) => {
+                    names.extend(fordir.key_var.iter().map(|var| var.to_string()));
+                    names.insert(fordir.value_var.to_string());
+                    local_variables(&fordir.collection_expr, names);
+                    template_locals(&fordir.template, names);
+                }
+                Element::Literal(_) => {}
+            }
+        }
+    }
+
+    match expr {
+        Expression::ForExpr(forexpr) => {
+            names.extend(forexpr.key_var.iter().map(|var| var.to_string()));
+            names.insert(forexpr.value_var.to_string());
+            local_variables(&forexpr.collection_expr, names);
+            forexpr
+                .key_expr
+                .iter()
+                .for_each(|e| local_variables(e, names));
+            local_variables(&forexpr.value_expr, names);
+            forexpr
+                .cond_expr
+                .iter()
+                .for_each(|e| local_variables(e, names));
+        }
+        Expression::Traversal(traversal) => {
+            local_variables(&traversal.expr, names);
+            for operator in &traversal.operators {
+                if let TraversalOperator::Index(index) = operator {
+                    local_variables(index, names);
+                }
+            }
+        }
+        Expression::Array(array) => array.iter().for_each(|e| local_variables(e, names)),
+        Expression::Object(object) => object.values().for_each(|e| local_variables(e, names)),
+        Expression::TemplateExpr(template_expr) => {
+            if let Ok(template) = Template::from_expr(template_expr) {
+                template_locals(&template, names);
+            }
+        }
+        Expression::FuncCall(func_call) => func_call
+            .args
+            .iter()
+            .for_each(|e| local_variables(e, names)),
+        Expression::Parenthesis(expr) => local_variables(expr, names),
+        Expression::Conditional(cond) => {
+            local_variables(&cond.cond_expr, names);
+            local_variables(&cond.true_expr, names);
+            local_variables(&cond.false_expr, names);
+        }
+        Expression::Operation(operation) => match operation.as_ref() {
+            hcl::Operation::Binary(binop) => {
+                local_variables(&binop.lhs_expr, names);
+                local_variables(&binop.rhs_expr, names);
+            }
+            hcl::Operation::Unary(unop) => local_variables(&unop.expr, names),
+        },
+        _ => {}
+    }
+}
+
+/// Comment directly above a block or attribute (no blank line in between), without markers
+///
+/// Consecutive `#`/`//` lines are joined with newlines, a `/* */` comment is used as is.
+pub(crate) fn doc_comment(decor: &hcl_edit::Decor) -> Option<String> {
+    let prefix = decor.prefix()?;
+    // the indentation of the element itself
+    let prefix = prefix.trim_end_matches([' ', '\t']);
+    let prefix = prefix.strip_suffix('\n').unwrap_or(prefix);
+
+    if let Some(comment) = prefix.strip_suffix("*/") {
+        let start = comment.rfind("/*")?;
+        let lines: Vec<_> = comment[start + 2..]
+            .lines()
+            .map(|line| line.trim().trim_start_matches('*').trim())
+            .collect();
+        let text = lines.join("\n").trim().to_string();
+        return (!text.is_empty()).then_some(text);
+    }
+
+    let mut lines = vec![];
+    // a blank line ends the comment
+    for line in prefix.split('\n').rev() {
+        let line = line.trim();
+        let Some(text) = line.strip_prefix('#').or_else(|| line.strip_prefix("//")) else {
+            break;
+        };
+        lines.push(text.strip_prefix(' ').unwrap_or(text).trim_end());
+    }
+    lines.reverse();
+    let text = lines.join("\n").trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use hcl_edit::Decorate;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn doc_comments() {
+        let body: hcl_edit::structure::Body = r#"
+# file header
+
+# The application
+#   with indentation
+data app {
+  // port to listen on
+  port = 80
+
+  /*
+   * multi-line
+   * block comment
+   */
+  host = "x"
+  # separated
+
+  none = 1
+  inline = 2 # not a doc comment
+  after = 3
+}
+"#
+        .parse()
+        .unwrap();
+        let block = body.blocks().next().unwrap();
+        assert_eq!(
+            doc_comment(block.decor()).as_deref(),
+            Some("The application\n  with indentation")
+        );
+
+        let docs: Vec<_> = block
+            .body
+            .attributes()
+            .map(|attribute| doc_comment(attribute.decor()))
+            .collect();
+        assert_eq!(
+            docs,
+            [
+                Some("port to listen on".to_string()),
+                Some("multi-line\nblock comment".to_string()),
+                None,
+                None,
+                None,
+            ]
+        );
+    }
+
     #[test]
     fn apply_substitution() {
         let mut traversal = Traversal::builder(hcl::Variable::unchecked("one"))
@@ -156,4 +401,19 @@ mod test {
 
         assert_eq!(traversal, expected);
     }
+
+    #[test]
+    fn longest_path_with_string_indices() {
+        let expression: hcl_edit::expr::Expression =
+            r#"service["my-weird name"].port[0]["x"]"#.parse().unwrap();
+        let Expression::Traversal(traversal) = Expression::from(expression) else {
+            panic!("not a traversal");
+        };
+        let path: Vec<_> = traversal
+            .get_longest_path()
+            .iter()
+            .map(|ident| ident.to_string())
+            .collect();
+        assert_eq!(path, ["service", "my-weird_name", "port"]);
+    }
 }
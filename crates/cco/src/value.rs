@@ -5,6 +5,7 @@
 //! - integer (signed, currently: i64 - may change)
 //! - decimal (currently: f64 - may change)
 //! - string (utf-8)
+//! - bytes (binary data, e.g. certificates or keys)
 //! - array ("list" of values)
 //! - object (order-preserving "map"/"dictionary", where the key is of type string)
 //!
@@ -13,6 +14,11 @@
 //! - the only valid **implicit** conversion: every `integer` is also a `decimal`
 //! - numeric type ranges (min/max) for `integer` or `decimal` are currently not defined and are subject to change
 //!
+//! HCL has no binary type. While expressions are evaluated bytes are an object with the single key
+//! [bytes_key] holding them as base64 string, the conversions from and to `hcl` values translate
+//! between both forms. Text formats (JSON, YAML) serialize bytes as base64 string, binary formats
+//! as raw bytes.
+//!
 //! TODO: Currently we pretend that `null` or out-of-bounds integers do not exist.
 //!
 use serde::{
@@ -20,6 +26,21 @@ use serde::{
     Serializer,
 };
 
+/// Key of the object representing bytes in HCL expressions, e.g. `{"$bytes:9c0f…" = "AAE="}`
+///
+/// The key ends with a token chosen at random for each process. Documents and decoded data can
+/// not spell it out, an object they contain is never taken for bytes.
+pub fn bytes_key() -> &'static str {
+    static KEY: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    KEY.get_or_init(|| {
+        use std::hash::{BuildHasher, Hasher};
+        let token = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        format!("$bytes:{token:016x}")
+    })
+}
+
 /// All possible value types
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -27,6 +48,7 @@ pub enum Value {
     Integer(i64),
     Decimal(f64),
     String(String),
+    Bytes(Vec<u8>),
     Array(Vec<Value>),
     Object(indexmap::IndexMap<String, Value>),
 }
@@ -43,6 +65,18 @@ impl From<&str> for Value {
     }
 }
 
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Decimal(value)
+    }
+}
+
 impl From<hcl::Body> for Value {
     fn from(value: hcl::Body) -> Self {
         Value::Object(
@@ -75,7 +109,12 @@ impl From<hcl::Expression> for Value {
             }
             Expression::String(s) => s.into(),
             Expression::Array(array) => array.into(),
-            Expression::Object(object) => object.into(),
+            Expression::Object(object) => {
+                match bytes(object.iter().map(|(k, v)| (k.to_string(), v))) {
+                    Some(bytes) => Value::Bytes(bytes),
+                    None => object.into(),
+                }
+            }
             Expression::Null => {
                 // TODO: Don't panic. Handle errors.
                 panic!("null value found. This should never happen. Please report this.")
@@ -143,7 +182,10 @@ impl From<hcl::Value> for Value {
             hcl::Value::Number(n) => n.into(),
             hcl::Value::String(s) => s.into(),
             hcl::Value::Array(a) => a.into(),
-            hcl::Value::Object(o) => o.into(),
+            hcl::Value::Object(o) => match bytes(o.iter().map(|(k, v)| (k.clone(), v))) {
+                Some(bytes) => Value::Bytes(bytes),
+                None => o.into(),
+            },
             hcl::Value::Null => {
                 // FIXME: We assume that we never hit `null`
                 panic!("null value found. This should never happen. Please report this.")
@@ -152,6 +194,115 @@ impl From<hcl::Value> for Value {
     }
 }
 
+/// Contents of the object representing bytes, see [bytes_key]
+fn bytes<'a, V>(mut entries: impl Iterator<Item = (String, &'a V)>) -> Option<Vec<u8>>
+where
+    V: 'a + Into<Value> + Clone,
+{
+    let (key, value) = entries.next()?;
+    if key != bytes_key() || entries.next().is_some() {
+        return None;
+    }
+    match value.clone().into() {
+        Value::String(encoded) => crate::base64::decode(&encoded).ok(),
+        _ => None,
+    }
+}
+
+/// Non-finite decimals become `null`, just like serializing them as JSON would
+///
+/// Bytes become base64 strings.
+impl From<Value> for serde_json::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Boolean(value) => value.into(),
+            Value::Integer(value) => value.into(),
+            Value::Decimal(value) => serde_json::Number::from_f64(value)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Value::String(value) => value.into(),
+            Value::Bytes(value) => crate::base64::encode(&value).into(),
+            Value::Array(value) => {
+                serde_json::Value::Array(value.into_iter().map(Into::into).collect())
+            }
+            Value::Object(value) => {
+                serde_json::Value::Object(value.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
+
+impl TryFrom<serde_json::Value> for Value {
+    type Error = ConversionError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        Ok(match value {
+            serde_json::Value::Null => return Err(ConversionError::Null),
+            serde_json::Value::Bool(value) => value.into(),
+            serde_json::Value::Number(value) => match value.as_i64() {
+                Some(int) => Value::Integer(int),
+                None => match value.as_u64() {
+                    Some(_) => return Err(ConversionError::OutOfBounds(value.to_string())),
+                    None => Value::Decimal(
+                        value
+                            .as_f64()
+                            .ok_or_else(|| ConversionError::OutOfBounds(value.to_string()))?,
+                    ),
+                },
+            },
+            serde_json::Value::String(value) => value.into(),
+            serde_json::Value::Array(value) => Value::Array(
+                value
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<_, _>>()?,
+            ),
+            serde_json::Value::Object(value) => Value::Object(
+                value
+                    .into_iter()
+                    .map(|(k, v)| v.try_into().map(|v| (k, v)))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+}
+
+/// Non-finite decimals become `null`, bytes the object described at [bytes_key]
+impl From<Value> for hcl::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Boolean(value) => hcl::Value::Bool(value),
+            Value::Integer(value) => hcl::Value::Number(value.into()),
+            Value::Decimal(value) => hcl::Number::from_f64(value)
+                .map(hcl::Value::Number)
+                .unwrap_or(hcl::Value::Null),
+            Value::String(value) => hcl::Value::String(value),
+            Value::Bytes(value) => hcl::Value::Object(
+                [(
+                    bytes_key().to_string(),
+                    crate::base64::encode(&value).into(),
+                )]
+                .into_iter()
+                .collect(),
+            ),
+            Value::Array(value) => hcl::Value::Array(value.into_iter().map(Into::into).collect()),
+            Value::Object(value) => {
+                hcl::Value::Object(value.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
+
+/// Error converting foreign values that can not be represented as [Value]
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    #[error("null values are not supported")]
+    Null,
+    #[error("number out of bounds: {0}")]
+    OutOfBounds(String),
+}
+
+/// Bytes are base64 strings in human readable formats
 impl serde::ser::Serialize for Value {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -162,6 +313,10 @@ impl serde::ser::Serialize for Value {
             Value::Integer(value) => serializer.serialize_i64(*value),
             Value::Decimal(value) => serializer.serialize_f64(*value),
             Value::String(value) => serializer.serialize_str(value),
+            Value::Bytes(value) if serializer.is_human_readable() => {
+                serializer.serialize_str(&crate::base64::encode(value))
+            }
+            Value::Bytes(value) => serializer.serialize_bytes(value),
             Value::Array(value) => {
                 let mut ser = serializer.serialize_seq(Some(value.len()))?;
                 for element in value {
@@ -179,3 +334,439 @@ impl serde::ser::Serialize for Value {
         }
     }
 }
+
+impl Value {
+    /// Look up a nested value by a dot separated path
+    ///
+    /// Segments select object keys, numeric segments also select array elements.
+    ///
+    /// ```
+    /// # use cco::value::Value;
+    /// let value = Value::Object([("a".to_string(), vec![1i64, 2].into())].into_iter().collect());
+    /// assert_eq!(value.get("a.1").and_then(Value::as_i64), Some(2));
+    /// assert!(value.get("a.2").is_none());
+    /// ```
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        path.split('.')
+            .try_fold(self, |value, segment| match value {
+                Value::Object(object) => object.get(segment),
+                Value::Array(array) => array.get(segment.parse::<usize>().ok()?),
+                _ => None,
+            })
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Every `integer` is also a `decimal`
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(value) => Some(*value as f64),
+            Value::Decimal(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&indexmap::IndexMap<String, Value>> {
+        match self {
+            Value::Object(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Elements of an array, nothing for other values
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        self.as_array().into_iter().flatten()
+    }
+
+    /// Entries of an object in order, nothing for other values
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.as_object()
+            .into_iter()
+            .flatten()
+            .map(|(key, value)| (key.as_str(), value))
+    }
+
+    /// Name of the value type as used in the module documentation
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Boolean(_) => "boolean",
+            Value::Integer(_) => "integer",
+            Value::Decimal(_) => "decimal",
+            Value::String(_) => "string",
+            Value::Bytes(_) => "bytes",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+
+    /// Sort object keys lexicographically, recursively
+    ///
+    /// Objects keep source order otherwise.
+    pub fn sort_keys(&mut self) {
+        match self {
+            Value::Object(object) => {
+                object.sort_unstable_keys();
+                object.values_mut().for_each(Value::sort_keys);
+            }
+            Value::Array(array) => array.iter_mut().for_each(Value::sort_keys),
+            _ => {}
+        }
+    }
+
+    /// Canonical form for stable output: sorted keys and integral decimals as integers
+    ///
+    /// `1.0` becomes `1`, so values do not change representation depending on how they were
+    /// computed.
+    pub fn normalize(&mut self) {
+        self.sort_keys();
+        self.normalize_numbers();
+    }
+
+    fn normalize_numbers(&mut self) {
+        match self {
+            Value::Decimal(decimal)
+                if decimal.fract() == 0.0 && decimal.abs() < i64::MAX as f64 =>
+            {
+                *self = Value::Integer(*decimal as i64)
+            }
+            Value::Object(object) => object.values_mut().for_each(Value::normalize_numbers),
+            Value::Array(array) => array.iter_mut().for_each(Value::normalize_numbers),
+            _ => {}
+        }
+    }
+
+    /// Deserialize into any type implementing [serde::Deserialize]
+    ///
+    /// ```
+    /// # use cco::value::Value;
+    /// #[derive(serde::Deserialize)]
+    /// struct Service {
+    ///     port: u16,
+    /// }
+    ///
+    /// let value = Value::Object([("port".to_string(), Value::Integer(8080))].into_iter().collect());
+    /// let service: Service = value.deserialize_into().unwrap();
+    /// assert_eq!(service.port, 8080);
+    /// ```
+    pub fn deserialize_into<T: serde::de::DeserializeOwned>(self) -> Result<T, DeserializeError> {
+        T::deserialize(self)
+    }
+}
+
+/// Access an object entry
+///
+/// # Panic
+/// Panics if the value is not an object or the key does not exist. Use [Value::get] to handle
+/// missing keys.
+impl std::ops::Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Self::Output {
+        match self {
+            Value::Object(object) => object
+                .get(key)
+                .unwrap_or_else(|| panic!("no entry found for key {key:?}")),
+            _ => panic!("cannot index {} with key {key:?}", self.type_name()),
+        }
+    }
+}
+
+/// Access an array element
+///
+/// # Panic
+/// Panics if the value is not an array or the index is out of bounds.
+impl std::ops::Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match self {
+            Value::Array(array) => &array[index],
+            _ => panic!("cannot index {} with {index}", self.type_name()),
+        }
+    }
+}
+
+/// Error returned when a [Value] does not match the shape of the target type
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("{0}")]
+pub struct DeserializeError(String);
+
+impl serde::de::Error for DeserializeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        DeserializeError(msg.to_string())
+    }
+}
+
+impl<'de> serde::de::IntoDeserializer<'de, DeserializeError> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for Value {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        use serde::de::value::{MapDeserializer, SeqDeserializer};
+
+        match self {
+            Value::Boolean(value) => visitor.visit_bool(value),
+            Value::Integer(value) => visitor.visit_i64(value),
+            Value::Decimal(value) => visitor.visit_f64(value),
+            Value::String(value) => visitor.visit_string(value),
+            Value::Bytes(value) => visitor.visit_byte_buf(value),
+            Value::Array(value) => {
+                let mut seq = SeqDeserializer::new(value.into_iter());
+                let result = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(result)
+            }
+            Value::Object(value) => {
+                let mut map = MapDeserializer::new(value.into_iter());
+                let result = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(result)
+            }
+        }
+    }
+
+    /// There is no `null`, so every value is `Some`
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Unit variants are strings, other variants are objects with a single key
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        use serde::de::IntoDeserializer;
+        use serde::de::{value::MapAccessDeserializer, value::MapDeserializer, Error};
+
+        match self {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Object(object) if object.len() == 1 => visitor.visit_enum(
+                MapAccessDeserializer::new(MapDeserializer::new(object.into_iter())),
+            ),
+            _ => Err(DeserializeError::custom(
+                "expected a string or an object with a single key for an enum",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sort_keys_and_normalize() {
+        let mut value = Value::try_from(serde_json::json!({
+            "b": { "y": 1.0, "x": 1.5 },
+            "a": [{ "d": 2, "c": 3 }],
+        }))
+        .unwrap();
+
+        value.sort_keys();
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"a":[{"c":3,"d":2}],"b":{"x":1.5,"y":1.0}}"#
+        );
+
+        value.normalize();
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"a":[{"c":3,"d":2}],"b":{"x":1.5,"y":1}}"#
+        );
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Config {
+        name: String,
+        ratio: f64,
+        replicas: Option<u8>,
+        tags: Vec<String>,
+        mode: Mode,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Active,
+    }
+
+    fn object(entries: Vec<(&str, Value)>) -> Value {
+        Value::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn deserialize_struct() {
+        let value = object(vec![
+            ("name", "app".into()),
+            ("ratio", Value::Integer(1)),
+            ("replicas", Value::Integer(3)),
+            ("tags", vec!["a", "b"].into()),
+            ("mode", "active".into()),
+        ]);
+
+        assert_eq!(
+            value.deserialize_into::<Config>().unwrap(),
+            Config {
+                name: "app".to_string(),
+                ratio: 1.0,
+                replicas: Some(3),
+                tags: vec!["a".to_string(), "b".to_string()],
+                mode: Mode::Active,
+            }
+        );
+    }
+
+    #[test]
+    fn accessors() {
+        let value = object(vec![(
+            "a",
+            object(vec![("b", vec![Value::Integer(1), "two".into()].into())]),
+        )]);
+
+        assert_eq!(value.get("a.b.0").and_then(Value::as_i64), Some(1));
+        assert_eq!(value.get("a.b.1").and_then(Value::as_str), Some("two"));
+        assert!(value.get("a.c").is_none());
+        assert!(value.get("a.b.x").is_none());
+
+        assert_eq!(value["a"]["b"][1].as_str(), Some("two"));
+        assert_eq!(value["a"]["b"].iter().count(), 2);
+        assert_eq!(
+            value["a"].entries().map(|(key, _)| key).collect::<Vec<_>>(),
+            ["b"]
+        );
+        assert_eq!(Value::Integer(2).as_f64(), Some(2.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_missing_key() {
+        let _ = &object(vec![])["missing"];
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let value = object(vec![
+            ("int", Value::Integer(-1)),
+            ("dec", Value::Decimal(1.5)),
+            ("list", vec!["a"].into()),
+        ]);
+
+        let json = serde_json::Value::from(value.clone());
+        let back = Value::try_from(json).unwrap();
+        assert_eq!(
+            serde_json::to_string(&back).unwrap(),
+            serde_json::to_string(&value).unwrap()
+        );
+
+        assert_eq!(
+            Value::try_from(serde_json::json!({"a": null})).unwrap_err(),
+            ConversionError::Null
+        );
+        assert!(matches!(
+            Value::try_from(serde_json::json!(u64::MAX)),
+            Err(ConversionError::OutOfBounds(_))
+        ));
+    }
+
+    #[test]
+    fn bytes() {
+        let value = Value::Bytes(vec![0xff, 0x00]);
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#""/wA=""#);
+        assert_eq!(value.type_name(), "bytes");
+
+        let hcl = hcl::Value::from(value);
+        assert_eq!(
+            hcl.as_object().and_then(|object| object.get(bytes_key())),
+            Some(&hcl::Value::from("/wA="))
+        );
+        let back = Value::from(hcl);
+        assert_eq!(back.as_bytes(), Some(&[0xff, 0x00][..]));
+
+        // user data is never taken for bytes
+        let expression: hcl_edit::expr::Expression = r#"{ "$bytes" = "AAE=" }"#.parse().unwrap();
+        let expression = hcl::Expression::from(expression);
+        assert!(Value::from(expression).as_object().is_some());
+
+        let object = hcl::Value::from_iter([(bytes_key(), "AAE="), ("other", "x")]);
+        assert!(Value::from(object).as_object().is_some());
+    }
+
+    #[test]
+    fn deserialize_type_mismatch() {
+        let value = object(vec![("name", Value::Integer(1))]);
+        assert!(value.deserialize_into::<Config>().is_err());
+    }
+}
@@ -1,5 +1,7 @@
 //! visitor pattern helpers
+mod visit_func_calls;
 mod visit_traversals;
+pub use visit_func_calls::VisitFuncCallsMut;
 pub use visit_traversals::VisitTraversalsMut;
 
 /// Visitor that visits is subjects mutably
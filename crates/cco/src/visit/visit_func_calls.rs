@@ -0,0 +1,113 @@
+use super::VisitMut;
+use hcl::{
+    template::{Directive, Element},
+    Expression, FuncCall, ObjectKey, Operation, Template, TemplateExpr, TraversalOperator,
+};
+
+/// Recursively visit all [hcl::FuncCall]s mutably, arguments before the call itself
+pub trait VisitFuncCallsMut {
+    fn visit_func_calls_mut(&mut self, visitor: &mut dyn VisitMut<FuncCall>);
+}
+
+impl VisitFuncCallsMut for Expression {
+    fn visit_func_calls_mut(&mut self, visitor: &mut dyn VisitMut<FuncCall>) {
+        match self {
+            Expression::FuncCall(func_call) => {
+                for arg in func_call.args.iter_mut() {
+                    arg.visit_func_calls_mut(visitor);
+                }
+                visitor.visit_mut(func_call);
+            }
+            Expression::Traversal(traversal) => {
+                traversal.expr.visit_func_calls_mut(visitor);
+                for operator in traversal.operators.iter_mut() {
+                    if let TraversalOperator::Index(index) = operator {
+                        index.visit_func_calls_mut(visitor);
+                    }
+                }
+            }
+            Expression::Array(array) => {
+                for expr in array {
+                    expr.visit_func_calls_mut(visitor);
+                }
+            }
+            Expression::Object(object) => {
+                // keys can not be changed in place, the object is rebuilt in order
+                *object = std::mem::take(object)
+                    .into_iter()
+                    .map(|(mut key, mut value)| {
+                        if let ObjectKey::Expression(expr) = &mut key {
+                            expr.visit_func_calls_mut(visitor);
+                        }
+                        value.visit_func_calls_mut(visitor);
+                        (key, value)
+                    })
+                    .collect();
+            }
+            Expression::TemplateExpr(template_expr) => {
+                let Ok(mut template) = Template::from_expr(template_expr) else {
+                    return;
+                };
+                template.visit_func_calls_mut(visitor);
+                **template_expr = TemplateExpr::QuotedString(template.to_string());
+            }
+            Expression::Parenthesis(expr) => {
+                expr.visit_func_calls_mut(visitor);
+            }
+            Expression::Conditional(cond) => {
+                cond.cond_expr.visit_func_calls_mut(visitor);
+                cond.true_expr.visit_func_calls_mut(visitor);
+                cond.false_expr.visit_func_calls_mut(visitor);
+            }
+            Expression::Operation(operation) => match operation.as_mut() {
+                Operation::Binary(binop) => {
+                    binop.lhs_expr.visit_func_calls_mut(visitor);
+                    binop.rhs_expr.visit_func_calls_mut(visitor);
+                }
+                Operation::Unary(unop) => {
+                    unop.expr.visit_func_calls_mut(visitor);
+                }
+            },
+            Expression::ForExpr(forexpr) => {
+                forexpr.collection_expr.visit_func_calls_mut(visitor);
+                forexpr
+                    .key_expr
+                    .iter_mut()
+                    .for_each(|e| e.visit_func_calls_mut(visitor));
+                forexpr.value_expr.visit_func_calls_mut(visitor);
+                forexpr
+                    .cond_expr
+                    .iter_mut()
+                    .for_each(|e| e.visit_func_calls_mut(visitor));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl VisitFuncCallsMut for Template {
+    fn visit_func_calls_mut(&mut self, visitor: &mut dyn VisitMut<FuncCall>) {
+        for element in self.elements_mut() {
+            match element {
+                Element::Interpolation(interpolation) => {
+                    interpolation.expr.visit_func_calls_mut(visitor);
+                }
+                Element::Directive(directive) => match directive {
+                    Directive::If(ifdir) => {
+                        ifdir.cond_expr.visit_func_calls_mut(visitor);
+                        ifdir.true_template.visit_func_calls_mut(visitor);
+                        ifdir
+                            .false_template
+                            .iter_mut()
+                            .for_each(|t| t.visit_func_calls_mut(visitor));
+                    }
+                    Directive::For(fordir) => {
+                        fordir.collection_expr.visit_func_calls_mut(visitor);
+                        fordir.template.visit_func_calls_mut(visitor);
+                    }
+                },
+                Element::Literal(_) => {}
+            }
+        }
+    }
+}
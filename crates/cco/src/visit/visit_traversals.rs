@@ -30,15 +30,23 @@ impl VisitTraversalsMut for Expression {
                     Vec::<TraversalOperator>::new(),
                 );
                 visitor.visit_mut(&mut traversal);
-                if let Expression::Variable(new_variable) = traversal.expr {
-                    *variable = new_variable
+                *self = if traversal.operators.is_empty() {
+                    traversal.expr
                 } else {
-                    panic!("Traversal rewrite caused a variable to become something else");
-                }
+                    Expression::Traversal(Box::new(traversal))
+                };
             }
             Expression::Traversal(traversal) => {
                 visitor.visit_mut(traversal);
-                traversal.expr.visit_traversals_mut(visitor);
+                // a root variable was already part of the visited traversal
+                if !matches!(traversal.expr, Expression::Variable(_)) {
+                    traversal.expr.visit_traversals_mut(visitor);
+                }
+                for operator in traversal.operators.iter_mut() {
+                    if let TraversalOperator::Index(index) = operator {
+                        index.visit_traversals_mut(visitor);
+                    }
+                }
             }
             Expression::Array(array) => {
                 for expr in array {
@@ -54,9 +62,13 @@ impl VisitTraversalsMut for Expression {
                 let mut template = Template::from_expr(template_expr).unwrap();
                 template.visit_traversals_mut(visitor);
                 // FIXME: Does template round-trip properly?
-                *template_expr = Box::new(TemplateExpr::QuotedString(template.to_string()));
+                **template_expr = TemplateExpr::QuotedString(template.to_string());
+            }
+            Expression::FuncCall(func_call) => {
+                for arg in func_call.args.iter_mut() {
+                    arg.visit_traversals_mut(visitor);
+                }
             }
-            Expression::FuncCall(_) => {}
             Expression::Parenthesis(expr) => {
                 expr.visit_traversals_mut(visitor);
             }